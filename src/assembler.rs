@@ -0,0 +1,621 @@
+use std::{fmt::Display, rc::Rc};
+
+use crate::{
+    chunk::{Capture, Chunk, Constant, OpCode, Program, Prototype},
+    matcha::Type,
+    parser::Parser,
+    scanner::Scanner,
+    source::Source,
+    statement::Statement,
+    token::{Span, Token, TokenType},
+};
+
+#[derive(Debug)]
+pub struct AssemblerError {
+    pub message: String,
+    pub line: usize,
+}
+
+impl Display for AssemblerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Assembler error at line {}. {}", self.line, self.message)
+    }
+}
+
+/// Reads a listing written by `disassembler::disassemble` back into a program the VM can run.
+/// Comments after `;` are ignored, so listings can be written by hand for testing the VM
+pub fn assemble(listing: &str) -> Result<Program<'_>, AssemblerError> {
+    Assembler {
+        rest: listing,
+        line: 1,
+    }
+    .program()
+}
+
+/// A function as it is listed, before the functions it creates are assembled
+struct Listing<'a> {
+    arity: usize,
+    r#type: Type,
+    captures: Vec<Capture>,
+    constants: Vec<ListedConstant<'a>>,
+    chunk: Chunk<'a>,
+}
+
+enum ListedConstant<'a> {
+    Constant(Constant<'a>),
+    /// The index of a function listed later, along with the line it was referenced at
+    Function(usize, usize),
+}
+
+#[derive(Clone, Copy)]
+struct Assembler<'a> {
+    /// The part of the listing that wasn't read yet
+    rest: &'a str,
+    line: usize,
+}
+
+impl<'a> Assembler<'a> {
+    fn program(mut self) -> Result<Program<'a>, AssemblerError> {
+        self.skip_blank_lines();
+        self.keyword("globals")?;
+
+        let mut globals = Vec::new();
+
+        while let Some(global) = self.word() {
+            globals.push(global);
+        }
+
+        self.end_line()?;
+
+        let mut listings = Vec::new();
+
+        loop {
+            self.skip_blank_lines();
+
+            if self.rest.is_empty() {
+                break;
+            }
+
+            listings.push(self.function(listings.len())?);
+        }
+
+        if listings.is_empty() {
+            return Err(self.error("Expected at least one function".to_owned()));
+        }
+
+        // Functions only create functions listed after them, so assembling them backwards means
+        // every nested function is ready by the time it's needed
+        let mut prototypes: Vec<Option<Rc<Prototype>>> = vec![None; listings.len()];
+
+        for (index, listing) in listings.into_iter().enumerate().rev() {
+            let constants = listing
+                .constants
+                .into_iter()
+                .map(|constant| match constant {
+                    ListedConstant::Constant(constant) => Ok(constant),
+                    ListedConstant::Function(function, line) => {
+                        match prototypes.get(function).filter(|_| function > index) {
+                            Some(Some(prototype)) => Ok(Constant::Function(Rc::clone(prototype))),
+                            _ => Err(AssemblerError {
+                                message: format!(
+                                    "Function {} must be listed after function {}",
+                                    function, index
+                                ),
+                                line,
+                            }),
+                        }
+                    }
+                })
+                .collect::<Result<Vec<Constant>, AssemblerError>>()?;
+
+            prototypes[index] = Some(Rc::new(Prototype {
+                arity: listing.arity,
+                captures: listing.captures,
+                chunk: Chunk {
+                    constants,
+                    ..listing.chunk
+                },
+                r#type: listing.r#type,
+            }));
+        }
+
+        Ok(Program {
+            globals,
+            main: prototypes[0].take().expect("Main function was assembled"),
+        })
+    }
+
+    fn function(&mut self, index: usize) -> Result<Listing<'a>, AssemblerError> {
+        self.keyword("function")?;
+
+        if self.number("function index")? != index {
+            return Err(self.error(format!("Expected function {}", index)));
+        }
+
+        self.keyword("arity")?;
+        let arity = self.number("arity")?;
+        self.keyword("type")?;
+
+        let type_line = self.line;
+        let r#type = match parse_type(self.rest_of_line()) {
+            Some((r#type, "")) => r#type,
+            _ => {
+                return Err(AssemblerError {
+                    message: "Invalid function type".to_owned(),
+                    line: type_line,
+                })
+            }
+        };
+
+        self.end_line()?;
+
+        let mut listing = Listing {
+            arity,
+            r#type,
+            captures: Vec::new(),
+            constants: Vec::new(),
+            chunk: Chunk::default(),
+        };
+
+        loop {
+            self.skip_blank_lines();
+
+            match self.word() {
+                Some("capture") => {
+                    let local = match self.word() {
+                        Some("local") => true,
+                        Some("upvalue") => false,
+                        _ => return Err(self.error("Expected 'local' or 'upvalue'".to_owned())),
+                    };
+                    let index = self.operand("capture index")?;
+
+                    listing.captures.push(Capture { local, index });
+                }
+                Some("constant") => {
+                    if self.number("constant index")? != listing.constants.len() {
+                        return Err(
+                            self.error(format!("Expected constant {}", listing.constants.len()))
+                        );
+                    }
+
+                    let constant = self.constant()?;
+                    listing.constants.push(constant);
+                }
+                Some("code") => {
+                    self.end_line()?;
+                    break;
+                }
+                _ => return Err(self.error("Expected 'capture', 'constant' or 'code'".to_owned())),
+            }
+
+            self.end_line()?;
+        }
+
+        loop {
+            self.skip_blank_lines();
+
+            if self.rest.is_empty() || self.peek_word() == Some("function") {
+                break;
+            }
+
+            self.instruction(&mut listing.chunk)?;
+        }
+
+        Ok(listing)
+    }
+
+    fn constant(&mut self) -> Result<ListedConstant<'a>, AssemblerError> {
+        // Records are written like their declaration in source code, so the parser can read them
+        if self.peek_word() == Some("record") {
+            self.skip_spaces();
+            let text = self.rest_of_line();
+
+            return match record_declaration(text) {
+                Some(Statement::Record(declaration)) => Ok(ListedConstant::Constant(
+                    Constant::Record(Rc::new(declaration)),
+                )),
+                _ => Err(self.error(format!("Invalid record declaration '{}'", text))),
+            };
+        }
+
+        let constant = match self.word() {
+            Some("literal") => {
+                let text = self.lexeme()?;
+                let token = Assembler::token(text, self.line as u64, 0);
+
+                match Parser::literal(&token) {
+                    Ok(literal) => Constant::Literal(literal),
+                    _ => return Err(self.error(format!("Invalid literal '{}'", text))),
+                }
+            }
+            Some("name") => Constant::Name(self.expect_word("name")?),
+            Some("names") => {
+                let mut names = Vec::new();
+
+                while let Some(name) = self.word() {
+                    names.push(name);
+                }
+
+                Constant::Names(names)
+            }
+            Some("function") => {
+                let line = self.line;
+
+                return Ok(ListedConstant::Function(
+                    self.number("function index")?,
+                    line,
+                ));
+            }
+            _ => return Err(self.error("Expected a constant".to_owned())),
+        };
+
+        Ok(ListedConstant::Constant(constant))
+    }
+
+    fn instruction(&mut self, chunk: &mut Chunk<'a>) -> Result<(), AssemblerError> {
+        if self.number("offset")? != chunk.code.len() {
+            return Err(self.error(format!("Expected offset {:04}", chunk.code.len())));
+        }
+
+        let location = match self.expect_word("location")? {
+            "|" => None,
+            location => {
+                let position = location.split_once(':').and_then(|(line, position)| {
+                    Some((line.parse().ok()?, position.parse().ok()?))
+                });
+
+                match position {
+                    Some(position) => Some(position),
+                    None => return Err(self.error(format!("Invalid location '{}'", location))),
+                }
+            }
+        };
+
+        let op = self.opcode()?;
+
+        if let Some((line, position)) = location {
+            let lexeme = self.lexeme()?;
+
+            chunk.tokens.push((
+                chunk.code.len() as u32,
+                Assembler::token(lexeme, line, position),
+            ));
+        }
+
+        chunk.code.push(op);
+
+        self.end_line()
+    }
+
+    fn opcode(&mut self) -> Result<OpCode, AssemblerError> {
+        let name = self.expect_word("opcode")?;
+
+        Ok(match name {
+            "Constant" => OpCode::Constant(self.operand("constant")?),
+            "Empty" => OpCode::Empty,
+            "None" => OpCode::None,
+            "Pop" => OpCode::Pop,
+            "GetLocal" => OpCode::GetLocal(self.operand("slot")?),
+            "SetLocal" => OpCode::SetLocal(self.operand("slot")?),
+            "GetUpvalue" => OpCode::GetUpvalue(self.operand("upvalue")?),
+            "SetUpvalue" => OpCode::SetUpvalue(self.operand("upvalue")?),
+            "GetGlobal" => OpCode::GetGlobal(self.operand("global")?),
+            "SetGlobal" => OpCode::SetGlobal(self.operand("global")?),
+            "DeclareGlobal" => OpCode::DeclareGlobal {
+                index: self.operand("global")?,
+                record: self.kind()?,
+            },
+            "DefineGlobal" => OpCode::DefineGlobal(self.operand("global")?),
+            "Redeclare" => OpCode::Redeclare {
+                record: self.kind()?,
+            },
+            "EndScope" => OpCode::EndScope(self.operand("count")?),
+            "PopScope" => OpCode::PopScope(self.operand("count")?),
+            "Add" => OpCode::Add,
+            "Subtract" => OpCode::Subtract,
+            "Multiply" => OpCode::Multiply,
+            "Divide" => OpCode::Divide,
+            "Greater" => OpCode::Greater,
+            "GreaterEqual" => OpCode::GreaterEqual,
+            "Less" => OpCode::Less,
+            "LessEqual" => OpCode::LessEqual,
+            "Equal" => OpCode::Equal,
+            "NotEqual" => OpCode::NotEqual,
+            "BitwiseAnd" => OpCode::BitwiseAnd,
+            "BitwiseOr" => OpCode::BitwiseOr,
+            "BitwiseXor" => OpCode::BitwiseXor,
+            "LeftShift" => OpCode::LeftShift,
+            "RightShift" => OpCode::RightShift,
+            "Negate" => OpCode::Negate,
+            "Not" => OpCode::Not,
+            "BitwiseNot" => OpCode::BitwiseNot,
+            "Jump" => OpCode::Jump(self.target()?),
+            "JumpIfFalse" => OpCode::JumpIfFalse(self.target()?),
+            "And" => OpCode::And(self.target()?),
+            "Or" => OpCode::Or(self.target()?),
+            "Bool" => OpCode::Bool,
+            "Wrap" => OpCode::Wrap,
+            "Unwrap" => OpCode::Unwrap,
+            "Coalesce" => OpCode::Coalesce(self.target()?),
+            "Bind" => OpCode::Bind(self.target()?),
+            "Closure" => OpCode::Closure(self.operand("constant")?),
+            "CheckCall" => OpCode::CheckCall(self.operand("argument count")?),
+            "Call" => OpCode::Call(self.operand("argument count")?),
+            "Return" => OpCode::Return,
+            "Array" => OpCode::Array(self.operand("length")?),
+            "CheckArray" => OpCode::CheckArray,
+            "GetIndex" => OpCode::GetIndex,
+            "CheckIndex" => OpCode::CheckIndex,
+            "SetIndex" => OpCode::SetIndex,
+            "CheckRecord" => OpCode::CheckRecord,
+            "CheckRecordField" => OpCode::CheckRecordField {
+                name: self.operand("constant")?,
+                depth: self.operand("depth")?,
+            },
+            "Record" => OpCode::Record(self.operand("constant")?),
+            "GetField" => OpCode::GetField(self.operand("constant")?),
+            "CheckField" => OpCode::CheckField(self.operand("constant")?),
+            "SetField" => OpCode::SetField(self.operand("constant")?),
+            name => return Err(self.error(format!("Unknown opcode '{}'", name))),
+        })
+    }
+
+    // Helpers:
+
+    fn error(&self, message: String) -> AssemblerError {
+        AssemblerError {
+            message,
+            line: self.line,
+        }
+    }
+
+    /// Builds the token of an instruction, finding its type by scanning the lexeme again
+    fn token(lexeme: &'a str, line: u64, position: u64) -> Token<'a> {
+        let (tokens, _) = Scanner {
+            source: Source::new(lexeme),
+        }
+        .scan_recovering();
+        let token_type = match tokens.first() {
+            Some(token) if token.lexeme == lexeme => token.token_type,
+            _ => TokenType::Error,
+        };
+
+        Token::new(token_type, lexeme, line, position, Span::default())
+    }
+
+    fn skip_blank_lines(&mut self) {
+        loop {
+            let end = self.rest.find('\n');
+            let line = &self.rest[..end.unwrap_or(self.rest.len())];
+
+            if !line.trim().is_empty() {
+                return;
+            }
+
+            match end {
+                Some(end) => {
+                    self.rest = &self.rest[end + 1..];
+                    self.line += 1;
+                }
+                None => {
+                    self.rest = "";
+                    return;
+                }
+            }
+        }
+    }
+
+    fn skip_spaces(&mut self) {
+        self.rest = self.rest.trim_start_matches([' ', '\t']);
+    }
+
+    /// Reads the next word on the current line, stopping before comments
+    fn word(&mut self) -> Option<&'a str> {
+        self.skip_spaces();
+
+        let end = self
+            .rest
+            .find(|c: char| c.is_whitespace())
+            .unwrap_or(self.rest.len());
+        let word = &self.rest[..end];
+
+        if word.is_empty() || word.starts_with(';') {
+            return None;
+        }
+
+        self.rest = &self.rest[end..];
+
+        Some(word)
+    }
+
+    fn peek_word(&self) -> Option<&'a str> {
+        let mut copy = *self;
+
+        copy.word()
+    }
+
+    fn expect_word(&mut self, name: &str) -> Result<&'a str, AssemblerError> {
+        self.word()
+            .ok_or_else(|| self.error(format!("Expected {}", name)))
+    }
+
+    fn keyword(&mut self, keyword: &str) -> Result<(), AssemblerError> {
+        match self.word() {
+            Some(word) if word == keyword => Ok(()),
+            _ => Err(self.error(format!("Expected '{}'", keyword))),
+        }
+    }
+
+    fn number(&mut self, name: &str) -> Result<usize, AssemblerError> {
+        let word = self.expect_word(name)?;
+
+        word.parse()
+            .map_err(|_| self.error(format!("Invalid {} '{}'", name, word)))
+    }
+
+    fn operand(&mut self, name: &str) -> Result<u16, AssemblerError> {
+        let word = self.expect_word(name)?;
+
+        word.parse()
+            .map_err(|_| self.error(format!("Invalid {} '{}'", name, word)))
+    }
+
+    fn target(&mut self) -> Result<u32, AssemblerError> {
+        let word = self.expect_word("jump target")?;
+
+        word.parse()
+            .map_err(|_| self.error(format!("Invalid jump target '{}'", word)))
+    }
+
+    fn kind(&mut self) -> Result<bool, AssemblerError> {
+        match self.word() {
+            Some("record") => Ok(true),
+            Some("variable") => Ok(false),
+            _ => Err(self.error("Expected 'variable' or 'record'".to_owned())),
+        }
+    }
+
+    /// Reads a lexeme, which is a single word unless it's a string. Strings are read up to their
+    /// closing quote, even across lines
+    fn lexeme(&mut self) -> Result<&'a str, AssemblerError> {
+        self.skip_spaces();
+
+        if !self.rest.starts_with('"') {
+            return self.expect_word("lexeme");
+        }
+
+        let mut escaped = false;
+        let mut lines = 0;
+
+        for (index, c) in self.rest.char_indices().skip(1) {
+            match c {
+                '"' if !escaped => {
+                    let lexeme = &self.rest[..index + 1];
+
+                    self.rest = &self.rest[index + 1..];
+                    self.line += lines;
+
+                    return Ok(lexeme);
+                }
+                '\n' => lines += 1,
+                _ => {}
+            }
+
+            escaped = c == '\\' && !escaped;
+        }
+
+        Err(self.error("Unterminated string".to_owned()))
+    }
+
+    /// Reads the rest of the current line, up to a comment
+    fn rest_of_line(&mut self) -> &'a str {
+        let end = self.rest.find([';', '\n']).unwrap_or(self.rest.len());
+        let text = &self.rest[..end];
+
+        self.rest = &self.rest[end..];
+
+        text.trim()
+    }
+
+    /// Skips a trailing comment and moves to the next line, failing if anything else is left
+    fn end_line(&mut self) -> Result<(), AssemblerError> {
+        self.skip_spaces();
+
+        if self.rest.starts_with(';') {
+            let end = self.rest.find('\n').unwrap_or(self.rest.len());
+            self.rest = &self.rest[end..];
+        }
+
+        let rest = self.rest.trim_start_matches('\r');
+
+        if let Some(rest) = rest.strip_prefix('\n') {
+            self.rest = rest;
+            self.line += 1;
+
+            return Ok(());
+        }
+
+        if rest.is_empty() {
+            self.rest = rest;
+
+            return Ok(());
+        }
+
+        Err(self.error(format!(
+            "Unexpected '{}'",
+            rest.split_whitespace().next().unwrap_or(rest)
+        )))
+    }
+}
+
+/// Reads a type as it is displayed, e.g. `fn(Int, Float?): Unknown`, returning what's left after it
+fn parse_type(text: &str) -> Option<(Type, &str)> {
+    let text = text.trim_start();
+
+    let (mut r#type, mut rest) = if let Some(mut rest) = text.strip_prefix("fn(") {
+        let mut parameters = Vec::new();
+
+        rest = rest.trim_start();
+
+        while !rest.starts_with(')') {
+            let (parameter, after) = parse_type(rest)?;
+
+            parameters.push(parameter);
+            rest = after.trim_start();
+
+            if let Some(after) = rest.strip_prefix(',') {
+                rest = after.trim_start();
+            }
+        }
+
+        let rest = rest[1..].trim_start().strip_prefix(':')?;
+        let (return_type, rest) = parse_type(rest)?;
+
+        (
+            Type::Function {
+                parameters,
+                return_type: Box::new(return_type),
+            },
+            rest,
+        )
+    } else if let Some(rest) = text.strip_prefix('[') {
+        let (element, rest) = parse_type(rest)?;
+
+        (Type::Array(Box::new(element)), rest.strip_prefix(']')?)
+    } else {
+        let end = text
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(text.len());
+        let r#type = match &text[..end] {
+            "Empty" => Type::Empty,
+            "Unknown" => Type::Unknown,
+            name => Type::from_name(name)?,
+        };
+
+        (r#type, &text[end..])
+    };
+
+    while let Some(after) = rest.strip_prefix('?') {
+        r#type = Type::Optional(Box::new(r#type));
+        rest = after;
+    }
+
+    Some((r#type, rest))
+}
+
+/// Parses `text` as a record declaration
+fn record_declaration(text: &str) -> Option<Statement<'_>> {
+    let (tokens, errors) = Scanner {
+        source: Source::new(text),
+    }
+    .scan_recovering();
+
+    if !errors.is_empty() {
+        return None;
+    }
+
+    match Parser::new(tokens).parse() {
+        Ok(mut statements) if statements.len() == 1 => statements.pop(),
+        _ => None,
+    }
+}
@@ -0,0 +1,163 @@
+use std::rc::Rc;
+
+use crate::{
+    matcha::{Literal, Type},
+    statement::RecordDeclaration,
+    token::Token,
+};
+
+/// A single VM instruction. Operands index into the constant pool, the current frame's slots or
+/// the closure's upvalues, except for jumps which hold the absolute instruction to continue at.
+///
+/// Instructions that can fail have their token recorded in the chunk so errors point at the same
+/// place the tree-walker reports. Checks that the tree-walker performs before evaluating a later
+/// operand get their own `Check*` instruction, so that errors are reported in the same order.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OpCode {
+    Constant(u16),
+    Empty,
+    None,
+    Pop,
+
+    GetLocal(u16),
+    SetLocal(u16),
+    GetUpvalue(u16),
+    SetUpvalue(u16),
+    GetGlobal(u16),
+    SetGlobal(u16),
+    /// Fails if the global is already defined. Emitted before the initializer is evaluated
+    DeclareGlobal {
+        index: u16,
+        record: bool,
+    },
+    /// Pops the initializer into the global
+    DefineGlobal(u16),
+    /// Always fails, for a local declared twice in the same scope
+    Redeclare {
+        record: bool,
+    },
+    /// Drops the given number of locals below the value on top of the stack
+    EndScope(u16),
+    /// Drops the given number of locals
+    PopScope(u16),
+
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Equal,
+    NotEqual,
+    BitwiseAnd,
+    BitwiseOr,
+    BitwiseXor,
+    LeftShift,
+    RightShift,
+    Negate,
+    Not,
+    BitwiseNot,
+
+    Jump(u32),
+    /// Pops the condition and jumps if it is false
+    JumpIfFalse(u32),
+    /// Jumps with `false` on the stack if the left operand is false, or pops it otherwise
+    And(u32),
+    /// Jumps with `true` on the stack if the left operand is true, or pops it otherwise
+    Or(u32),
+    /// Checks that the right operand of a logical operator is a boolean
+    Bool,
+
+    /// Wraps the value on top of the stack into an optional
+    Wrap,
+    Unwrap,
+    /// Jumps with the unwrapped value on the stack if the left operand holds one
+    Coalesce(u32),
+    /// Unwraps the value to bind in `if x := ...`, or pops it and jumps to the else branch
+    Bind(u32),
+
+    Closure(u16),
+    /// Checks the callee before its arguments are evaluated
+    CheckCall(u16),
+    Call(u16),
+    Return,
+
+    Array(u16),
+    CheckArray,
+    GetIndex,
+    /// Checks the index of an assignment before the value is evaluated
+    CheckIndex,
+    SetIndex,
+
+    /// Checks that the name of a record literal refers to a record declaration
+    CheckRecord,
+    /// Checks that the declaration `depth` slots below the top of the stack has this field
+    CheckRecordField {
+        name: u16,
+        depth: u16,
+    },
+    /// Pops the declaration and the values of the fields listed in the constant
+    Record(u16),
+    GetField(u16),
+    /// Checks the target of a field assignment before the value is evaluated
+    CheckField(u16),
+    SetField(u16),
+}
+
+#[derive(Debug)]
+pub enum Constant<'a> {
+    Literal(Literal<'a>),
+    /// The name of a variable or field
+    Name(&'a str),
+    /// The fields set by a record literal, in the order they are pushed
+    Names(Vec<&'a str>),
+    Function(Rc<Prototype<'a>>),
+    Record(Rc<RecordDeclaration<'a>>),
+}
+
+#[derive(Debug, Default)]
+pub struct Chunk<'a> {
+    pub code: Vec<OpCode>,
+    pub constants: Vec<Constant<'a>>,
+    /// The token of every instruction that can fail, sorted by instruction
+    pub tokens: Vec<(u32, Token<'a>)>,
+}
+
+impl<'a> Chunk<'a> {
+    /// The token recorded for the instruction at `index`, or for the closest one before it
+    pub fn token(&self, index: usize) -> &Token<'a> {
+        let index = index as u32;
+        let position = match self.tokens.binary_search_by_key(&index, |(at, _)| *at) {
+            Ok(position) => position,
+            Err(position) => position.saturating_sub(1),
+        };
+
+        &self.tokens[position].1
+    }
+}
+
+/// A compiled function, shared by every closure created from it
+#[derive(Debug)]
+pub struct Prototype<'a> {
+    pub arity: usize,
+    /// Where each upvalue of a closure is captured from when it is created
+    pub captures: Vec<Capture>,
+    pub chunk: Chunk<'a>,
+    pub r#type: Type,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Capture {
+    /// Whether the variable is a local of the enclosing function, or one of its upvalues
+    pub local: bool,
+    pub index: u16,
+}
+
+#[derive(Debug)]
+pub struct Program<'a> {
+    /// The names of the globals, indexed by the operands of the global instructions
+    pub globals: Vec<&'a str>,
+    pub main: Rc<Prototype<'a>>,
+}
@@ -0,0 +1,751 @@
+use std::{collections::HashMap, rc::Rc};
+
+use crate::{
+    chunk::{Capture, Chunk, Constant, OpCode, Program, Prototype},
+    interpreter::InterpreterError,
+    matcha::{function_type, Type},
+    statement::{
+        BinaryExpression, CallExpression, Expression, FunctionExpression, IfStatement,
+        RecordDeclaration, RecordExpression, Statement, TypeAnnotation, VariableDeclaration,
+    },
+    token::{Token, TokenType},
+};
+
+/// Compiles statements into bytecode for the VM.
+///
+/// Variables are resolved while compiling: locals become stack slots, locals of enclosing
+/// functions become upvalues, and anything declared at the top level is a global looked up by
+/// name. Unlike the tree-walker, a function can't see locals its enclosing function declares
+/// after it, since they don't exist yet when its body is compiled.
+pub struct Compiler<'a> {
+    /// The functions being compiled, innermost last
+    functions: Vec<FunctionState<'a>>,
+    globals: Vec<&'a str>,
+    global_indices: HashMap<&'a str, u16>,
+}
+
+struct FunctionState<'a> {
+    chunk: Chunk<'a>,
+    /// Mirrors the slots of the function's frame, the first one being the callee itself
+    locals: Vec<Local<'a>>,
+    captures: Vec<Capture>,
+    depth: usize,
+    /// Whether returned values are wrapped into an optional
+    optional_return: bool,
+}
+
+struct Local<'a> {
+    name: &'a str,
+    depth: usize,
+}
+
+enum Variable {
+    Local(u16),
+    Upvalue(u16),
+    Global(u16),
+}
+
+impl<'a> FunctionState<'a> {
+    fn new(depth: usize, optional_return: bool) -> FunctionState<'a> {
+        FunctionState {
+            chunk: Chunk::default(),
+            // The callee can't be referred to by name, since identifiers are never empty
+            locals: vec![Local { name: "", depth }],
+            captures: Vec::new(),
+            depth,
+            optional_return,
+        }
+    }
+}
+
+impl<'a> Compiler<'a> {
+    pub fn compile(statements: &[Statement<'a>]) -> Result<Program<'a>, InterpreterError<'a>> {
+        let mut compiler = Compiler {
+            functions: vec![FunctionState::new(0, false)],
+            globals: Vec::new(),
+            global_indices: HashMap::new(),
+        };
+
+        // The program evaluates to its last statement, like a function body
+        compiler.statements(statements, true)?;
+        compiler.emit(OpCode::Return);
+
+        let main = compiler.functions.pop().expect("Main function must exist");
+
+        Ok(Program {
+            globals: compiler.globals,
+            main: Rc::new(Prototype {
+                arity: 0,
+                captures: Vec::new(),
+                chunk: main.chunk,
+                r#type: Type::Function {
+                    parameters: Vec::new(),
+                    return_type: Box::new(Type::Unknown),
+                },
+            }),
+        })
+    }
+
+    /// Compiles a list of statements. When `keep` is set, the value of the last one is left on the
+    /// stack
+    fn statements(
+        &mut self,
+        statements: &[Statement<'a>],
+        keep: bool,
+    ) -> Result<(), InterpreterError<'a>> {
+        if statements.is_empty() && keep {
+            self.emit(OpCode::Empty);
+        }
+
+        for (index, statement) in statements.iter().enumerate() {
+            self.statement(statement, keep && index == statements.len() - 1)?;
+        }
+
+        Ok(())
+    }
+
+    fn statement(
+        &mut self,
+        statement: &Statement<'a>,
+        keep: bool,
+    ) -> Result<(), InterpreterError<'a>> {
+        match statement {
+            Statement::Expression(expression) => {
+                self.expression(expression)?;
+
+                if !keep {
+                    self.emit(OpCode::Pop);
+                }
+            }
+            Statement::VariableDeclaration(declaration) => {
+                self.variable_declaration(declaration)?;
+
+                if keep {
+                    self.emit(OpCode::Empty);
+                }
+            }
+            Statement::Block(block) => self.block(&block.statements, keep)?,
+            Statement::If(if_statement) => self.if_statement(if_statement, keep)?,
+            Statement::For(for_statement) => {
+                let start = self.position();
+
+                self.expression(&for_statement.condition)?;
+                let exit =
+                    self.emit_with_token(OpCode::JumpIfFalse(0), for_statement.condition.token());
+                self.block(&for_statement.statements, false)?;
+                self.emit(OpCode::Jump(start));
+                self.patch(exit);
+
+                if keep {
+                    self.emit(OpCode::Empty);
+                }
+            }
+            Statement::Return(return_statement) => {
+                match return_statement.value {
+                    Some(ref value) => self.expression(value)?,
+                    None => {
+                        self.emit(OpCode::Empty);
+                    }
+                }
+
+                self.return_value();
+            }
+            Statement::Record(record) => {
+                self.record_declaration(record)?;
+
+                if keep {
+                    self.emit(OpCode::Empty);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn variable_declaration(
+        &mut self,
+        declaration: &VariableDeclaration<'a>,
+    ) -> Result<(), InterpreterError<'a>> {
+        let identifier = &declaration.identifier;
+
+        if self.current().depth == 0 {
+            let index = self.global(identifier)?;
+
+            self.emit_with_token(
+                OpCode::DeclareGlobal {
+                    index,
+                    record: false,
+                },
+                identifier,
+            );
+            self.expression(&declaration.initializer)?;
+            self.wrap(declaration.r#type.as_ref());
+            self.emit(OpCode::DefineGlobal(index));
+
+            return Ok(());
+        }
+
+        self.check_redeclaration(identifier, false);
+
+        // Functions are declared first so they can call themselves
+        if let Expression::Function(_) = declaration.initializer {
+            self.add_local(identifier)?;
+            self.expression(&declaration.initializer)?;
+        } else {
+            self.expression(&declaration.initializer)?;
+            self.add_local(identifier)?;
+        }
+
+        self.wrap(declaration.r#type.as_ref());
+
+        Ok(())
+    }
+
+    fn record_declaration(
+        &mut self,
+        record: &RecordDeclaration<'a>,
+    ) -> Result<(), InterpreterError<'a>> {
+        let constant = self.constant(Constant::Record(Rc::new(record.clone())), &record.name)?;
+
+        if self.current().depth == 0 {
+            let index = self.global(&record.name)?;
+
+            self.emit_with_token(
+                OpCode::DeclareGlobal {
+                    index,
+                    record: true,
+                },
+                &record.name,
+            );
+            self.emit(OpCode::Constant(constant));
+            self.emit(OpCode::DefineGlobal(index));
+
+            return Ok(());
+        }
+
+        self.check_redeclaration(&record.name, true);
+        self.emit(OpCode::Constant(constant));
+        self.add_local(&record.name)?;
+
+        Ok(())
+    }
+
+    fn block(
+        &mut self,
+        statements: &[Statement<'a>],
+        keep: bool,
+    ) -> Result<(), InterpreterError<'a>> {
+        self.begin_scope();
+        self.statements(statements, keep)?;
+        self.end_scope(keep);
+
+        Ok(())
+    }
+
+    fn if_statement(
+        &mut self,
+        if_statement: &IfStatement<'a>,
+        keep: bool,
+    ) -> Result<(), InterpreterError<'a>> {
+        self.expression(&if_statement.condition)?;
+
+        let otherwise = match if_statement.binding {
+            // The unwrapped value stays on the stack as the binding's slot
+            Some(ref binding) => {
+                let otherwise = self.emit_with_token(OpCode::Bind(0), binding);
+
+                self.begin_scope();
+                self.add_local(binding)?;
+                self.statements(&if_statement.statements, keep)?;
+                self.end_scope(keep);
+
+                otherwise
+            }
+            None => {
+                let otherwise =
+                    self.emit_with_token(OpCode::JumpIfFalse(0), if_statement.condition.token());
+
+                self.block(&if_statement.statements, keep)?;
+
+                otherwise
+            }
+        };
+
+        let end = self.emit(OpCode::Jump(0));
+        self.patch(otherwise);
+
+        match if_statement.else_statements {
+            Some(ref statements) => self.block(statements, keep)?,
+            None if keep => {
+                self.emit(OpCode::Empty);
+            }
+            None => {}
+        }
+
+        self.patch(end);
+
+        Ok(())
+    }
+
+    fn expression(&mut self, expression: &Expression<'a>) -> Result<(), InterpreterError<'a>> {
+        match expression {
+            Expression::Literal(literal) => {
+                let constant =
+                    self.constant(Constant::Literal(literal.literal.clone()), &literal.value)?;
+
+                self.emit(OpCode::Constant(constant));
+            }
+            Expression::Unary(unary) => {
+                self.expression(&unary.left)?;
+
+                // The VM applies unary operators according to the token, so any other operator is
+                // reported there just like the tree-walker does
+                let op = match unary.operator.token_type {
+                    TokenType::Minus => OpCode::Negate,
+                    TokenType::Bang => OpCode::Not,
+                    _ => OpCode::BitwiseNot,
+                };
+
+                self.emit_with_token(op, &unary.operator);
+            }
+            Expression::Grouping(grouping) => self.expression(&grouping.expression)?,
+            Expression::Binary(binary) => self.binary(binary)?,
+            Expression::Variable(variable) => self.get_variable(&variable.value)?,
+            Expression::Assignment(assignment) => {
+                self.expression(&assignment.value)?;
+
+                let op = match self.resolve(&assignment.identifier)? {
+                    Variable::Local(slot) => OpCode::SetLocal(slot),
+                    Variable::Upvalue(index) => OpCode::SetUpvalue(index),
+                    Variable::Global(index) => OpCode::SetGlobal(index),
+                };
+
+                self.emit_with_token(op, &assignment.identifier);
+            }
+            Expression::Logical(logical) => {
+                self.expression(&logical.left)?;
+
+                let op = match logical.operator.token_type {
+                    TokenType::And => OpCode::And(0),
+                    TokenType::Or => OpCode::Or(0),
+                    _ => {
+                        return Err(InterpreterError::new(
+                            format!("Invalid logical operator '{}'", logical.operator.lexeme),
+                            logical.operator.clone(),
+                        ))
+                    }
+                };
+                let end = self.emit_with_token(op, &logical.operator);
+
+                self.expression(&logical.right)?;
+                self.emit_with_token(OpCode::Bool, &logical.operator);
+                self.patch(end);
+            }
+            Expression::Function(function) => {
+                let prototype = self.function(function)?;
+                let constant = self.constant(Constant::Function(prototype), &function.keyword)?;
+
+                self.emit(OpCode::Closure(constant));
+            }
+            Expression::Call(call) => self.call(call)?,
+            Expression::Array(array) => {
+                for element in &array.elements {
+                    self.expression(element)?;
+                }
+
+                let length =
+                    Compiler::operand(array.elements.len(), "array elements", &array.bracket)?;
+
+                self.emit(OpCode::Array(length));
+            }
+            Expression::Index(index) => {
+                self.expression(&index.target)?;
+                self.emit_with_token(OpCode::CheckArray, &index.bracket);
+                self.expression(&index.index)?;
+                self.emit_with_token(OpCode::GetIndex, &index.bracket);
+            }
+            Expression::IndexAssignment(assignment) => {
+                self.expression(&assignment.target)?;
+                self.emit_with_token(OpCode::CheckArray, &assignment.bracket);
+                self.expression(&assignment.index)?;
+                self.emit_with_token(OpCode::CheckIndex, &assignment.bracket);
+                self.expression(&assignment.value)?;
+                self.emit_with_token(OpCode::SetIndex, &assignment.bracket);
+            }
+            Expression::None(_) => {
+                self.emit(OpCode::None);
+            }
+            Expression::Unwrap(unwrap) => {
+                self.expression(&unwrap.target)?;
+                self.emit_with_token(OpCode::Unwrap, &unwrap.operator);
+            }
+            Expression::Coalesce(coalesce) => {
+                self.expression(&coalesce.left)?;
+
+                let end = self.emit_with_token(OpCode::Coalesce(0), &coalesce.operator);
+
+                self.expression(&coalesce.right)?;
+                self.patch(end);
+            }
+            Expression::Record(record) => self.record(record)?,
+            Expression::Field(field) => {
+                self.expression(&field.target)?;
+
+                let name = self.constant(Constant::Name(field.name.lexeme), &field.name)?;
+
+                self.emit_with_token(OpCode::GetField(name), &field.name);
+            }
+            Expression::FieldAssignment(assignment) => {
+                self.expression(&assignment.target)?;
+
+                let name =
+                    self.constant(Constant::Name(assignment.name.lexeme), &assignment.name)?;
+
+                self.emit_with_token(OpCode::CheckField(name), &assignment.name);
+                self.expression(&assignment.value)?;
+                self.emit_with_token(OpCode::SetField(name), &assignment.name);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn binary(&mut self, binary: &BinaryExpression<'a>) -> Result<(), InterpreterError<'a>> {
+        let op = match binary.operator.token_type {
+            TokenType::Plus => OpCode::Add,
+            TokenType::Minus => OpCode::Subtract,
+            TokenType::Star => OpCode::Multiply,
+            TokenType::Slash => OpCode::Divide,
+            TokenType::Greater => OpCode::Greater,
+            TokenType::GreaterEqual => OpCode::GreaterEqual,
+            TokenType::Less => OpCode::Less,
+            TokenType::LessEqual => OpCode::LessEqual,
+            TokenType::DoubleEqual => OpCode::Equal,
+            TokenType::BangEqual => OpCode::NotEqual,
+            TokenType::BitwiseAnd => OpCode::BitwiseAnd,
+            TokenType::BitwiseOr => OpCode::BitwiseOr,
+            TokenType::BitwiseXor => OpCode::BitwiseXor,
+            TokenType::LeftShift => OpCode::LeftShift,
+            TokenType::RightShift => OpCode::RightShift,
+            _ => {
+                return Err(InterpreterError::new(
+                    format!("Invalid operator '{}'", binary.operator.lexeme),
+                    binary.operator.clone(),
+                ))
+            }
+        };
+
+        self.expression(&binary.left)?;
+        self.expression(&binary.right)?;
+        self.emit_with_token(op, &binary.operator);
+
+        Ok(())
+    }
+
+    fn call(&mut self, call: &CallExpression<'a>) -> Result<(), InterpreterError<'a>> {
+        let arguments = Compiler::operand(call.arguments.len(), "arguments", &call.paren)?;
+
+        self.expression(&call.callee)?;
+        self.emit_with_token(OpCode::CheckCall(arguments), &call.paren);
+
+        for argument in &call.arguments {
+            self.expression(argument)?;
+        }
+
+        self.emit_with_token(OpCode::Call(arguments), &call.paren);
+
+        Ok(())
+    }
+
+    fn record(&mut self, record: &RecordExpression<'a>) -> Result<(), InterpreterError<'a>> {
+        self.get_variable(&record.name)?;
+        self.emit_with_token(OpCode::CheckRecord, &record.name);
+
+        for (depth, initializer) in record.fields.iter().enumerate() {
+            let name = self.constant(
+                Constant::Name(initializer.identifier.lexeme),
+                &initializer.identifier,
+            )?;
+            let depth = Compiler::operand(depth, "fields", &initializer.identifier)?;
+
+            self.emit_with_token(
+                OpCode::CheckRecordField { name, depth },
+                &initializer.identifier,
+            );
+            self.expression(&initializer.value)?;
+        }
+
+        let names = record
+            .fields
+            .iter()
+            .map(|initializer| initializer.identifier.lexeme)
+            .collect();
+        let names = self.constant(Constant::Names(names), &record.name)?;
+
+        self.emit_with_token(OpCode::Record(names), &record.name);
+
+        Ok(())
+    }
+
+    fn function(
+        &mut self,
+        function: &FunctionExpression<'a>,
+    ) -> Result<Rc<Prototype<'a>>, InterpreterError<'a>> {
+        let optional_return = function
+            .return_type
+            .as_ref()
+            .is_some_and(|annotation| annotation.optional);
+
+        // The body runs in the same scope as the parameters
+        self.functions.push(FunctionState::new(1, optional_return));
+
+        for parameter in &function.parameters {
+            self.add_local(&parameter.identifier)?;
+        }
+
+        for (index, parameter) in function.parameters.iter().enumerate() {
+            if parameter
+                .r#type
+                .as_ref()
+                .is_some_and(|annotation| annotation.optional)
+            {
+                let slot = Compiler::operand(index + 1, "parameters", &parameter.identifier)?;
+
+                self.emit(OpCode::GetLocal(slot));
+                self.emit(OpCode::Wrap);
+                self.emit(OpCode::SetLocal(slot));
+                self.emit(OpCode::Pop);
+            }
+        }
+
+        self.statements(&function.body, true)?;
+        self.return_value();
+
+        let state = self.functions.pop().expect("Function must be compiling");
+
+        Ok(Rc::new(Prototype {
+            arity: function.parameters.len(),
+            captures: state.captures,
+            chunk: state.chunk,
+            r#type: function_type(function),
+        }))
+    }
+
+    // Helpers:
+
+    fn current(&mut self) -> &mut FunctionState<'a> {
+        self.functions
+            .last_mut()
+            .expect("A function must be compiling")
+    }
+
+    fn emit(&mut self, op: OpCode) -> usize {
+        let code = &mut self.current().chunk.code;
+        code.push(op);
+
+        code.len() - 1
+    }
+
+    /// Emits an instruction that can fail, recording the token its errors are reported at
+    fn emit_with_token(&mut self, op: OpCode, token: &Token<'a>) -> usize {
+        let index = self.emit(op);
+
+        self.current()
+            .chunk
+            .tokens
+            .push((index as u32, token.clone()));
+
+        index
+    }
+
+    fn position(&mut self) -> u32 {
+        u32::try_from(self.current().chunk.code.len()).expect("Chunk is too large to jump in")
+    }
+
+    /// Points the jump at `index` to the next instruction
+    fn patch(&mut self, index: usize) {
+        let target = self.position();
+        let op = &mut self.current().chunk.code[index];
+
+        *op = match *op {
+            OpCode::Jump(_) => OpCode::Jump(target),
+            OpCode::JumpIfFalse(_) => OpCode::JumpIfFalse(target),
+            OpCode::And(_) => OpCode::And(target),
+            OpCode::Or(_) => OpCode::Or(target),
+            OpCode::Coalesce(_) => OpCode::Coalesce(target),
+            OpCode::Bind(_) => OpCode::Bind(target),
+            op => unreachable!("{:?} is not a jump", op),
+        };
+    }
+
+    fn constant(
+        &mut self,
+        constant: Constant<'a>,
+        token: &Token<'a>,
+    ) -> Result<u16, InterpreterError<'a>> {
+        let constants = &mut self.current().chunk.constants;
+        constants.push(constant);
+
+        Compiler::operand(constants.len() - 1, "constants", token)
+    }
+
+    /// Converts `value` into an instruction operand, failing if it doesn't fit
+    fn operand(value: usize, name: &str, token: &Token<'a>) -> Result<u16, InterpreterError<'a>> {
+        u16::try_from(value).map_err(|_| {
+            InterpreterError::new(format!("Too many {} in one function", name), token.clone())
+        })
+    }
+
+    fn wrap(&mut self, annotation: Option<&TypeAnnotation>) {
+        if annotation.is_some_and(|annotation| annotation.optional) {
+            self.emit(OpCode::Wrap);
+        }
+    }
+
+    fn return_value(&mut self) {
+        if self.current().optional_return {
+            self.emit(OpCode::Wrap);
+        }
+
+        self.emit(OpCode::Return);
+    }
+
+    fn begin_scope(&mut self) {
+        self.current().depth += 1;
+    }
+
+    /// Drops the locals of the innermost scope. When `keep` is set, the value on top of the stack
+    /// is kept above them
+    fn end_scope(&mut self, keep: bool) {
+        let state = self.current();
+        state.depth -= 1;
+
+        let depth = state.depth;
+        let count = state
+            .locals
+            .iter()
+            .rev()
+            .take_while(|local| local.depth > depth)
+            .count();
+
+        state.locals.truncate(state.locals.len() - count);
+
+        if count > 0 {
+            // A frame can't hold more than `u16::MAX` locals, so neither can a scope
+            let count = count as u16;
+
+            self.emit(match keep {
+                true => OpCode::EndScope(count),
+                false => OpCode::PopScope(count),
+            });
+        }
+    }
+
+    fn add_local(&mut self, identifier: &Token<'a>) -> Result<(), InterpreterError<'a>> {
+        let state = self.current();
+
+        Compiler::operand(state.locals.len(), "local variables", identifier)?;
+
+        let depth = state.depth;
+        state.locals.push(Local {
+            name: identifier.lexeme,
+            depth,
+        });
+
+        Ok(())
+    }
+
+    /// Declaring a local twice in the same scope fails when the declaration is reached, like it
+    /// does in the tree-walker
+    fn check_redeclaration(&mut self, identifier: &Token<'a>, record: bool) {
+        let state = self.current();
+        let redeclared = state
+            .locals
+            .iter()
+            .rev()
+            .take_while(|local| local.depth == state.depth)
+            .any(|local| local.name == identifier.lexeme);
+
+        if redeclared {
+            self.emit_with_token(OpCode::Redeclare { record }, identifier);
+        }
+    }
+
+    fn global(&mut self, identifier: &Token<'a>) -> Result<u16, InterpreterError<'a>> {
+        if let Some(index) = self.global_indices.get(identifier.lexeme) {
+            return Ok(*index);
+        }
+
+        let index = Compiler::operand(self.globals.len(), "global variables", identifier)?;
+
+        self.globals.push(identifier.lexeme);
+        self.global_indices.insert(identifier.lexeme, index);
+
+        Ok(index)
+    }
+
+    fn get_variable(&mut self, identifier: &Token<'a>) -> Result<(), InterpreterError<'a>> {
+        match self.resolve(identifier)? {
+            Variable::Local(slot) => self.emit(OpCode::GetLocal(slot)),
+            Variable::Upvalue(index) => self.emit(OpCode::GetUpvalue(index)),
+            Variable::Global(index) => self.emit_with_token(OpCode::GetGlobal(index), identifier),
+        };
+
+        Ok(())
+    }
+
+    fn resolve(&mut self, identifier: &Token<'a>) -> Result<Variable, InterpreterError<'a>> {
+        let function = self.functions.len() - 1;
+
+        if let Some(slot) = self.resolve_local(function, identifier.lexeme) {
+            return Ok(Variable::Local(slot));
+        }
+
+        if let Some(index) = self.resolve_upvalue(function, identifier)? {
+            return Ok(Variable::Upvalue(index));
+        }
+
+        Ok(Variable::Global(self.global(identifier)?))
+    }
+
+    fn resolve_local(&self, function: usize, name: &str) -> Option<u16> {
+        self.functions[function]
+            .locals
+            .iter()
+            .rposition(|local| local.name == name)
+            // Locals are limited to `u16::MAX` when they are added
+            .map(|slot| slot as u16)
+    }
+
+    /// Finds a local of an enclosing function, capturing it through every function in between
+    fn resolve_upvalue(
+        &mut self,
+        function: usize,
+        identifier: &Token<'a>,
+    ) -> Result<Option<u16>, InterpreterError<'a>> {
+        if function == 0 {
+            return Ok(None);
+        }
+
+        let capture = match self.resolve_local(function - 1, identifier.lexeme) {
+            Some(index) => Capture { local: true, index },
+            None => match self.resolve_upvalue(function - 1, identifier)? {
+                Some(index) => Capture {
+                    local: false,
+                    index,
+                },
+                None => return Ok(None),
+            },
+        };
+
+        let captures = &mut self.functions[function].captures;
+
+        if let Some(index) = captures.iter().position(|existing| *existing == capture) {
+            return Ok(Some(index as u16));
+        }
+
+        captures.push(capture);
+
+        Compiler::operand(captures.len() - 1, "captured variables", identifier).map(Some)
+    }
+}
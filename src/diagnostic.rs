@@ -0,0 +1,269 @@
+use std::fmt::Display;
+
+use crate::{
+    interpreter::InterpreterError, parser::ParserError, rename::RenameError,
+    resolver::ResolverError, scanner::ScannerError, token::Token, token::TokenType,
+    type_checker::TypeError,
+};
+
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// The step of the pipeline that found the problem
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stage {
+    Scanner,
+    Parser,
+    Resolver,
+    Type,
+    Runtime,
+    /// A refactoring that was refused, rather than a problem in the program
+    Rename,
+}
+
+impl Display for Stage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Stage::Scanner => write!(f, "Scanner"),
+            Stage::Parser => write!(f, "Parser"),
+            Stage::Resolver => write!(f, "Resolver"),
+            Stage::Type => write!(f, "Type"),
+            Stage::Runtime => write!(f, "Runtime"),
+            Stage::Rename => write!(f, "Rename"),
+        }
+    }
+}
+
+/// Whether a diagnostic stops the program from running
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A problem in the source, reported the same way regardless of which stage found it
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub stage: Stage,
+    pub severity: Severity,
+    pub message: String,
+    pub line: u64,
+    pub position: u64,
+    /// How many characters to underline, starting at `position`
+    pub length: usize,
+    pub note: Option<String>,
+    pub help: Option<&'static str>,
+}
+
+impl Diagnostic {
+    fn at_token(stage: Stage, message: &str, token: &Token) -> Diagnostic {
+        Diagnostic {
+            stage,
+            severity: Severity::Error,
+            message: message.to_owned(),
+            line: token.line,
+            position: token.position,
+            // Only the first line of a multi-line lexeme is shown
+            length: token.lexeme.lines().next().unwrap_or("").chars().count(),
+            note: None,
+            help: None,
+        }
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} at {}:{}. {}",
+            self.stage, self.severity, self.line, self.position, self.message
+        )
+    }
+}
+
+impl From<&ScannerError> for Diagnostic {
+    fn from(error: &ScannerError) -> Self {
+        Diagnostic {
+            stage: Stage::Scanner,
+            severity: Severity::Error,
+            message: error.message.to_owned(),
+            line: error.line,
+            position: error.position,
+            length: 1,
+            note: None,
+            help: None,
+        }
+    }
+}
+
+impl From<&ParserError<'_>> for Diagnostic {
+    fn from(error: &ParserError<'_>) -> Self {
+        Diagnostic {
+            note: error.note.clone(),
+            help: error.help,
+            ..Diagnostic::at_token(Stage::Parser, &error.message, &error.token)
+        }
+    }
+}
+
+impl From<&ResolverError<'_>> for Diagnostic {
+    fn from(error: &ResolverError<'_>) -> Self {
+        Diagnostic {
+            severity: error.severity,
+            note: error.note.clone(),
+            help: error.help,
+            ..Diagnostic::at_token(Stage::Resolver, &error.message, &error.token)
+        }
+    }
+}
+
+impl From<&RenameError<'_>> for Diagnostic {
+    fn from(error: &RenameError<'_>) -> Self {
+        Diagnostic {
+            note: error.note.clone(),
+            help: error.help,
+            ..Diagnostic::at_token(Stage::Rename, &error.message, &error.token)
+        }
+    }
+}
+
+impl From<&TypeError<'_>> for Diagnostic {
+    fn from(error: &TypeError<'_>) -> Self {
+        Diagnostic {
+            note: error.note.clone(),
+            help: error.help,
+            ..Diagnostic::at_token(Stage::Type, &error.message, &error.token)
+        }
+    }
+}
+
+impl From<&InterpreterError<'_>> for Diagnostic {
+    fn from(error: &InterpreterError<'_>) -> Self {
+        Diagnostic {
+            note: error.note.clone(),
+            help: error.help,
+            ..Diagnostic::at_token(Stage::Runtime, &error.message, &error.token)
+        }
+    }
+}
+
+/// Merges the errors of the scanner and the parser, in the order they appear in the source
+pub fn merge(scanner_errors: &[ScannerError], parser_errors: &[ParserError]) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = scanner_errors
+        .iter()
+        .map(Diagnostic::from)
+        .chain(
+            parser_errors
+                .iter()
+                // The scanner already reported the text behind an error token
+                .filter(|error| error.token.token_type != TokenType::Error)
+                .map(Diagnostic::from),
+        )
+        .collect();
+
+    // The sort is stable, so a scanner error comes first when both stages report the same spot
+    diagnostics.sort_by_key(|diagnostic| (diagnostic.line, diagnostic.position));
+
+    diagnostics
+}
+
+/// Renders diagnostics with the line of source they point at, e.g.
+///
+/// ```text
+/// Runtime error: Division by zero
+///  --> main.mt:1:3
+///   |
+/// 1 | 1 / 0;
+///   |   ^
+/// ```
+pub struct Renderer<'a> {
+    pub file_name: &'a str,
+    pub source: &'a str,
+    pub color: bool,
+}
+
+impl Renderer<'_> {
+    pub fn render(&self, diagnostic: &Diagnostic) -> String {
+        let line_number = diagnostic.line.to_string();
+        let gutter = " ".repeat(line_number.len());
+        let color = match diagnostic.severity {
+            Severity::Error => RED,
+            Severity::Warning => YELLOW,
+        };
+
+        let mut output = format!(
+            "{}: {}\n{}{} {}:{}:{}",
+            self.paint(
+                &format!("{} {}", diagnostic.stage, diagnostic.severity),
+                color
+            ),
+            self.paint(&diagnostic.message, BOLD),
+            gutter,
+            self.paint("-->", BLUE),
+            self.file_name,
+            diagnostic.line,
+            diagnostic.position
+        );
+
+        let line = usize::try_from(diagnostic.line)
+            .ok()
+            .and_then(|line| self.source.lines().nth(line.checked_sub(1)?));
+
+        if let Some(line) = line {
+            let column = usize::try_from(diagnostic.position)
+                .unwrap_or(1)
+                .saturating_sub(1);
+            // Keep tabs so the underline lines up with the source however wide they are drawn
+            let padding: String = line
+                .chars()
+                .take(column)
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+            let available = line.chars().count().saturating_sub(column);
+            let underline = "^".repeat(diagnostic.length.min(available).max(1));
+
+            output += &format!(
+                "\n{gutter} {bar}\n{number} {bar} {line}\n{gutter} {bar} {padding}{underline}",
+                bar = self.paint("|", BLUE),
+                number = self.paint(&line_number, BLUE),
+                underline = self.paint(&underline, color),
+            );
+        }
+
+        for (label, text) in [
+            ("note", diagnostic.note.as_deref()),
+            ("help", diagnostic.help),
+        ] {
+            if let Some(text) = text {
+                output += &format!(
+                    "\n{} {} {}",
+                    gutter,
+                    self.paint(&format!("= {}:", label), BOLD),
+                    text
+                );
+            }
+        }
+
+        output
+    }
+
+    fn paint(&self, text: &str, color: &str) -> String {
+        if self.color {
+            format!("{}{}{}", color, text, RESET)
+        } else {
+            text.to_owned()
+        }
+    }
+}
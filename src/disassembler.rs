@@ -0,0 +1,239 @@
+use std::{fmt::Write, rc::Rc};
+
+use crate::{
+    chunk::{Constant, OpCode, Program, Prototype},
+    statement::RecordDeclaration,
+};
+
+/// Renders a compiled program as a listing that `assembler::assemble` can read back.
+///
+/// The main function comes first, followed by the functions it creates in the order they are
+/// referenced. Every instruction shows its offset, the source position of its token (or `|` for
+/// instructions that can't fail), the opcode and its operands, the token's lexeme and, for some
+/// instructions, a `;` comment describing the operands.
+pub fn disassemble(program: &Program) -> String {
+    let mut output = String::from("globals");
+
+    for global in &program.globals {
+        let _ = write!(output, " {}", global);
+    }
+
+    output.push('\n');
+
+    let mut prototypes = vec![Rc::clone(&program.main)];
+    let mut index = 0;
+
+    while let Some(prototype) = prototypes.get(index).cloned() {
+        output.push('\n');
+        function(
+            &mut output,
+            index,
+            &prototype,
+            &mut prototypes,
+            &program.globals,
+        );
+        index += 1;
+    }
+
+    output
+}
+
+fn function<'a>(
+    output: &mut String,
+    index: usize,
+    prototype: &Prototype<'a>,
+    prototypes: &mut Vec<Rc<Prototype<'a>>>,
+    globals: &[&str],
+) {
+    let chunk = &prototype.chunk;
+
+    let _ = writeln!(
+        output,
+        "function {} arity {} type {}",
+        index, prototype.arity, prototype.r#type
+    );
+
+    for capture in &prototype.captures {
+        let kind = if capture.local { "local" } else { "upvalue" };
+        let _ = writeln!(output, "capture {} {}", kind, capture.index);
+    }
+
+    // Nested functions are numbered as they are found, so they are always listed after this one
+    let mut functions = vec![None; chunk.constants.len()];
+
+    for (index, constant) in chunk.constants.iter().enumerate() {
+        if let Constant::Function(nested) = constant {
+            prototypes.push(Rc::clone(nested));
+            functions[index] = Some(prototypes.len() - 1);
+        }
+
+        let _ = writeln!(
+            output,
+            "constant {} {}",
+            index,
+            constant_text(constant, functions[index])
+        );
+    }
+
+    output.push_str("code\n");
+
+    let mut tokens = chunk.tokens.iter().peekable();
+
+    for (offset, op) in chunk.code.iter().enumerate() {
+        let token = tokens
+            .next_if(|(at, _)| *at as usize == offset)
+            .map(|(_, token)| token);
+        let location = match token {
+            Some(token) => format!("{}:{}", token.line, token.position),
+            None => "|".to_owned(),
+        };
+        let (name, operands) = instruction(*op);
+
+        let mut line = format!(
+            "{:04}  {:<7} {:<16} {:<10}",
+            offset,
+            location,
+            name,
+            operands.join(" ")
+        );
+
+        if let Some(token) = token {
+            let _ = write!(line, " {}", token.lexeme);
+        }
+
+        if let Some(comment) = comment(*op, &chunk.constants, &functions, globals) {
+            let _ = write!(line, " ; {}", comment);
+        }
+
+        let _ = writeln!(output, "{}", line.trim_end());
+    }
+}
+
+/// The name of an opcode and its operands, as the assembler expects them
+fn instruction(op: OpCode) -> (&'static str, Vec<String>) {
+    let kind = |record: bool| if record { "record" } else { "variable" }.to_owned();
+
+    match op {
+        OpCode::Constant(index) => ("Constant", vec![index.to_string()]),
+        OpCode::Empty => ("Empty", vec![]),
+        OpCode::None => ("None", vec![]),
+        OpCode::Pop => ("Pop", vec![]),
+        OpCode::GetLocal(slot) => ("GetLocal", vec![slot.to_string()]),
+        OpCode::SetLocal(slot) => ("SetLocal", vec![slot.to_string()]),
+        OpCode::GetUpvalue(index) => ("GetUpvalue", vec![index.to_string()]),
+        OpCode::SetUpvalue(index) => ("SetUpvalue", vec![index.to_string()]),
+        OpCode::GetGlobal(index) => ("GetGlobal", vec![index.to_string()]),
+        OpCode::SetGlobal(index) => ("SetGlobal", vec![index.to_string()]),
+        OpCode::DeclareGlobal { index, record } => {
+            ("DeclareGlobal", vec![index.to_string(), kind(record)])
+        }
+        OpCode::DefineGlobal(index) => ("DefineGlobal", vec![index.to_string()]),
+        OpCode::Redeclare { record } => ("Redeclare", vec![kind(record)]),
+        OpCode::EndScope(count) => ("EndScope", vec![count.to_string()]),
+        OpCode::PopScope(count) => ("PopScope", vec![count.to_string()]),
+        OpCode::Add => ("Add", vec![]),
+        OpCode::Subtract => ("Subtract", vec![]),
+        OpCode::Multiply => ("Multiply", vec![]),
+        OpCode::Divide => ("Divide", vec![]),
+        OpCode::Greater => ("Greater", vec![]),
+        OpCode::GreaterEqual => ("GreaterEqual", vec![]),
+        OpCode::Less => ("Less", vec![]),
+        OpCode::LessEqual => ("LessEqual", vec![]),
+        OpCode::Equal => ("Equal", vec![]),
+        OpCode::NotEqual => ("NotEqual", vec![]),
+        OpCode::BitwiseAnd => ("BitwiseAnd", vec![]),
+        OpCode::BitwiseOr => ("BitwiseOr", vec![]),
+        OpCode::BitwiseXor => ("BitwiseXor", vec![]),
+        OpCode::LeftShift => ("LeftShift", vec![]),
+        OpCode::RightShift => ("RightShift", vec![]),
+        OpCode::Negate => ("Negate", vec![]),
+        OpCode::Not => ("Not", vec![]),
+        OpCode::BitwiseNot => ("BitwiseNot", vec![]),
+        OpCode::Jump(target) => ("Jump", vec![format!("{:04}", target)]),
+        OpCode::JumpIfFalse(target) => ("JumpIfFalse", vec![format!("{:04}", target)]),
+        OpCode::And(target) => ("And", vec![format!("{:04}", target)]),
+        OpCode::Or(target) => ("Or", vec![format!("{:04}", target)]),
+        OpCode::Bool => ("Bool", vec![]),
+        OpCode::Wrap => ("Wrap", vec![]),
+        OpCode::Unwrap => ("Unwrap", vec![]),
+        OpCode::Coalesce(target) => ("Coalesce", vec![format!("{:04}", target)]),
+        OpCode::Bind(target) => ("Bind", vec![format!("{:04}", target)]),
+        OpCode::Closure(index) => ("Closure", vec![index.to_string()]),
+        OpCode::CheckCall(arguments) => ("CheckCall", vec![arguments.to_string()]),
+        OpCode::Call(arguments) => ("Call", vec![arguments.to_string()]),
+        OpCode::Return => ("Return", vec![]),
+        OpCode::Array(length) => ("Array", vec![length.to_string()]),
+        OpCode::CheckArray => ("CheckArray", vec![]),
+        OpCode::GetIndex => ("GetIndex", vec![]),
+        OpCode::CheckIndex => ("CheckIndex", vec![]),
+        OpCode::SetIndex => ("SetIndex", vec![]),
+        OpCode::CheckRecord => ("CheckRecord", vec![]),
+        OpCode::CheckRecordField { name, depth } => (
+            "CheckRecordField",
+            vec![name.to_string(), depth.to_string()],
+        ),
+        OpCode::Record(names) => ("Record", vec![names.to_string()]),
+        OpCode::GetField(name) => ("GetField", vec![name.to_string()]),
+        OpCode::CheckField(name) => ("CheckField", vec![name.to_string()]),
+        OpCode::SetField(name) => ("SetField", vec![name.to_string()]),
+    }
+}
+
+/// Describes operands that refer to constants or globals, which are otherwise just numbers
+fn comment(
+    op: OpCode,
+    constants: &[Constant],
+    functions: &[Option<usize>],
+    globals: &[&str],
+) -> Option<String> {
+    match op {
+        OpCode::Constant(index)
+        | OpCode::CheckRecordField { name: index, .. }
+        | OpCode::Record(index)
+        | OpCode::GetField(index)
+        | OpCode::CheckField(index)
+        | OpCode::SetField(index) => Some(constant_text(&constants[index as usize], None)),
+        OpCode::Closure(index) => {
+            functions[index as usize].map(|function| format!("function {}", function))
+        }
+        OpCode::DeclareGlobal { index, .. } | OpCode::DefineGlobal(index) => {
+            Some(globals[index as usize].to_owned())
+        }
+        _ => None,
+    }
+}
+
+fn constant_text(constant: &Constant, function: Option<usize>) -> String {
+    match constant {
+        Constant::Literal(literal) => format!("literal {}", literal.source_text()),
+        Constant::Name(name) => format!("name {}", name),
+        Constant::Names(names) => {
+            let mut text = String::from("names");
+
+            for name in names {
+                let _ = write!(text, " {}", name);
+            }
+
+            text
+        }
+        Constant::Function(_) => match function {
+            Some(function) => format!("function {}", function),
+            None => "function".to_owned(),
+        },
+        Constant::Record(declaration) => format!("record {}", record_text(declaration)),
+    }
+}
+
+fn record_text(declaration: &RecordDeclaration) -> String {
+    let fields: Vec<String> = declaration
+        .fields
+        .iter()
+        .map(|field| format!("{}: {}", field.identifier.lexeme, field.r#type))
+        .collect();
+
+    if fields.is_empty() {
+        return format!("{} {{}}", declaration.name.lexeme);
+    }
+
+    format!("{} {{ {} }}", declaration.name.lexeme, fields.join(", "))
+}
@@ -6,6 +6,8 @@ use crate::matcha::Value;
 pub struct Environment<'a> {
     pub values: HashMap<String, Value<'a>>,
     pub parent: Option<Rc<RefCell<Environment<'a>>>>,
+    /// How many calls the code running in this environment is nested in
+    pub calls: usize,
 }
 
 impl Default for Environment<'_> {
@@ -19,13 +21,17 @@ impl<'a> Environment<'a> {
         Environment {
             values: HashMap::new(),
             parent: None,
+            calls: 0,
         }
     }
 
     pub fn with_parent(parent: Rc<RefCell<Environment>>) -> Environment {
+        let calls = parent.borrow().calls;
+
         Environment {
             values: HashMap::new(),
             parent: Some(parent),
+            calls,
         }
     }
 }
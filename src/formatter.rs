@@ -0,0 +1,408 @@
+use crate::{
+    diagnostic::{self, Diagnostic},
+    parser::Parser,
+    scanner::Scanner,
+    source::Source,
+    statement::{Expression, FunctionExpression, Statement},
+    token::{Token, TokenType},
+};
+
+const INDENT: &str = "    ";
+
+/// Re-emits `source` with canonical indentation and spacing, keeping its `//` comments and
+/// single blank lines between statements. Fails with the diagnostics of the scanner and the
+/// parser, since only valid code can be formatted
+pub fn format(source: &str) -> Result<String, Vec<Diagnostic>> {
+    let (tokens, scanner_errors) = Scanner {
+        source: Source::new(source),
+    }
+    .scan_with_trivia();
+
+    let (comments, tokens): (Vec<Token>, Vec<Token>) = tokens
+        .into_iter()
+        .filter(|token| token.token_type != TokenType::Whitespace)
+        .partition(|token| token.token_type == TokenType::Comment);
+
+    let statements = match Parser::new(tokens.clone()).parse() {
+        Ok(statements) if scanner_errors.is_empty() => statements,
+        Ok(_) => return Err(diagnostic::merge(&scanner_errors, &[])),
+        Err(errors) => return Err(diagnostic::merge(&scanner_errors, &errors)),
+    };
+
+    let mut formatter = Formatter {
+        source,
+        tokens,
+        comments,
+        comment: 0,
+        indent: 0,
+    };
+    let mut output = String::new();
+
+    formatter.block(&statements, source.len(), &mut output);
+
+    Ok(output)
+}
+
+struct Formatter<'a> {
+    source: &'a str,
+    /// Every token but the comments, to find the punctuation that the AST doesn't keep
+    tokens: Vec<Token<'a>>,
+    comments: Vec<Token<'a>>,
+    /// The first comment that hasn't been written yet
+    comment: usize,
+    indent: usize,
+}
+
+impl<'a> Formatter<'a> {
+    /// Writes `statements` one per line, followed by the comments that come before `end`
+    fn block(&mut self, statements: &[Statement<'a>], end: usize, output: &mut String) {
+        // Where the last statement or comment written ended, to keep the blank lines after it
+        let mut previous = None;
+
+        for statement in statements {
+            let start = statement.span().start;
+            let statement_end = self.statement_end(statement);
+
+            self.comments(start, &mut previous, output);
+            self.separate(previous, start, output);
+
+            let mut text = String::new();
+            self.statement(statement, &mut text);
+
+            // Comments in the middle of an expression can't stay where they were, so they go
+            // above the statement instead
+            while let Some(comment) = self.next_comment(statement_end) {
+                self.line(comment.lexeme.trim_end(), output);
+            }
+
+            output.push_str(&text);
+            previous = Some(statement_end);
+
+            if let Some(comment) = self.trailing_comment(statement_end) {
+                output.pop();
+                output.push(' ');
+                output.push_str(comment.lexeme.trim_end());
+                output.push('\n');
+                previous = Some(comment.span.end);
+            }
+        }
+
+        self.comments(end, &mut previous, output);
+    }
+
+    fn statement(&mut self, statement: &Statement<'a>, output: &mut String) {
+        match statement {
+            Statement::Expression(expression) => {
+                let text = self.expression(expression);
+                let semicolon = match self.token_at(expression.span().end).token_type {
+                    TokenType::SemiColon => ";",
+                    // The last expression of a block may omit its ';'
+                    _ => "",
+                };
+
+                self.line(&format!("{}{}", text, semicolon), output);
+            }
+            Statement::VariableDeclaration(declaration) => {
+                let initializer = self.expression(&declaration.initializer);
+                let text = match declaration.r#type {
+                    Some(ref r#type) => format!(
+                        "{}: {} = {};",
+                        declaration.identifier.lexeme, r#type, initializer
+                    ),
+                    None => format!("{} := {};", declaration.identifier.lexeme, initializer),
+                };
+
+                self.line(&text, output);
+            }
+            Statement::Block(block) => {
+                let body = self.body(&block.statements, block.span.end - 1);
+
+                self.line(&body, output);
+            }
+            Statement::If(if_statement) => {
+                let condition = self.expression(&if_statement.condition);
+                let header = match if_statement.binding {
+                    Some(ref binding) => format!("if {} := {}", binding.lexeme, condition),
+                    None => format!("if {}", condition),
+                };
+
+                // The span only ends at the last brace, so find the one that closes `then`
+                let then_end = self.closing_brace(
+                    if_statement
+                        .statements
+                        .last()
+                        .map_or(if_statement.condition.span().end, |last| last.span().end),
+                );
+                let then = self.body(&if_statement.statements, then_end);
+
+                let text = match if_statement.else_statements {
+                    Some(ref statements) => {
+                        let otherwise = self.body(statements, if_statement.span.end - 1);
+
+                        format!("{} {} else {}", header, then, otherwise)
+                    }
+                    None => format!("{} {}", header, then),
+                };
+
+                self.line(&text, output);
+            }
+            Statement::For(for_statement) => {
+                let condition = self.expression(&for_statement.condition);
+                let body = self.body(&for_statement.statements, for_statement.span.end - 1);
+
+                self.line(&format!("for {} {}", condition, body), output);
+            }
+            Statement::Return(return_statement) => {
+                let text = match return_statement.value {
+                    Some(ref value) => format!("return {};", self.expression(value)),
+                    None => "return;".to_owned(),
+                };
+
+                self.line(&text, output);
+            }
+            Statement::Record(record) => {
+                let fields: Vec<String> = record
+                    .fields
+                    .iter()
+                    .map(|field| format!("{}: {}", field.identifier.lexeme, field.r#type))
+                    .collect();
+
+                self.line(
+                    &format!("record {} {}", record.name.lexeme, braced(&fields)),
+                    output,
+                );
+            }
+        }
+    }
+
+    fn expression(&mut self, expression: &Expression<'a>) -> String {
+        match expression {
+            Expression::Binary(ex) | Expression::Logical(ex) | Expression::Coalesce(ex) => {
+                format!(
+                    "{} {} {}",
+                    self.expression(&ex.left),
+                    ex.operator.lexeme,
+                    self.expression(&ex.right)
+                )
+            }
+            Expression::Unary(ex) => format!("{}{}", ex.operator.lexeme, self.expression(&ex.left)),
+            Expression::Literal(ex) => ex.value.lexeme.to_owned(),
+            Expression::Grouping(ex) => format!("({})", self.expression(&ex.expression)),
+            Expression::Variable(ex) => ex.value.lexeme.to_owned(),
+            Expression::Assignment(ex) => {
+                format!("{} = {}", ex.identifier.lexeme, self.expression(&ex.value))
+            }
+            Expression::Function(ex) => self.function(ex),
+            Expression::Call(ex) => {
+                let callee = self.expression(&ex.callee);
+
+                format!("{}({})", callee, self.list(&ex.arguments))
+            }
+            Expression::Array(ex) => format!("[{}]", self.list(&ex.elements)),
+            Expression::Index(ex) => {
+                format!(
+                    "{}[{}]",
+                    self.expression(&ex.target),
+                    self.expression(&ex.index)
+                )
+            }
+            Expression::IndexAssignment(ex) => format!(
+                "{}[{}] = {}",
+                self.expression(&ex.target),
+                self.expression(&ex.index),
+                self.expression(&ex.value)
+            ),
+            Expression::None(_) => "none".to_owned(),
+            Expression::Unwrap(ex) => format!("{}!", self.expression(&ex.target)),
+            Expression::Record(ex) => {
+                let fields: Vec<String> = ex
+                    .fields
+                    .iter()
+                    .map(|field| {
+                        format!(
+                            "{}: {}",
+                            field.identifier.lexeme,
+                            self.expression(&field.value)
+                        )
+                    })
+                    .collect();
+
+                format!("{} {}", ex.name.lexeme, braced(&fields))
+            }
+            Expression::Field(ex) => format!("{}.{}", self.expression(&ex.target), ex.name.lexeme),
+            Expression::FieldAssignment(ex) => format!(
+                "{}.{} = {}",
+                self.expression(&ex.target),
+                ex.name.lexeme,
+                self.expression(&ex.value)
+            ),
+        }
+    }
+
+    fn function(&mut self, function: &FunctionExpression<'a>) -> String {
+        let parameters: Vec<String> = function
+            .parameters
+            .iter()
+            .map(|parameter| match parameter.r#type {
+                Some(ref r#type) => format!("{}: {}", parameter.identifier.lexeme, r#type),
+                None => parameter.identifier.lexeme.to_owned(),
+            })
+            .collect();
+        let return_type = match function.return_type {
+            Some(ref r#type) => format!(": {}", r#type),
+            None => "".to_owned(),
+        };
+        let end = function.span.end - 1;
+
+        // A body that is just a value, e.g. `fn(a: Int): Int { a * 2 }`, stays on one line
+        if let [Statement::Expression(value)] = function.body.as_slice() {
+            let value_end = value.span().end;
+
+            if self.token_at(value_end).token_type == TokenType::RightBrace
+                && self.next_comment_start().is_none_or(|start| start > end)
+            {
+                let value = self.expression(value);
+
+                if !value.contains('\n') {
+                    return format!(
+                        "fn({}){} {{ {} }}",
+                        parameters.join(", "),
+                        return_type,
+                        value
+                    );
+                }
+            }
+        }
+
+        format!(
+            "fn({}){} {}",
+            parameters.join(", "),
+            return_type,
+            self.body(&function.body, end)
+        )
+    }
+
+    /// Formats a comma separated list of expressions
+    fn list(&mut self, expressions: &[Expression<'a>]) -> String {
+        let expressions: Vec<String> = expressions
+            .iter()
+            .map(|expression| self.expression(expression))
+            .collect();
+
+        expressions.join(", ")
+    }
+
+    /// Formats a block whose closing brace is at `end`, one level deeper than the current line
+    fn body(&mut self, statements: &[Statement<'a>], end: usize) -> String {
+        if statements.is_empty() && self.next_comment_start().is_none_or(|start| start > end) {
+            return "{}".to_owned();
+        }
+
+        let mut output = String::from("{\n");
+
+        self.indent += 1;
+        self.block(statements, end, &mut output);
+        self.indent -= 1;
+
+        output.push_str(&INDENT.repeat(self.indent));
+        output.push('}');
+
+        output
+    }
+
+    /// Writes the comments that start before `end` on their own lines
+    fn comments(&mut self, end: usize, previous: &mut Option<usize>, output: &mut String) {
+        while let Some(comment) = self.next_comment(end) {
+            self.separate(*previous, comment.span.start, output);
+            self.line(comment.lexeme.trim_end(), output);
+            *previous = Some(comment.span.end);
+        }
+    }
+
+    /// Keeps one blank line between `previous` and `start` if there was at least one
+    fn separate(&self, previous: Option<usize>, start: usize, output: &mut String) {
+        if previous.is_some_and(|previous| self.source[previous..start].matches('\n').count() > 1) {
+            output.push('\n');
+        }
+    }
+
+    fn line(&self, text: &str, output: &mut String) {
+        output.push_str(&INDENT.repeat(self.indent));
+        output.push_str(text);
+        output.push('\n');
+    }
+
+    fn next_comment_start(&self) -> Option<usize> {
+        self.comments
+            .get(self.comment)
+            .map(|comment| comment.span.start)
+    }
+
+    /// Takes the next comment if it starts before `end`
+    fn next_comment(&mut self, end: usize) -> Option<Token<'a>> {
+        let comment = self.comments.get(self.comment)?;
+
+        if comment.span.start >= end {
+            return None;
+        }
+
+        self.comment += 1;
+        Some(comment.clone())
+    }
+
+    /// Takes the next comment if it is on the same line as `end`, with nothing else before it
+    fn trailing_comment(&mut self, end: usize) -> Option<Token<'a>> {
+        let start = self.next_comment_start()?;
+
+        if self.source[end..start].contains('\n') || self.token_at(end).span.start < start {
+            return None;
+        }
+
+        self.next_comment(start + 1)
+    }
+
+    /// Where a statement ends, including its `;`
+    fn statement_end(&self, statement: &Statement<'a>) -> usize {
+        let end = statement.span().end;
+        let next = self.token_at(end);
+
+        match statement {
+            Statement::Expression(_) | Statement::VariableDeclaration(_) | Statement::Return(_)
+                if next.token_type == TokenType::SemiColon =>
+            {
+                next.span.end
+            }
+            _ => end,
+        }
+    }
+
+    /// The first `}` that starts at or after `offset`
+    fn closing_brace(&self, offset: usize) -> usize {
+        let index = self
+            .tokens
+            .partition_point(|token| token.span.start < offset);
+
+        self.tokens[index..]
+            .iter()
+            .find(|token| token.token_type == TokenType::RightBrace)
+            .map_or(self.source.len(), |token| token.span.start)
+    }
+
+    /// The first token that starts at or after `offset`, which is `Eof` past the last one
+    fn token_at(&self, offset: usize) -> &Token<'a> {
+        let index = self
+            .tokens
+            .partition_point(|token| token.span.start < offset);
+
+        &self.tokens[index.min(self.tokens.len() - 1)]
+    }
+}
+
+/// `{ a, b }`, or `{}` when there is nothing to put inside
+fn braced(items: &[String]) -> String {
+    if items.is_empty() {
+        return "{}".to_owned();
+    }
+
+    format!("{{ {} }}", items.join(", "))
+}
@@ -0,0 +1,352 @@
+use std::collections::HashMap;
+
+use crate::{
+    json::Json,
+    parser::Parser,
+    resolver::Resolver,
+    scanner::Scanner,
+    source::Source,
+    statement::{Expression, FunctionExpression, Statement, TypeAnnotation},
+    token::{Token, TokenType},
+};
+
+/// What a token is to someone reading the code, named like the semantic token types of LSP
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Keyword,
+    Variable,
+    /// A variable declared with a function
+    Function,
+    Parameter,
+    /// A record or a built-in type
+    Type,
+    /// A record field
+    Property,
+    String,
+    Number,
+    Operator,
+    Punctuation,
+    Comment,
+    Whitespace,
+    Error,
+}
+
+impl Kind {
+    pub fn name(self) -> &'static str {
+        match self {
+            Kind::Keyword => "keyword",
+            Kind::Variable => "variable",
+            Kind::Function => "function",
+            Kind::Parameter => "parameter",
+            Kind::Type => "type",
+            Kind::Property => "property",
+            Kind::String => "string",
+            Kind::Number => "number",
+            Kind::Operator => "operator",
+            Kind::Punctuation => "punctuation",
+            Kind::Comment => "comment",
+            Kind::Whitespace => "whitespace",
+            Kind::Error => "error",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Modifier {
+    /// The name is being declared
+    Declaration,
+    /// The name refers to a declaration
+    Reference,
+}
+
+impl Modifier {
+    pub fn name(self) -> &'static str {
+        match self {
+            Modifier::Declaration => "declaration",
+            Modifier::Reference => "reference",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Highlight<'a> {
+    pub token: Token<'a>,
+    pub kind: Kind,
+    pub modifiers: Vec<Modifier>,
+}
+
+impl Highlight<'_> {
+    /// `{"kind", "token", "text", "line", "position", "span", "modifiers"}`, with the token type
+    /// as the scanner names it
+    pub fn to_json(&self) -> Json {
+        Json::object([
+            ("kind", self.kind.name().into()),
+            ("token", format!("{:?}", self.token.token_type).into()),
+            ("text", self.token.lexeme.into()),
+            ("line", (self.token.line as usize).into()),
+            ("position", (self.token.position as usize).into()),
+            (
+                "span",
+                Json::object([
+                    ("start", self.token.span.start.into()),
+                    ("end", self.token.span.end.into()),
+                ]),
+            ),
+            (
+                "modifiers",
+                self.modifiers
+                    .iter()
+                    .map(|modifier| modifier.name().into())
+                    .collect::<Vec<Json>>()
+                    .into(),
+            ),
+        ])
+    }
+}
+
+/// Classifies every token of `source`, comments and whitespace included, so that their text put
+/// together is the whole source. Names are told apart with the parser and the resolver, and are
+/// all plain variables when the source doesn't parse
+pub fn highlight(source: &str) -> Vec<Highlight<'_>> {
+    let (mut tokens, scanner_errors) = Scanner {
+        source: Source::new(source),
+    }
+    .scan_with_trivia();
+
+    let code: Vec<Token> = tokens
+        .iter()
+        .filter(|token| !token.token_type.is_trivia())
+        .cloned()
+        .collect();
+    let mut names = Names::default();
+
+    // Eof has no text to highlight
+    tokens.pop();
+
+    if let (true, Ok(mut statements)) = (scanner_errors.is_empty(), Parser::new(code).parse()) {
+        let (_, references) = Resolver::new().resolve_references(&mut statements);
+
+        names.statements(&statements);
+
+        for reference in references {
+            let declaration = reference.declaration.span.start;
+            let kind = names.kinds.get(&declaration).copied();
+            let modifier = match reference.name.span == reference.declaration.span {
+                true => Modifier::Declaration,
+                false => Modifier::Reference,
+            };
+
+            let name = names
+                .names
+                .entry(reference.name.span.start)
+                .or_insert((Kind::Variable, None));
+            name.0 = kind.unwrap_or(name.0);
+            name.1 = Some(modifier);
+        }
+    }
+
+    tokens
+        .into_iter()
+        .map(|token| {
+            let (kind, modifier) = match token.token_type {
+                TokenType::Identifier => names
+                    .names
+                    .get(&token.span.start)
+                    .copied()
+                    .unwrap_or((Kind::Variable, None)),
+                token_type => (lexical_kind(token_type), None),
+            };
+
+            Highlight {
+                token,
+                kind,
+                modifiers: modifier.into_iter().collect(),
+            }
+        })
+        .collect()
+}
+
+fn lexical_kind(token_type: TokenType) -> Kind {
+    match token_type {
+        TokenType::If
+        | TokenType::Else
+        | TokenType::True
+        | TokenType::False
+        | TokenType::For
+        | TokenType::Fn
+        | TokenType::Return
+        | TokenType::None
+        | TokenType::Record => Kind::Keyword,
+        TokenType::Identifier => Kind::Variable,
+        TokenType::String => Kind::String,
+        TokenType::Integer | TokenType::Float => Kind::Number,
+        TokenType::LeftParen
+        | TokenType::RightParen
+        | TokenType::LeftBrace
+        | TokenType::RightBrace
+        | TokenType::LeftBracket
+        | TokenType::RightBracket
+        | TokenType::Comma
+        | TokenType::Dot
+        | TokenType::Colon
+        | TokenType::SemiColon => Kind::Punctuation,
+        TokenType::Comment => Kind::Comment,
+        TokenType::Whitespace => Kind::Whitespace,
+        TokenType::Error | TokenType::Eof => Kind::Error,
+        _ => Kind::Operator,
+    }
+}
+
+/// The kinds of the names in a program, by where they start
+#[derive(Default)]
+struct Names {
+    /// What each declaration declares
+    kinds: HashMap<usize, Kind>,
+    /// Every name that isn't a plain variable, with whether it declares or refers to something
+    names: HashMap<usize, (Kind, Option<Modifier>)>,
+}
+
+impl Names {
+    fn declare(&mut self, name: &Token, kind: Kind) {
+        self.kinds.insert(name.span.start, kind);
+        self.names
+            .insert(name.span.start, (kind, Some(Modifier::Declaration)));
+    }
+
+    fn name(&mut self, name: &Token, kind: Kind) {
+        self.names.insert(name.span.start, (kind, None));
+    }
+
+    fn annotation(&mut self, annotation: Option<&TypeAnnotation>) {
+        let Some(annotation) = annotation else {
+            return;
+        };
+
+        match annotation.function {
+            // The `fn` of a function type is highlighted as the keyword it is
+            Some(ref function) => {
+                for parameter in &function.parameters {
+                    self.annotation(Some(parameter));
+                }
+
+                self.annotation(function.return_type.as_deref());
+            }
+            None => self.name(&annotation.name, Kind::Type),
+        }
+    }
+
+    fn statements(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Expression(expression) => self.expression(expression),
+            Statement::VariableDeclaration(declaration) => {
+                let kind = match declaration.initializer {
+                    Expression::Function(_) => Kind::Function,
+                    _ => Kind::Variable,
+                };
+
+                self.declare(&declaration.identifier, kind);
+                self.annotation(declaration.r#type.as_ref());
+                self.expression(&declaration.initializer);
+            }
+            Statement::Block(block) => self.statements(&block.statements),
+            Statement::If(if_statement) => {
+                if let Some(ref binding) = if_statement.binding {
+                    self.declare(binding, Kind::Variable);
+                }
+
+                self.expression(&if_statement.condition);
+                self.statements(&if_statement.statements);
+
+                if let Some(ref statements) = if_statement.else_statements {
+                    self.statements(statements);
+                }
+            }
+            Statement::For(for_statement) => {
+                self.expression(&for_statement.condition);
+                self.statements(&for_statement.statements);
+            }
+            Statement::Return(return_statement) => {
+                if let Some(ref value) = return_statement.value {
+                    self.expression(value);
+                }
+            }
+            Statement::Record(record) => {
+                self.declare(&record.name, Kind::Type);
+
+                for field in &record.fields {
+                    self.declare(&field.identifier, Kind::Property);
+                    self.annotation(Some(&field.r#type));
+                }
+            }
+        }
+    }
+
+    fn expression(&mut self, expression: &Expression) {
+        match expression {
+            Expression::Binary(ex) | Expression::Logical(ex) | Expression::Coalesce(ex) => {
+                self.expression(&ex.left);
+                self.expression(&ex.right);
+            }
+            Expression::Unary(ex) => self.expression(&ex.left),
+            Expression::Grouping(ex) => self.expression(&ex.expression),
+            Expression::Literal(_) | Expression::Variable(_) | Expression::None(_) => {}
+            Expression::Assignment(ex) => self.expression(&ex.value),
+            Expression::Function(ex) => self.function(ex),
+            Expression::Call(ex) => {
+                self.expression(&ex.callee);
+
+                for argument in &ex.arguments {
+                    self.expression(argument);
+                }
+            }
+            Expression::Array(ex) => {
+                for element in &ex.elements {
+                    self.expression(element);
+                }
+            }
+            Expression::Index(ex) => {
+                self.expression(&ex.target);
+                self.expression(&ex.index);
+            }
+            Expression::IndexAssignment(ex) => {
+                self.expression(&ex.target);
+                self.expression(&ex.index);
+                self.expression(&ex.value);
+            }
+            Expression::Unwrap(ex) => self.expression(&ex.target),
+            Expression::Record(ex) => {
+                self.name(&ex.name, Kind::Type);
+
+                for field in &ex.fields {
+                    self.name(&field.identifier, Kind::Property);
+                    self.expression(&field.value);
+                }
+            }
+            Expression::Field(ex) => {
+                self.expression(&ex.target);
+                self.name(&ex.name, Kind::Property);
+            }
+            Expression::FieldAssignment(ex) => {
+                self.expression(&ex.target);
+                self.name(&ex.name, Kind::Property);
+                self.expression(&ex.value);
+            }
+        }
+    }
+
+    fn function(&mut self, function: &FunctionExpression) {
+        for parameter in &function.parameters {
+            self.declare(&parameter.identifier, Kind::Parameter);
+            self.annotation(parameter.r#type.as_ref());
+        }
+
+        self.annotation(function.return_type.as_ref());
+        self.statements(&function.body);
+    }
+}
@@ -2,13 +2,15 @@ use std::{cell::RefCell, fmt::Display, rc::Rc};
 
 use crate::{
     environment::Environment,
-    matcha::{Literal, NumberLiteral, Value},
+    matcha::{Array, Function, Literal, NumberLiteral, Record, RecordValue, Value},
     statement::{
-        AssignmentExpression, BinaryExpression, Expression, ForStatement, GroupingExpression,
-        IfStatement, LiteralExpression, Statement, UnaryExpression, VariableDeclaration,
-        VariableExpression,
+        ArrayExpression, AssignmentExpression, BinaryExpression, CallExpression, Expression,
+        FieldAssignmentExpression, FieldExpression, ForStatement, GroupingExpression, IfStatement,
+        IndexAssignmentExpression, IndexExpression, LiteralExpression, RecordDeclaration,
+        RecordExpression, ReturnStatement, Statement, TypeAnnotation, UnaryExpression,
+        UnwrapExpression, VariableDeclaration, VariableExpression,
     },
-    token::TokenType,
+    token::{Token, TokenType},
 };
 
 const NULLABLE_VALUE_OPERATION_ERROR_MESSAGE: &str =
@@ -16,15 +18,62 @@ const NULLABLE_VALUE_OPERATION_ERROR_MESSAGE: &str =
 const EMPTY_VALUE_OPERATION_ERROR_MESSAGE: &str =
     "Cannot execute a unary operation in an empty value";
 
+/// How deeply calls can nest before running out of stack is reported as an error, shared with the
+/// VM so that both stop at the same call
+pub const MAX_CALL_DEPTH: usize = 1000;
+
+/// The native stack needed to nest `MAX_CALL_DEPTH` calls in the tree-walker, with room to spare
+/// for debug builds, which use several kilobytes per call
+pub const STACK_SIZE: usize = 64 * 1024 * 1024;
+
 #[derive(Debug)]
 pub struct InterpreterError<'a> {
     pub message: String,
-    pub statement: Statement<'a>,
+    pub token: Token<'a>,
+    pub note: Option<String>,
+    pub help: Option<&'static str>,
+}
+
+impl<'a> InterpreterError<'a> {
+    pub fn new(message: String, token: Token<'a>) -> InterpreterError<'a> {
+        InterpreterError {
+            message,
+            token,
+            note: None,
+            help: None,
+        }
+    }
+
+    pub fn with_note(mut self, note: String) -> Self {
+        self.note = Some(note);
+        self
+    }
+
+    pub fn with_help(mut self, help: &'static str) -> Self {
+        self.help = Some(help);
+        self
+    }
 }
 
 impl Display for InterpreterError<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Runtime error at {}:{}. {}", self.statement)
+        write!(
+            f,
+            "Runtime error at {}:{}. {}",
+            self.token.line, self.token.position, self.message
+        )
+    }
+}
+
+/// Interrupts the execution of statements, either because of an error or a `return`
+enum Unwind<'a> {
+    Error(InterpreterError<'a>),
+    Return(Value<'a>),
+}
+
+impl<'a> From<InterpreterError<'a>> for Unwind<'a> {
+    fn from(error: InterpreterError<'a>) -> Self {
+        Unwind::Error(error)
     }
 }
 
@@ -35,32 +84,48 @@ impl<'a> Interpreter {
         environment: Rc<RefCell<Environment<'a>>>,
         statements: &'b [Statement<'a>],
     ) -> Result<Value<'a>, InterpreterError<'a>> {
-        for i in 0..statements.len() {
-            // Return last value
-            if i == statements.len() - 1 {
-                return Interpreter::evaluate(environment, &statements[i]);
-            }
+        match Interpreter::statements(environment, statements) {
+            // The parser rejects `return` outside of functions, but treat it as the result anyway
+            Ok(value) | Err(Unwind::Return(value)) => Ok(value),
+            Err(Unwind::Error(error)) => Err(error),
+        }
+    }
+
+    fn statements<'b>(
+        environment: Rc<RefCell<Environment<'a>>>,
+        statements: &'b [Statement<'a>],
+    ) -> Result<Value<'a>, Unwind<'a>> {
+        let mut result = Value::Empty;
 
-            Interpreter::evaluate(Rc::clone(&environment), &statements[i])?;
+        for statement in statements {
+            // Only the value of the last statement is returned
+            result = Interpreter::evaluate(Rc::clone(&environment), statement)?;
         }
 
-        Ok(Value::Empty)
+        Ok(result)
     }
 
     fn evaluate<'b>(
         environment: Rc<RefCell<Environment<'a>>>,
         statement: &'b Statement<'a>,
-    ) -> Result<Value<'a>, InterpreterError<'a>> {
+    ) -> Result<Value<'a>, Unwind<'a>> {
         match statement {
             Statement::VariableDeclaration(decl) => {
                 Interpreter::variable_declaration(environment, decl)?;
                 Ok(Value::Empty)
             }
-            Statement::Expression(expression) => Interpreter::expression(environment, expression),
-            Statement::Block(block) => Interpreter::block(environment, block),
+            Statement::Expression(expression) => {
+                Ok(Interpreter::expression(environment, expression)?)
+            }
+            Statement::Block(block) => Interpreter::block(environment, &block.statements),
             Statement::If(if_statement) => Interpreter::if_statement(environment, if_statement),
-            Statement::While(while_statement) => {
-                Interpreter::while_statement(environment, while_statement)
+            Statement::For(for_statement) => Interpreter::for_statement(environment, for_statement),
+            Statement::Return(return_statement) => {
+                Err(Interpreter::return_statement(environment, return_statement))
+            }
+            Statement::Record(record) => {
+                Interpreter::record_declaration(environment, record)?;
+                Ok(Value::Empty)
             }
         }
     }
@@ -82,21 +147,411 @@ impl<'a> Interpreter {
             }
             Expression::Assignment(assignment) => Interpreter::assign(environment, assignment),
             Expression::Logical(logical) => Interpreter::logical(environment, logical),
+            Expression::Function(function) => Ok(Value::Function(Rc::new(Function {
+                declaration: function.clone(),
+                closure: environment,
+            }))),
+            Expression::Call(call) => Interpreter::call(environment, call),
+            Expression::Array(array) => Interpreter::array(environment, array),
+            Expression::Index(index) => Interpreter::index(environment, index),
+            Expression::IndexAssignment(assignment) => {
+                Interpreter::index_assignment(environment, assignment)
+            }
+            Expression::None(_) => Ok(Value::Optional(None)),
+            Expression::Unwrap(unwrap) => Interpreter::unwrap(environment, unwrap),
+            Expression::Coalesce(coalesce) => Interpreter::coalesce(environment, coalesce),
+            Expression::Record(record) => Interpreter::record(environment, record),
+            Expression::Field(field) => Interpreter::field(environment, field),
+            Expression::FieldAssignment(assignment) => {
+                Interpreter::field_assignment(environment, assignment)
+            }
         }
     }
 
-    fn literal(literal: &LiteralExpression<'a>) -> Result<Value<'a>, InterpreterError<'a>> {
-        let value = &literal.value.literal;
+    fn record_declaration<'b>(
+        environment: Rc<RefCell<Environment<'a>>>,
+        record: &'b RecordDeclaration<'a>,
+    ) -> Result<(), InterpreterError<'a>> {
+        let mut environment = environment.borrow_mut();
+
+        if environment.values.contains_key(record.name.lexeme) {
+            return Err(InterpreterError::new(
+                format!(
+                    "Record '{}' already declared in this scope",
+                    record.name.lexeme
+                ),
+                record.name.clone(),
+            ));
+        }
+
+        environment.values.insert(
+            record.name.lexeme.to_owned(),
+            Value::RecordDeclaration(Rc::new(record.clone())),
+        );
+
+        Ok(())
+    }
+
+    fn record<'b>(
+        environment: Rc<RefCell<Environment<'a>>>,
+        record: &'b RecordExpression<'a>,
+    ) -> Result<Value<'a>, InterpreterError<'a>> {
+        let declaration = Interpreter::record_declaration_of(
+            Interpreter::lookup(&environment.borrow(), &record.name, None)?,
+            &record.name,
+        )?;
+
+        let mut fields: Vec<Option<Value>> = vec![None; declaration.fields.len()];
+
+        for initializer in &record.fields {
+            let index = Interpreter::initializer_index(&declaration, &initializer.identifier)?;
+            let value = Interpreter::expression(Rc::clone(&environment), &initializer.value)?;
+
+            fields[index] = Some(value);
+        }
+
+        Interpreter::record_value(declaration, fields, &record.name)
+    }
+
+    /// Checks that the value named in a record literal is a record declaration
+    pub fn record_declaration_of(
+        value: Value<'a>,
+        name: &Token<'a>,
+    ) -> Result<Rc<RecordDeclaration<'a>>, InterpreterError<'a>> {
         match value {
-            Some(value) => Ok(Value::Literal(value.clone())),
-            None => Err(InterpreterError {
-                message: "Literal expression value is None. This should never be the case."
-                    .to_owned(),
-                statement: Statement::Expression(Expression::Literal(literal.clone())),
-            }),
+            Value::RecordDeclaration(declaration) => Ok(declaration),
+            value => Err(InterpreterError::new(
+                format!(
+                    "'{}' is not a record, got {}",
+                    name.lexeme,
+                    value.get_type()
+                ),
+                name.clone(),
+            )),
         }
     }
 
+    /// The position in `declaration` of the field set by a record literal
+    pub fn initializer_index(
+        declaration: &RecordDeclaration<'a>,
+        identifier: &Token<'a>,
+    ) -> Result<usize, InterpreterError<'a>> {
+        declaration
+            .fields
+            .iter()
+            .position(|field| field.identifier.lexeme == identifier.lexeme)
+            .ok_or_else(|| {
+                InterpreterError::new(
+                    format!(
+                        "Record {} has no field '{}'",
+                        declaration.name.lexeme, identifier.lexeme
+                    ),
+                    identifier.clone(),
+                )
+            })
+    }
+
+    /// Builds a record from the values of its fields, in declaration order, failing if any is
+    /// missing
+    pub fn record_value(
+        declaration: Rc<RecordDeclaration<'a>>,
+        fields: Vec<Option<Value<'a>>>,
+        name: &Token<'a>,
+    ) -> Result<Value<'a>, InterpreterError<'a>> {
+        let fields = fields
+            .into_iter()
+            .zip(declaration.fields.iter())
+            .map(|(value, field)| match value {
+                Some(value) => Ok(Interpreter::wrap(value, Some(&field.r#type))),
+                None => Err(InterpreterError::new(
+                    format!(
+                        "Missing field '{}' in {}",
+                        field.identifier.lexeme, declaration.name.lexeme
+                    ),
+                    name.clone(),
+                )),
+            })
+            .collect::<Result<Vec<Value>, InterpreterError>>()?;
+
+        Ok(Value::Record(Rc::new(RefCell::new(RecordValue {
+            declaration,
+            fields,
+        }))))
+    }
+
+    fn field<'b>(
+        environment: Rc<RefCell<Environment<'a>>>,
+        field: &'b FieldExpression<'a>,
+    ) -> Result<Value<'a>, InterpreterError<'a>> {
+        let (record, index) = Interpreter::record_field(environment, &field.target, &field.name)?;
+
+        let value = record.borrow().fields[index].clone();
+
+        Ok(value)
+    }
+
+    fn field_assignment<'b>(
+        environment: Rc<RefCell<Environment<'a>>>,
+        assignment: &'b FieldAssignmentExpression<'a>,
+    ) -> Result<Value<'a>, InterpreterError<'a>> {
+        let (record, index) = Interpreter::record_field(
+            Rc::clone(&environment),
+            &assignment.target,
+            &assignment.name,
+        )?;
+
+        let value = Interpreter::expression(environment, &assignment.value)?;
+
+        let mut record = record.borrow_mut();
+        record.fields[index] = Interpreter::reassign(&record.fields[index], value);
+
+        Ok(Value::Empty)
+    }
+
+    /// Evaluates `target.name` into the record and the position of the field
+    fn record_field<'b>(
+        environment: Rc<RefCell<Environment<'a>>>,
+        target: &'b Expression<'a>,
+        name: &'b Token<'a>,
+    ) -> Result<(Record<'a>, usize), InterpreterError<'a>> {
+        Interpreter::field_of(Interpreter::expression(environment, target)?, name)
+    }
+
+    /// The record held by `value` and the position of its field called `name`
+    pub fn field_of(
+        value: Value<'a>,
+        name: &Token<'a>,
+    ) -> Result<(Record<'a>, usize), InterpreterError<'a>> {
+        let record = match value {
+            Value::Record(record) => record,
+            value => {
+                return Err(InterpreterError::new(
+                    format!(
+                        "Can only access fields on records, got {}",
+                        value.get_type()
+                    ),
+                    name.clone(),
+                ))
+            }
+        };
+
+        let index = record.borrow().field_index(name.lexeme);
+
+        match index {
+            Some(index) => Ok((record, index)),
+            None => Err(InterpreterError::new(
+                format!(
+                    "Record {} has no field '{}'",
+                    record.borrow().declaration.name.lexeme,
+                    name.lexeme
+                ),
+                name.clone(),
+            )),
+        }
+    }
+
+    fn unwrap<'b>(
+        environment: Rc<RefCell<Environment<'a>>>,
+        unwrap: &'b UnwrapExpression<'a>,
+    ) -> Result<Value<'a>, InterpreterError<'a>> {
+        Interpreter::unwrap_optional(
+            Interpreter::expression(environment, &unwrap.target)?,
+            &unwrap.operator,
+        )
+    }
+
+    /// The value held by an optional, failing on `none` and on non-optional values
+    pub fn unwrap_optional(
+        value: Value<'a>,
+        operator: &Token<'a>,
+    ) -> Result<Value<'a>, InterpreterError<'a>> {
+        match value {
+            Value::Optional(Some(value)) => Ok(*value),
+            Value::Optional(None) => Err(InterpreterError::new(
+                "Cannot unwrap a none value".to_owned(),
+                operator.clone(),
+            )
+            .with_help(
+                "provide a fallback with `??`, or check for a value with `if x := ... { }`",
+            )),
+            value => Err(InterpreterError::new(
+                format!(
+                    "Cannot unwrap a non-optional value of type {}",
+                    value.get_type()
+                ),
+                operator.clone(),
+            )),
+        }
+    }
+
+    fn coalesce<'b>(
+        environment: Rc<RefCell<Environment<'a>>>,
+        coalesce: &'b BinaryExpression<'a>,
+    ) -> Result<Value<'a>, InterpreterError<'a>> {
+        let left = Interpreter::expression(Rc::clone(&environment), &coalesce.left)?;
+
+        match Interpreter::coalesced(left, &coalesce.operator)? {
+            Some(value) => Ok(value),
+            // The fallback is only evaluated when needed
+            None => Interpreter::expression(environment, &coalesce.right),
+        }
+    }
+
+    /// The value held by the left side of `??`, or `None` when the fallback should be used
+    pub fn coalesced(
+        value: Value<'a>,
+        operator: &Token<'a>,
+    ) -> Result<Option<Value<'a>>, InterpreterError<'a>> {
+        match value {
+            Value::Optional(value) => Ok(value.map(|value| *value)),
+            value => Err(InterpreterError::new(
+                format!(
+                    "Operator '??' expects an optional value, got {}",
+                    value.get_type()
+                ),
+                operator.clone(),
+            )),
+        }
+    }
+
+    /// The value that replaces `previous` on assignment. Optionals keep being optionals
+    pub fn reassign(previous: &Value<'a>, value: Value<'a>) -> Value<'a> {
+        match (previous, value) {
+            (Value::Optional(_), value @ Value::Optional(_)) => value,
+            (Value::Optional(_), value) => Value::Optional(Some(Box::new(value))),
+            (_, value) => value,
+        }
+    }
+
+    /// Wraps values stored into an optional annotation, so that `x: Int? = 5;` holds an optional
+    fn wrap(value: Value<'a>, annotation: Option<&TypeAnnotation>) -> Value<'a> {
+        match annotation {
+            Some(annotation) if annotation.optional => Interpreter::optional(value),
+            _ => value,
+        }
+    }
+
+    /// Wraps a value into an optional, unless it already is one
+    pub fn optional(value: Value<'a>) -> Value<'a> {
+        match value {
+            value @ Value::Optional(_) => value,
+            value => Value::Optional(Some(Box::new(value))),
+        }
+    }
+
+    fn array<'b>(
+        environment: Rc<RefCell<Environment<'a>>>,
+        array: &'b ArrayExpression<'a>,
+    ) -> Result<Value<'a>, InterpreterError<'a>> {
+        let elements = array
+            .elements
+            .iter()
+            .map(|element| Interpreter::expression(Rc::clone(&environment), element))
+            .collect::<Result<Vec<Value>, InterpreterError>>()?;
+
+        Ok(Value::Array(Rc::new(RefCell::new(elements))))
+    }
+
+    fn index<'b>(
+        environment: Rc<RefCell<Environment<'a>>>,
+        index: &'b IndexExpression<'a>,
+    ) -> Result<Value<'a>, InterpreterError<'a>> {
+        let (elements, position) =
+            Interpreter::array_element(environment, &index.target, &index.index, &index.bracket)?;
+
+        let value = elements.borrow()[position].clone();
+
+        Ok(value)
+    }
+
+    fn index_assignment<'b>(
+        environment: Rc<RefCell<Environment<'a>>>,
+        assignment: &'b IndexAssignmentExpression<'a>,
+    ) -> Result<Value<'a>, InterpreterError<'a>> {
+        let (elements, position) = Interpreter::array_element(
+            Rc::clone(&environment),
+            &assignment.target,
+            &assignment.index,
+            &assignment.bracket,
+        )?;
+
+        let value = Interpreter::expression(environment, &assignment.value)?;
+
+        let mut elements = elements.borrow_mut();
+        elements[position] = Interpreter::reassign(&elements[position], value);
+
+        Ok(Value::Empty)
+    }
+
+    /// Evaluates `target[index]` into the array and a position that is known to be in bounds
+    fn array_element<'b>(
+        environment: Rc<RefCell<Environment<'a>>>,
+        target: &'b Expression<'a>,
+        index: &'b Expression<'a>,
+        bracket: &'b Token<'a>,
+    ) -> Result<(Array<'a>, usize), InterpreterError<'a>> {
+        let elements = Interpreter::array_of(
+            Interpreter::expression(Rc::clone(&environment), target)?,
+            bracket,
+        )?;
+        let index = Interpreter::expression(environment, index)?;
+        let position = Interpreter::position_of(&elements, index, bracket)?;
+
+        Ok((elements, position))
+    }
+
+    /// Checks that an indexed value is an array
+    pub fn array_of(
+        value: Value<'a>,
+        bracket: &Token<'a>,
+    ) -> Result<Array<'a>, InterpreterError<'a>> {
+        match value {
+            Value::Array(elements) => Ok(elements),
+            value => Err(InterpreterError::new(
+                format!("Can only index arrays, got {}", value.get_type()),
+                bracket.clone(),
+            )),
+        }
+    }
+
+    /// Converts an index into a position that is known to be in bounds of `elements`
+    pub fn position_of(
+        elements: &Array<'a>,
+        index: Value<'a>,
+        bracket: &Token<'a>,
+    ) -> Result<usize, InterpreterError<'a>> {
+        let position = match index {
+            Value::Literal(Literal::Number(NumberLiteral::Integer(integer))) => integer,
+            value => {
+                return Err(InterpreterError::new(
+                    format!("Array index must be an Int, got {}", value.get_type()),
+                    bracket.clone(),
+                ))
+            }
+        };
+
+        let length = elements.borrow().len();
+
+        match usize::try_from(position) {
+            Ok(position) if position < length => Ok(position),
+            _ => Err(InterpreterError::new(
+                format!(
+                    "Index {} out of bounds for array of length {}",
+                    position, length
+                ),
+                bracket.clone(),
+            )
+            .with_note(match length {
+                0 => "the array is empty".to_owned(),
+                _ => format!("valid indices are 0 to {}", length - 1),
+            })),
+        }
+    }
+
+    fn literal(literal: &LiteralExpression<'a>) -> Result<Value<'a>, InterpreterError<'a>> {
+        Ok(Value::Literal(literal.literal.clone()))
+    }
+
     fn grouping<'b>(
         environment: Rc<RefCell<Environment<'a>>>,
         grouping: &'b GroupingExpression<'a>,
@@ -108,50 +563,81 @@ impl<'a> Interpreter {
         environment: Rc<RefCell<Environment<'a>>>,
         unary: &'b UnaryExpression<'a>,
     ) -> Result<Value<'a>, InterpreterError<'a>> {
-        let value = match Interpreter::expression(environment, &unary.left) {
-            Ok(value) => match value {
-                Value::Empty => Err(InterpreterError {
-                    message: EMPTY_VALUE_OPERATION_ERROR_MESSAGE.to_owned(),
-                    statement: Statement::Expression(Expression::Unary(unary.clone())),
-                }),
-                Value::Optional(_) => Err(InterpreterError {
-                    message: NULLABLE_VALUE_OPERATION_ERROR_MESSAGE.to_owned(),
-                    statement: Statement::Expression(Expression::Unary(unary.clone())),
-                }),
-                Value::Literal(literal) => Ok(literal),
-            },
-            Err(e) => Err(e),
+        Interpreter::unary_operation(
+            &unary.operator,
+            Interpreter::expression(environment, &unary.left)?,
+        )
+    }
+
+    /// Applies a unary operator to an evaluated operand
+    pub fn unary_operation(
+        operator: &Token<'a>,
+        value: Value<'a>,
+    ) -> Result<Value<'a>, InterpreterError<'a>> {
+        let value = match value {
+            Value::Empty => Err(InterpreterError::new(
+                EMPTY_VALUE_OPERATION_ERROR_MESSAGE.to_owned(),
+                operator.clone(),
+            )),
+            Value::Optional(_) => Err(InterpreterError::new(
+                NULLABLE_VALUE_OPERATION_ERROR_MESSAGE.to_owned(),
+                operator.clone(),
+            )),
+            Value::Literal(literal) => Ok(literal),
+            value => Err(InterpreterError::new(
+                format!(
+                    "Cannot use operator \"{}\" on a value of type {}",
+                    operator.lexeme,
+                    value.get_type()
+                ),
+                operator.clone(),
+            )),
         }?;
 
-        match unary.operator.token_type {
+        match operator.token_type {
             TokenType::Minus => match value {
                 Literal::Number(number) => match number {
-                    NumberLiteral::Integer(integer) => Ok(Value::Literal(Literal::Number(
-                        NumberLiteral::Integer(-integer),
-                    ))),
+                    NumberLiteral::Integer(integer) => match integer.checked_neg() {
+                        Some(integer) => Ok(Value::Literal(Literal::Number(
+                            NumberLiteral::Integer(integer),
+                        ))),
+                        None => Err(Interpreter::overflow(operator)),
+                    },
                     NumberLiteral::Float(float) => Ok(Value::Literal(Literal::Number(
                         NumberLiteral::Float(-float),
                     ))),
                 },
-                _ => Err(InterpreterError {
-                    message: "Cannot use operator \"-\" on non-numeric value".to_owned(),
-                    statement: Statement::Expression(Expression::Unary(unary.clone())),
-                }),
+                _ => Err(InterpreterError::new(
+                    "Cannot use operator \"-\" on non-numeric value".to_owned(),
+                    operator.clone(),
+                )),
             },
             TokenType::Bang => match value {
                 Literal::Boolean(bool) => Ok(Value::Literal(Literal::Boolean(!bool))),
-                _ => Err(InterpreterError {
-                    message: "Cannot negate non-boolean value".to_owned(),
-                    statement: Statement::Expression(Expression::Unary(unary.clone())),
-                }),
+                _ => Err(InterpreterError::new(
+                    "Cannot negate non-boolean value".to_owned(),
+                    operator.clone(),
+                )),
+            },
+            TokenType::BitwiseNot => match value {
+                Literal::Number(NumberLiteral::Integer(integer)) => Ok(Value::Literal(
+                    Literal::Number(NumberLiteral::Integer(!integer)),
+                )),
+                _ => Err(InterpreterError::new(
+                    format!(
+                        "Operator '~' expects an Int operand, got {}",
+                        value.get_type()
+                    ),
+                    operator.clone(),
+                )),
             },
-            _ => Err(InterpreterError {
-                message: format!(
+            _ => Err(InterpreterError::new(
+                format!(
                     "Unexpected unary operator. {} is not a valid unary operator",
-                    &unary.operator.lexeme
+                    &operator.lexeme
                 ),
-                statement: Statement::Expression(Expression::Unary(unary.clone())),
-            }),
+                operator.clone(),
+            )),
         }
     }
 
@@ -162,140 +648,241 @@ impl<'a> Interpreter {
         let left_value = Interpreter::expression(Rc::clone(&environment), &binary.left)?;
         let right_value = Interpreter::expression(Rc::clone(&environment), &binary.right)?;
 
-        match binary.operator.token_type {
-            TokenType::Plus => {
-                let left = Interpreter::unwrap_number(left_value, binary)?;
-                let right = Interpreter::unwrap_number(right_value, binary)?;
-
-                Ok(Value::Literal(Literal::Number(left + right)))
-            }
-            TokenType::Minus => {
-                let left = Interpreter::unwrap_number(left_value, binary)?;
-                let right = Interpreter::unwrap_number(right_value, binary)?;
-
-                Ok(Value::Literal(Literal::Number(left - right)))
-            }
-            TokenType::Star => {
-                let left = Interpreter::unwrap_number(left_value, binary)?;
-                let right = Interpreter::unwrap_number(right_value, binary)?;
+        Interpreter::binary_operation(&binary.operator, left_value, right_value)
+    }
 
-                Ok(Value::Literal(Literal::Number(left * right)))
-            }
-            TokenType::Slash => {
-                let left = Interpreter::unwrap_number(left_value, binary)?;
-                let right = Interpreter::unwrap_number(right_value, binary)?;
+    /// Applies a binary operator to its evaluated operands
+    pub fn binary_operation(
+        operator: &Token<'a>,
+        left_value: Value<'a>,
+        right_value: Value<'a>,
+    ) -> Result<Value<'a>, InterpreterError<'a>> {
+        match operator.token_type {
+            TokenType::Plus | TokenType::Minus | TokenType::Star | TokenType::Slash => {
+                let left = Interpreter::unwrap_number(left_value, operator)?;
+                let right = Interpreter::unwrap_number(right_value, operator)?;
 
-                Ok(Value::Literal(Literal::Number(left / right)))
+                Ok(Value::Literal(Literal::Number(Interpreter::arithmetic(
+                    left, right, operator,
+                )?)))
             }
             TokenType::Greater => {
-                let left = Interpreter::unwrap_number(left_value, binary)?;
-                let right = Interpreter::unwrap_number(right_value, binary)?;
+                let left = Interpreter::unwrap_number(left_value, operator)?;
+                let right = Interpreter::unwrap_number(right_value, operator)?;
 
                 Ok(Value::Literal(Literal::Boolean(left > right)))
             }
             TokenType::GreaterEqual => {
-                let left = Interpreter::unwrap_number(left_value, binary)?;
-                let right = Interpreter::unwrap_number(right_value, binary)?;
+                let left = Interpreter::unwrap_number(left_value, operator)?;
+                let right = Interpreter::unwrap_number(right_value, operator)?;
 
                 Ok(Value::Literal(Literal::Boolean(left >= right)))
             }
             TokenType::Less => {
-                let left = Interpreter::unwrap_number(left_value, binary)?;
-                let right = Interpreter::unwrap_number(right_value, binary)?;
+                let left = Interpreter::unwrap_number(left_value, operator)?;
+                let right = Interpreter::unwrap_number(right_value, operator)?;
 
                 Ok(Value::Literal(Literal::Boolean(left < right)))
             }
             TokenType::LessEqual => {
-                let left = Interpreter::unwrap_number(left_value, binary)?;
-                let right = Interpreter::unwrap_number(right_value, binary)?;
+                let left = Interpreter::unwrap_number(left_value, operator)?;
+                let right = Interpreter::unwrap_number(right_value, operator)?;
 
                 Ok(Value::Literal(Literal::Boolean(left <= right)))
             }
-            TokenType::DoubleEqual => match (left_value, right_value) {
-                (Value::Literal(ref left_literal), Value::Literal(ref right_literal)) => {
-                    match (left_literal, right_literal) {
-                        (Literal::Number(left_number), Literal::Number(right_number)) => Ok(
-                            Value::Literal(Literal::Boolean(left_number == right_number)),
-                        ),
-                        (Literal::String(left_string), Literal::String(right_string)) => Ok(
-                            Value::Literal(Literal::Boolean(left_string == right_string)),
-                        ),
-                        (Literal::Boolean(left_bool), Literal::Boolean(right_bool)) => {
-                            Ok(Value::Literal(Literal::Boolean(left_bool == right_bool)))
-                        }
-                        _ => Err(InterpreterError {
-                            message: format!(
-                                "Can't compare {} with {}",
-                                left_literal.get_type(),
-                                right_literal.get_type()
-                            ),
-                            statement: Statement::Expression(Expression::Binary(binary.clone())),
-                        }),
+            TokenType::BitwiseAnd
+            | TokenType::BitwiseOr
+            | TokenType::BitwiseXor
+            | TokenType::LeftShift
+            | TokenType::RightShift => {
+                let left = Interpreter::unwrap_integer(left_value, operator)?;
+                let right = Interpreter::unwrap_integer(right_value, operator)?;
+
+                Ok(Value::Literal(Literal::Number(NumberLiteral::Integer(
+                    Interpreter::bitwise(left, right, operator)?,
+                ))))
+            }
+            TokenType::DoubleEqual => Ok(Value::Literal(Literal::Boolean(Interpreter::equals(
+                left_value,
+                right_value,
+                operator,
+            )?))),
+            TokenType::BangEqual => Ok(Value::Literal(Literal::Boolean(!Interpreter::equals(
+                left_value,
+                right_value,
+                operator,
+            )?))),
+            _ => Err(InterpreterError::new(
+                format!("Invalid operator '{}'", operator.lexeme),
+                operator.clone(),
+            )),
+        }
+    }
+
+    fn equals(
+        left_value: Value<'a>,
+        right_value: Value<'a>,
+        operator: &Token<'a>,
+    ) -> Result<bool, InterpreterError<'a>> {
+        match (left_value, right_value) {
+            (Value::Optional(None), Value::Optional(None)) => Ok(true),
+            (Value::Optional(None), _) | (_, Value::Optional(None)) => Ok(false),
+            (Value::Optional(Some(left)), right) => Interpreter::equals(*left, right, operator),
+            (left, Value::Optional(Some(right))) => Interpreter::equals(left, *right, operator),
+            (Value::Literal(ref left_literal), Value::Literal(ref right_literal)) => {
+                match (left_literal, right_literal) {
+                    (Literal::Number(left_number), Literal::Number(right_number)) => {
+                        Ok(left_number == right_number)
                     }
-                }
-                _ => Err(InterpreterError {
-                    message: "Can't compare non-literal values".to_owned(),
-                    statement: Statement::Expression(Expression::Binary(binary.clone())),
-                }),
-            },
-            TokenType::BangEqual => match (left_value, right_value) {
-                (Value::Literal(ref left_literal), Value::Literal(ref right_literal)) => {
-                    match (left_literal, right_literal) {
-                        (Literal::Number(left_number), Literal::Number(right_number)) => Ok(
-                            Value::Literal(Literal::Boolean(left_number != right_number)),
-                        ),
-                        (Literal::String(left_string), Literal::String(right_string)) => Ok(
-                            Value::Literal(Literal::Boolean(left_string != right_string)),
-                        ),
-                        (Literal::Boolean(left_bool), Literal::Boolean(right_bool)) => {
-                            Ok(Value::Literal(Literal::Boolean(left_bool != right_bool)))
-                        }
-                        _ => Err(InterpreterError {
-                            message: format!(
-                                "Can't compare {} with {}",
-                                left_literal.get_type(),
-                                right_literal.get_type()
-                            ),
-                            statement: Statement::Expression(Expression::Binary(binary.clone())),
-                        }),
+                    (Literal::String(left_string), Literal::String(right_string)) => {
+                        Ok(left_string == right_string)
                     }
+                    (Literal::Boolean(left_bool), Literal::Boolean(right_bool)) => {
+                        Ok(left_bool == right_bool)
+                    }
+                    _ => Err(InterpreterError::new(
+                        format!(
+                            "Can't compare {} with {}",
+                            left_literal.get_type(),
+                            right_literal.get_type()
+                        ),
+                        operator.clone(),
+                    )),
                 }
-                _ => Err(InterpreterError {
-                    message: "Can't compare non-literal values".to_owned(),
-                    statement: Statement::Expression(Expression::Binary(binary.clone())),
-                }),
-            },
-            _ => Err(InterpreterError {
-                message: format!("Invalid operator '{}'", binary.operator.lexeme),
-                statement: Statement::Expression(Expression::Binary(binary.clone())),
-            }),
+            }
+            _ => Err(InterpreterError::new(
+                "Can't compare non-literal values".to_owned(),
+                operator.clone(),
+            )),
+        }
+    }
+
+    /// Applies `+`, `-`, `*` or `/` to two numbers. Integer results that don't fit in an `Int` are
+    /// errors rather than wrapping around
+    fn arithmetic(
+        left: NumberLiteral,
+        right: NumberLiteral,
+        operator: &Token<'a>,
+    ) -> Result<NumberLiteral, InterpreterError<'a>> {
+        if operator.token_type == TokenType::Slash
+            && right == NumberLiteral::Integer(0)
+            && matches!(left, NumberLiteral::Integer(_))
+        {
+            return Err(InterpreterError::new(
+                "Division by zero".to_owned(),
+                operator.clone(),
+            ));
+        }
+
+        if let (NumberLiteral::Integer(left), NumberLiteral::Integer(right)) = (&left, &right) {
+            let result = match operator.token_type {
+                TokenType::Plus => left.checked_add(*right),
+                TokenType::Minus => left.checked_sub(*right),
+                TokenType::Star => left.checked_mul(*right),
+                _ => left.checked_div(*right),
+            };
+
+            return result
+                .map(NumberLiteral::Integer)
+                .ok_or_else(|| Interpreter::overflow(operator));
+        }
+
+        match operator.token_type {
+            TokenType::Plus => Ok(left + right),
+            TokenType::Minus => Ok(left - right),
+            TokenType::Star => Ok(left * right),
+            _ => Ok(left / right),
+        }
+    }
+
+    pub fn stack_overflow(paren: &Token<'a>) -> InterpreterError<'a> {
+        InterpreterError::new("Stack overflow".to_owned(), paren.clone())
+            .with_note(format!("calls can only be nested {} deep", MAX_CALL_DEPTH))
+    }
+
+    fn overflow(operator: &Token<'a>) -> InterpreterError<'a> {
+        InterpreterError::new("Integer overflow".to_owned(), operator.clone()).with_note(format!(
+            "the result of '{}' doesn't fit in an Int",
+            operator.lexeme
+        ))
+    }
+
+    fn bitwise(left: i32, right: i32, operator: &Token<'a>) -> Result<i32, InterpreterError<'a>> {
+        match operator.token_type {
+            TokenType::BitwiseAnd => Ok(left & right),
+            TokenType::BitwiseOr => Ok(left | right),
+            TokenType::BitwiseXor => Ok(left ^ right),
+            TokenType::LeftShift | TokenType::RightShift => {
+                if !(0..32).contains(&right) {
+                    return Err(InterpreterError::new(
+                        format!("Shift amount {} is out of range 0..32", right),
+                        operator.clone(),
+                    ));
+                }
+
+                if operator.token_type == TokenType::LeftShift {
+                    Ok(left << right)
+                } else {
+                    Ok(left >> right)
+                }
+            }
+            _ => Err(InterpreterError::new(
+                format!("Invalid bitwise operator '{}'", operator.lexeme),
+                operator.clone(),
+            )),
+        }
+    }
+
+    fn unwrap_integer(value: Value<'a>, operator: &Token<'a>) -> Result<i32, InterpreterError<'a>> {
+        match value {
+            Value::Literal(Literal::Number(NumberLiteral::Integer(integer))) => Ok(integer),
+            Value::Empty => Err(InterpreterError::new(
+                EMPTY_VALUE_OPERATION_ERROR_MESSAGE.to_owned(),
+                operator.clone(),
+            )),
+            Value::Optional(_) => Err(InterpreterError::new(
+                NULLABLE_VALUE_OPERATION_ERROR_MESSAGE.to_owned(),
+                operator.clone(),
+            )),
+            value => Err(InterpreterError::new(
+                format!(
+                    "Operator '{}' expects Int operands, got {}",
+                    operator.lexeme,
+                    value.get_type()
+                ),
+                operator.clone(),
+            )),
         }
     }
 
     fn unwrap_number(
         value: Value<'a>,
-        binary: &BinaryExpression<'a>,
+        operator: &Token<'a>,
     ) -> Result<NumberLiteral, InterpreterError<'a>> {
         match value {
             Value::Literal(literal) => match literal {
                 Literal::Number(number) => Ok(number),
-                Literal::String(_) => Err(InterpreterError {
-                    message: "Expected number, got string".to_owned(),
-                    statement: Statement::Expression(Expression::Binary(binary.clone())),
-                }),
-                Literal::Boolean(_) => Err(InterpreterError {
-                    message: "Expected number, got boolean".to_owned(),
-                    statement: Statement::Expression(Expression::Binary(binary.clone())),
-                }),
+                Literal::String(_) => Err(InterpreterError::new(
+                    "Expected number, got string".to_owned(),
+                    operator.clone(),
+                )),
+                Literal::Boolean(_) => Err(InterpreterError::new(
+                    "Expected number, got boolean".to_owned(),
+                    operator.clone(),
+                )),
             },
-            Value::Empty => Err(InterpreterError {
-                message: EMPTY_VALUE_OPERATION_ERROR_MESSAGE.to_owned(),
-                statement: Statement::Expression(Expression::Binary(binary.clone())),
-            }),
-            Value::Optional(_) => Err(InterpreterError {
-                message: NULLABLE_VALUE_OPERATION_ERROR_MESSAGE.to_owned(),
-                statement: Statement::Expression(Expression::Binary(binary.clone())),
-            }),
+            Value::Empty => Err(InterpreterError::new(
+                EMPTY_VALUE_OPERATION_ERROR_MESSAGE.to_owned(),
+                operator.clone(),
+            )),
+            Value::Optional(_) => Err(InterpreterError::new(
+                NULLABLE_VALUE_OPERATION_ERROR_MESSAGE.to_owned(),
+                operator.clone(),
+            )),
+            value => Err(InterpreterError::new(
+                format!("Expected number, got {}", value.get_type()),
+                operator.clone(),
+            )),
         }
     }
 
@@ -303,31 +890,28 @@ impl<'a> Interpreter {
         environment: Rc<RefCell<Environment<'a>>>,
         decl: &'b VariableDeclaration<'a>,
     ) -> Result<(), InterpreterError<'a>> {
-        let value = match decl.initializer {
-            Some(ref initializer) => match initializer {
-                Expression::Literal(literal_expression) => match literal_expression.value.literal {
-                    Some(ref literal) => Value::Literal(literal.clone()),
-                    None => Value::Optional(None),
-                },
-                _ => Interpreter::expression(Rc::clone(&environment), initializer)?,
-            },
-            None => Value::Empty,
-        };
-
-        let result = environment
-            .borrow_mut()
+        if environment
+            .borrow()
             .values
-            .insert(decl.identifier.lexeme.to_owned(), value);
-
-        if result.is_some() {
-            return Err(InterpreterError {
-                statement: Statement::VariableDeclaration(decl.to_owned()),
-                message: format!(
+            .contains_key(decl.identifier.lexeme)
+        {
+            return Err(InterpreterError::new(
+                format!(
                     "Variable '{}' already declared in this scope",
                     decl.identifier.lexeme
                 ),
-            });
+                decl.identifier.clone(),
+            ));
         }
+
+        let value = Interpreter::expression(Rc::clone(&environment), &decl.initializer)?;
+        let value = Interpreter::wrap(value, decl.r#type.as_ref());
+
+        environment
+            .borrow_mut()
+            .values
+            .insert(decl.identifier.lexeme.to_owned(), value);
+
         Ok(())
     }
 
@@ -335,51 +919,144 @@ impl<'a> Interpreter {
         environment: &Environment<'a>,
         variable: &'b VariableExpression<'a>,
     ) -> Result<Value<'a>, InterpreterError<'a>> {
-        match environment.values.get(variable.value.lexeme) {
-            Some(value) => Ok(value.clone()),
-            None => match environment.parent {
-                Some(ref parent) => Interpreter::variable_expression(&parent.borrow(), variable),
-                None => Err(InterpreterError {
-                    statement: Statement::Expression(Expression::Variable(variable.clone())),
-                    message: format!(
-                        "Variable '{}' not found in the current scope",
-                        variable.value.lexeme
-                    ),
-                }),
-            },
+        Interpreter::lookup(
+            environment,
+            &variable.value,
+            variable.resolved.map(|resolved| resolved.depth),
+        )
+    }
+
+    /// Finds the value of the variable named `identifier`, `depth` scopes up when the resolver
+    /// knows where it was declared, or in the closest scope that has it otherwise
+    fn lookup(
+        environment: &Environment<'a>,
+        identifier: &Token<'a>,
+        depth: Option<usize>,
+    ) -> Result<Value<'a>, InterpreterError<'a>> {
+        let value = match depth {
+            Some(0) | None => environment.values.get(identifier.lexeme),
+            Some(_) => None,
+        };
+
+        match (value, &environment.parent) {
+            (Some(value), _) => Ok(value.clone()),
+            (None, Some(parent)) if depth != Some(0) => {
+                Interpreter::lookup(&parent.borrow(), identifier, depth.map(|depth| depth - 1))
+            }
+            _ => Err(InterpreterError::new(
+                format!(
+                    "Variable '{}' not found in the current scope",
+                    identifier.lexeme
+                ),
+                identifier.clone(),
+            )),
         }
     }
 
     fn block<'b>(
         environment: Rc<RefCell<Environment<'a>>>,
-        statements: &'b Vec<Statement<'a>>,
-    ) -> Result<Value<'a>, InterpreterError<'a>> {
+        statements: &'b [Statement<'a>],
+    ) -> Result<Value<'a>, Unwind<'a>> {
         let inner_environment = Rc::new(RefCell::new(Environment::with_parent(environment)));
 
-        Interpreter::interpret(inner_environment, statements)
+        Interpreter::statements(inner_environment, statements)
+    }
+
+    fn return_statement<'b>(
+        environment: Rc<RefCell<Environment<'a>>>,
+        return_statement: &'b ReturnStatement<'a>,
+    ) -> Unwind<'a> {
+        let value = match return_statement.value {
+            Some(ref value) => Interpreter::expression(environment, value),
+            None => Ok(Value::Empty),
+        };
+
+        match value {
+            Ok(value) => Unwind::Return(value),
+            Err(error) => Unwind::Error(error),
+        }
+    }
+
+    fn call<'b>(
+        environment: Rc<RefCell<Environment<'a>>>,
+        call: &'b CallExpression<'a>,
+    ) -> Result<Value<'a>, InterpreterError<'a>> {
+        let function = match Interpreter::expression(Rc::clone(&environment), &call.callee)? {
+            Value::Function(function) => function,
+            value => return Err(Interpreter::not_callable(&value, &call.paren)),
+        };
+
+        if call.arguments.len() != function.arity() {
+            return Err(Interpreter::arity_error(
+                function.arity(),
+                call.arguments.len(),
+                &call.paren,
+            ));
+        }
+
+        let mut function_environment = Environment::with_parent(Rc::clone(&function.closure));
+
+        for (parameter, argument) in function
+            .declaration
+            .parameters
+            .iter()
+            .zip(call.arguments.iter())
+        {
+            let value = Interpreter::expression(Rc::clone(&environment), argument)?;
+            let value = Interpreter::wrap(value, parameter.r#type.as_ref());
+
+            function_environment
+                .values
+                .insert(parameter.identifier.lexeme.to_owned(), value);
+        }
+
+        // The body runs one call deeper than the caller, wherever the function was declared
+        function_environment.calls = environment.borrow().calls + 1;
+
+        if function_environment.calls > MAX_CALL_DEPTH {
+            return Err(Interpreter::stack_overflow(&call.paren));
+        }
+
+        let function_environment = Rc::new(RefCell::new(function_environment));
+
+        match Interpreter::statements(function_environment, &function.declaration.body) {
+            // Functions evaluate to their last statement unless they return early
+            Ok(value) | Err(Unwind::Return(value)) => Ok(Interpreter::wrap(
+                value,
+                function.declaration.return_type.as_ref(),
+            )),
+            Err(Unwind::Error(error)) => Err(error),
+        }
+    }
+
+    pub fn not_callable(value: &Value<'a>, paren: &Token<'a>) -> InterpreterError<'a> {
+        InterpreterError::new(
+            format!("Can only call functions, got {}", value.get_type()),
+            paren.clone(),
+        )
+    }
+
+    pub fn arity_error(arity: usize, arguments: usize, paren: &Token<'a>) -> InterpreterError<'a> {
+        InterpreterError::new(
+            format!("Expected {} arguments but got {}", arity, arguments),
+            paren.clone(),
+        )
     }
 
     fn if_statement<'b>(
         environment: Rc<RefCell<Environment<'a>>>,
         if_statement: &'b IfStatement<'a>,
-    ) -> Result<Value<'a>, InterpreterError<'a>> {
-        let condition_result =
-            Interpreter::expression(Rc::clone(&environment), &if_statement.condition)?;
+    ) -> Result<Value<'a>, Unwind<'a>> {
+        if let Some(ref binding) = if_statement.binding {
+            return Interpreter::if_binding(environment, if_statement, binding);
+        }
 
-        let statements_to_execute = match condition_result {
-            Value::Literal(Literal::Boolean(bool_literal)) => {
-                if bool_literal {
-                    Some(&if_statement.statements)
-                } else {
-                    if_statement.else_statements.as_ref()
-                }
-            }
-            _ => {
-                return Err(InterpreterError {
-                    message: "Expected boolean condition".to_owned(),
-                    statement: Statement::If(if_statement.clone()),
-                })
-            }
+        let condition = Interpreter::condition(Rc::clone(&environment), &if_statement.condition)?;
+
+        let statements_to_execute = if condition {
+            Some(&if_statement.statements)
+        } else {
+            if_statement.else_statements.as_ref()
         };
 
         match statements_to_execute {
@@ -388,71 +1065,136 @@ impl<'a> Interpreter {
         }
     }
 
+    /// Runs `if x := optional { ... }`, binding the unwrapped value to `x` inside the first block
+    fn if_binding<'b>(
+        environment: Rc<RefCell<Environment<'a>>>,
+        if_statement: &'b IfStatement<'a>,
+        binding: &'b Token<'a>,
+    ) -> Result<Value<'a>, Unwind<'a>> {
+        let value = Interpreter::expression(Rc::clone(&environment), &if_statement.condition)?;
+
+        match Interpreter::bound(value, binding)? {
+            Some(value) => {
+                let mut inner_environment = Environment::with_parent(environment);
+                inner_environment
+                    .values
+                    .insert(binding.lexeme.to_owned(), value);
+
+                Interpreter::statements(
+                    Rc::new(RefCell::new(inner_environment)),
+                    &if_statement.statements,
+                )
+            }
+            None => match if_statement.else_statements {
+                Some(ref statements) => Interpreter::block(environment, statements),
+                None => Ok(Value::Empty),
+            },
+        }
+    }
+
+    /// The value to bind in `if x := optional`, or `None` when the else branch should run
+    pub fn bound(
+        value: Value<'a>,
+        binding: &Token<'a>,
+    ) -> Result<Option<Value<'a>>, InterpreterError<'a>> {
+        match value {
+            Value::Optional(value) => Ok(value.map(|value| *value)),
+            value => Err(InterpreterError::new(
+                format!(
+                    "Expected an optional value to bind to '{}', got {}",
+                    binding.lexeme,
+                    value.get_type()
+                ),
+                binding.clone(),
+            )),
+        }
+    }
+
     fn assign<'b>(
         environment: Rc<RefCell<Environment<'a>>>,
         assignment: &'b AssignmentExpression<'a>,
     ) -> Result<Value<'a>, InterpreterError<'a>> {
-        let env_borrow = environment.borrow();
-        let current_value = env_borrow.values.get(assignment.name.lexeme);
+        // The value is evaluated in the current scope, even if the variable lives in an outer one
+        let value = Interpreter::expression(Rc::clone(&environment), &assignment.value)?;
 
-        match current_value {
-            Some(_) => {
-                drop(env_borrow);
+        Interpreter::store(
+            environment,
+            &assignment.identifier,
+            value,
+            assignment.resolved.map(|resolved| resolved.depth),
+        )?;
 
-                let new_value =
-                    Interpreter::expression(Rc::clone(&environment), &assignment.value)?;
-                let mut env_borrow_mut = environment.borrow_mut();
-                let prev = env_borrow_mut
-                    .values
-                    .get_mut(assignment.name.lexeme)
-                    .unwrap();
+        Ok(Value::Empty)
+    }
 
-                *prev = new_value;
+    /// Replaces the value of the variable named `identifier`, found like in `lookup`
+    fn store(
+        environment: Rc<RefCell<Environment<'a>>>,
+        identifier: &Token<'a>,
+        value: Value<'a>,
+        depth: Option<usize>,
+    ) -> Result<(), InterpreterError<'a>> {
+        let mut env_borrow_mut = environment.borrow_mut();
 
-                Ok(Value::Empty)
-            }
-            None => match &env_borrow.parent {
-                Some(parent) => Interpreter::assign(Rc::clone(parent), assignment),
-                None => Err(InterpreterError {
-                    message: format!(
-                        "Cannot assign a value to undeclared variable '{}'",
-                        assignment.name.lexeme
-                    ),
-                    statement: Statement::Expression(Expression::Assignment(assignment.clone())),
-                }),
-            },
+        let previous = match depth {
+            Some(0) | None => env_borrow_mut.values.get_mut(identifier.lexeme),
+            Some(_) => None,
+        };
+
+        if let Some(prev) = previous {
+            *prev = Interpreter::reassign(prev, value);
+
+            return Ok(());
+        }
+
+        match env_borrow_mut.parent {
+            Some(ref parent) if depth != Some(0) => Interpreter::store(
+                Rc::clone(parent),
+                identifier,
+                value,
+                depth.map(|depth| depth - 1),
+            ),
+            _ => Err(InterpreterError::new(
+                format!(
+                    "Cannot assign a value to undeclared variable '{}'",
+                    identifier.lexeme
+                ),
+                identifier.clone(),
+            )),
         }
     }
 
-    fn while_statement<'b>(
+    fn for_statement<'b>(
         environment: Rc<RefCell<Environment<'a>>>,
-        while_statement: &'b ForStatement<'a>,
-    ) -> Result<Value<'a>, InterpreterError<'a>> {
-        while match Interpreter::unwrap_bool(Interpreter::expression(
-            Rc::clone(&environment),
-            &while_statement.condition,
-        )?) {
-            Ok(boolean) => Ok(boolean),
-            Err(message) => Err(InterpreterError {
-                message,
-                statement: Statement::While(while_statement.clone()),
-            }),
-        }? {
-            Interpreter::block(Rc::clone(&environment), &while_statement.statements)?;
+        for_statement: &'b ForStatement<'a>,
+    ) -> Result<Value<'a>, Unwind<'a>> {
+        while Interpreter::condition(Rc::clone(&environment), &for_statement.condition)? {
+            Interpreter::block(Rc::clone(&environment), &for_statement.statements)?;
         }
 
         Ok(Value::Empty)
     }
 
-    fn unwrap_bool(value: Value) -> Result<bool, String> {
+    fn condition<'b>(
+        environment: Rc<RefCell<Environment<'a>>>,
+        condition: &'b Expression<'a>,
+    ) -> Result<bool, InterpreterError<'a>> {
+        let value = Interpreter::expression(environment, condition)?;
+
+        Interpreter::unwrap_bool(value)
+            .map_err(|message| InterpreterError::new(message, condition.token().clone()))
+    }
+
+    pub fn unwrap_bool(value: Value) -> Result<bool, String> {
         match value {
             Value::Literal(literal) => match literal {
                 Literal::Boolean(boolean) => Ok(boolean),
                 Literal::Number(_) => Err("Expected boolean, got number".to_owned()),
-                Literal::String(_) => Err("Expected number, got string".to_owned()),
+                Literal::String(_) => Err("Expected boolean, got string".to_owned()),
             },
             Value::Empty => Err(EMPTY_VALUE_OPERATION_ERROR_MESSAGE.to_owned()),
             Value::Optional(_) => Err(NULLABLE_VALUE_OPERATION_ERROR_MESSAGE.to_owned()),
+            value => Err(format!("Expected boolean, got {}", value.get_type())),
         }
     }
 
@@ -460,74 +1202,34 @@ impl<'a> Interpreter {
         environment: Rc<RefCell<Environment<'a>>>,
         logical: &'b BinaryExpression<'a>,
     ) -> Result<Value<'a>, InterpreterError<'a>> {
-        match logical.operator.token_type {
-            TokenType::Or => {
-                let left_result = Interpreter::unwrap_bool(Interpreter::expression(
-                    Rc::clone(&environment),
-                    &logical.left,
-                )?);
-
-                let left_value = match left_result {
-                    Ok(boolean) => Ok(boolean),
-                    Err(message) => Err(InterpreterError {
-                        message,
-                        statement: Statement::Expression(Expression::Logical(logical.clone())),
-                    }),
-                }?;
-
-                if left_value {
-                    Ok(Value::Literal(Literal::Boolean(true)))
-                } else {
-                    let right_result = Interpreter::unwrap_bool(Interpreter::expression(
-                        Rc::clone(&environment),
-                        &logical.right,
-                    )?);
-
-                    let right_value = match right_result {
-                        Ok(boolean) => Ok(boolean),
-                        Err(message) => Err(InterpreterError {
-                            message,
-                            statement: Statement::Expression(Expression::Logical(logical.clone())),
-                        }),
-                    }?;
-
-                    Ok(Value::Literal(Literal::Boolean(right_value)))
-                }
+        let left_value = Interpreter::logical_operand(Rc::clone(&environment), logical, true)?;
+
+        let result = match logical.operator.token_type {
+            // Short-circuit without evaluating the right side
+            TokenType::Or if left_value => true,
+            TokenType::And if !left_value => false,
+            TokenType::Or | TokenType::And => {
+                Interpreter::logical_operand(environment, logical, false)?
             }
-            TokenType::And => {
-                let left_result = Interpreter::unwrap_bool(Interpreter::expression(
-                    Rc::clone(&environment),
-                    &logical.left,
-                )?);
-
-                let left_value = match left_result {
-                    Ok(boolean) => Ok(boolean),
-                    Err(message) => Err(InterpreterError {
-                        message,
-                        statement: Statement::Expression(Expression::Logical(logical.clone())),
-                    }),
-                }?;
-
-                if !left_value {
-                    Ok(Value::Literal(Literal::Boolean(false)))
-                } else {
-                    let right_result = Interpreter::unwrap_bool(Interpreter::expression(
-                        Rc::clone(&environment),
-                        &logical.right,
-                    )?);
-
-                    let right_value = match right_result {
-                        Ok(boolean) => Ok(boolean),
-                        Err(message) => Err(InterpreterError {
-                            message,
-                            statement: Statement::Expression(Expression::Logical(logical.clone())),
-                        }),
-                    }?;
-
-                    Ok(Value::Literal(Literal::Boolean(left_value && right_value)))
-                }
+            _ => {
+                return Err(InterpreterError::new(
+                    format!("Invalid logical operator '{}'", logical.operator.lexeme),
+                    logical.operator.clone(),
+                ))
             }
-            _ => todo!(),
-        }
+        };
+
+        Ok(Value::Literal(Literal::Boolean(result)))
+    }
+
+    fn logical_operand<'b>(
+        environment: Rc<RefCell<Environment<'a>>>,
+        logical: &'b BinaryExpression<'a>,
+        left: bool,
+    ) -> Result<bool, InterpreterError<'a>> {
+        let operand = if left { &logical.left } else { &logical.right };
+
+        Interpreter::unwrap_bool(Interpreter::expression(environment, operand)?)
+            .map_err(|message| InterpreterError::new(message, logical.operator.clone()))
     }
 }
@@ -0,0 +1,315 @@
+use std::{fmt::Display, iter::Peekable, str::Chars};
+
+/// A JSON value, enough to speak JSON-RPC without any dependencies
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    /// Keeps the fields in the order they were written
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn parse(text: &str) -> Result<Json, String> {
+        let mut parser = JsonParser {
+            chars: text.chars().peekable(),
+        };
+
+        let value = parser.value()?;
+        parser.whitespace();
+
+        match parser.chars.next() {
+            Some(c) => Err(format!("Unexpected '{}' after the value", c)),
+            None => Ok(value),
+        }
+    }
+
+    pub fn object<'a>(fields: impl IntoIterator<Item = (&'a str, Json)>) -> Json {
+        Json::Object(
+            fields
+                .into_iter()
+                .map(|(key, value)| (key.to_owned(), value))
+                .collect(),
+        )
+    }
+
+    /// The value of a field, if this is an object that has it
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    pub fn as_usize(&self) -> Option<usize> {
+        match self {
+            Json::Number(number) if *number >= 0.0 && number.fract() == 0.0 => {
+                Some(*number as usize)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(elements) => Some(elements),
+            _ => None,
+        }
+    }
+}
+
+impl From<&str> for Json {
+    fn from(string: &str) -> Self {
+        Json::String(string.to_owned())
+    }
+}
+
+impl From<String> for Json {
+    fn from(string: String) -> Self {
+        Json::String(string)
+    }
+}
+
+impl From<bool> for Json {
+    fn from(bool: bool) -> Self {
+        Json::Bool(bool)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(number: usize) -> Self {
+        Json::Number(number as f64)
+    }
+}
+
+impl From<Vec<Json>> for Json {
+    fn from(elements: Vec<Json>) -> Self {
+        Json::Array(elements)
+    }
+}
+
+impl Display for Json {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(bool) => write!(f, "{}", bool),
+            Json::Number(number) => write!(f, "{}", number),
+            Json::String(string) => write_string(f, string),
+            Json::Array(elements) => {
+                write!(f, "[")?;
+
+                for (index, element) in elements.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ",")?;
+                    }
+
+                    write!(f, "{}", element)?;
+                }
+
+                write!(f, "]")
+            }
+            Json::Object(fields) => {
+                write!(f, "{{")?;
+
+                for (index, (key, value)) in fields.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ",")?;
+                    }
+
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut std::fmt::Formatter<'_>, string: &str) -> std::fmt::Result {
+    write!(f, "\"")?;
+
+    for c in string.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+
+    write!(f, "\"")
+}
+
+struct JsonParser<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl JsonParser<'_> {
+    fn value(&mut self) -> Result<Json, String> {
+        self.whitespace();
+
+        match self.chars.peek() {
+            Some('{') => self.object(),
+            Some('[') => self.array(),
+            Some('"') => Ok(Json::String(self.string()?)),
+            Some('t') => self.keyword("true", Json::Bool(true)),
+            Some('f') => self.keyword("false", Json::Bool(false)),
+            Some('n') => self.keyword("null", Json::Null),
+            Some(c) if *c == '-' || c.is_ascii_digit() => self.number(),
+            Some(c) => Err(format!("Unexpected '{}'", c)),
+            None => Err("Unexpected end of input".to_owned()),
+        }
+    }
+
+    fn object(&mut self) -> Result<Json, String> {
+        self.expect('{')?;
+        let mut fields = Vec::new();
+
+        self.whitespace();
+
+        if self.chars.next_if_eq(&'}').is_some() {
+            return Ok(Json::Object(fields));
+        }
+
+        loop {
+            self.whitespace();
+            let key = self.string()?;
+
+            self.whitespace();
+            self.expect(':')?;
+            fields.push((key, self.value()?));
+            self.whitespace();
+
+            match self.chars.next() {
+                Some(',') => continue,
+                Some('}') => return Ok(Json::Object(fields)),
+                _ => return Err("Expected ',' or '}' in object".to_owned()),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Json, String> {
+        self.expect('[')?;
+        let mut elements = Vec::new();
+
+        self.whitespace();
+
+        if self.chars.next_if_eq(&']').is_some() {
+            return Ok(Json::Array(elements));
+        }
+
+        loop {
+            elements.push(self.value()?);
+            self.whitespace();
+
+            match self.chars.next() {
+                Some(',') => continue,
+                Some(']') => return Ok(Json::Array(elements)),
+                _ => return Err("Expected ',' or ']' in array".to_owned()),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut string = String::new();
+
+        loop {
+            match self.chars.next() {
+                Some('"') => return Ok(string),
+                Some('\\') => match self.chars.next() {
+                    Some('"') => string.push('"'),
+                    Some('\\') => string.push('\\'),
+                    Some('/') => string.push('/'),
+                    Some('b') => string.push('\u{8}'),
+                    Some('f') => string.push('\u{c}'),
+                    Some('n') => string.push('\n'),
+                    Some('r') => string.push('\r'),
+                    Some('t') => string.push('\t'),
+                    Some('u') => string.push(self.unicode_escape()?),
+                    _ => return Err("Invalid escape sequence".to_owned()),
+                },
+                Some(c) => string.push(c),
+                None => return Err("Unterminated string".to_owned()),
+            }
+        }
+    }
+
+    /// Reads the digits after `\u`, combining surrogate pairs into one character
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let high = self.hex()?;
+
+        if !(0xD800..0xDC00).contains(&high) {
+            return char::from_u32(high).ok_or_else(|| "Invalid unicode escape".to_owned());
+        }
+
+        if self.chars.next() != Some('\\') || self.chars.next() != Some('u') {
+            return Err("Expected a low surrogate".to_owned());
+        }
+
+        let low = self.hex()?;
+
+        char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low.wrapping_sub(0xDC00) & 0x3FF))
+            .ok_or_else(|| "Invalid unicode escape".to_owned())
+    }
+
+    fn hex(&mut self) -> Result<u32, String> {
+        let digits: String = (0..4).filter_map(|_| self.chars.next()).collect();
+
+        u32::from_str_radix(&digits, 16).map_err(|_| "Invalid unicode escape".to_owned())
+    }
+
+    fn number(&mut self) -> Result<Json, String> {
+        let mut text = String::new();
+
+        while let Some(c) = self
+            .chars
+            .next_if(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E'))
+        {
+            text.push(c);
+        }
+
+        text.parse()
+            .map(Json::Number)
+            .map_err(|_| format!("Invalid number '{}'", text))
+    }
+
+    fn keyword(&mut self, keyword: &str, value: Json) -> Result<Json, String> {
+        for expected in keyword.chars() {
+            if self.chars.next() != Some(expected) {
+                return Err(format!("Expected '{}'", keyword));
+            }
+        }
+
+        Ok(value)
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.chars.next() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => Err(format!("Expected '{}', got '{}'", expected, c)),
+            None => Err(format!("Expected '{}'", expected)),
+        }
+    }
+
+    fn whitespace(&mut self) {
+        while self.chars.next_if(|c| c.is_ascii_whitespace()).is_some() {}
+    }
+}
@@ -0,0 +1,237 @@
+#[cfg(test)]
+mod assembler;
+mod chunk;
+mod compiler;
+pub mod diagnostic;
+mod disassembler;
+pub mod environment;
+pub mod formatter;
+pub mod highlight;
+pub mod interpreter;
+pub mod json;
+mod lower;
+pub mod lsp;
+pub mod matcha;
+mod optimizer;
+mod parser;
+pub mod rename;
+pub mod repl;
+mod resolver;
+mod scanner;
+mod source;
+mod statement;
+mod syntax;
+mod tests;
+mod token;
+mod type_checker;
+mod vm;
+
+use std::cell::RefCell;
+use std::println;
+use std::rc::Rc;
+
+use environment::Environment;
+use matcha::Type;
+use matcha::Value;
+use source::Source;
+use statement::Statement;
+use token::Token;
+
+use crate::compiler::Compiler;
+use crate::diagnostic::Diagnostic;
+use crate::diagnostic::Renderer;
+use crate::diagnostic::Severity;
+use crate::interpreter::Interpreter;
+use crate::parser::Parser;
+use crate::resolver::Resolver;
+use crate::scanner::Scanner;
+use crate::type_checker::TypeChecker;
+use crate::vm::Vm;
+
+#[derive(Clone)]
+#[cfg_attr(test, derive(Default))]
+pub struct Options {
+    pub ast: bool,
+    /// Whether the syntax tree is printed again after optimizing, for programs that type check
+    pub ast_optimized: bool,
+    /// Whether the concrete syntax tree is printed, with every token and its trivia
+    pub cst: bool,
+    pub lexer_out: bool,
+    /// Whether diagnostics are printed with ANSI colors
+    pub color: bool,
+    /// Whether programs are compiled to bytecode and run by the VM instead of the tree-walker
+    pub vm: bool,
+    /// Whether the compiled bytecode is printed before running
+    pub bytecode: bool,
+    /// Whether the type is printed next to the value of a final expression, like the REPL does
+    pub echo: bool,
+}
+
+pub fn run<'a>(
+    options: &Options,
+    file_name: &str,
+    program: &'a str,
+    environment: Rc<RefCell<Environment<'a>>>,
+) -> u8 {
+    let renderer = Renderer {
+        file_name,
+        source: program,
+        color: options.color,
+    };
+
+    let Some((statements, r#type)) = check(options, &renderer, program, &environment.borrow())
+    else {
+        return 1;
+    };
+
+    execute(options, &renderer, &statements, &r#type, environment)
+}
+
+/// Runs statements that passed `check`, printing the result or the runtime error. Returns the
+/// exit code
+pub fn execute<'a>(
+    options: &Options,
+    renderer: &Renderer,
+    statements: &[Statement<'a>],
+    r#type: &Type,
+    environment: Rc<RefCell<Environment<'a>>>,
+) -> u8 {
+    let report = |diagnostic: Diagnostic| eprintln!("{}\n", renderer.render(&diagnostic));
+
+    let compiled = if options.vm || options.bytecode {
+        match Compiler::compile(statements) {
+            Ok(program) => Some(program),
+            Err(e) => {
+                report((&e).into());
+                return 1;
+            }
+        }
+    } else {
+        None
+    };
+
+    if let (true, Some(program)) = (options.bytecode, &compiled) {
+        print!("{}", disassembler::disassemble(program));
+    }
+
+    let interpreter_result = match compiled {
+        Some(program) if options.vm => Vm::interpret(environment, &program),
+        _ => Interpreter::interpret(environment, statements),
+    };
+
+    match interpreter_result {
+        Ok(result) => {
+            if let Some(text) = result_text(&result, r#type, options.echo) {
+                println!("{}", text);
+            }
+            0
+        }
+        Err(e) => {
+            report((&e).into());
+            1
+        }
+    }
+}
+
+/// What to print for the result of a program whose last statement has type `type`, if anything
+fn result_text(result: &Value, r#type: &Type, echo: bool) -> Option<String> {
+    // Only an expression statement has a value to show, not e.g. an `if` that ends in one
+    if *r#type == Type::Empty || matches!(result, Value::Empty) {
+        return None;
+    }
+
+    match echo {
+        true => Some(format!("{}: {}", result, r#type)),
+        false => Some(result.to_string()),
+    }
+}
+
+/// Scans, parses, resolves, type checks and optimizes `program` against the variables in
+/// `environment`, reporting every problem found. Returns the statements and the type of the last
+/// one if it is an expression, or `None` if the program can't run
+pub fn check<'a>(
+    options: &Options,
+    renderer: &Renderer,
+    program: &'a str,
+    environment: &Environment,
+) -> Option<(Vec<Statement<'a>>, Type)> {
+    let report = |diagnostic: Diagnostic| eprintln!("{}\n", renderer.render(&diagnostic));
+
+    let mut scanner = Scanner {
+        source: Source::new(program),
+    };
+
+    // Keep going after scanner errors, so that parser errors are reported in the same run
+    let (tokens, scanner_errors) = scanner.scan_with_trivia();
+
+    if options.lexer_out {
+        let tokens: Vec<&Token> = tokens
+            .iter()
+            .filter(|token| !token.token_type.is_trivia())
+            .collect();
+
+        println!("{:#?}", tokens);
+    }
+
+    let (tree, parser_errors) = Parser::new(tokens).parse_syntax();
+
+    if options.cst {
+        println!("{}", tree.format(0));
+    }
+
+    if !scanner_errors.is_empty() || !parser_errors.is_empty() {
+        diagnostic::merge(&scanner_errors, &parser_errors)
+            .into_iter()
+            .for_each(report);
+        return None;
+    }
+
+    let mut statements = lower::statements(&tree);
+
+    let resolver_errors = Resolver::with_environment(environment).resolve(&mut statements);
+
+    let failed = resolver_errors
+        .iter()
+        .any(|error| error.severity == Severity::Error);
+    let checked = match failed {
+        true => None,
+        false => Some(TypeChecker::with_environment(environment).infer(&statements)),
+    };
+
+    if options.ast {
+        for statement in &statements {
+            println!("{}", statement.format(0));
+        }
+    }
+
+    // Only programs that type check are optimized
+    let statements = match checked {
+        Some(Ok(_)) => {
+            let statements = optimizer::optimize(statements);
+
+            if options.ast_optimized {
+                for statement in &statements {
+                    println!("{}", statement.format(0));
+                }
+            }
+
+            statements
+        }
+        _ => statements,
+    };
+
+    for error in &resolver_errors {
+        report(error.into());
+    }
+
+    match checked? {
+        Ok(r#type) => Some((statements, r#type)),
+        Err(errors) => {
+            for error in &errors {
+                report(error.into());
+            }
+
+            None
+        }
+    }
+}
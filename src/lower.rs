@@ -0,0 +1,256 @@
+use std::rc::Rc;
+
+use crate::{
+    parser::Parser,
+    statement::{
+        ArrayExpression, AssignmentExpression, BinaryExpression, BlockStatement, CallExpression,
+        Expression, FieldAssignmentExpression, FieldExpression, FieldInitializer, ForStatement,
+        FunctionAnnotation, FunctionExpression, GroupingExpression, IfStatement,
+        IndexAssignmentExpression, IndexExpression, LiteralExpression, NoneExpression, Parameter,
+        RecordDeclaration, RecordExpression, RecordField, ReturnStatement, Statement,
+        TypeAnnotation, UnaryExpression, UnwrapExpression, VariableDeclaration, VariableExpression,
+    },
+    syntax::{SyntaxKind, SyntaxNode},
+    token::{Token, TokenType},
+};
+
+// The parser only builds trees of the shapes below when it reports no errors, which is the only
+// time they are lowered, so missing children are bugs in the parser
+
+/// The statements of a root or a block, which must have been parsed without errors
+pub fn statements<'a>(node: &SyntaxNode<'a>) -> Vec<Statement<'a>> {
+    node.nodes().map(statement).collect()
+}
+
+fn statement<'a>(node: &SyntaxNode<'a>) -> Statement<'a> {
+    match node.kind {
+        SyntaxKind::ExpressionStatement => Statement::Expression(expression(child(node))),
+        SyntaxKind::VariableDeclaration => Statement::VariableDeclaration(VariableDeclaration {
+            identifier: token(node, TokenType::Identifier),
+            r#type: find(node, SyntaxKind::TypeAnnotation).map(annotation),
+            initializer: expression(node.nodes().last().expect("Missing initializer")),
+        }),
+        SyntaxKind::Block => Statement::Block(BlockStatement {
+            statements: statements(node),
+            span: node.span(),
+        }),
+        SyntaxKind::If => {
+            let mut children = node.nodes();
+            let condition = expression(children.next().expect("Missing condition"));
+            let statements = statements(children.next().expect("Missing block"));
+
+            Statement::If(IfStatement {
+                binding: node.token(TokenType::Identifier).cloned(),
+                condition,
+                statements,
+                else_statements: children.next().map(self::statements),
+                span: node.span(),
+            })
+        }
+        SyntaxKind::For => {
+            let mut children = node.nodes();
+
+            Statement::For(ForStatement {
+                condition: expression(children.next().expect("Missing condition")),
+                statements: statements(children.next().expect("Missing block")),
+                span: node.span(),
+            })
+        }
+        SyntaxKind::Return => Statement::Return(ReturnStatement {
+            keyword: token(node, TokenType::Return),
+            value: node.nodes().next().map(expression),
+        }),
+        SyntaxKind::RecordDeclaration => Statement::Record(RecordDeclaration {
+            name: token(node, TokenType::Identifier),
+            fields: node
+                .nodes()
+                .map(|field| RecordField {
+                    identifier: token(field, TokenType::Identifier),
+                    r#type: annotation(child(field)),
+                })
+                .collect(),
+            span: node.span(),
+        }),
+        kind => unreachable!("{:?} is not a statement", kind),
+    }
+}
+
+fn expression<'a>(node: &SyntaxNode<'a>) -> Expression<'a> {
+    match node.kind {
+        SyntaxKind::Binary => {
+            let mut children = node.nodes();
+            let operator = node.tokens().next().expect("Missing operator").clone();
+            let binary = BinaryExpression {
+                left: Box::new(expression(children.next().expect("Missing operand"))),
+                right: Box::new(expression(children.next().expect("Missing operand"))),
+                operator,
+            };
+
+            match binary.operator.token_type {
+                TokenType::And | TokenType::Or => Expression::Logical(binary),
+                TokenType::DoubleQuestion => Expression::Coalesce(binary),
+                _ => Expression::Binary(binary),
+            }
+        }
+        SyntaxKind::Unary => Expression::Unary(UnaryExpression {
+            operator: node.tokens().next().expect("Missing operator").clone(),
+            left: Box::new(expression(child(node))),
+        }),
+        SyntaxKind::Literal => {
+            let value = node.tokens().next().expect("Missing literal").clone();
+            let literal = Parser::literal(&value).expect("Literals are checked by the parser");
+
+            Expression::Literal(LiteralExpression { value, literal })
+        }
+        SyntaxKind::Grouping => Expression::Grouping(GroupingExpression {
+            expression: Box::new(expression(child(node))),
+            span: node.span(),
+        }),
+        SyntaxKind::Variable => Expression::Variable(VariableExpression {
+            value: token(node, TokenType::Identifier),
+            resolved: None,
+        }),
+        SyntaxKind::Assignment => {
+            let mut children = node.nodes();
+            let target = expression(children.next().expect("Missing target"));
+            let value = Box::new(expression(children.next().expect("Missing value")));
+
+            match target {
+                Expression::Variable(variable) => Expression::Assignment(AssignmentExpression {
+                    identifier: variable.value,
+                    value,
+                    resolved: None,
+                }),
+                Expression::Index(index) => {
+                    Expression::IndexAssignment(IndexAssignmentExpression {
+                        target: index.target,
+                        bracket: index.bracket,
+                        index: index.index,
+                        value,
+                    })
+                }
+                Expression::Field(field) => {
+                    Expression::FieldAssignment(FieldAssignmentExpression {
+                        target: field.target,
+                        name: field.name,
+                        value,
+                    })
+                }
+                _ => unreachable!("The parser only assigns to variables, elements and fields"),
+            }
+        }
+        SyntaxKind::Function => {
+            let parameters = find(node, SyntaxKind::ParameterList)
+                .expect("Missing parameters")
+                .nodes()
+                .map(|parameter| Parameter {
+                    identifier: token(parameter, TokenType::Identifier),
+                    r#type: parameter.nodes().next().map(annotation),
+                })
+                .collect();
+
+            Expression::Function(FunctionExpression {
+                keyword: token(node, TokenType::Fn),
+                parameters,
+                return_type: find(node, SyntaxKind::TypeAnnotation).map(annotation),
+                body: Rc::new(statements(
+                    find(node, SyntaxKind::Block).expect("Missing body"),
+                )),
+                span: node.span(),
+            })
+        }
+        SyntaxKind::Call => {
+            let arguments = find(node, SyntaxKind::ArgumentList).expect("Missing arguments");
+
+            Expression::Call(CallExpression {
+                callee: Box::new(expression(child(node))),
+                paren: token(arguments, TokenType::LeftParen),
+                arguments: arguments.nodes().map(expression).collect(),
+                span: node.span(),
+            })
+        }
+        SyntaxKind::Array => Expression::Array(ArrayExpression {
+            bracket: token(node, TokenType::LeftBracket),
+            elements: node.nodes().map(expression).collect(),
+            span: node.span(),
+        }),
+        SyntaxKind::Index => {
+            let mut children = node.nodes();
+
+            Expression::Index(IndexExpression {
+                target: Box::new(expression(children.next().expect("Missing target"))),
+                bracket: token(node, TokenType::LeftBracket),
+                index: Box::new(expression(children.next().expect("Missing index"))),
+                span: node.span(),
+            })
+        }
+        SyntaxKind::None => Expression::None(NoneExpression {
+            keyword: token(node, TokenType::None),
+        }),
+        SyntaxKind::Unwrap => Expression::Unwrap(UnwrapExpression {
+            target: Box::new(expression(child(node))),
+            operator: token(node, TokenType::Bang),
+        }),
+        SyntaxKind::RecordLiteral => Expression::Record(RecordExpression {
+            name: token(node, TokenType::Identifier),
+            fields: node
+                .nodes()
+                .map(|field| FieldInitializer {
+                    identifier: token(field, TokenType::Identifier),
+                    value: expression(child(field)),
+                })
+                .collect(),
+            span: node.span(),
+        }),
+        SyntaxKind::Field => Expression::Field(FieldExpression {
+            target: Box::new(expression(child(node))),
+            name: token(node, TokenType::Identifier),
+        }),
+        kind => unreachable!("{:?} is not an expression", kind),
+    }
+}
+
+fn annotation<'a>(node: &SyntaxNode<'a>) -> TypeAnnotation<'a> {
+    let Some(r#fn) = node.token(TokenType::Fn) else {
+        return TypeAnnotation {
+            name: token(node, TokenType::Identifier),
+            function: None,
+            optional: node.token(TokenType::Question).is_some(),
+        };
+    };
+
+    // The return type is the annotation after the colon, following every parameter type
+    let mut types: Vec<TypeAnnotation> = node.nodes().map(annotation).collect();
+    let return_type = match node.token(TokenType::Colon) {
+        Some(_) => types.pop().map(Box::new),
+        None => None,
+    };
+
+    TypeAnnotation {
+        name: r#fn.clone(),
+        function: Some(FunctionAnnotation {
+            parameters: types,
+            return_type,
+        }),
+        optional: false,
+    }
+}
+
+/// The first child node
+fn child<'b, 'a>(node: &'b SyntaxNode<'a>) -> &'b SyntaxNode<'a> {
+    node.nodes()
+        .next()
+        .unwrap_or_else(|| panic!("Missing the child of {:?}", node.kind))
+}
+
+/// The first child node of the given kind
+fn find<'b, 'a>(node: &'b SyntaxNode<'a>, kind: SyntaxKind) -> Option<&'b SyntaxNode<'a>> {
+    node.nodes().find(|node| node.kind == kind)
+}
+
+/// The first direct token of the given type
+fn token<'a>(node: &SyntaxNode<'a>, token_type: TokenType) -> Token<'a> {
+    node.token(token_type)
+        .unwrap_or_else(|| panic!("Missing {:?} in {:?}", token_type, node.kind))
+        .clone()
+}
@@ -0,0 +1,519 @@
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, Write},
+};
+
+use crate::{
+    diagnostic::{self, Diagnostic, Severity},
+    json::Json,
+    matcha::Type,
+    parser::Parser,
+    resolver::{Reference, Resolver},
+    scanner::Scanner,
+    source::Source,
+    statement::{Expression, Statement},
+    token::{Span, Token, TokenType},
+    type_checker::TypeChecker,
+};
+
+const PARSE_ERROR: i32 = -32700;
+const INVALID_REQUEST: i32 = -32600;
+const METHOD_NOT_FOUND: i32 = -32601;
+
+/// Full document sync, the client sends the whole text on every change
+const SYNC_FULL: usize = 1;
+
+const SYMBOL_FUNCTION: usize = 12;
+const SYMBOL_VARIABLE: usize = 13;
+const SYMBOL_STRUCT: usize = 23;
+
+/// Runs a language server that reads JSON-RPC messages from `input` and writes to `output` until
+/// the client asks it to exit. Returns the exit code, which is only 0 after a proper shutdown
+pub fn serve(mut input: impl BufRead, output: impl Write) -> io::Result<i32> {
+    let mut server = Server {
+        output,
+        documents: HashMap::new(),
+        shutdown: false,
+    };
+
+    while let Some(body) = read_message(&mut input)? {
+        let message = match Json::parse(&body) {
+            Ok(message) => message,
+            Err(error) => {
+                server.error(Json::Null, PARSE_ERROR, &error)?;
+                continue;
+            }
+        };
+
+        let method = message.get("method").and_then(Json::as_str).unwrap_or("");
+        let params = message.get("params").unwrap_or(&Json::Null);
+
+        // Requests have an id to answer to, notifications don't
+        let Some(id) = message.get("id").cloned() else {
+            if method == "exit" {
+                return Ok(if server.shutdown { 0 } else { 1 });
+            }
+
+            server.notification(method, params)?;
+            continue;
+        };
+
+        if server.shutdown {
+            server.error(id, INVALID_REQUEST, "The server is shutting down")?;
+            continue;
+        }
+
+        match server.request(method, params) {
+            Some(result) => server.respond(id, result)?,
+            None => server.error(id, METHOD_NOT_FOUND, &format!("Unknown method {}", method))?,
+        }
+    }
+
+    // The client went away without asking the server to exit
+    Ok(1)
+}
+
+/// Reads the body of the next message, or `None` at the end of the input
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut length = None;
+    let mut header = String::new();
+
+    loop {
+        header.clear();
+
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+
+        let header = header.trim_end();
+
+        if header.is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+
+    let Some(length) = length else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Missing Content-Length header",
+        ));
+    };
+
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+
+    String::from_utf8(body)
+        .map(Some)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+struct Server<W: Write> {
+    output: W,
+    /// The text of every open document, by URI
+    documents: HashMap<String, String>,
+    shutdown: bool,
+}
+
+impl<W: Write> Server<W> {
+    /// Answers a request, or returns `None` for methods the server doesn't know
+    fn request(&mut self, method: &str, params: &Json) -> Option<Json> {
+        let result = match method {
+            "initialize" => Json::object([
+                (
+                    "capabilities",
+                    Json::object([
+                        ("textDocumentSync", SYNC_FULL.into()),
+                        ("hoverProvider", true.into()),
+                        ("definitionProvider", true.into()),
+                        ("documentSymbolProvider", true.into()),
+                    ]),
+                ),
+                (
+                    "serverInfo",
+                    Json::object([
+                        ("name", "matcha".into()),
+                        ("version", env!("CARGO_PKG_VERSION").into()),
+                    ]),
+                ),
+            ]),
+            "shutdown" => {
+                self.shutdown = true;
+                Json::Null
+            }
+            "textDocument/hover" => self.at_position(params, hover),
+            "textDocument/definition" => self.at_position(params, definition),
+            "textDocument/documentSymbol" => {
+                let uri = document_uri(params);
+
+                match self.documents.get(uri) {
+                    Some(source) => symbols(source),
+                    None => Json::Null,
+                }
+            }
+            _ => return None,
+        };
+
+        Some(result)
+    }
+
+    fn notification(&mut self, method: &str, params: &Json) -> io::Result<()> {
+        let uri = document_uri(params).to_owned();
+
+        match method {
+            "textDocument/didOpen" => {
+                let text = params
+                    .get("textDocument")
+                    .and_then(|document| document.get("text"))
+                    .and_then(Json::as_str)
+                    .unwrap_or("");
+
+                self.documents.insert(uri.clone(), text.to_owned());
+                self.publish(&uri)
+            }
+            "textDocument/didChange" => {
+                // With full sync the last change holds the whole document
+                let text = params
+                    .get("contentChanges")
+                    .and_then(Json::as_array)
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change.get("text"))
+                    .and_then(Json::as_str);
+
+                if let Some(text) = text {
+                    self.documents.insert(uri.clone(), text.to_owned());
+                }
+
+                self.publish(&uri)
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                self.publish(&uri)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Runs `handler` with the document and the offset of the position a request points at
+    fn at_position(&self, params: &Json, handler: fn(&str, &str, usize) -> Json) -> Json {
+        let uri = document_uri(params);
+        let position = params.get("position");
+        let line = position.and_then(|position| position.get("line"));
+        let character = position.and_then(|position| position.get("character"));
+
+        let (Some(source), Some(line), Some(character)) = (
+            self.documents.get(uri),
+            line.and_then(Json::as_usize),
+            character.and_then(Json::as_usize),
+        ) else {
+            return Json::Null;
+        };
+
+        match offset(source, line, character) {
+            Some(offset) => handler(uri, source, offset),
+            None => Json::Null,
+        }
+    }
+
+    /// Sends the diagnostics of a document, or clears them once it's closed
+    fn publish(&mut self, uri: &str) -> io::Result<()> {
+        let diagnostics = match self.documents.get(uri) {
+            Some(source) => analyze(source)
+                .diagnostics
+                .iter()
+                .map(|diagnostic| lsp_diagnostic(source, diagnostic))
+                .collect(),
+            None => Vec::new(),
+        };
+
+        self.send(Json::object([
+            ("jsonrpc", "2.0".into()),
+            ("method", "textDocument/publishDiagnostics".into()),
+            (
+                "params",
+                Json::object([("uri", uri.into()), ("diagnostics", diagnostics.into())]),
+            ),
+        ]))
+    }
+
+    fn respond(&mut self, id: Json, result: Json) -> io::Result<()> {
+        self.send(Json::object([
+            ("jsonrpc", "2.0".into()),
+            ("id", id),
+            ("result", result),
+        ]))
+    }
+
+    fn error(&mut self, id: Json, code: i32, message: &str) -> io::Result<()> {
+        self.send(Json::object([
+            ("jsonrpc", "2.0".into()),
+            ("id", id),
+            (
+                "error",
+                Json::object([
+                    ("code", Json::Number(code.into())),
+                    ("message", message.into()),
+                ]),
+            ),
+        ]))
+    }
+
+    fn send(&mut self, message: Json) -> io::Result<()> {
+        let body = message.to_string();
+
+        write!(
+            self.output,
+            "Content-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        )?;
+        self.output.flush()
+    }
+}
+
+/// What the server knows about a document, found the same way `run` would before running it
+struct Analysis<'a> {
+    tokens: Vec<Token<'a>>,
+    statements: Vec<Statement<'a>>,
+    references: Vec<Reference<'a>>,
+    types: Vec<(Span, Type)>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+fn analyze(source: &str) -> Analysis<'_> {
+    let (tokens, scanner_errors) = Scanner {
+        source: Source::new(source),
+    }
+    .scan_recovering();
+
+    let mut statements = match Parser::new(tokens.clone()).parse() {
+        Ok(statements) if scanner_errors.is_empty() => statements,
+        result => {
+            let parser_errors = result.err().unwrap_or_default();
+
+            return Analysis {
+                tokens,
+                statements: Vec::new(),
+                references: Vec::new(),
+                types: Vec::new(),
+                diagnostics: diagnostic::merge(&scanner_errors, &parser_errors),
+            };
+        }
+    };
+
+    let (resolver_errors, references) = Resolver::new().resolve_references(&mut statements);
+    let (types, type_errors) = TypeChecker::new().types(&statements);
+
+    let mut diagnostics: Vec<Diagnostic> = resolver_errors.iter().map(Diagnostic::from).collect();
+
+    // Like `run`, type errors only matter once every name resolves
+    if !resolver_errors
+        .iter()
+        .any(|error| error.severity == Severity::Error)
+    {
+        diagnostics.extend(type_errors.iter().map(Diagnostic::from));
+    }
+
+    Analysis {
+        tokens,
+        statements,
+        references,
+        types,
+        diagnostics,
+    }
+}
+
+fn hover(_: &str, source: &str, offset: usize) -> Json {
+    let analysis = analyze(source);
+
+    let Some(name) = identifier_at(&analysis.tokens, offset) else {
+        return Json::Null;
+    };
+
+    let Some((_, r#type)) = analysis.types.iter().find(|(span, _)| *span == name.span) else {
+        return Json::Null;
+    };
+
+    Json::object([
+        (
+            "contents",
+            Json::object([
+                ("kind", "markdown".into()),
+                (
+                    "value",
+                    format!("```matcha\n{}: {}\n```", name.lexeme, r#type).into(),
+                ),
+            ]),
+        ),
+        ("range", range(source, name.span)),
+    ])
+}
+
+fn definition(uri: &str, source: &str, offset: usize) -> Json {
+    let analysis = analyze(source);
+
+    let declaration = identifier_at(&analysis.tokens, offset).and_then(|name| {
+        analysis
+            .references
+            .iter()
+            .find(|reference| reference.name.span == name.span)
+    });
+
+    match declaration {
+        Some(reference) => Json::object([
+            ("uri", uri.into()),
+            ("range", range(source, reference.declaration.span)),
+        ]),
+        None => Json::Null,
+    }
+}
+
+/// The variables and records declared at the top level of a document
+fn symbols(source: &str) -> Json {
+    let analysis = analyze(source);
+
+    let symbols = analysis
+        .statements
+        .iter()
+        .filter_map(|statement| {
+            let (name, kind) = match statement {
+                Statement::VariableDeclaration(declaration) => match declaration.initializer {
+                    Expression::Function(_) => (&declaration.identifier, SYMBOL_FUNCTION),
+                    _ => (&declaration.identifier, SYMBOL_VARIABLE),
+                },
+                Statement::Record(record) => (&record.name, SYMBOL_STRUCT),
+                _ => return None,
+            };
+
+            let mut symbol = vec![
+                ("name", name.lexeme.into()),
+                ("kind", kind.into()),
+                ("range", range(source, statement.span())),
+                ("selectionRange", range(source, name.span)),
+            ];
+
+            if let Some((_, r#type)) = analysis.types.iter().find(|(span, _)| *span == name.span) {
+                symbol.push(("detail", r#type.to_string().into()));
+            }
+
+            Some(Json::object(symbol))
+        })
+        .collect();
+
+    Json::Array(symbols)
+}
+
+fn lsp_diagnostic(source: &str, diagnostic: &Diagnostic) -> Json {
+    let start = column_offset(source, diagnostic.line, diagnostic.position);
+    let line_end = source[start..]
+        .find('\n')
+        .map_or(source.len(), |end| start + end);
+    let end = source[start..line_end]
+        .char_indices()
+        .nth(diagnostic.length)
+        .map_or(line_end, |(index, _)| start + index);
+
+    let mut message = diagnostic.message.clone();
+
+    for (label, text) in [
+        ("note", diagnostic.note.as_deref()),
+        ("help", diagnostic.help),
+    ] {
+        if let Some(text) = text {
+            message += &format!("\n{}: {}", label, text);
+        }
+    }
+
+    let severity: usize = match diagnostic.severity {
+        Severity::Error => 1,
+        Severity::Warning => 2,
+    };
+
+    Json::object([
+        ("range", range(source, Span::new(start, end))),
+        ("severity", severity.into()),
+        ("source", "matcha".into()),
+        ("message", message.into()),
+    ])
+}
+
+fn document_uri(params: &Json) -> &str {
+    params
+        .get("textDocument")
+        .and_then(|document| document.get("uri"))
+        .and_then(Json::as_str)
+        .unwrap_or("")
+}
+
+/// The identifier under the cursor, including when the cursor is right after it
+fn identifier_at<'a, 'b>(tokens: &'b [Token<'a>], offset: usize) -> Option<&'b Token<'a>> {
+    tokens.iter().find(|token| {
+        token.token_type == TokenType::Identifier
+            && token.span.start <= offset
+            && offset <= token.span.end
+    })
+}
+
+fn range(source: &str, span: Span) -> Json {
+    Json::object([
+        ("start", position(source, span.start)),
+        ("end", position(source, span.end)),
+    ])
+}
+
+/// The LSP position of a byte offset, a zero-based line and a column in UTF-16 code units
+fn position(source: &str, offset: usize) -> Json {
+    let before = &source[..offset];
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+
+    Json::object([
+        ("line", before.matches('\n').count().into()),
+        (
+            "character",
+            before[line_start..].encode_utf16().count().into(),
+        ),
+    ])
+}
+
+/// The byte offset of an LSP position, clamped to the end of its line
+fn offset(source: &str, line: usize, character: usize) -> Option<usize> {
+    let line_start = match line {
+        0 => 0,
+        line => source.match_indices('\n').nth(line - 1)?.0 + 1,
+    };
+    let mut units = 0;
+
+    for (index, c) in source[line_start..].char_indices() {
+        if units >= character || c == '\n' {
+            return Some(line_start + index);
+        }
+
+        units += c.len_utf16();
+    }
+
+    Some(source.len())
+}
+
+/// The byte offset of a one-based line and character column, as diagnostics report them
+fn column_offset(source: &str, line: u64, column: u64) -> usize {
+    let line_start = match line {
+        0 | 1 => 0,
+        line => source
+            .match_indices('\n')
+            .nth(line as usize - 2)
+            .map_or(source.len(), |(newline, _)| newline + 1),
+    };
+    let line_end = source[line_start..]
+        .find('\n')
+        .map_or(source.len(), |end| line_start + end);
+
+    source[line_start..line_end]
+        .char_indices()
+        .nth(column.saturating_sub(1) as usize)
+        .map_or(line_end, |(index, _)| line_start + index)
+}
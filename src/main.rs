@@ -1,39 +1,53 @@
-mod environment;
-// mod interpreter;
-mod matcha;
-mod parser;
-mod scanner;
-mod source;
-mod statement;
-mod tests;
-mod token;
-
 use std::cell::RefCell;
-use std::collections::HashMap;
 use std::env;
 use std::fs;
 use std::io;
-use std::io::Write;
+use std::io::IsTerminal;
 use std::println;
 use std::rc::Rc;
+use std::thread;
 
-use environment::Environment;
-use matcha::Literal;
-use matcha::NumberLiteral;
-use matcha::Value;
-use source::Source;
+use matcha::diagnostic::Renderer;
+use matcha::environment::Environment;
+use matcha::{formatter, highlight, interpreter, json, lsp, rename, repl, run, Options};
 
-// use crate::interpreter::Interpreter;
-use crate::parser::Parser;
-use crate::scanner::Scanner;
+fn main() {
+    // The tree-walker recurses on the native stack for every call, so it runs on a thread with
+    // enough of it to reach the call depth limit in any build
+    let cli = thread::Builder::new()
+        .stack_size(interpreter::STACK_SIZE)
+        .spawn(cli)
+        .expect("Failed to start the interpreter thread");
 
-#[cfg_attr(test, derive(Default))]
-pub struct Options {
-    pub ast: bool,
-    pub lexer_out: bool,
+    if cli.join().is_err() {
+        std::process::exit(101);
+    }
 }
 
-fn main() {
+fn cli() {
+    match env::args().nth(1).as_deref() {
+        Some("fmt") => {
+            let arguments: Vec<String> = env::args().skip(2).collect();
+            std::process::exit(format_files(&arguments));
+        }
+        Some("rename") => {
+            let arguments: Vec<String> = env::args().skip(2).collect();
+            std::process::exit(rename_file(&arguments));
+        }
+        Some("tokens") => {
+            let arguments: Vec<String> = env::args().skip(2).collect();
+            std::process::exit(print_tokens(&arguments));
+        }
+        Some("lsp") => match lsp::serve(io::stdin().lock(), io::stdout().lock()) {
+            Ok(exit_code) => std::process::exit(exit_code),
+            Err(error) => {
+                eprintln!("Language server error: {}", error);
+                std::process::exit(1);
+            }
+        },
+        _ => {}
+    }
+
     let args: Vec<String> = env::args()
         .skip(1)
         .filter(|arg| arg.starts_with("--"))
@@ -44,7 +58,13 @@ fn main() {
         .collect();
     let mut options = Options {
         ast: false,
+        ast_optimized: false,
+        cst: false,
         lexer_out: false,
+        color: io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none(),
+        vm: false,
+        bytecode: false,
+        echo: false,
     };
 
     for arg in args {
@@ -52,9 +72,24 @@ fn main() {
             "--ast" => {
                 options.ast = true;
             }
+            "--ast-optimized" => {
+                options.ast_optimized = true;
+            }
+            "--cst" => {
+                options.cst = true;
+            }
             "--lexer-out" => {
                 options.lexer_out = true;
             }
+            "--no-color" => {
+                options.color = false;
+            }
+            "--vm" => {
+                options.vm = true;
+            }
+            "--bytecode" => {
+                options.bytecode = true;
+            }
             _ => {
                 eprintln!("Unknown argument {}", arg.split_at(2).1)
             }
@@ -66,164 +101,227 @@ fn main() {
             run_file(&options, &file);
         }
     } else {
-        repl(&options);
+        repl::repl(&options);
     }
 }
 
-fn run_file(options: &Options, path: &str) {
-    let contents = fs::read_to_string(path).unwrap();
-    let environment = Rc::new(RefCell::new(Environment::new()));
+/// `matcha fmt [--check] [files]` rewrites each file in place, or only lists the files that aren't
+/// formatted with `--check`. Without files it formats stdin to stdout. Returns the exit code
+fn format_files(arguments: &[String]) -> i32 {
+    let mut check = false;
+    let mut color = io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none();
+    let mut files = Vec::new();
 
-    let exit_code = run(options, &contents, environment);
-
-    if exit_code != 0 {
-        std::process::exit(1);
+    for argument in arguments {
+        match argument.as_str() {
+            "--check" => check = true,
+            "--no-color" => color = false,
+            flag if flag.starts_with("--") => {
+                eprintln!("Unknown argument {}", flag.split_at(2).1);
+                return 2;
+            }
+            file => files.push(file),
+        }
     }
-}
 
-#[derive(Clone)]
-pub enum OwnedValue {
-    Empty,
-    Optional(Option<OwnedLiteral>),
-    Literal(OwnedLiteral),
-}
+    let format = |file_name: &str, source: &str| match formatter::format(source) {
+        Ok(formatted) => Some(formatted),
+        Err(diagnostics) => {
+            let renderer = Renderer {
+                file_name,
+                source,
+                color,
+            };
+
+            for diagnostic in diagnostics {
+                eprintln!("{}\n", renderer.render(&diagnostic));
+            }
 
-impl<'a> From<&'a OwnedValue> for Value<'a> {
-    fn from(value: &'a OwnedValue) -> Value<'a> {
-        match value {
-            OwnedValue::Empty => Value::Empty,
-            OwnedValue::Literal(l) => Value::Literal(match l {
-                OwnedLiteral::Boolean(v) => Literal::Boolean(*v),
-                OwnedLiteral::Number(n) => Literal::Number(n.clone()),
-                OwnedLiteral::String(s) => Literal::String(s),
-            }),
-            OwnedValue::Optional(o) => Value::Optional(match o {
-                None => None,
-                Some(OwnedLiteral::Boolean(v)) => Some(Literal::Boolean(*v)),
-                Some(OwnedLiteral::Number(n)) => Some(Literal::Number(n.clone())),
-                Some(OwnedLiteral::String(s)) => Some(Literal::String(s)),
-            }),
+            None
         }
+    };
+
+    if files.is_empty() {
+        let source = match io::read_to_string(io::stdin()) {
+            Ok(source) => source,
+            Err(error) => {
+                eprintln!("Could not read stdin: {}", error);
+                return 1;
+            }
+        };
+
+        return match format("<stdin>", &source) {
+            Some(formatted) if check => i32::from(formatted != source),
+            Some(formatted) => {
+                print!("{}", formatted);
+                0
+            }
+            None => 1,
+        };
     }
-}
 
-impl From<&Value<'_>> for OwnedValue {
-    fn from(value: &Value<'_>) -> OwnedValue {
-        match value {
-            Value::Empty => OwnedValue::Empty,
-            Value::Literal(l) => OwnedValue::Literal(match l {
-                Literal::Boolean(v) => OwnedLiteral::Boolean(*v),
-                Literal::Number(n) => OwnedLiteral::Number(n.clone()),
-                Literal::String(s) => OwnedLiteral::String(s.to_string()),
-            }),
-            Value::Optional(o) => OwnedValue::Optional(match o {
-                None => None,
-                Some(Literal::Boolean(v)) => Some(OwnedLiteral::Boolean(*v)),
-                Some(Literal::Number(n)) => Some(OwnedLiteral::Number(n.clone())),
-                Some(Literal::String(s)) => Some(OwnedLiteral::String(s.to_string())),
-            }),
+    let mut exit_code = 0;
+
+    for file in files {
+        let source = match fs::read_to_string(file) {
+            Ok(source) => source,
+            Err(error) => {
+                eprintln!("Could not read {}: {}", file, error);
+                exit_code = 1;
+                continue;
+            }
+        };
+
+        let Some(formatted) = format(file, &source) else {
+            exit_code = 1;
+            continue;
+        };
+
+        if formatted == source {
+            continue;
+        }
+
+        if check {
+            println!("{} is not formatted", file);
+            exit_code = 1;
+        } else if let Err(error) = fs::write(file, formatted) {
+            eprintln!("Could not write {}: {}", file, error);
+            exit_code = 1;
         }
     }
-}
 
-#[derive(Clone)]
-pub enum OwnedLiteral {
-    String(String),
-    Number(NumberLiteral),
-    Boolean(bool),
+    exit_code
 }
 
-fn repl(options: &Options) {
-    println!("Matcha 🍵 {}", env!("CARGO_PKG_VERSION"));
-    let mut line = String::new();
-    let mut prev_environment = HashMap::<String, OwnedValue>::new();
-
-    loop {
-        print!(">>> ");
-        io::stdout().flush().unwrap();
-        io::stdin().read_line(&mut line).unwrap();
-
-        if !line.is_empty() {
-            let _env = prev_environment.clone();
-
-            let environment = Environment {
-                values: _env
-                    .iter()
-                    .map(|(k, v)| (k.to_string(), v.into()))
-                    .collect(),
-                parent: None,
-            };
+/// `matcha rename <file> <line>:<position> <name>` renames the variable at the position and every
+/// use of it, rewriting the file in place. Returns the exit code
+fn rename_file(arguments: &[String]) -> i32 {
+    let mut color = io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none();
+    let mut rest = Vec::new();
+
+    for argument in arguments {
+        match argument.as_str() {
+            "--no-color" => color = false,
+            flag if flag.starts_with("--") => {
+                eprintln!("Unknown argument {}", flag.split_at(2).1);
+                return 2;
+            }
+            argument => rest.push(argument),
+        }
+    }
 
-            let environment = Rc::new(RefCell::new(environment));
+    let [file, location, name] = rest[..] else {
+        eprintln!("Usage: matcha rename <file> <line>:<position> <name>");
+        return 2;
+    };
 
-            run(options, &line, Rc::clone(&environment));
+    let Some((line, position)) = location
+        .split_once(':')
+        .and_then(|(line, position)| Some((line.parse().ok()?, position.parse().ok()?)))
+    else {
+        eprintln!("Expected a location like 3:14, got {}", location);
+        return 2;
+    };
 
-            prev_environment = environment
-                .borrow()
-                .values
-                .iter()
-                .map(|(k, v)| (k.to_string(), v.into()))
-                .collect();
+    let source = match fs::read_to_string(file) {
+        Ok(source) => source,
+        Err(error) => {
+            eprintln!("Could not read {}: {}", file, error);
+            return 1;
         }
+    };
+
+    match rename::rename(&source, line, position, name) {
+        Ok(renamed) => match fs::write(file, renamed) {
+            Ok(()) => 0,
+            Err(error) => {
+                eprintln!("Could not write {}: {}", file, error);
+                1
+            }
+        },
+        Err(diagnostics) => {
+            let renderer = Renderer {
+                file_name: file,
+                source: &source,
+                color,
+            };
 
-        line.clear()
+            for diagnostic in diagnostics {
+                eprintln!("{}\n", renderer.render(&diagnostic));
+            }
+
+            1
+        }
     }
 }
 
-pub fn run<'a>(
-    options: &Options,
-    program: &'a str,
-    environment: Rc<RefCell<Environment<'a>>>,
-) -> u8 {
-    let mut scanner = Scanner {
-        source: Source::new(program),
-    };
+/// `matcha tokens [--json] [file]` lists every token of the file, or of stdin without one,
+/// including comments and whitespace, with what it means. Returns the exit code
+fn print_tokens(arguments: &[String]) -> i32 {
+    let mut json = false;
+    let mut file = None;
 
-    let tokens_result = scanner.scan();
-
-    match tokens_result {
-        Ok(tokens) => {
-            if options.lexer_out {
-                println!("{:#?}", tokens);
-            }
-
-            let parser = Parser::new(tokens);
-            let parser_result = parser.parse();
-
-            match parser_result {
-                Ok(statements) => {
-                    if options.ast {
-                        for statement in &statements {
-                            println!("{}", statement.format(0));
-                        }
-                    }
-
-                    0
-
-                    // let interpreter_result = Interpreter::interpret(environment, &statements);
-
-                    // match interpreter_result {
-                    //     Ok(result) => {
-                    //         println!("{}", result);
-                    //         0
-                    //     }
-                    //     Err(e) => {
-                    //         eprintln!("{:#?}", e);
-                    //         1
-                    //     }
-                    // }
-                }
-                Err(errors) => {
-                    for error in errors {
-                        eprintln!("{}", error);
-                    }
-                    1
-                }
+    for argument in arguments {
+        match argument.as_str() {
+            "--json" => json = true,
+            flag if flag.starts_with("--") => {
+                eprintln!("Unknown argument {}", flag.split_at(2).1);
+                return 2;
             }
+            path => file = Some(path),
         }
-        Err(e) => {
-            eprintln!("{}", e);
-            1
+    }
+
+    let source = match file {
+        Some(path) => fs::read_to_string(path),
+        None => io::read_to_string(io::stdin()),
+    };
+    let source = match source {
+        Ok(source) => source,
+        Err(error) => {
+            eprintln!("Could not read {}: {}", file.unwrap_or("stdin"), error);
+            return 1;
         }
+    };
+
+    let highlights = highlight::highlight(&source);
+
+    if json {
+        let tokens: Vec<json::Json> = highlights.iter().map(|token| token.to_json()).collect();
+        println!("{}", json::Json::from(tokens));
+
+        return 0;
+    }
+
+    for highlight in highlights {
+        let modifiers: Vec<&str> = highlight
+            .modifiers
+            .iter()
+            .map(|modifier| modifier.name())
+            .collect();
+
+        let line = format!(
+            "{}:{} {} {:?} {}",
+            highlight.token.line,
+            highlight.token.position,
+            highlight.kind.name(),
+            highlight.token.lexeme,
+            modifiers.join(" ")
+        );
+
+        println!("{}", line.trim_end());
+    }
+
+    0
+}
+
+fn run_file(options: &Options, path: &str) {
+    let contents = fs::read_to_string(path).unwrap();
+    let environment = Rc::new(RefCell::new(Environment::new()));
+
+    let exit_code = run(options, path, &contents, environment);
+
+    if exit_code != 0 {
+        std::process::exit(1);
     }
 }
@@ -1,11 +1,19 @@
 use std::{
+    borrow::Cow,
+    cell::RefCell,
     collections::HashMap,
     fmt::Display,
     ops::{Add, Div, Mul, Sub},
+    rc::Rc,
     sync::LazyLock,
 };
 
-use crate::token::TokenType;
+use crate::{
+    chunk::Prototype,
+    environment::Environment,
+    statement::{FunctionExpression, RecordDeclaration, TypeAnnotation},
+    token::TokenType,
+};
 
 pub static KEYWORDS: LazyLock<HashMap<&str, TokenType>> = LazyLock::new(|| {
     HashMap::from([
@@ -14,25 +22,58 @@ pub static KEYWORDS: LazyLock<HashMap<&str, TokenType>> = LazyLock::new(|| {
         ("true", TokenType::True),
         ("false", TokenType::False),
         ("for", TokenType::For),
+        ("fn", TokenType::Fn),
+        ("return", TokenType::Return),
+        ("none", TokenType::None),
+        ("record", TokenType::Record),
     ])
 });
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Literal<'a> {
-    String(&'a str),
+    String(Cow<'a, str>),
     Number(NumberLiteral),
     Boolean(bool),
 }
 
 impl Literal<'_> {
-    pub fn get_type(&self) -> &str {
+    pub fn get_type(&self) -> Type {
         match self {
-            Literal::String(_) => "String",
+            Literal::String(_) => Type::String,
             Literal::Number(number) => match number {
-                NumberLiteral::Float(_) => "Float",
-                NumberLiteral::Integer(_) => "Integer",
+                NumberLiteral::Float(_) => Type::Float,
+                NumberLiteral::Integer(_) => Type::Integer,
             },
-            Literal::Boolean(_) => "Boolean",
+            Literal::Boolean(_) => Type::Boolean,
+        }
+    }
+
+    /// Writes a literal the way it would appear in source code, so the scanner can read it back
+    pub fn source_text(&self) -> String {
+        match self {
+            Literal::String(string) => {
+                let mut text = String::from("\"");
+
+                for c in string.chars() {
+                    match c {
+                        '\n' => text.push_str("\\n"),
+                        '\t' => text.push_str("\\t"),
+                        '\r' => text.push_str("\\r"),
+                        '\0' => text.push_str("\\0"),
+                        '\\' => text.push_str("\\\\"),
+                        '"' => text.push_str("\\\""),
+                        c => text.push(c),
+                    }
+                }
+
+                text.push('"');
+                text
+            }
+            // Floats always need a fractional part to be scanned as floats
+            Literal::Number(NumberLiteral::Float(float)) if float.fract() == 0.0 => {
+                format!("{}.0", float)
+            }
+            literal => literal.to_string(),
         }
     }
 }
@@ -180,22 +221,318 @@ impl PartialOrd for NumberLiteral {
     }
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Integer,
+    Float,
+    String,
+    Boolean,
+    Empty,
+    Function {
+        parameters: Vec<Type>,
+        return_type: Box<Type>,
+    },
+    Array(Box<Type>),
+    /// A value of the inner type, or `none`
+    Optional(Box<Type>),
+    /// An instance of a record, e.g. `Point { x: 1, y: 2 }`
+    Record(RecordType),
+    /// The record itself, which is what its name refers to
+    RecordDeclaration(RecordType),
+    /// The type could not be determined statically, so it is checked at runtime instead
+    Unknown,
+}
+
+impl Type {
+    /// Resolves the name used in a type annotation, e.g. `x: Int = 5;`
+    pub fn from_name(name: &str) -> Option<Type> {
+        match name {
+            "Int" => Some(Type::Integer),
+            "Float" => Some(Type::Float),
+            "String" => Some(Type::String),
+            "Bool" => Some(Type::Boolean),
+            _ => None,
+        }
+    }
+
+    /// Resolves an optional annotation, treating missing or unknown names as `Type::Unknown`
+    pub fn from_annotation(annotation: Option<&TypeAnnotation>) -> Type {
+        match annotation {
+            Some(TypeAnnotation {
+                function: Some(function),
+                ..
+            }) => Type::Function {
+                parameters: function
+                    .parameters
+                    .iter()
+                    .map(|parameter| Type::from_annotation(Some(parameter)))
+                    .collect(),
+                return_type: Box::new(Type::from_annotation(function.return_type.as_deref())),
+            },
+            Some(annotation) => {
+                let r#type = Type::from_name(annotation.name.lexeme).unwrap_or(Type::Unknown);
+
+                match annotation.optional {
+                    true => Type::Optional(Box::new(r#type)),
+                    false => r#type,
+                }
+            }
+            None => Type::Unknown,
+        }
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        matches!(self, Type::Integer | Type::Float)
+    }
+
+    /// The type held by an optional, or the type itself otherwise
+    pub fn unwrapped(&self) -> &Type {
+        match self {
+            Type::Optional(inner) => inner,
+            r#type => r#type,
+        }
+    }
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Type::Integer => write!(f, "Int"),
+            Type::Float => write!(f, "Float"),
+            Type::String => write!(f, "String"),
+            Type::Boolean => write!(f, "Bool"),
+            Type::Empty => write!(f, "Empty"),
+            Type::Function {
+                parameters,
+                return_type,
+            } => {
+                let parameters: Vec<String> = parameters
+                    .iter()
+                    .map(|parameter| parameter.to_string())
+                    .collect();
+
+                write!(f, "fn({}): {}", parameters.join(", "), return_type)
+            }
+            Type::Array(element) => write!(f, "[{}]", element),
+            Type::Optional(inner) => write!(f, "{}?", inner),
+            Type::Record(record) => write!(f, "{}", record.name),
+            Type::RecordDeclaration(record) => write!(f, "record {}", record.name),
+            Type::Unknown => write!(f, "Unknown"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RecordType {
+    pub name: String,
+    pub fields: Vec<(String, Type)>,
+}
+
+impl RecordType {
+    pub fn field(&self, name: &str) -> Option<&Type> {
+        self.fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, r#type)| r#type)
+    }
+}
+
+// Records are nominal, and comparing by name also keeps self-referencing records finite
+impl PartialEq for RecordType {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum Value<'a> {
     Empty,
-    Optional(Option<Literal<'a>>),
+    Optional(Option<Box<Value<'a>>>),
     Literal(Literal<'a>),
+    Function(Rc<Function<'a>>),
+    /// A function compiled to bytecode, created by the VM instead of the tree-walker
+    Closure(Rc<Closure<'a>>),
+    Array(Array<'a>),
+    Record(Record<'a>),
+    RecordDeclaration(Rc<RecordDeclaration<'a>>),
+}
+
+/// Arrays are shared by reference, so mutating one through any alias is visible to all
+pub type Array<'a> = Rc<RefCell<Vec<Value<'a>>>>;
+
+/// Records are shared by reference like arrays, so `p.x = 1` is visible through every alias
+pub type Record<'a> = Rc<RefCell<RecordValue<'a>>>;
+
+#[derive(Debug)]
+pub struct RecordValue<'a> {
+    pub declaration: Rc<RecordDeclaration<'a>>,
+    /// The values of the fields, in the order they were declared
+    pub fields: Vec<Value<'a>>,
+}
+
+impl RecordValue<'_> {
+    /// The position of the field called `name`, if the record has one
+    pub fn field_index(&self, name: &str) -> Option<usize> {
+        self.declaration
+            .fields
+            .iter()
+            .position(|field| field.identifier.lexeme == name)
+    }
+}
+
+/// The type of a record according to its annotations
+pub fn record_type(declaration: &RecordDeclaration) -> RecordType {
+    RecordType {
+        name: declaration.name.lexeme.to_owned(),
+        fields: declaration
+            .fields
+            .iter()
+            .map(|field| {
+                (
+                    field.identifier.lexeme.to_owned(),
+                    Type::from_annotation(Some(&field.r#type)),
+                )
+            })
+            .collect(),
+    }
+}
+
+pub struct Function<'a> {
+    pub declaration: FunctionExpression<'a>,
+    /// The environment the function was defined in, captured so the body can access it
+    pub closure: Rc<RefCell<Environment<'a>>>,
+}
+
+impl Function<'_> {
+    pub fn arity(&self) -> usize {
+        self.declaration.parameters.len()
+    }
+
+    pub fn get_type(&self) -> Type {
+        function_type(&self.declaration)
+    }
+}
+
+/// The type of a function according to its annotations
+pub fn function_type(declaration: &FunctionExpression) -> Type {
+    Type::Function {
+        parameters: declaration
+            .parameters
+            .iter()
+            .map(|parameter| Type::from_annotation(parameter.r#type.as_ref()))
+            .collect(),
+        return_type: Box::new(Type::from_annotation(declaration.return_type.as_ref())),
+    }
+}
+
+// The closure is omitted since it usually contains the function itself
+impl std::fmt::Debug for Function<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Function")
+            .field("declaration", &self.declaration)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Display for Function<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<{}>", self.get_type())
+    }
+}
+
+#[derive(Debug)]
+pub struct Closure<'a> {
+    pub prototype: Rc<Prototype<'a>>,
+    /// The variables captured from enclosing functions, in the order the prototype lists them
+    pub upvalues: Vec<Upvalue<'a>>,
+}
+
+impl Display for Closure<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<{}>", self.prototype.r#type)
+    }
+}
+
+/// A captured variable, shared by every closure that captures it
+pub type Upvalue<'a> = Rc<RefCell<UpvalueState<'a>>>;
+
+#[derive(Debug)]
+pub enum UpvalueState<'a> {
+    /// The variable still lives on the VM stack, at this absolute slot
+    Open(usize),
+    /// The variable went out of scope, so the upvalue holds it from now on
+    Closed(Value<'a>),
+}
+
+impl Value<'_> {
+    pub fn get_type(&self) -> Type {
+        match self {
+            Value::Empty => Type::Empty,
+            Value::Optional(None) => Type::Optional(Box::new(Type::Unknown)),
+            Value::Optional(Some(value)) => Type::Optional(Box::new(value.get_type())),
+            Value::Literal(literal) => literal.get_type(),
+            Value::Function(function) => function.get_type(),
+            Value::Closure(closure) => closure.prototype.r#type.clone(),
+            Value::Array(elements) => Type::Array(Box::new(
+                // Arrays are homogeneous, so the first element determines the type
+                elements
+                    .borrow()
+                    .first()
+                    .map(|element| element.get_type())
+                    .unwrap_or(Type::Unknown),
+            )),
+            Value::Record(record) => Type::Record(record_type(&record.borrow().declaration)),
+            Value::RecordDeclaration(declaration) => {
+                Type::RecordDeclaration(record_type(declaration))
+            }
+        }
+    }
 }
 
 impl Display for Value<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Value::Empty => write!(f, "<empty>"),
-            Value::Optional(optional_literal) => match optional_literal {
-                None => write!(f, "None"),
-                Some(literal) => write!(f, "{}", literal),
+            Value::Optional(optional) => match optional {
+                None => write!(f, "none"),
+                Some(value) => write!(f, "{}", value),
             },
             Value::Literal(literal) => write!(f, "{}", literal),
+            Value::Function(function) => write!(f, "{}", function),
+            Value::Closure(closure) => write!(f, "{}", closure),
+            Value::Array(elements) => {
+                let elements: Vec<String> = elements
+                    .borrow()
+                    .iter()
+                    .map(|element| element.to_string())
+                    .collect();
+
+                write!(f, "[{}]", elements.join(", "))
+            }
+            Value::Record(record) => {
+                let record = record.borrow();
+                let fields: Vec<String> = record
+                    .declaration
+                    .fields
+                    .iter()
+                    .zip(record.fields.iter())
+                    .map(|(field, value)| format!("{}: {}", field.identifier.lexeme, value))
+                    .collect();
+
+                if fields.is_empty() {
+                    return write!(f, "{} {{}}", record.declaration.name.lexeme);
+                }
+
+                write!(
+                    f,
+                    "{} {{ {} }}",
+                    record.declaration.name.lexeme,
+                    fields.join(", ")
+                )
+            }
+            Value::RecordDeclaration(declaration) => {
+                write!(f, "<record {}>", declaration.name.lexeme)
+            }
         }
     }
 }
@@ -0,0 +1,305 @@
+use std::rc::Rc;
+
+use crate::{
+    interpreter::Interpreter,
+    matcha::{Literal, NumberLiteral, Value},
+    statement::{
+        ArrayExpression, AssignmentExpression, BinaryExpression, BlockStatement, CallExpression,
+        Expression, FieldAssignmentExpression, FieldExpression, FieldInitializer, ForStatement,
+        FunctionExpression, GroupingExpression, IfStatement, IndexAssignmentExpression,
+        IndexExpression, LiteralExpression, RecordExpression, ReturnStatement, Statement,
+        UnaryExpression, UnwrapExpression, VariableDeclaration,
+    },
+    token::{Span, Token, TokenType},
+};
+
+// Optimizing runs after type checking, so operands have the types the checker inferred and
+// anything that would only fail at runtime, like an integer division by zero, is left as it is.
+// Scopes are never merged or removed around code that still runs, which keeps the depths and
+// slots set by the resolver valid
+
+/// Folds arithmetic on literals, like `60 * 60 * 24`, and removes the branches of `if` and `for`
+/// statements whose conditions are constant
+pub fn optimize(statements: Vec<Statement>) -> Vec<Statement> {
+    let count = statements.len();
+
+    statements
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, statement)| self::statement(statement, index + 1 == count))
+        .collect()
+}
+
+/// The optimized statement, or `None` if it never runs. The last statement of a block gives the
+/// block its value, so it is replaced by an empty block instead of being removed
+fn statement(statement: Statement, last: bool) -> Option<Statement> {
+    match statement {
+        Statement::Expression(ex) => Some(Statement::Expression(expression(ex))),
+        Statement::VariableDeclaration(declaration) => {
+            Some(Statement::VariableDeclaration(VariableDeclaration {
+                initializer: expression(declaration.initializer),
+                ..declaration
+            }))
+        }
+        Statement::Block(block) => Some(Statement::Block(BlockStatement {
+            statements: optimize(block.statements),
+            span: block.span,
+        })),
+        Statement::If(if_statement) => self::if_statement(if_statement, last),
+        Statement::For(for_statement) => {
+            let condition = expression(for_statement.condition);
+
+            match constant(&condition) {
+                Some(false) => removed(for_statement.span, last),
+                _ => Some(Statement::For(ForStatement {
+                    condition,
+                    statements: optimize(for_statement.statements),
+                    span: for_statement.span,
+                })),
+            }
+        }
+        Statement::Return(return_statement) => Some(Statement::Return(ReturnStatement {
+            value: return_statement.value.map(expression),
+            ..return_statement
+        })),
+        Statement::Record(record) => Some(Statement::Record(record)),
+    }
+}
+
+/// Replaces an `if` with a constant condition by a block of the branch that runs, if any
+fn if_statement(if_statement: IfStatement, last: bool) -> Option<Statement> {
+    let IfStatement {
+        binding,
+        condition,
+        statements,
+        else_statements,
+        span,
+    } = if_statement;
+
+    let condition = expression(condition);
+    let statements = optimize(statements);
+    let else_statements = else_statements.map(optimize);
+
+    // A binding unwraps an optional, so its condition is never a constant boolean
+    let taken = match binding {
+        Some(_) => None,
+        None => constant(&condition),
+    };
+
+    match (taken, else_statements) {
+        (Some(true), _) => Some(Statement::Block(BlockStatement { statements, span })),
+        (Some(false), Some(statements)) => {
+            Some(Statement::Block(BlockStatement { statements, span }))
+        }
+        (Some(false), None) => removed(span, last),
+        (None, else_statements) => Some(Statement::If(IfStatement {
+            binding,
+            condition,
+            statements,
+            else_statements,
+            span,
+        })),
+    }
+}
+
+fn removed<'a>(span: Span, last: bool) -> Option<Statement<'a>> {
+    last.then_some(Statement::Block(BlockStatement {
+        statements: Vec::new(),
+        span,
+    }))
+}
+
+/// The value of a condition that is a boolean literal
+fn constant(condition: &Expression) -> Option<bool> {
+    match condition {
+        Expression::Literal(LiteralExpression {
+            literal: Literal::Boolean(value),
+            ..
+        }) => Some(*value),
+        _ => None,
+    }
+}
+
+fn expression(expression: Expression) -> Expression {
+    match expression {
+        Expression::Binary(binary) => {
+            let binary = operands(binary);
+
+            fold_binary(&binary).unwrap_or(Expression::Binary(binary))
+        }
+        Expression::Logical(logical) => Expression::Logical(operands(logical)),
+        Expression::Coalesce(coalesce) => Expression::Coalesce(operands(coalesce)),
+        Expression::Unary(unary) => {
+            let unary = UnaryExpression {
+                left: boxed(*unary.left),
+                operator: unary.operator,
+            };
+
+            fold_unary(&unary).unwrap_or(Expression::Unary(unary))
+        }
+        Expression::Grouping(grouping) => match self::expression(*grouping.expression) {
+            // Parentheses around a literal don't group anything anymore
+            Expression::Literal(literal) => Expression::Literal(LiteralExpression {
+                value: Token {
+                    span: grouping.span,
+                    ..literal.value
+                },
+                literal: literal.literal,
+            }),
+            inner => Expression::Grouping(GroupingExpression {
+                expression: Box::new(inner),
+                span: grouping.span,
+            }),
+        },
+        Expression::Assignment(assignment) => Expression::Assignment(AssignmentExpression {
+            value: boxed(*assignment.value),
+            ..assignment
+        }),
+        Expression::Function(function) => Expression::Function(FunctionExpression {
+            body: Rc::new(optimize(Rc::unwrap_or_clone(function.body))),
+            ..function
+        }),
+        Expression::Call(call) => Expression::Call(CallExpression {
+            callee: boxed(*call.callee),
+            arguments: call.arguments.into_iter().map(self::expression).collect(),
+            ..call
+        }),
+        Expression::Array(array) => Expression::Array(ArrayExpression {
+            elements: array.elements.into_iter().map(self::expression).collect(),
+            ..array
+        }),
+        Expression::Index(index) => Expression::Index(IndexExpression {
+            target: boxed(*index.target),
+            index: boxed(*index.index),
+            ..index
+        }),
+        Expression::IndexAssignment(assignment) => {
+            Expression::IndexAssignment(IndexAssignmentExpression {
+                target: boxed(*assignment.target),
+                index: boxed(*assignment.index),
+                value: boxed(*assignment.value),
+                ..assignment
+            })
+        }
+        Expression::Unwrap(unwrap) => Expression::Unwrap(UnwrapExpression {
+            target: boxed(*unwrap.target),
+            ..unwrap
+        }),
+        Expression::Record(record) => Expression::Record(RecordExpression {
+            fields: record
+                .fields
+                .into_iter()
+                .map(|field| FieldInitializer {
+                    value: self::expression(field.value),
+                    ..field
+                })
+                .collect(),
+            ..record
+        }),
+        Expression::Field(field) => Expression::Field(FieldExpression {
+            target: boxed(*field.target),
+            ..field
+        }),
+        Expression::FieldAssignment(assignment) => {
+            Expression::FieldAssignment(FieldAssignmentExpression {
+                target: boxed(*assignment.target),
+                value: boxed(*assignment.value),
+                ..assignment
+            })
+        }
+        ex @ (Expression::Literal(_) | Expression::Variable(_) | Expression::None(_)) => ex,
+    }
+}
+
+fn boxed(ex: Expression) -> Box<Expression> {
+    Box::new(expression(ex))
+}
+
+fn operands(binary: BinaryExpression) -> BinaryExpression {
+    BinaryExpression {
+        left: boxed(*binary.left),
+        operator: binary.operator,
+        right: boxed(*binary.right),
+    }
+}
+
+/// `+`, `-`, `*` and `/` on two number literals. They are applied like at runtime, and left as
+/// they are when that fails, so the error is still reported when the expression runs
+fn fold_binary<'a>(binary: &BinaryExpression<'a>) -> Option<Expression<'a>> {
+    let (Expression::Literal(left), Expression::Literal(right)) = (&*binary.left, &*binary.right)
+    else {
+        return None;
+    };
+
+    if !matches!(
+        (binary.operator.token_type, &left.literal, &right.literal),
+        (
+            TokenType::Plus | TokenType::Minus | TokenType::Star | TokenType::Slash,
+            Literal::Number(_),
+            Literal::Number(_)
+        )
+    ) {
+        return None;
+    }
+
+    let value = Interpreter::binary_operation(
+        &binary.operator,
+        Value::Literal(left.literal.clone()),
+        Value::Literal(right.literal.clone()),
+    )
+    .ok()?;
+
+    literal(
+        value,
+        &left.value,
+        binary.left.span().to(binary.right.span()),
+    )
+}
+
+/// `-` on a number literal and `!` on a boolean one, applied like at runtime
+fn fold_unary<'a>(unary: &UnaryExpression<'a>) -> Option<Expression<'a>> {
+    let Expression::Literal(operand) = &*unary.left else {
+        return None;
+    };
+
+    if !matches!(
+        (unary.operator.token_type, &operand.literal),
+        (TokenType::Minus, Literal::Number(_)) | (TokenType::Bang, Literal::Boolean(_))
+    ) {
+        return None;
+    }
+
+    let value =
+        Interpreter::unary_operation(&unary.operator, Value::Literal(operand.literal.clone()))
+            .ok()?;
+
+    literal(
+        value,
+        &unary.operator,
+        unary.operator.span.to(operand.value.span),
+    )
+}
+
+/// A literal that replaces a folded expression, if its value is one. Its token is the first one of
+/// the expression, covering all of it, so that errors are still reported where the expression was
+fn literal<'a>(value: Value<'a>, first: &Token<'a>, span: Span) -> Option<Expression<'a>> {
+    let Value::Literal(literal) = value else {
+        return None;
+    };
+    let token_type = match literal {
+        Literal::Number(NumberLiteral::Integer(_)) => TokenType::Integer,
+        Literal::Number(NumberLiteral::Float(_)) => TokenType::Float,
+        Literal::Boolean(true) => TokenType::True,
+        Literal::Boolean(false) => TokenType::False,
+        Literal::String(_) => TokenType::String,
+    };
+
+    Some(Expression::Literal(LiteralExpression {
+        value: Token {
+            token_type,
+            span,
+            ..first.clone()
+        },
+        literal,
+    }))
+}
@@ -1,11 +1,10 @@
-use std::fmt::Display;
+use std::{borrow::Cow, fmt::Display, mem};
 
 use crate::{
-    statement::{
-        AssignmentExpression, BinaryExpression, Expression, ForStatement, GroupingExpression,
-        IfStatement, LiteralExpression, Statement, UnaryExpression, VariableDeclaration,
-        VariableExpression,
-    },
+    lower,
+    matcha::{Literal, NumberLiteral},
+    statement::Statement,
+    syntax::{self, Checkpoint, SyntaxKind, SyntaxNode, TreeBuilder},
     token::{Token, TokenType},
 };
 
@@ -13,11 +12,23 @@ use crate::{
 pub struct ParserError<'a> {
     pub message: String,
     pub token: Token<'a>,
+    pub note: Option<String>,
+    pub help: Option<&'static str>,
 }
 
 impl ParserError<'_> {
     pub fn new(message: String, token: Token) -> ParserError {
-        ParserError { message, token }
+        ParserError {
+            message,
+            token,
+            note: None,
+            help: None,
+        }
+    }
+
+    pub fn with_help(mut self, help: &'static str) -> Self {
+        self.help = Some(help);
+        self
     }
 }
 
@@ -33,42 +44,67 @@ impl Display for ParserError<'_> {
 
 pub struct Parser<'a> {
     current_index: usize,
+    /// The tokens without trivia, which is all the grammar looks at
     tokens: Vec<Token<'a>>,
+    /// The trivia before each token, put back in the tree as the tokens are consumed
+    trivia: Vec<Vec<Token<'a>>>,
+    builder: TreeBuilder<'a>,
+    /// How many function bodies enclose the current token, used to validate `return`
+    function_depth: usize,
+    /// Whether `Name { ... }` may start a record literal. Disabled in conditions, where the brace
+    /// opens the block instead, e.g. `if ready { ... }`
+    record_literals: bool,
 }
 
 impl<'a> Parser<'a> {
+    /// Takes the tokens of a scanner, with or without trivia
     pub fn new(tokens: Vec<Token>) -> Parser {
+        let (tokens, trivia) = syntax::split_trivia(tokens);
+
         Parser {
             current_index: 0,
             tokens,
+            trivia,
+            builder: TreeBuilder::new(),
+            function_depth: 0,
+            record_literals: true,
         }
     }
 
-    pub fn parse(mut self) -> Result<Vec<Statement<'a>>, Vec<ParserError<'a>>> {
-        self.current_index = 0;
+    /// Parses the tokens into statements, which are lowered from the syntax tree
+    pub fn parse(self) -> Result<Vec<Statement<'a>>, Vec<ParserError<'a>>> {
+        let (tree, errors) = self.parse_syntax();
+
+        if errors.is_empty() {
+            return Ok(lower::statements(&tree));
+        }
+
+        Err(errors)
+    }
 
-        let mut statements = Vec::<Statement>::new();
+    /// Parses the tokens into a concrete syntax tree that keeps every one of them, so that it
+    /// reproduces the source when the tokens include the trivia. Code that can't be parsed is
+    /// kept in `SyntaxKind::Error` nodes along with the tokens skipped after it
+    pub fn parse_syntax(mut self) -> (SyntaxNode<'a>, Vec<ParserError<'a>>) {
         let mut errors = Vec::<ParserError<'a>>::new();
 
         while !self.is_end() {
-            let result = self.statement();
+            let checkpoint = self.checkpoint();
 
-            match result {
-                Ok(statement) => {
-                    statements.push(statement);
-                }
-                Err(e) => {
-                    errors.push(e);
-                    self.sync()
-                }
+            if let Err(e) = self.statement() {
+                errors.push(e);
+
+                self.builder.finish_nodes();
+                self.builder.start_node_at(checkpoint, SyntaxKind::Error);
+                self.sync();
+                self.builder.finish_node();
             }
         }
 
-        if errors.is_empty() {
-            return Ok(statements);
-        }
+        // `Eof` and the trivia before it
+        self.bump();
 
-        Err(errors)
+        (self.builder.finish(), errors)
     }
 
     #[inline]
@@ -89,239 +125,332 @@ impl<'a> Parser<'a> {
     }
 
     #[inline]
-    fn statement<'b>(&'b mut self) -> Result<Statement<'a>, ParserError<'a>> {
-        if self.consumed_one_of([TokenType::If]) {
+    fn statement<'b>(&'b mut self) -> Result<(), ParserError<'a>> {
+        if self.next_matches(TokenType::If) {
             return self.if_statement();
         }
 
-        if self.consumed_one_of([TokenType::For]) {
+        if self.next_matches(TokenType::For) {
             return self.while_statement();
         }
 
+        if self.next_matches(TokenType::Return) {
+            return self.return_statement();
+        }
+
+        if self.next_matches(TokenType::Record) {
+            return self.record_declaration();
+        }
+
         match self.lookahead_many::<4>().map(|t| t.map(|t| t.token_type)) {
             [Some(TokenType::Identifier), Some(TokenType::Colon), Some(TokenType::Identifier), Some(TokenType::Equal)]
+            | [Some(TokenType::Identifier), Some(TokenType::Colon), Some(TokenType::Identifier), Some(TokenType::Question)]
+            | [Some(TokenType::Identifier), Some(TokenType::Colon), Some(TokenType::Fn), ..]
             | [Some(TokenType::Identifier), Some(TokenType::VarDec), ..] => {
                 return self.variable_declaration()
             }
             _ => {}
         }
 
-        if self.consumed_one_of([TokenType::LeftBrace]) {
-            return Ok(Statement::Block(self.block()?));
+        if self.next_matches(TokenType::LeftBrace) {
+            return self.block("Expected '{'");
         }
 
         self.expression_statement()
     }
 
     #[inline]
-    fn expression_statement<'b>(&'b mut self) -> Result<Statement<'a>, ParserError<'a>> {
-        let expr = self.expression()?;
+    fn expression_statement<'b>(&'b mut self) -> Result<(), ParserError<'a>> {
+        self.start_node(SyntaxKind::ExpressionStatement);
+        self.expression()?;
 
-        let _ = self.consume_and_expect(TokenType::SemiColon, "Expected ';'".to_owned())?;
+        // The last expression of a block may omit its ';', e.g. `fn(a: Int) { a + 1 }`
+        if !self.next_matches(TokenType::RightBrace) {
+            let _ = self.consume_and_expect(TokenType::SemiColon, "Expected ';'".to_owned())?;
+        }
 
-        Ok(Statement::Expression(expr))
+        self.builder.finish_node();
+
+        Ok(())
     }
 
+    /// Parses an expression, returning the kind of its outermost node
     #[inline]
-    fn expression<'b>(&'b mut self) -> Result<Expression<'a>, ParserError<'a>> {
+    fn expression<'b>(&'b mut self) -> Result<SyntaxKind, ParserError<'a>> {
         self.assignment()
     }
 
     #[inline]
-    fn variable_declaration<'b>(&'b mut self) -> Result<Statement<'a>, ParserError<'a>> {
-        let identifier = self
-            .consume_and_expect(TokenType::Identifier, "Expected identifier".to_owned())?
-            .clone();
+    fn variable_declaration<'b>(&'b mut self) -> Result<(), ParserError<'a>> {
+        self.start_node(SyntaxKind::VariableDeclaration);
 
-        let r#type = if self.consumed_one_of([TokenType::Colon]) {
-            Some(self.variable_declaration_type()?)
-        } else {
-            None
-        };
+        let _ = self.consume_and_expect(TokenType::Identifier, "Expected identifier".to_owned())?;
 
-        let initializer = if self.consumed_one_of([TokenType::VarDec, TokenType::Equal]) {
-            self.expression()?
-        } else {
+        if self.consumed_one_of([TokenType::Colon]) {
+            self.variable_declaration_type()?;
+        }
+
+        if !self.consumed_one_of([TokenType::VarDec, TokenType::Equal]) {
             unreachable!()
-        };
+        }
 
-        let declaration = Statement::VariableDeclaration(VariableDeclaration {
-            identifier,
-            initializer,
-            r#type,
-        });
+        self.expression()?;
 
         let _ = self.consume_and_expect(TokenType::SemiColon, "Expected ';'".to_owned())?;
 
-        Ok(declaration)
+        self.builder.finish_node();
+
+        Ok(())
     }
 
-    #[inline(always)]
-    fn variable_declaration_type(&mut self) -> Result<Token<'a>, ParserError<'a>> {
-        let identifier = self
-            .consume_and_expect(TokenType::Identifier, "Expected type identifier".to_owned())?
-            .clone();
+    /// A type name like `Int?`, or a function type like `fn(Int, Float): Bool`
+    fn variable_declaration_type(&mut self) -> Result<(), ParserError<'a>> {
+        self.start_node(SyntaxKind::TypeAnnotation);
 
-        Ok(identifier)
+        if self.consumed_one_of([TokenType::Fn]) {
+            let _ = self
+                .consume_and_expect(TokenType::LeftParen, "Expected '(' after 'fn'".to_owned())?;
+
+            if !self.next_matches(TokenType::RightParen) {
+                loop {
+                    self.variable_declaration_type()?;
+
+                    if !self.consumed_one_of([TokenType::Comma]) {
+                        break;
+                    }
+                }
+            }
+
+            let _ = self.consume_and_expect(
+                TokenType::RightParen,
+                "Expected ')' after parameter types".to_owned(),
+            )?;
+
+            // The `?` of `fn(): Int?` belongs to the return type
+            if self.consumed_one_of([TokenType::Colon]) {
+                self.variable_declaration_type()?;
+            }
+        } else {
+            let _ = self
+                .consume_and_expect(TokenType::Identifier, "Expected type identifier".to_owned())?;
+
+            self.consumed_one_of([TokenType::Question]);
+        }
+
+        self.builder.finish_node();
+
+        Ok(())
     }
 
     #[inline]
-    fn assignment<'b>(&'b mut self) -> Result<Expression<'a>, ParserError<'a>> {
-        let expr = self.or()?;
+    fn assignment<'b>(&'b mut self) -> Result<SyntaxKind, ParserError<'a>> {
+        let checkpoint = self.checkpoint();
+        let target = self.coalesce()?;
 
         if self.consumed_one_of([TokenType::Equal]) {
-            let equals = self.previous();
-
-            match expr {
-                Expression::Variable(variable) => {
-                    return Ok(Expression::Assignment(AssignmentExpression {
-                        value: Box::new(self.assignment()?),
-                        identifier: variable.value,
-                    }))
+            return match target {
+                SyntaxKind::Variable | SyntaxKind::Index | SyntaxKind::Field => {
+                    self.builder
+                        .start_node_at(checkpoint, SyntaxKind::Assignment);
+                    self.assignment()?;
+                    self.builder.finish_node();
+
+                    Ok(SyntaxKind::Assignment)
                 }
-                _ => {
-                    return Err(ParserError {
-                        message: "Invalid assignment target".to_owned(),
-                        token: equals.clone(),
-                    })
-                }
-            }
-        };
+                _ => Err(ParserError::new(
+                    "Invalid assignment target".to_owned(),
+                    self.previous().clone(),
+                )
+                .with_help("only variables, array elements and fields can be assigned to")),
+            };
+        }
 
-        Ok(expr)
+        Ok(target)
     }
 
     #[inline]
-    fn or<'b>(&'b mut self) -> Result<Expression<'a>, ParserError<'a>> {
-        let mut expr = self.and()?;
+    fn coalesce(&mut self) -> Result<SyntaxKind, ParserError<'a>> {
+        let checkpoint = self.checkpoint();
+        let kind = self.or()?;
 
-        while self.consumed_one_of([TokenType::Or]) {
-            let operator = self.previous().clone();
-            let right = self.and()?;
+        // Right associative, so that `a ?? b ?? c` tries each side in order
+        if self.consumed_one_of([TokenType::DoubleQuestion]) {
+            self.builder.start_node_at(checkpoint, SyntaxKind::Binary);
+            self.coalesce()?;
+            self.builder.finish_node();
 
-            expr = Expression::Logical(BinaryExpression {
-                left: Box::new(expr),
-                operator,
-                right: Box::new(right),
-            });
+            return Ok(SyntaxKind::Binary);
         }
 
-        Ok(expr)
+        Ok(kind)
     }
 
     #[inline]
-    fn and<'b>(&'b mut self) -> Result<Expression<'a>, ParserError<'a>> {
-        let mut expr = self.equality()?;
-
-        while self.consumed_one_of([TokenType::And]) {
-            let operator = self.previous().clone();
-            let right = self.equality()?;
+    fn or<'b>(&'b mut self) -> Result<SyntaxKind, ParserError<'a>> {
+        self.binary([TokenType::Or], Parser::and)
+    }
 
-            expr = Expression::Logical(BinaryExpression {
-                left: Box::new(expr),
-                operator,
-                right: Box::new(right),
-            });
-        }
+    #[inline]
+    fn and<'b>(&'b mut self) -> Result<SyntaxKind, ParserError<'a>> {
+        self.binary([TokenType::And], Parser::bitwise_or)
+    }
 
-        Ok(expr)
+    #[inline]
+    fn bitwise_or(&mut self) -> Result<SyntaxKind, ParserError<'a>> {
+        self.binary([TokenType::BitwiseOr], Parser::bitwise_xor)
     }
 
     #[inline]
-    fn equality(&mut self) -> Result<Expression<'a>, ParserError<'a>> {
-        let mut expr = self.comparison()?;
+    fn bitwise_xor(&mut self) -> Result<SyntaxKind, ParserError<'a>> {
+        self.binary([TokenType::BitwiseXor], Parser::bitwise_and)
+    }
 
-        self.next_matches(TokenType::Equal);
+    #[inline]
+    fn bitwise_and(&mut self) -> Result<SyntaxKind, ParserError<'a>> {
+        self.binary([TokenType::BitwiseAnd], Parser::equality)
+    }
 
-        while self.consumed_one_of([TokenType::DoubleEqual, TokenType::BangEqual]) {
-            let operator = self.previous().clone();
-            let right = Box::new(self.comparison()?);
+    #[inline]
+    fn equality(&mut self) -> Result<SyntaxKind, ParserError<'a>> {
+        self.binary(
+            [TokenType::DoubleEqual, TokenType::BangEqual],
+            Parser::comparison,
+        )
+    }
 
-            expr = Expression::Binary(BinaryExpression {
-                left: Box::new(expr),
-                operator,
-                right,
-            });
-        }
+    #[inline]
+    fn comparison(&mut self) -> Result<SyntaxKind, ParserError<'a>> {
+        self.binary(
+            [
+                TokenType::Greater,
+                TokenType::GreaterEqual,
+                TokenType::Less,
+                TokenType::LessEqual,
+            ],
+            Parser::shift,
+        )
+    }
 
-        Ok(expr)
+    #[inline]
+    fn shift(&mut self) -> Result<SyntaxKind, ParserError<'a>> {
+        self.binary([TokenType::LeftShift, TokenType::RightShift], Parser::term)
     }
 
     #[inline]
-    fn comparison(&mut self) -> Result<Expression<'a>, ParserError<'a>> {
-        let mut expr = self.term()?;
+    fn term(&mut self) -> Result<SyntaxKind, ParserError<'a>> {
+        self.binary([TokenType::Minus, TokenType::Plus], Parser::factor)
+    }
 
-        while self.consumed_one_of([
-            TokenType::Greater,
-            TokenType::GreaterEqual,
-            TokenType::Less,
-            TokenType::LessEqual,
-        ]) {
-            let operator = self.previous().clone();
-            let right = self.term()?;
+    #[inline]
+    fn factor(&mut self) -> Result<SyntaxKind, ParserError<'a>> {
+        self.binary([TokenType::Slash, TokenType::Star], Parser::unary)
+    }
 
-            expr = Expression::Binary(BinaryExpression {
-                left: Box::new(expr),
-                operator,
-                right: Box::new(right),
-            });
+    /// Parses a left associative chain of `operand`s separated by any of the `operators`
+    #[inline]
+    fn binary<const SIZE: usize>(
+        &mut self,
+        operators: [TokenType; SIZE],
+        operand: fn(&mut Self) -> Result<SyntaxKind, ParserError<'a>>,
+    ) -> Result<SyntaxKind, ParserError<'a>> {
+        let checkpoint = self.checkpoint();
+        let mut kind = operand(self)?;
+
+        while self.consumed_one_of(operators) {
+            self.builder.start_node_at(checkpoint, SyntaxKind::Binary);
+            operand(self)?;
+            self.builder.finish_node();
+
+            kind = SyntaxKind::Binary;
         }
 
-        Ok(expr)
+        Ok(kind)
     }
 
     #[inline]
-    fn term(&mut self) -> Result<Expression<'a>, ParserError<'a>> {
-        let mut expr = self.factor()?;
+    fn unary(&mut self) -> Result<SyntaxKind, ParserError<'a>> {
+        let checkpoint = self.checkpoint();
 
-        while self.consumed_one_of([TokenType::Minus, TokenType::Plus]) {
-            let operator = self.previous().clone();
-            let right = self.factor()?;
+        if self.consumed_one_of([TokenType::Bang, TokenType::Minus, TokenType::BitwiseNot]) {
+            self.builder.start_node_at(checkpoint, SyntaxKind::Unary);
+            self.unary()?;
+            self.builder.finish_node();
 
-            expr = Expression::Binary(BinaryExpression {
-                left: Box::new(expr),
-                operator,
-                right: Box::new(right),
-            });
+            return Ok(SyntaxKind::Unary);
         }
 
-        Ok(expr)
+        self.call()
     }
 
     #[inline]
-    fn factor(&mut self) -> Result<Expression<'a>, ParserError<'a>> {
-        let mut expr = self.unary()?;
-
-        while self.consumed_one_of([TokenType::Slash, TokenType::Star]) {
-            let operator = self.previous().clone();
-            let right = self.unary()?;
+    fn call(&mut self) -> Result<SyntaxKind, ParserError<'a>> {
+        let checkpoint = self.checkpoint();
+        let mut kind = self.primary()?;
+
+        loop {
+            if self.next_matches(TokenType::LeftParen) {
+                self.builder.start_node_at(checkpoint, SyntaxKind::Call);
+                self.start_node(SyntaxKind::ArgumentList);
+                self.advance();
+                self.arguments(TokenType::RightParen)?;
+
+                let _ = self.consume_and_expect(
+                    TokenType::RightParen,
+                    "Expected ')' after arguments".to_owned(),
+                )?;
+
+                self.builder.finish_node();
+                kind = SyntaxKind::Call;
+            } else if self.consumed_one_of([TokenType::LeftBracket]) {
+                self.builder.start_node_at(checkpoint, SyntaxKind::Index);
+                self.expression()?;
+
+                let _ = self.consume_and_expect(
+                    TokenType::RightBracket,
+                    "Expected ']' after index".to_owned(),
+                )?;
+
+                kind = SyntaxKind::Index;
+            } else if self.consumed_one_of([TokenType::Dot]) {
+                self.builder.start_node_at(checkpoint, SyntaxKind::Field);
+
+                let _ = self.consume_and_expect(
+                    TokenType::Identifier,
+                    "Expected field name after '.'".to_owned(),
+                )?;
+
+                kind = SyntaxKind::Field;
+            } else if self.consumed_one_of([TokenType::Bang]) {
+                self.builder.start_node_at(checkpoint, SyntaxKind::Unwrap);
+                kind = SyntaxKind::Unwrap;
+            } else {
+                break;
+            }
 
-            expr = Expression::Binary(BinaryExpression {
-                left: Box::new(expr),
-                operator,
-                right: Box::new(right),
-            });
+            self.builder.finish_node();
         }
 
-        Ok(expr)
+        Ok(kind)
     }
 
+    /// Parses a comma separated list of expressions until the `closing` token, without consuming it
     #[inline]
-    fn unary(&mut self) -> Result<Expression<'a>, ParserError<'a>> {
-        if self.consumed_one_of([TokenType::Bang, TokenType::Minus]) {
-            let operator = self.previous().clone();
+    fn arguments(&mut self, closing: TokenType) -> Result<(), ParserError<'a>> {
+        while !self.next_matches(closing) {
+            self.with_record_literals(true, Parser::expression)?;
 
-            return Ok(Expression::Unary(UnaryExpression {
-                operator,
-                left: Box::new(self.unary()?),
-            }));
+            if !self.consumed_one_of([TokenType::Comma]) {
+                break;
+            }
         }
 
-        self.primary()
+        Ok(())
     }
 
     #[inline]
-    fn primary<'b>(&'b mut self) -> Result<Expression<'a>, ParserError<'a>> {
+    fn primary<'b>(&'b mut self) -> Result<SyntaxKind, ParserError<'a>> {
+        let checkpoint = self.checkpoint();
+
         if self.consumed_one_of([
             TokenType::False,
             TokenType::True,
@@ -329,21 +458,47 @@ impl<'a> Parser<'a> {
             TokenType::Integer,
             TokenType::Float,
         ]) {
-            let value = self.previous();
-            return Ok(Expression::Literal(LiteralExpression {
-                value: value.clone(),
-            }));
+            Parser::literal(self.previous())?;
+
+            return Ok(self.wrap(checkpoint, SyntaxKind::Literal));
         }
 
         if self.next().token_type == TokenType::Identifier {
             self.advance();
-            return Ok(Expression::Variable(VariableExpression {
-                value: self.previous().clone(),
-            }));
+
+            if self.record_literals && self.next_matches(TokenType::LeftBrace) {
+                return self.record_literal(checkpoint);
+            }
+
+            return Ok(self.wrap(checkpoint, SyntaxKind::Variable));
+        }
+
+        if self.consumed_one_of([TokenType::None]) {
+            return Ok(self.wrap(checkpoint, SyntaxKind::None));
+        }
+
+        if self.consumed_one_of([TokenType::Fn]) {
+            return self.function(checkpoint);
+        }
+
+        if self.consumed_one_of([TokenType::LeftBracket]) {
+            self.builder.start_node_at(checkpoint, SyntaxKind::Array);
+            self.arguments(TokenType::RightBracket)?;
+
+            let _ = self.consume_and_expect(
+                TokenType::RightBracket,
+                "Expected ']' after array elements".to_owned(),
+            )?;
+
+            self.builder.finish_node();
+
+            return Ok(SyntaxKind::Array);
         }
 
         if self.consumed_one_of([TokenType::LeftParen]) {
-            let expression = self.expression()?;
+            self.builder.start_node_at(checkpoint, SyntaxKind::Grouping);
+            self.with_record_literals(true, Parser::expression)?;
+
             if !self.next_matches(TokenType::RightParen) {
                 let token = self.next();
                 return Err(ParserError::new(
@@ -353,80 +508,378 @@ impl<'a> Parser<'a> {
             }
 
             self.advance();
+            self.builder.finish_node();
 
-            return Ok(Expression::Grouping(GroupingExpression {
-                expression: Box::new(expression),
-            }));
+            return Ok(SyntaxKind::Grouping);
         }
 
         let current = self.next();
+        let message = match current.token_type {
+            TokenType::Eof => "Unexpected end of input".to_owned(),
+            _ => format!("Unexpected token '{}'", current.lexeme),
+        };
 
-        Err(ParserError::new(
-            format!("Unexpected token '{:#?}'", current),
-            current.clone(),
-        ))
+        Err(ParserError::new(message, current.clone()))
     }
 
+    /// Parses a function after its `fn`, which was consumed after `checkpoint`
+    fn function<'b>(&'b mut self, checkpoint: Checkpoint) -> Result<SyntaxKind, ParserError<'a>> {
+        self.builder.start_node_at(checkpoint, SyntaxKind::Function);
+        self.start_node(SyntaxKind::ParameterList);
+
+        let _ =
+            self.consume_and_expect(TokenType::LeftParen, "Expected '(' after 'fn'".to_owned())?;
+
+        let mut names = Vec::<&str>::new();
+
+        if !self.next_matches(TokenType::RightParen) {
+            loop {
+                self.start_node(SyntaxKind::Parameter);
+
+                let identifier = self
+                    .consume_and_expect(
+                        TokenType::Identifier,
+                        "Expected parameter name".to_owned(),
+                    )?
+                    .clone();
+
+                if names.contains(&identifier.lexeme) {
+                    return Err(ParserError::new(
+                        format!("Duplicate parameter '{}'", identifier.lexeme),
+                        identifier,
+                    ));
+                }
+
+                names.push(identifier.lexeme);
+
+                if self.consumed_one_of([TokenType::Colon]) {
+                    self.variable_declaration_type()?;
+                }
+
+                self.builder.finish_node();
+
+                if !self.consumed_one_of([TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+
+        let _ = self.consume_and_expect(
+            TokenType::RightParen,
+            "Expected ')' after parameters".to_owned(),
+        )?;
+
+        self.builder.finish_node();
+
+        if self.consumed_one_of([TokenType::Colon]) {
+            self.variable_declaration_type()?;
+        }
+
+        self.function_depth += 1;
+        let body = self.block("Expected '{' before function body");
+        self.function_depth -= 1;
+
+        body?;
+        self.builder.finish_node();
+
+        Ok(SyntaxKind::Function)
+    }
+
+    /// Parses `Name { field: value, ... }`, after the name has been consumed after `checkpoint`
+    fn record_literal(&mut self, checkpoint: Checkpoint) -> Result<SyntaxKind, ParserError<'a>> {
+        self.builder
+            .start_node_at(checkpoint, SyntaxKind::RecordLiteral);
+        self.advance();
+
+        let mut names = Vec::<&str>::new();
+
+        while !self.next_matches(TokenType::RightBrace) {
+            self.start_node(SyntaxKind::FieldInitializer);
+            names.push(self.field_name(&names)?.lexeme);
+
+            let _ = self
+                .consume_and_expect(TokenType::Colon, "Expected ':' after field name".to_owned())?;
+
+            self.with_record_literals(true, Parser::expression)?;
+            self.builder.finish_node();
+
+            if !self.consumed_one_of([TokenType::Comma]) {
+                break;
+            }
+        }
+
+        let _ = self.consume_and_expect(
+            TokenType::RightBrace,
+            "Expected '}' after record fields".to_owned(),
+        )?;
+
+        self.builder.finish_node();
+
+        Ok(SyntaxKind::RecordLiteral)
+    }
+
+    fn record_declaration(&mut self) -> Result<(), ParserError<'a>> {
+        self.start_node(SyntaxKind::RecordDeclaration);
+        self.advance();
+
+        let _ =
+            self.consume_and_expect(TokenType::Identifier, "Expected record name".to_owned())?;
+
+        let _ = self.consume_and_expect(
+            TokenType::LeftBrace,
+            "Expected '{' after record name".to_owned(),
+        )?;
+
+        let mut names = Vec::<&str>::new();
+
+        while !self.next_matches(TokenType::RightBrace) {
+            self.start_node(SyntaxKind::RecordField);
+            names.push(self.field_name(&names)?.lexeme);
+
+            let _ = self
+                .consume_and_expect(TokenType::Colon, "Expected ':' after field name".to_owned())?;
+
+            self.variable_declaration_type()?;
+            self.builder.finish_node();
+
+            if !self.consumed_one_of([TokenType::Comma]) {
+                break;
+            }
+        }
+
+        let _ = self.consume_and_expect(
+            TokenType::RightBrace,
+            "Expected '}' after record fields".to_owned(),
+        )?;
+
+        self.builder.finish_node();
+
+        Ok(())
+    }
+
+    /// Consumes the name of a field, rejecting names that were already used in the same record
+    fn field_name(&mut self, previous: &[&str]) -> Result<Token<'a>, ParserError<'a>> {
+        let identifier = self
+            .consume_and_expect(TokenType::Identifier, "Expected field name".to_owned())?
+            .clone();
+
+        if previous.contains(&identifier.lexeme) {
+            return Err(ParserError::new(
+                format!("Duplicate field '{}'", identifier.lexeme),
+                identifier,
+            ));
+        }
+
+        Ok(identifier)
+    }
+
+    pub fn literal(token: &Token<'a>) -> Result<Literal<'a>, ParserError<'a>> {
+        match token.token_type {
+            TokenType::True => Ok(Literal::Boolean(true)),
+            TokenType::False => Ok(Literal::Boolean(false)),
+            TokenType::Integer => match token.lexeme.parse::<i32>() {
+                Ok(integer) => Ok(Literal::Number(NumberLiteral::Integer(integer))),
+                Err(_) => Err(ParserError::new(
+                    format!(
+                        "Integer literal '{}' does not fit in a 32-bit integer",
+                        token.lexeme
+                    ),
+                    token.clone(),
+                )),
+            },
+            TokenType::Float => match token.lexeme.parse::<f64>() {
+                Ok(float) => Ok(Literal::Number(NumberLiteral::Float(float))),
+                Err(_) => Err(ParserError::new(
+                    format!("Invalid float literal '{}'", token.lexeme),
+                    token.clone(),
+                )),
+            },
+            TokenType::String => {
+                // Strip the surrounding quotes
+                let content = &token.lexeme[1..token.lexeme.len() - 1];
+
+                Ok(Literal::String(Parser::unescape(content, token)?))
+            }
+            _ => Err(ParserError::new(
+                format!("Expected literal, got '{}'", token.lexeme),
+                token.clone(),
+            )),
+        }
+    }
+
+    fn unescape(content: &'a str, token: &Token<'a>) -> Result<Cow<'a, str>, ParserError<'a>> {
+        // Avoid allocating for the common case of strings without escape sequences
+        if !content.contains('\\') {
+            return Ok(Cow::Borrowed(content));
+        }
+
+        let mut result = String::with_capacity(content.len());
+        let mut chars = content.chars();
+
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                result.push(c);
+                continue;
+            }
+
+            match chars.next() {
+                Some('n') => result.push('\n'),
+                Some('t') => result.push('\t'),
+                Some('r') => result.push('\r'),
+                Some('0') => result.push('\0'),
+                Some('\\') => result.push('\\'),
+                Some('"') => result.push('"'),
+                Some(other) => {
+                    return Err(ParserError::new(
+                        format!("Invalid escape sequence '\\{}'", other),
+                        token.clone(),
+                    ))
+                }
+                None => {
+                    return Err(ParserError::new(
+                        "Unfinished escape sequence".to_owned(),
+                        token.clone(),
+                    ))
+                }
+            }
+        }
+
+        Ok(Cow::Owned(result))
+    }
+
+    /// Parses `{ ... }`, failing with `error_message` when the next token isn't the brace
     #[inline]
-    fn block<'b>(&'b mut self) -> Result<Vec<Statement<'a>>, ParserError<'a>> {
-        let mut statements = Vec::<Statement>::new();
+    fn block<'b>(&'b mut self, error_message: &str) -> Result<(), ParserError<'a>> {
+        self.start_node(SyntaxKind::Block);
+
+        let _ = self.consume_and_expect(TokenType::LeftBrace, error_message.to_owned())?;
 
         while !self.next_matches(TokenType::RightBrace) && !self.is_end() {
-            statements.push(self.statement()?);
+            self.with_record_literals(true, Parser::statement)?;
         }
 
         let _ =
             self.consume_and_expect(TokenType::RightBrace, "Expected '}' after block".to_owned())?;
 
-        Ok(statements)
+        self.builder.finish_node();
+
+        Ok(())
     }
 
     #[inline]
-    fn if_statement<'b>(&'b mut self) -> Result<Statement<'a>, ParserError<'a>> {
-        let condition = self.expression()?;
+    fn if_statement<'b>(&'b mut self) -> Result<(), ParserError<'a>> {
+        self.start_node(SyntaxKind::If);
+        self.advance();
 
-        let _ = self.consume_and_expect(
-            TokenType::LeftBrace,
-            "Expected '{{' after condition".to_owned(),
-        )?;
+        if let [Some(TokenType::Identifier), Some(TokenType::VarDec)] =
+            self.lookahead_many::<2>().map(|t| t.map(|t| t.token_type))
+        {
+            self.advance();
+            self.advance();
+        }
 
-        let statements = self.block()?;
+        self.with_record_literals(false, Parser::expression)?;
+        self.block("Expected '{{' after condition")?;
 
-        let else_statements = if self.consumed_one_of([TokenType::Else]) {
-            let _ = self.consume_and_expect(
-                TokenType::LeftBrace,
-                "Expected '{{' after condition".to_owned(),
-            )?;
+        if self.consumed_one_of([TokenType::Else]) {
+            self.block("Expected '{{' after condition")?;
+        }
 
-            Some(self.block()?)
-        } else {
-            None
-        };
+        self.builder.finish_node();
 
-        Ok(Statement::If(IfStatement {
-            condition,
-            statements,
-            else_statements,
-        }))
+        Ok(())
     }
 
     #[inline]
-    fn while_statement<'b>(&'b mut self) -> Result<Statement<'a>, ParserError<'a>> {
-        let condition = self.expression()?;
+    fn while_statement<'b>(&'b mut self) -> Result<(), ParserError<'a>> {
+        self.start_node(SyntaxKind::For);
+        self.advance();
+        self.with_record_literals(false, Parser::expression)?;
+        self.block("Expected '{{' after condition")?;
+        self.builder.finish_node();
 
-        let _ = self.consume_and_expect(
-            TokenType::LeftBrace,
-            "Expected '{{' after condition".to_owned(),
-        )?;
+        Ok(())
+    }
+
+    #[inline]
+    fn return_statement<'b>(&'b mut self) -> Result<(), ParserError<'a>> {
+        self.start_node(SyntaxKind::Return);
+
+        let keyword = self.advance().clone();
+
+        if self.function_depth == 0 {
+            return Err(
+                ParserError::new("Cannot return from top-level code".to_owned(), keyword)
+                    .with_help("`return` can only be used inside a function body"),
+            );
+        }
+
+        if !self.next_matches(TokenType::SemiColon) {
+            self.expression()?;
+        }
+
+        let _ = self.consume_and_expect(TokenType::SemiColon, "Expected ';'".to_owned())?;
+
+        self.builder.finish_node();
+
+        Ok(())
+    }
+
+    /// Runs `parse` with record literals allowed or not, restoring the previous setting afterwards
+    #[inline]
+    fn with_record_literals<T>(
+        &mut self,
+        allowed: bool,
+        parse: impl FnOnce(&mut Self) -> Result<T, ParserError<'a>>,
+    ) -> Result<T, ParserError<'a>> {
+        let previous = std::mem::replace(&mut self.record_literals, allowed);
+        let result = parse(self);
+        self.record_literals = previous;
+
+        result
+    }
+
+    // Tree building:
+
+    /// Starts a node at the next token, leaving the trivia before it outside
+    #[inline]
+    fn start_node(&mut self, kind: SyntaxKind) {
+        self.flush_trivia();
+        self.builder.start_node(kind);
+    }
+
+    /// Marks the next token as the start of a node that may be started later
+    #[inline]
+    fn checkpoint(&mut self) -> Checkpoint {
+        self.flush_trivia();
+        self.builder.checkpoint()
+    }
+
+    /// Puts everything since `checkpoint` in a node of its own, returning its kind
+    #[inline]
+    fn wrap(&mut self, checkpoint: Checkpoint, kind: SyntaxKind) -> SyntaxKind {
+        self.builder.start_node_at(checkpoint, kind);
+        self.builder.finish_node();
 
-        let statements = self.block()?;
+        kind
+    }
 
-        Ok(Statement::For(ForStatement {
-            condition,
-            statements,
-        }))
+    #[inline]
+    fn flush_trivia(&mut self) {
+        for trivia in mem::take(&mut self.trivia[self.current_index]) {
+            self.builder.token(trivia);
+        }
     }
 
+    /// Adds the next token to the tree, after the trivia before it
+    #[inline]
+    fn bump(&mut self) {
+        self.flush_trivia();
+        self.builder.token(self.tokens[self.current_index].clone());
+    }
+
+    // Token navigation:
+
     #[inline]
     fn is_end(&self) -> bool {
         self.next().token_type == TokenType::Eof
@@ -454,6 +907,7 @@ impl<'a> Parser<'a> {
     #[inline]
     fn advance<'b>(&'b mut self) -> &'b Token<'a> {
         if !self.is_end() {
+            self.bump();
             self.current_index += 1;
         }
 
@@ -494,7 +948,7 @@ impl<'a> Parser<'a> {
     }
 
     #[inline(always)]
-    fn lookahead_many<const AMOUNT: usize>(&self) -> [Option<&Token>; AMOUNT] {
+    fn lookahead_many<const AMOUNT: usize>(&self) -> [Option<&Token<'a>>; AMOUNT] {
         let mut tokens = [None; AMOUNT];
 
         for (i, token) in tokens.iter_mut().enumerate().take(AMOUNT) {
@@ -503,23 +957,4 @@ impl<'a> Parser<'a> {
 
         tokens
     }
-
-    #[inline(always)]
-    fn lookahead_matches_one_of<const SIZE: usize>(
-        &self,
-        amount: usize,
-        types: [TokenType; SIZE],
-    ) -> bool {
-        let Some(token) = self.lookahead(amount) else {
-            return false;
-        };
-
-        for token_type in types {
-            if token_type == token.token_type {
-                return true;
-            }
-        }
-
-        false
-    }
 }
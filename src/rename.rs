@@ -0,0 +1,257 @@
+use std::collections::HashMap;
+
+use crate::{
+    diagnostic::{self, Diagnostic, Severity},
+    lower,
+    matcha::KEYWORDS,
+    parser::Parser,
+    resolver::{Reference, Resolver, ResolverError},
+    scanner::Scanner,
+    source::Source,
+    syntax::{SyntaxKind, SyntaxNode},
+    token::{Token, TokenType},
+};
+
+/// Why a rename was refused
+#[derive(Debug)]
+pub struct RenameError<'a> {
+    pub message: String,
+    pub token: Token<'a>,
+    pub note: Option<String>,
+    pub help: Option<&'static str>,
+}
+
+impl RenameError<'_> {
+    pub fn new(message: String, token: Token) -> RenameError {
+        RenameError {
+            message,
+            token,
+            note: None,
+            help: None,
+        }
+    }
+}
+
+/// Renames the variable whose name is at `line` and `position` in `source`, along with every use
+/// of it, returning the new source. Fails with the diagnostics of the program when it doesn't
+/// parse or resolve, and refuses names that are keywords or that would change what any name in
+/// the program refers to, like a shadowed variable or a redeclaration in the same scope
+pub fn rename(
+    source: &str,
+    line: u64,
+    position: u64,
+    new_name: &str,
+) -> Result<String, Vec<Diagnostic>> {
+    let (tree, references) = analyze(source)?;
+    let tokens = tree.descendant_tokens();
+    let refuse = |error: RenameError| Err(vec![Diagnostic::from(&error)]);
+
+    // The token under the position, or `Eof` past the last one
+    let token: &Token = tokens
+        .iter()
+        .copied()
+        .find(|token| {
+            token.line == line
+                && token.position <= position
+                && position < token.position + token.lexeme.chars().count() as u64
+        })
+        .unwrap_or_else(|| tokens.last().expect("There is always an Eof"));
+
+    let Some(target) = references
+        .iter()
+        .find(|reference| reference.name.span == token.span)
+    else {
+        return refuse(RenameError::new(
+            "Expected the name of a variable to rename".to_owned(),
+            token.clone(),
+        ));
+    };
+
+    let declaration = &target.declaration;
+    let old_name = declaration.lexeme;
+
+    if !declares_variable(&tree, declaration) {
+        return refuse(RenameError {
+            help: Some("only variables, parameters and `if` bindings can be renamed"),
+            ..RenameError::new(format!("'{}' is not a variable", old_name), token.clone())
+        });
+    }
+
+    if KEYWORDS.contains_key(new_name) {
+        return refuse(RenameError::new(
+            format!("Cannot rename '{}' to the keyword '{}'", old_name, new_name),
+            token.clone(),
+        ));
+    }
+
+    if !is_identifier(new_name) {
+        return refuse(RenameError {
+            help: Some("names start with a letter or '_', followed by letters, digits or '_'"),
+            ..RenameError::new(format!("'{}' is not a valid name", new_name), token.clone())
+        });
+    }
+
+    let occurrences: Vec<&Token> = references
+        .iter()
+        .filter(|reference| reference.declaration.span == declaration.span)
+        .map(|reference| &reference.name)
+        .collect();
+
+    let mut renamed = String::with_capacity(source.len());
+    let mut end = 0;
+
+    for occurrence in &occurrences {
+        renamed.push_str(&source[end..occurrence.span.start]);
+        renamed.push_str(new_name);
+        end = occurrence.span.end;
+    }
+
+    renamed.push_str(&source[end..]);
+
+    // Every name has to refer to the same declaration as before, which fails when the new name
+    // shadows another variable, is shadowed by one, or is declared twice in the same scope
+    let shift = |offset: usize| {
+        let before = occurrences
+            .iter()
+            .filter(|occurrence| occurrence.span.start < offset)
+            .count();
+
+        offset + before * new_name.len() - before * old_name.len()
+    };
+    let conflict = |token, note| RenameError {
+        note,
+        help: Some("choose a name that isn't used where the variable is"),
+        ..RenameError::new(
+            format!(
+                "Renaming '{}' to '{}' would change what a name refers to",
+                old_name, new_name
+            ),
+            token,
+        )
+    };
+
+    // The token of the original source at an offset of the renamed one
+    let original = |offset: usize| {
+        tokens
+            .iter()
+            .copied()
+            .find(|token| shift(token.span.start) == offset)
+    };
+
+    let (renamed_references, renamed_errors) =
+        resolve(&renamed).expect("Renaming a variable keeps the program parsing");
+
+    if let Some(error) = renamed_errors
+        .iter()
+        .find(|error| error.severity == Severity::Error)
+    {
+        let token = original(error.token.span.start).unwrap_or(declaration);
+
+        return refuse(conflict(token.clone(), Some(error.message.clone())));
+    }
+
+    let renamed_declarations: HashMap<usize, usize> = renamed_references
+        .iter()
+        .map(|reference| (reference.name.span.start, reference.declaration.span.start))
+        .collect();
+
+    for reference in &references {
+        let expected = shift(reference.declaration.span.start);
+        let actual = renamed_declarations.get(&shift(reference.name.span.start));
+
+        if actual != Some(&expected) {
+            let note = actual.and_then(|&offset| original(offset)).map(|other| {
+                format!(
+                    "it would refer to the declaration at {}:{} instead",
+                    other.line, other.position
+                )
+            });
+
+            return refuse(conflict(reference.name.clone(), note));
+        }
+    }
+
+    Ok(renamed)
+}
+
+/// Parses and resolves `source`, keeping the syntax tree to look at the tokens and declarations
+fn analyze(source: &str) -> Result<(SyntaxNode<'_>, Vec<Reference<'_>>), Vec<Diagnostic>> {
+    let (tokens, scanner_errors) = Scanner {
+        source: Source::new(source),
+    }
+    .scan_with_trivia();
+
+    let (tree, parser_errors) = Parser::new(tokens).parse_syntax();
+
+    if !scanner_errors.is_empty() || !parser_errors.is_empty() {
+        return Err(diagnostic::merge(&scanner_errors, &parser_errors));
+    }
+
+    let mut statements = lower::statements(&tree);
+    let (errors, references) = Resolver::new().resolve_references(&mut statements);
+
+    // Names that don't resolve can't be renamed safely, since what they mean is unknown
+    let errors: Vec<Diagnostic> = errors
+        .iter()
+        .filter(|error| error.severity == Severity::Error)
+        .map(Diagnostic::from)
+        .collect();
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    Ok((tree, references))
+}
+
+/// The references and resolver errors of `source`, if it parses
+fn resolve(source: &str) -> Option<(Vec<Reference<'_>>, Vec<ResolverError<'_>>)> {
+    let (tokens, scanner_errors) = Scanner {
+        source: Source::new(source),
+    }
+    .scan_recovering();
+
+    if !scanner_errors.is_empty() {
+        return None;
+    }
+
+    let mut statements = Parser::new(tokens).parse().ok()?;
+    let (errors, references) = Resolver::new().resolve_references(&mut statements);
+
+    Some((references, errors))
+}
+
+/// Whether `declaration` is the name of a variable, a parameter or an `if` binding, rather than
+/// a record
+fn declares_variable(node: &SyntaxNode, declaration: &Token) -> bool {
+    if node.tokens().any(|token| token.span == declaration.span) {
+        return matches!(
+            node.kind,
+            SyntaxKind::VariableDeclaration | SyntaxKind::Parameter | SyntaxKind::If
+        );
+    }
+
+    node.nodes()
+        .find(|child| {
+            let span = child.span();
+            span.start <= declaration.span.start && declaration.span.end <= span.end
+        })
+        .is_some_and(|child| declares_variable(child, declaration))
+}
+
+/// Whether `name` scans as a single identifier
+fn is_identifier(name: &str) -> bool {
+    let (tokens, errors) = Scanner {
+        source: Source::new(name),
+    }
+    .scan_with_trivia();
+
+    errors.is_empty()
+        && matches!(
+            tokens
+                .iter()
+                .map(|token| token.token_type)
+                .collect::<Vec<_>>()[..],
+            [TokenType::Identifier, TokenType::Eof]
+        )
+}
@@ -0,0 +1,392 @@
+use std::{
+    cell::RefCell,
+    env, fs,
+    io::{self, Write},
+    mem,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use rustyline::{
+    completion::Completer, error::ReadlineError, highlight::Highlighter, hint::Hinter,
+    history::DefaultHistory, validate::Validator, Context, Editor, Helper,
+};
+use typed_arena::Arena;
+
+use crate::{
+    check,
+    diagnostic::Renderer,
+    environment::Environment,
+    execute,
+    matcha::KEYWORDS,
+    parser::Parser,
+    scanner::{Scanner, UNTERMINATED_STRING_MESSAGE},
+    source::Source,
+    token::Token,
+    Options,
+};
+
+const PROMPT: &str = ">>> ";
+const CONTINUATION_PROMPT: &str = "... ";
+const HISTORY_FILE: &str = ".matcha_history";
+const COMMANDS: &str =
+    ":env, :type <expr>, :ast [code], :tokens [code], :load <file>, :reset, :quit";
+
+pub fn repl(options: &Options) {
+    println!("Matcha 🍵 {}", env!("CARGO_PKG_VERSION"));
+    let history = history_path();
+    let mut options = options.clone();
+
+    // `:reset` ends a session, which frees every input its values borrowed from, and starts a new
+    // one with the same options
+    loop {
+        let sources = Arena::new();
+        let mut session = Session::new(&options, &sources);
+
+        match session.read(history.as_deref()) {
+            Flow::Reset => options = session.options,
+            _ => break,
+        }
+    }
+}
+
+/// Whether the REPL keeps reading after a command
+#[derive(Debug, PartialEq)]
+pub enum Flow {
+    Continue,
+    /// Start over with an empty environment
+    Reset,
+    Quit,
+}
+
+/// The options and variables that last from one REPL input to the next. Values borrow from the
+/// input that created them, so every input is kept in `sources` for as long as the session lasts
+pub struct Session<'s> {
+    pub options: Options,
+    pub environment: Rc<RefCell<Environment<'s>>>,
+    sources: &'s Arena<String>,
+}
+
+impl<'s> Session<'s> {
+    pub fn new(options: &Options, sources: &'s Arena<String>) -> Session<'s> {
+        Session {
+            options: Options {
+                echo: true,
+                ..options.clone()
+            },
+            environment: Rc::new(RefCell::new(Environment::new())),
+            sources,
+        }
+    }
+
+    /// Reads and runs inputs until the user quits or resets the session
+    fn read(&mut self, history: Option<&Path>) -> Flow {
+        let mut editor = match Editor::<ReplHelper, DefaultHistory>::new() {
+            Ok(editor) => editor,
+            Err(error) => {
+                eprintln!("Could not start the REPL: {}", error);
+                return Flow::Quit;
+            }
+        };
+        editor.set_helper(Some(ReplHelper {
+            environment: Rc::clone(&self.environment),
+        }));
+
+        if let Some(path) = history {
+            // There is no history to load the first time the REPL runs
+            let _ = editor.load_history(path);
+        }
+
+        let mut input = String::new();
+
+        loop {
+            let prompt = match input.is_empty() {
+                true => PROMPT,
+                false => CONTINUATION_PROMPT,
+            };
+
+            let line = match editor.readline(prompt) {
+                Ok(line) => line,
+                // Ctrl-C drops whatever has been typed so far, including earlier lines of the input
+                Err(ReadlineError::Interrupted) => {
+                    input.clear();
+                    continue;
+                }
+                Err(ReadlineError::Eof) => return Flow::Quit,
+                Err(error) => {
+                    eprintln!("Could not read the input: {}", error);
+                    return Flow::Quit;
+                }
+            };
+
+            // Commands only fit on one line, so they can't appear in the middle of an input
+            if input.is_empty() && line.trim_start().starts_with(':') {
+                remember(&mut editor, history, line.trim());
+
+                match self.command(line.trim(), &mut io::stdout()) {
+                    Ok(Flow::Continue) | Err(_) => continue,
+                    Ok(flow) => return flow,
+                }
+            }
+
+            // Two empty lines in a row discard an incomplete input
+            if line.trim().is_empty()
+                && input
+                    .lines()
+                    .last()
+                    .is_some_and(|last| last.trim().is_empty())
+            {
+                input.clear();
+                continue;
+            }
+
+            input.push_str(&line);
+            input.push('\n');
+
+            if input.trim().is_empty() {
+                input.clear();
+                continue;
+            }
+
+            if is_incomplete(&input) {
+                continue;
+            }
+
+            // A multi-line input is recalled as a whole, so it can be edited and run again
+            remember(&mut editor, history, input.trim_end());
+
+            self.run("<repl>", mem::take(&mut input));
+        }
+    }
+
+    /// Runs a complete input in the session's environment, returning its exit code
+    pub fn run(&mut self, file_name: &str, input: String) -> u8 {
+        let source = self.sources.alloc(input);
+        let renderer = Renderer {
+            file_name,
+            source,
+            color: self.options.color,
+        };
+
+        let Some((statements, r#type)) =
+            check(&self.options, &renderer, source, &self.environment.borrow())
+        else {
+            return 1;
+        };
+
+        execute(
+            &self.options,
+            &renderer,
+            &statements,
+            &r#type,
+            Rc::clone(&self.environment),
+        )
+    }
+
+    /// Runs a command such as `:env` or `:type x + 1`, writing what it shows to `output`.
+    /// Diagnostics and the output of the program itself go to stderr and stdout as usual
+    pub fn command(&mut self, line: &str, output: &mut impl Write) -> io::Result<Flow> {
+        let (name, argument) = match line.split_once(char::is_whitespace) {
+            Some((name, argument)) => (name, argument.trim()),
+            None => (line, ""),
+        };
+
+        match name {
+            ":env" => self.env(output)?,
+            ":type" => self.r#type(argument, output)?,
+            ":ast" => self.toggle(|options| &mut options.ast, "AST", argument, output)?,
+            ":tokens" => {
+                self.toggle(|options| &mut options.lexer_out, "Tokens", argument, output)?
+            }
+            ":load" => self.load(argument, output)?,
+            ":reset" => return Ok(Flow::Reset),
+            ":quit" => return Ok(Flow::Quit),
+            _ => writeln!(output, "Unknown command {}. Commands: {}", name, COMMANDS)?,
+        }
+
+        Ok(Flow::Continue)
+    }
+
+    /// Lists the variables in the environment with their types
+    fn env(&self, output: &mut impl Write) -> io::Result<()> {
+        let environment = self.environment.borrow();
+        let mut names: Vec<&String> = environment.values.keys().collect();
+        names.sort();
+
+        for name in names {
+            writeln!(output, "{}: {}", name, environment.values[name].get_type())?;
+        }
+
+        Ok(())
+    }
+
+    /// Shows the type of an expression without evaluating it
+    fn r#type(&self, expression: &str, output: &mut impl Write) -> io::Result<()> {
+        let source = match expression.ends_with(';') {
+            true => expression.to_owned(),
+            false => format!("{};", expression),
+        };
+        let renderer = Renderer {
+            file_name: "<repl>",
+            source: &source,
+            color: self.options.color,
+        };
+
+        if let Some((_, r#type)) = check(
+            &self.options,
+            &renderer,
+            &source,
+            &self.environment.borrow(),
+        ) {
+            writeln!(output, "{}", r#type)?;
+        }
+
+        Ok(())
+    }
+
+    /// Runs `code` with an option turned on, or flips the option for every input after this one
+    /// when there is no code
+    fn toggle(
+        &mut self,
+        option: fn(&mut Options) -> &mut bool,
+        label: &str,
+        code: &str,
+        output: &mut impl Write,
+    ) -> io::Result<()> {
+        if code.is_empty() {
+            let enabled = option(&mut self.options);
+            *enabled = !*enabled;
+
+            let state = if *enabled { "on" } else { "off" };
+            return writeln!(output, "{} output {}", label, state);
+        }
+
+        let previous = mem::replace(option(&mut self.options), true);
+        self.run("<repl>", code.to_owned());
+        *option(&mut self.options) = previous;
+
+        Ok(())
+    }
+
+    /// Runs a file in the session's environment, so its declarations stay available
+    fn load(&mut self, path: &str, output: &mut impl Write) -> io::Result<()> {
+        match fs::read_to_string(path) {
+            Ok(contents) => {
+                self.run(path, contents);
+                Ok(())
+            }
+            Err(error) => writeln!(output, "Could not read {}: {}", path, error),
+        }
+    }
+}
+
+/// Adds a complete input to the history, saving it right away so it isn't lost if the REPL is
+/// killed
+fn remember(editor: &mut Editor<ReplHelper, DefaultHistory>, history: Option<&Path>, entry: &str) {
+    let _ = editor.add_history_entry(entry);
+
+    if let Some(path) = history {
+        let _ = editor.save_history(path);
+    }
+}
+
+/// `~/.matcha_history`, if there is a home directory to put it in
+fn history_path() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .map(|home| PathBuf::from(home).join(HISTORY_FILE))
+}
+
+/// Completes keywords and the names declared in the REPL's environment
+struct ReplHelper<'s> {
+    environment: Rc<RefCell<Environment<'s>>>,
+}
+
+impl Completer for ReplHelper<'_> {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(completions(&self.environment.borrow(), line, pos))
+    }
+}
+
+impl Hinter for ReplHelper<'_> {
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper<'_> {}
+
+impl Validator for ReplHelper<'_> {}
+
+impl Helper for ReplHelper<'_> {}
+
+/// The keywords and variable names that could finish the word before `pos`, and where that word
+/// starts
+pub fn completions(environment: &Environment, line: &str, pos: usize) -> (usize, Vec<String>) {
+    let start = line[..pos]
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| c.is_alphanumeric() || *c == '_')
+        .last()
+        .map_or(pos, |(index, _)| index);
+    let word = &line[start..pos];
+
+    if word.is_empty() {
+        return (pos, vec![]);
+    }
+
+    let mut candidates: Vec<String> = KEYWORDS
+        .keys()
+        .copied()
+        .chain(environment.values.keys().map(String::as_str))
+        .filter(|candidate| candidate.starts_with(word))
+        .map(str::to_owned)
+        .collect();
+
+    candidates.sort();
+    candidates.dedup();
+
+    (start, candidates)
+}
+
+/// Whether `source` stops in the middle of a statement, e.g. `if x {` or `f(1,`, so the REPL
+/// should wait for more lines before running it
+pub fn is_incomplete(source: &str) -> bool {
+    let (tokens, scanner_errors) = Scanner {
+        source: Source::new(source),
+    }
+    .scan_recovering();
+
+    // Strings can span lines, and an unterminated one always runs until the end of the input
+    if let Some(error) = scanner_errors.first() {
+        return error.message == UNTERMINATED_STRING_MESSAGE;
+    }
+
+    let Some(error) = first_parser_error(tokens) else {
+        return false;
+    };
+
+    // An error caused by reaching the end of the input moves when more code follows it, while an
+    // error that more lines can't fix stays where it is
+    let probe = format!("{}\nx", source);
+    let (tokens, _) = Scanner {
+        source: Source::new(&probe),
+    }
+    .scan_recovering();
+
+    first_parser_error(tokens) != Some(error)
+}
+
+/// Where parsing `tokens` fails first, if it does
+fn first_parser_error(tokens: Vec<Token>) -> Option<(u64, u64)> {
+    let errors = Parser::new(tokens).parse().err()?;
+
+    errors
+        .first()
+        .map(|error| (error.token.line, error.token.position))
+}
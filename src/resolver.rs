@@ -0,0 +1,548 @@
+use std::{collections::HashMap, fmt::Display, rc::Rc};
+
+use crate::{
+    diagnostic::Severity,
+    environment::Environment,
+    matcha::Type,
+    statement::{
+        AssignmentExpression, Expression, FunctionExpression, IfStatement, Statement,
+        TypeAnnotation, VariableDeclaration,
+    },
+    token::Token,
+};
+
+/// Where a local variable was declared, relative to the scope that uses it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Resolved {
+    /// How many scopes up the variable was declared
+    pub depth: usize,
+    /// The position of the variable among the declarations of its scope
+    pub slot: usize,
+}
+
+/// A name in the source and the declaration it refers to
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reference<'a> {
+    pub name: Token<'a>,
+    /// The token that declared the name, the same as `name` for the declaration itself
+    pub declaration: Token<'a>,
+}
+
+#[derive(Debug)]
+pub struct ResolverError<'a> {
+    pub message: String,
+    pub token: Token<'a>,
+    pub note: Option<String>,
+    pub help: Option<&'static str>,
+    pub severity: Severity,
+}
+
+impl Display for ResolverError<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Resolver {} at {}:{}. {}",
+            self.severity, self.token.line, self.token.position, self.message
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Variable,
+    Record,
+    Parameter,
+    Binding,
+}
+
+struct Variable<'a> {
+    /// `None` for the variables that were defined before the program ran, e.g. in the REPL
+    token: Option<Token<'a>>,
+    kind: Kind,
+    slot: usize,
+    /// Whether the declaration was reached. Every declaration of a scope is known when it's
+    /// entered, so uses before the declaration can be told apart from unknown names
+    declared: bool,
+    used: bool,
+}
+
+struct Scope<'a> {
+    variables: HashMap<String, Variable<'a>>,
+    /// How many functions enclose the scope
+    function: usize,
+}
+
+/// Resolves every variable to the scope that declares it before the program runs, reporting
+/// variables that are used before their declaration, declared twice or never read
+pub struct Resolver<'a> {
+    scopes: Vec<Scope<'a>>,
+    /// How many functions enclose the code being resolved
+    function: usize,
+    errors: Vec<ResolverError<'a>>,
+    references: Vec<Reference<'a>>,
+}
+
+impl Default for Resolver<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> Resolver<'a> {
+    pub fn new() -> Resolver<'a> {
+        Resolver::with_environment(&Environment::new())
+    }
+
+    /// Creates a resolver that knows the variables already defined in `environment`
+    pub fn with_environment(environment: &Environment) -> Resolver<'a> {
+        let variables = environment
+            .values
+            .keys()
+            .enumerate()
+            .map(|(slot, name)| {
+                let variable = Variable {
+                    token: None,
+                    kind: Kind::Variable,
+                    slot,
+                    declared: true,
+                    used: false,
+                };
+
+                (name.to_owned(), variable)
+            })
+            .collect();
+
+        Resolver {
+            scopes: vec![Scope {
+                variables,
+                function: 0,
+            }],
+            function: 0,
+            errors: Vec::new(),
+            references: Vec::new(),
+        }
+    }
+
+    /// Stores the resolution of local variables in `statements` and returns the diagnostics, in
+    /// source order. The program shouldn't run if any of them is an error
+    pub fn resolve(self, statements: &mut [Statement<'a>]) -> Vec<ResolverError<'a>> {
+        self.resolve_references(statements).0
+    }
+
+    /// Resolves `statements` like `resolve`, also returning every declaration and use of a name
+    /// declared in the source, in source order
+    pub fn resolve_references(
+        mut self,
+        statements: &mut [Statement<'a>],
+    ) -> (Vec<ResolverError<'a>>, Vec<Reference<'a>>) {
+        self.hoist(statements);
+
+        // The global scope never ends, so unused globals aren't reported. The REPL can still use
+        // them later
+        for statement in statements.iter_mut() {
+            self.statement(statement);
+        }
+
+        self.errors
+            .sort_by_key(|error| (error.token.line, error.token.position));
+        self.references
+            .sort_by_key(|reference| reference.name.span.start);
+
+        (self.errors, self.references)
+    }
+
+    fn statement(&mut self, statement: &mut Statement<'a>) {
+        match statement {
+            Statement::Expression(expression) => self.expression(expression),
+            Statement::VariableDeclaration(declaration) => self.variable_declaration(declaration),
+            Statement::Block(block) => self.block(&mut block.statements, Vec::new()),
+            Statement::If(if_statement) => self.if_statement(if_statement),
+            Statement::For(for_statement) => {
+                self.expression(&mut for_statement.condition);
+                self.block(&mut for_statement.statements, Vec::new());
+            }
+            Statement::Return(return_statement) => {
+                if let Some(ref mut value) = return_statement.value {
+                    self.expression(value);
+                }
+            }
+            Statement::Record(record) => {
+                self.declare(&record.name, Kind::Record);
+
+                for field in &record.fields {
+                    self.annotation(Some(&field.r#type));
+                }
+            }
+        }
+    }
+
+    fn variable_declaration(&mut self, declaration: &mut VariableDeclaration<'a>) {
+        self.annotation(declaration.r#type.as_ref());
+
+        // Functions are declared first so they can call themselves, other initializers still see
+        // the variables they shadow
+        if let Expression::Function(_) = declaration.initializer {
+            self.declare(&declaration.identifier, Kind::Variable);
+            self.expression(&mut declaration.initializer);
+        } else {
+            self.expression(&mut declaration.initializer);
+            self.declare(&declaration.identifier, Kind::Variable);
+        }
+    }
+
+    fn if_statement(&mut self, if_statement: &mut IfStatement<'a>) {
+        self.expression(&mut if_statement.condition);
+
+        // The binding lives in the same scope as the statements it's bound for
+        let variables = match if_statement.binding {
+            Some(ref binding) => vec![(binding, Kind::Binding)],
+            None => Vec::new(),
+        };
+
+        self.block(&mut if_statement.statements, variables);
+
+        if let Some(ref mut else_statements) = if_statement.else_statements {
+            self.block(else_statements, Vec::new());
+        }
+    }
+
+    /// Resolves `statements` in a new scope, which starts with `variables` already declared
+    fn block(&mut self, statements: &mut [Statement<'a>], variables: Vec<(&Token<'a>, Kind)>) {
+        self.scopes.push(Scope {
+            variables: HashMap::new(),
+            function: self.function,
+        });
+
+        for (token, kind) in variables {
+            self.declare(token, kind);
+        }
+
+        self.hoist(statements);
+
+        for statement in statements.iter_mut() {
+            self.statement(statement);
+        }
+
+        self.end_scope();
+    }
+
+    fn function(&mut self, function: &mut FunctionExpression<'a>) {
+        for parameter in &function.parameters {
+            self.annotation(parameter.r#type.as_ref());
+        }
+
+        self.annotation(function.return_type.as_ref());
+
+        let parameters: Vec<(&Token<'a>, Kind)> = function
+            .parameters
+            .iter()
+            .map(|parameter| (&parameter.identifier, Kind::Parameter))
+            .collect();
+
+        self.function += 1;
+        self.block(Rc::make_mut(&mut function.body).as_mut_slice(), parameters);
+        self.function -= 1;
+    }
+
+    fn expression(&mut self, expression: &mut Expression<'a>) {
+        match expression {
+            Expression::Literal(_) | Expression::None(_) => {}
+            Expression::Grouping(grouping) => self.expression(&mut grouping.expression),
+            Expression::Variable(variable) => {
+                variable.resolved = self.lookup(&variable.value, true);
+            }
+            Expression::Assignment(assignment) => self.assignment(assignment),
+            Expression::Unary(unary) => self.expression(&mut unary.left),
+            Expression::Binary(binary)
+            | Expression::Logical(binary)
+            | Expression::Coalesce(binary) => {
+                self.expression(&mut binary.left);
+                self.expression(&mut binary.right);
+            }
+            Expression::Function(function) => self.function(function),
+            Expression::Call(call) => {
+                self.expression(&mut call.callee);
+
+                for argument in call.arguments.iter_mut() {
+                    self.expression(argument);
+                }
+            }
+            Expression::Array(array) => {
+                for element in array.elements.iter_mut() {
+                    self.expression(element);
+                }
+            }
+            Expression::Index(index) => {
+                self.expression(&mut index.target);
+                self.expression(&mut index.index);
+            }
+            Expression::IndexAssignment(assignment) => {
+                self.expression(&mut assignment.target);
+                self.expression(&mut assignment.index);
+                self.expression(&mut assignment.value);
+            }
+            Expression::Unwrap(unwrap) => self.expression(&mut unwrap.target),
+            Expression::Record(record) => {
+                self.lookup(&record.name, true);
+
+                for field in record.fields.iter_mut() {
+                    self.expression(&mut field.value);
+                }
+            }
+            Expression::Field(field) => self.expression(&mut field.target),
+            Expression::FieldAssignment(assignment) => {
+                self.expression(&mut assignment.target);
+                self.expression(&mut assignment.value);
+            }
+        }
+    }
+
+    fn assignment(&mut self, assignment: &mut AssignmentExpression<'a>) {
+        self.expression(&mut assignment.value);
+
+        // Assigning a variable doesn't count as using it
+        assignment.resolved = self.lookup(&assignment.identifier, false);
+    }
+
+    /// Records a type annotation as a use of the record it names
+    fn annotation(&mut self, annotation: Option<&TypeAnnotation<'a>>) {
+        let Some(annotation) = annotation else {
+            return;
+        };
+
+        if let Some(ref function) = annotation.function {
+            for parameter in &function.parameters {
+                self.annotation(Some(parameter));
+            }
+
+            return self.annotation(function.return_type.as_deref());
+        }
+
+        if Type::from_name(annotation.name.lexeme).is_some() {
+            return;
+        }
+
+        // Unknown types are reported by the type checker
+        if let Some(variable) = self
+            .scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.variables.get_mut(annotation.name.lexeme))
+        {
+            variable.used = true;
+
+            if let Some(ref declaration) = variable.token {
+                self.references.push(Reference {
+                    name: annotation.name.clone(),
+                    declaration: declaration.clone(),
+                });
+            }
+        }
+    }
+
+    /// Finds the declaration `identifier` refers to, returning `None` for globals
+    fn lookup(&mut self, identifier: &Token<'a>, read: bool) -> Option<Resolved> {
+        let globals = self.scopes.len() - 1;
+        let mut later = None;
+
+        for (depth, scope) in self.scopes.iter_mut().rev().enumerate() {
+            let Some(variable) = scope.variables.get_mut(identifier.lexeme) else {
+                continue;
+            };
+
+            // Code in the same function runs before the declaration, so it would find the
+            // variables of outer scopes instead. Nested functions run later, when it's declared
+            if !variable.declared && scope.function == self.function {
+                later = later.or(variable.token.clone());
+                continue;
+            }
+
+            variable.used |= read;
+
+            if let Some(ref declaration) = variable.token {
+                self.references.push(Reference {
+                    name: identifier.clone(),
+                    declaration: declaration.clone(),
+                });
+            }
+
+            return match depth == globals {
+                true => None,
+                false => Some(Resolved {
+                    depth,
+                    slot: variable.slot,
+                }),
+            };
+        }
+
+        let message = match (later, read) {
+            (Some(declaration), _) => {
+                self.error(
+                    format!(
+                        "Variable '{}' is used before its declaration",
+                        identifier.lexeme
+                    ),
+                    identifier,
+                );
+                self.note(format!(
+                    "'{}' is declared at {}:{}",
+                    identifier.lexeme, declaration.line, declaration.position
+                ));
+
+                return None;
+            }
+            (None, true) => format!(
+                "Variable '{}' not found in the current scope",
+                identifier.lexeme
+            ),
+            (None, false) => format!(
+                "Cannot assign a value to undeclared variable '{}'",
+                identifier.lexeme
+            ),
+        };
+
+        self.error(message, identifier);
+
+        None
+    }
+
+    /// Knows about the declarations of a scope before resolving it, see `Variable::declared`
+    fn hoist(&mut self, statements: &[Statement<'a>]) {
+        let scope = self
+            .scopes
+            .last_mut()
+            .expect("There must always be a scope");
+
+        for statement in statements {
+            let (token, kind) = match statement {
+                Statement::VariableDeclaration(declaration) => {
+                    (&declaration.identifier, Kind::Variable)
+                }
+                Statement::Record(record) => (&record.name, Kind::Record),
+                _ => continue,
+            };
+
+            let slot = scope.variables.len();
+
+            // Redeclarations keep the first one, and are reported when they are reached
+            scope
+                .variables
+                .entry(token.lexeme.to_owned())
+                .or_insert(Variable {
+                    token: Some(token.clone()),
+                    kind,
+                    slot,
+                    declared: false,
+                    used: false,
+                });
+        }
+    }
+
+    fn declare(&mut self, token: &Token<'a>, kind: Kind) {
+        let scope = self
+            .scopes
+            .last_mut()
+            .expect("There must always be a scope");
+        let slot = scope.variables.len();
+
+        let previous = match scope.variables.get_mut(token.lexeme) {
+            Some(variable) if !variable.declared => {
+                variable.declared = true;
+                self.references.push(Reference {
+                    name: token.clone(),
+                    declaration: token.clone(),
+                });
+
+                return;
+            }
+            Some(variable) => variable.token.clone(),
+            None => {
+                scope.variables.insert(
+                    token.lexeme.to_owned(),
+                    Variable {
+                        token: Some(token.clone()),
+                        kind,
+                        slot,
+                        declared: true,
+                        used: false,
+                    },
+                );
+                self.references.push(Reference {
+                    name: token.clone(),
+                    declaration: token.clone(),
+                });
+
+                return;
+            }
+        };
+
+        let name = match kind {
+            Kind::Record => "Record",
+            _ => "Variable",
+        };
+
+        self.error(
+            format!("{} '{}' already declared in this scope", name, token.lexeme),
+            token,
+        );
+
+        if let Some(previous) = previous {
+            self.note(format!(
+                "'{}' was first declared at {}:{}",
+                token.lexeme, previous.line, previous.position
+            ));
+        }
+
+        if kind == Kind::Variable {
+            self.help("use `=` instead of `:=` to assign a new value to it");
+        }
+    }
+
+    /// Leaves the current scope, warning about the variables that were never read
+    fn end_scope(&mut self) {
+        let scope = self.scopes.pop().expect("Scope must exist");
+
+        for variable in scope.variables.into_values() {
+            let Some(token) = variable.token else {
+                continue;
+            };
+
+            if variable.kind == Kind::Variable && variable.declared && !variable.used {
+                self.errors.push(ResolverError {
+                    message: format!("Variable '{}' is never read", token.lexeme),
+                    token,
+                    note: None,
+                    help: None,
+                    severity: Severity::Warning,
+                });
+            }
+        }
+    }
+
+    #[inline]
+    fn error(&mut self, message: String, token: &Token<'a>) {
+        self.errors.push(ResolverError {
+            message,
+            token: token.clone(),
+            note: None,
+            help: None,
+            severity: Severity::Error,
+        });
+    }
+
+    /// Attaches a note to the last reported error
+    #[inline]
+    fn note(&mut self, note: String) {
+        if let Some(error) = self.errors.last_mut() {
+            error.note = Some(note);
+        }
+    }
+
+    /// Attaches a suggestion to the last reported error
+    #[inline]
+    fn help(&mut self, help: &'static str) {
+        if let Some(error) = self.errors.last_mut() {
+            error.help = Some(help);
+        }
+    }
+}
@@ -7,7 +7,7 @@ use crate::{
 };
 
 const UNKNOWN_TOKEN_MESSAGE: &str = "Unknown token";
-const UNTERMINATED_STRING_MESSAGE: &str = "Unterminated string";
+pub const UNTERMINATED_STRING_MESSAGE: &str = "Unterminated string";
 const INVALID_NUMBER_MESSAGE: &str = "Invalid number";
 
 pub enum ScannerErrorType {
@@ -60,35 +60,86 @@ pub struct Scanner<'a> {
 }
 
 impl<'a> Scanner<'a> {
-    pub fn scan(&mut self) -> Result<Vec<Token<'a>>, ScannerError> {
+    /// Scans the whole source, failing with every error found. The interpreter itself uses
+    /// `scan_recovering` so it can keep parsing past scanner errors
+    #[cfg(test)]
+    pub fn scan(&mut self) -> Result<Vec<Token<'a>>, Vec<ScannerError>> {
+        let (tokens, errors) = self.scan_recovering();
+
+        if errors.is_empty() {
+            return Ok(tokens);
+        }
+
+        Err(errors)
+    }
+
+    /// Scans the whole source, replacing invalid text with `TokenType::Error` tokens instead of
+    /// stopping at the first error
+    pub fn scan_recovering(&mut self) -> (Vec<Token<'a>>, Vec<ScannerError>) {
+        let (mut tokens, errors) = self.scan_with_trivia();
+
+        tokens.retain(|token| !token.token_type.is_trivia());
+
+        (tokens, errors)
+    }
+
+    /// Like `scan_recovering`, but also keeps comments and whitespace as tokens, so that the
+    /// lexemes of the tokens put together are the whole source, for tools that reproduce it
+    pub fn scan_with_trivia(&mut self) -> (Vec<Token<'a>>, Vec<ScannerError>) {
         let mut line: u64 = 1;
         let mut position: u64 = 0;
         let mut tokens = Vec::<Token<'a>>::new();
+        let mut errors = Vec::<ScannerError>::new();
+
+        loop {
+            match Scanner::scan_token(&mut self.source, &mut line, &mut position, &mut tokens) {
+                Ok(Some(())) => {}
+                Ok(None) => break,
+                Err(error) => {
+                    let span = self.source.span();
+
+                    tokens.push(Token::new(
+                        TokenType::Error,
+                        self.source.pop_lexeme(),
+                        error.line,
+                        error.position,
+                        span,
+                    ));
+                    errors.push(error);
+                }
+            }
+        }
 
-        while (Scanner::scan_token(&mut self.source, &mut line, &mut position, &mut tokens)?)
-            .is_some()
-        {}
-
-        Scanner::add_token("", line, position, &mut tokens, TokenType::Eof);
+        Scanner::add_token(
+            &mut self.source,
+            line,
+            position + 1,
+            &mut tokens,
+            TokenType::Eof,
+        );
 
-        Ok(tokens)
+        (tokens, errors)
     }
 
     // Helpers:
 
+    /// Pops the current lexeme into a token that starts at `line` and `position`
     #[inline]
     fn add_token(
-        lexeme: &'a str,
+        source: &mut Source<'a>,
         line: u64,
         position: u64,
         tokens: &mut Vec<Token<'a>>,
         token_type: TokenType,
     ) {
+        let span = source.span();
+
         tokens.push(Token::new(
             token_type,
-            lexeme,
+            source.pop_lexeme(),
             line,
-            position - lexeme.len() as u64 + 1,
+            position,
+            span,
         ));
     }
 
@@ -102,252 +153,86 @@ impl<'a> Scanner<'a> {
         let Some(c) = Scanner::advance(source, position) else {
             return Ok(None);
         };
+        let start = *position;
 
         match c {
             // Single characters
-            '(' => Scanner::add_token(
-                source.pop_lexeme(),
-                *line,
-                *position,
-                tokens,
-                TokenType::LeftParen,
-            ),
-            ')' => Scanner::add_token(
-                source.pop_lexeme(),
-                *line,
-                *position,
-                tokens,
-                TokenType::RightParen,
-            ),
-            '{' => Scanner::add_token(
-                source.pop_lexeme(),
-                *line,
-                *position,
-                tokens,
-                TokenType::LeftBrace,
-            ),
-            '}' => Scanner::add_token(
-                source.pop_lexeme(),
-                *line,
-                *position,
-                tokens,
-                TokenType::RightBrace,
-            ),
-            '[' => Scanner::add_token(
-                source.pop_lexeme(),
-                *line,
-                *position,
-                tokens,
-                TokenType::LeftBracket,
-            ),
-            ']' => Scanner::add_token(
-                source.pop_lexeme(),
-                *line,
-                *position,
-                tokens,
-                TokenType::RightBracket,
-            ),
-            ',' => Scanner::add_token(
-                source.pop_lexeme(),
-                *line,
-                *position,
-                tokens,
-                TokenType::Comma,
-            ),
-            '.' => Scanner::add_token(
-                source.pop_lexeme(),
-                *line,
-                *position,
-                tokens,
-                TokenType::Dot,
-            ),
-            '-' => Scanner::add_token(
-                source.pop_lexeme(),
-                *line,
-                *position,
-                tokens,
-                TokenType::Minus,
-            ),
-            '+' => Scanner::add_token(
-                source.pop_lexeme(),
-                *line,
-                *position,
-                tokens,
-                TokenType::Plus,
-            ),
-            ';' => Scanner::add_token(
-                source.pop_lexeme(),
-                *line,
-                *position,
-                tokens,
-                TokenType::SemiColon,
-            ),
-            '*' => Scanner::add_token(
-                source.pop_lexeme(),
-                *line,
-                *position,
-                tokens,
-                TokenType::Star,
-            ),
+            '(' => Scanner::add_token(source, *line, start, tokens, TokenType::LeftParen),
+            ')' => Scanner::add_token(source, *line, start, tokens, TokenType::RightParen),
+            '{' => Scanner::add_token(source, *line, start, tokens, TokenType::LeftBrace),
+            '}' => Scanner::add_token(source, *line, start, tokens, TokenType::RightBrace),
+            '[' => Scanner::add_token(source, *line, start, tokens, TokenType::LeftBracket),
+            ']' => Scanner::add_token(source, *line, start, tokens, TokenType::RightBracket),
+            ',' => Scanner::add_token(source, *line, start, tokens, TokenType::Comma),
+            '.' => Scanner::add_token(source, *line, start, tokens, TokenType::Dot),
+            '-' => Scanner::add_token(source, *line, start, tokens, TokenType::Minus),
+            '+' => Scanner::add_token(source, *line, start, tokens, TokenType::Plus),
+            ';' => Scanner::add_token(source, *line, start, tokens, TokenType::SemiColon),
+            '*' => Scanner::add_token(source, *line, start, tokens, TokenType::Star),
 
             // Operators
             '&' => {
                 if Scanner::matches_next(source, position, '&') {
-                    Scanner::add_token(
-                        source.pop_lexeme(),
-                        *line,
-                        *position,
-                        tokens,
-                        TokenType::And,
-                    )
+                    Scanner::add_token(source, *line, start, tokens, TokenType::And)
                 } else {
-                    Scanner::add_token(
-                        source.pop_lexeme(),
-                        *line,
-                        *position,
-                        tokens,
-                        TokenType::BitwiseAnd,
-                    )
+                    Scanner::add_token(source, *line, start, tokens, TokenType::BitwiseAnd)
                 }
             }
             '|' => {
                 if Scanner::matches_next(source, position, '|') {
-                    Scanner::add_token(source.pop_lexeme(), *line, *position, tokens, TokenType::Or)
+                    Scanner::add_token(source, *line, start, tokens, TokenType::Or)
                 } else {
-                    Scanner::add_token(
-                        source.pop_lexeme(),
-                        *line,
-                        *position,
-                        tokens,
-                        TokenType::BitwiseOr,
-                    )
+                    Scanner::add_token(source, *line, start, tokens, TokenType::BitwiseOr)
                 }
             }
             '!' => {
                 if Scanner::matches_next(source, position, '=') {
-                    Scanner::add_token(
-                        source.pop_lexeme(),
-                        *line,
-                        *position,
-                        tokens,
-                        TokenType::BangEqual,
-                    )
+                    Scanner::add_token(source, *line, start, tokens, TokenType::BangEqual)
                 } else {
-                    Scanner::add_token(
-                        source.pop_lexeme(),
-                        *line,
-                        *position,
-                        tokens,
-                        TokenType::Bang,
-                    )
+                    Scanner::add_token(source, *line, start, tokens, TokenType::Bang)
                 }
             }
             ':' => {
                 if Scanner::matches_next(source, position, '=') {
-                    Scanner::add_token(
-                        source.pop_lexeme(),
-                        *line,
-                        *position,
-                        tokens,
-                        TokenType::VarDec,
-                    )
+                    Scanner::add_token(source, *line, start, tokens, TokenType::VarDec)
                 } else {
-                    Scanner::add_token(
-                        source.pop_lexeme(),
-                        *line,
-                        *position,
-                        tokens,
-                        TokenType::Colon,
-                    )
+                    Scanner::add_token(source, *line, start, tokens, TokenType::Colon)
                 }
             }
             '=' => {
                 if Scanner::matches_next(source, position, '=') {
-                    Scanner::add_token(
-                        source.pop_lexeme(),
-                        *line,
-                        *position,
-                        tokens,
-                        TokenType::DoubleEqual,
-                    )
+                    Scanner::add_token(source, *line, start, tokens, TokenType::DoubleEqual)
                 } else {
-                    Scanner::add_token(
-                        source.pop_lexeme(),
-                        *line,
-                        *position,
-                        tokens,
-                        TokenType::Equal,
-                    )
+                    Scanner::add_token(source, *line, start, tokens, TokenType::Equal)
                 }
             }
             '>' => {
                 if Scanner::matches_next(source, position, '=') {
-                    Scanner::add_token(
-                        source.pop_lexeme(),
-                        *line,
-                        *position,
-                        tokens,
-                        TokenType::GreaterEqual,
-                    )
+                    Scanner::add_token(source, *line, start, tokens, TokenType::GreaterEqual)
                 } else if Scanner::matches_next(source, position, '>') {
-                    Scanner::add_token(
-                        source.pop_lexeme(),
-                        *line,
-                        *position,
-                        tokens,
-                        TokenType::RightShift,
-                    )
+                    Scanner::add_token(source, *line, start, tokens, TokenType::RightShift)
                 } else {
-                    Scanner::add_token(
-                        source.pop_lexeme(),
-                        *line,
-                        *position,
-                        tokens,
-                        TokenType::Greater,
-                    )
+                    Scanner::add_token(source, *line, start, tokens, TokenType::Greater)
                 }
             }
             '<' => {
                 if Scanner::matches_next(source, position, '=') {
-                    Scanner::add_token(
-                        source.pop_lexeme(),
-                        *line,
-                        *position,
-                        tokens,
-                        TokenType::LessEqual,
-                    )
+                    Scanner::add_token(source, *line, start, tokens, TokenType::LessEqual)
                 } else if Scanner::matches_next(source, position, '<') {
-                    Scanner::add_token(
-                        source.pop_lexeme(),
-                        *line,
-                        *position,
-                        tokens,
-                        TokenType::LeftShift,
-                    )
+                    Scanner::add_token(source, *line, start, tokens, TokenType::LeftShift)
                 } else {
-                    Scanner::add_token(
-                        source.pop_lexeme(),
-                        *line,
-                        *position,
-                        tokens,
-                        TokenType::Less,
-                    )
+                    Scanner::add_token(source, *line, start, tokens, TokenType::Less)
                 }
             }
-            '^' => Scanner::add_token(
-                source.pop_lexeme(),
-                *line,
-                *position,
-                tokens,
-                TokenType::BitwiseXor,
-            ),
-            '~' => Scanner::add_token(
-                source.pop_lexeme(),
-                *line,
-                *position,
-                tokens,
-                TokenType::BitwiseNot,
-            ),
+            '^' => Scanner::add_token(source, *line, start, tokens, TokenType::BitwiseXor),
+            '?' => {
+                if Scanner::matches_next(source, position, '?') {
+                    Scanner::add_token(source, *line, start, tokens, TokenType::DoubleQuestion)
+                } else {
+                    Scanner::add_token(source, *line, start, tokens, TokenType::Question)
+                }
+            }
+            '~' => Scanner::add_token(source, *line, start, tokens, TokenType::BitwiseNot),
             // Division operator and comments
             '/' => {
                 if Scanner::matches_next(source, position, '/') {
@@ -360,26 +245,37 @@ impl<'a> Scanner<'a> {
                         source.next();
                         *position += 1;
                     }
-                    source.pop_lexeme();
+                    Scanner::add_token(source, *line, start, tokens, TokenType::Comment);
                 } else {
-                    Scanner::add_token(
-                        source.pop_lexeme(),
-                        *line,
-                        *position,
-                        tokens,
-                        TokenType::Slash,
-                    );
+                    Scanner::add_token(source, *line, start, tokens, TokenType::Slash);
                 };
             }
 
-            // Ignore characters without semantic meaning
-            ' ' | '\r' | '\t' => {
-                source.pop_lexeme();
-            }
-            '\n' => {
-                *line += 1;
-                *position = 0;
-                source.pop_lexeme();
+            // Characters without semantic meaning
+            ' ' | '\r' | '\t' | '\n' => {
+                let start_line = *line;
+
+                if c == '\n' {
+                    *line += 1;
+                    *position = 0;
+                }
+
+                while let Some(next) = source.peek() {
+                    if !matches!(next, ' ' | '\r' | '\t' | '\n') {
+                        break;
+                    }
+
+                    source.next();
+
+                    if next == '\n' {
+                        *line += 1;
+                        *position = 0;
+                    } else {
+                        *position += 1;
+                    }
+                }
+
+                Scanner::add_token(source, start_line, start, tokens, TokenType::Whitespace);
             }
 
             // String literals
@@ -444,6 +340,9 @@ impl<'a> Scanner<'a> {
         position: &mut u64,
         tokens: &mut Vec<Token<'a>>,
     ) -> Result<(), ScannerError> {
+        // Strings may span lines, so remember where the token starts
+        let (start_line, start_position) = (*line, *position);
+
         while let Some(next) = source.peek() {
             if next == '"' {
                 break;
@@ -453,7 +352,13 @@ impl<'a> Scanner<'a> {
 
             if next == '\n' {
                 *line += 1;
-                *position = 1;
+                *position = 0;
+            }
+
+            // Skip the escaped character so an escaped quote doesn't end the string.
+            // The escape sequence itself is validated by the parser.
+            if next == '\\' && source.peek().is_some_and(|c| c != '\n') {
+                Scanner::advance(source, position);
             }
         }
 
@@ -469,14 +374,16 @@ impl<'a> Scanner<'a> {
         let closing_quote = Scanner::advance(source, position);
         debug_assert_eq!(closing_quote, Some('"'));
 
-        let lexeme = source.pop_lexeme();
-
         // Must at least include the two quotes
-        debug_assert!(lexeme.len() >= 2);
-
-        let value = &lexeme[1..(lexeme.len() - 1)];
-
-        Scanner::add_token(lexeme, *line, *position, tokens, TokenType::String);
+        debug_assert!(source.lexeme().len() >= 2);
+
+        Scanner::add_token(
+            source,
+            start_line,
+            start_position,
+            tokens,
+            TokenType::String,
+        );
         Ok(())
     }
 
@@ -487,6 +394,7 @@ impl<'a> Scanner<'a> {
         position: &mut u64,
         tokens: &mut Vec<Token<'a>>,
     ) -> Result<(), ScannerError> {
+        let start = *position;
         let mut is_float = false;
 
         while let Some(next) = source.peek() {
@@ -515,12 +423,10 @@ impl<'a> Scanner<'a> {
             }
         }
 
-        let lexeme = source.pop_lexeme();
-
         if is_float {
-            Scanner::add_token(lexeme, *line, *position, tokens, TokenType::Float)
+            Scanner::add_token(source, *line, start, tokens, TokenType::Float)
         } else {
-            Scanner::add_token(lexeme, *line, *position, tokens, TokenType::Integer)
+            Scanner::add_token(source, *line, start, tokens, TokenType::Integer)
         }
 
         Ok(())
@@ -533,6 +439,8 @@ impl<'a> Scanner<'a> {
         position: &mut u64,
         tokens: &mut Vec<Token<'a>>,
     ) {
+        let start = *position;
+
         while source
             .peek()
             .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_')
@@ -540,13 +448,11 @@ impl<'a> Scanner<'a> {
             Scanner::advance(source, position);
         }
 
-        let value = source.pop_lexeme();
-
         // If the value is a known keyword, add the token and return early
-        if let Some(keyword) = KEYWORDS.get(value) {
-            Scanner::add_token(value, *line, *position, tokens, *keyword)
+        if let Some(keyword) = KEYWORDS.get(source.lexeme()) {
+            Scanner::add_token(source, *line, start, tokens, *keyword)
         } else {
-            Scanner::add_token(value, *line, *position, tokens, TokenType::Identifier)
+            Scanner::add_token(source, *line, start, tokens, TokenType::Identifier)
         }
     }
 }
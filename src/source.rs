@@ -1,5 +1,7 @@
 use std::str::Chars;
 
+use crate::token::Span;
+
 #[derive(Debug)]
 pub struct Source<'a> {
     source: &'a str,
@@ -18,23 +20,25 @@ impl<'a> Source<'a> {
         }
     }
 
+    /// Returns the characters consumed since the last call, and starts a new lexeme after them
     pub fn pop_lexeme(&mut self) -> &'a str {
-        debug_assert!(self.current_index >= self.lexeme_start);
-        debug_assert!(self.lexeme_start <= self.source.len());
-
-        let lexeme = if self.current_index == self.source.len() {
-            &self.source[self.lexeme_start - 1..]
-        } else if self.lexeme_start == self.current_index {
-            &self.source[self.lexeme_start - 1..self.current_index]
-        } else {
-            &self.source[self.lexeme_start..self.current_index]
-        };
+        let lexeme = self.lexeme();
 
         self.lexeme_start = self.current_index;
 
         lexeme
     }
 
+    /// Returns the characters consumed since the last `pop_lexeme`, without popping them
+    pub fn lexeme(&self) -> &'a str {
+        &self.source[self.lexeme_start..self.current_index]
+    }
+
+    /// The byte range of the current lexeme
+    pub fn span(&self) -> Span {
+        Span::new(self.lexeme_start, self.current_index)
+    }
+
     pub fn peek(&self) -> Option<char> {
         self.chars.clone().next()
     }
@@ -44,7 +48,11 @@ impl Iterator for Source<'_> {
     type Item = char;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.current_index += 1;
-        self.chars.next()
+        let next = self.chars.next()?;
+
+        // Indices are byte offsets, so that lexemes with multi-byte characters can be sliced
+        self.current_index += next.len_utf8();
+
+        Some(next)
     }
 }
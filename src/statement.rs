@@ -1,6 +1,12 @@
-use crate::token::Token;
+use std::{fmt::Display, rc::Rc};
 
-fn generate_left_pad(depth: usize) -> String {
+use crate::{
+    matcha::Literal,
+    resolver::Resolved,
+    token::{Span, Token},
+};
+
+pub fn generate_left_pad(depth: usize) -> String {
     if depth > 0 {
         "│  ".repeat(depth - 1) + "├─ "
     } else {
@@ -8,27 +14,59 @@ fn generate_left_pad(depth: usize) -> String {
     }
 }
 
+fn format_resolved(resolved: Option<Resolved>) -> String {
+    match resolved {
+        Some(resolved) => format!(" (depth {}, slot {})", resolved.depth, resolved.slot),
+        None => "".to_owned(),
+    }
+}
+
 #[cfg_attr(test, derive(PartialEq))]
 #[derive(Debug, Clone)]
 pub enum Statement<'a> {
     Expression(Expression<'a>),
     VariableDeclaration(VariableDeclaration<'a>),
-    Block(Vec<Statement<'a>>),
+    Block(BlockStatement<'a>),
     If(IfStatement<'a>),
     For(ForStatement<'a>),
+    Return(ReturnStatement<'a>),
+    Record(RecordDeclaration<'a>),
 }
 
 impl Statement<'_> {
+    /// Returns the range of source this statement was parsed from, without its trailing ';'
+    pub fn span(&self) -> Span {
+        match self {
+            Statement::Expression(ex) => ex.span(),
+            Statement::VariableDeclaration(declaration) => declaration
+                .identifier
+                .span
+                .to(declaration.initializer.span()),
+            Statement::Block(block) => block.span,
+            Statement::If(if_statement) => if_statement.span,
+            Statement::For(for_statement) => for_statement.span,
+            Statement::Return(return_statement) => match return_statement.value {
+                Some(ref value) => return_statement.keyword.span.to(value.span()),
+                None => return_statement.keyword.span,
+            },
+            Statement::Record(record) => record.span,
+        }
+    }
+
     pub fn format(&self, depth: usize) -> String {
         let result = match self {
             Statement::Expression(ex) => ex.format(depth),
             Statement::VariableDeclaration(declaration) => declaration.format(depth),
-            Statement::Block(block) => Statement::format_block(block, depth),
+            Statement::Block(block) => Statement::format_block(&block.statements, depth),
             Statement::If(if_statement) => {
                 let left_pad = generate_left_pad(depth);
                 let children_left_pad = generate_left_pad(depth + 1);
                 let condition = if_statement.condition.format(depth + 2);
                 let statements = Statement::format_block(&if_statement.statements, depth + 2);
+                let binding = match if_statement.binding {
+                    Some(ref binding) => format!("{}BIND {}\n", children_left_pad, binding.lexeme),
+                    None => "".to_owned(),
+                };
                 let else_block = match if_statement.else_statements {
                     Some(ref block) => format!(
                         "\n{}ELSE\n{}",
@@ -39,8 +77,8 @@ impl Statement<'_> {
                 };
 
                 format!(
-                    "{0}IF_STMT\n{1}CONDITION\n{2}\n{1}THEN\n{3}{4}",
-                    left_pad, children_left_pad, condition, statements, else_block
+                    "{0}IF_STMT\n{5}{1}CONDITION\n{2}\n{1}THEN\n{3}{4}",
+                    left_pad, children_left_pad, condition, statements, else_block, binding
                 )
             }
             Statement::For(for_statement) => {
@@ -54,23 +92,32 @@ impl Statement<'_> {
                     left_pad, children_left_pad, condition, statements
                 )
             }
+            Statement::Return(return_statement) => {
+                let left_pad = generate_left_pad(depth);
+
+                match return_statement.value {
+                    Some(ref value) => format!("{}RETURN\n{}", left_pad, value.format(depth + 1)),
+                    None => format!("{}RETURN", left_pad),
+                }
+            }
+            Statement::Record(record) => record.format(depth),
         };
 
         result.to_string()
     }
 
-    fn format_block(block: &Vec<Statement>, depth: usize) -> String {
+    fn format_block(block: &[Statement], depth: usize) -> String {
         let left_pad = generate_left_pad(depth);
-        let mut output: String = block
+        let output: Vec<String> = block
             .iter()
             .map(|statement| statement.format(depth + 1))
             .collect();
 
-        // Remove trailing '\n' from the last iteration
-        output.pop();
-        output.pop();
+        if output.is_empty() {
+            return format!("{}BLOCK", left_pad);
+        }
 
-        format!("{}BLOCK\n{}", left_pad, output)
+        format!("{}BLOCK\n{}", left_pad, output.join("\n"))
     }
 }
 
@@ -84,9 +131,68 @@ pub enum Expression<'a> {
     Variable(VariableExpression<'a>),
     Assignment(AssignmentExpression<'a>),
     Logical(BinaryExpression<'a>),
+    Function(FunctionExpression<'a>),
+    Call(CallExpression<'a>),
+    Array(ArrayExpression<'a>),
+    Index(IndexExpression<'a>),
+    IndexAssignment(IndexAssignmentExpression<'a>),
+    None(NoneExpression<'a>),
+    Unwrap(UnwrapExpression<'a>),
+    Coalesce(BinaryExpression<'a>),
+    Record(RecordExpression<'a>),
+    Field(FieldExpression<'a>),
+    FieldAssignment(FieldAssignmentExpression<'a>),
 }
 
-impl Expression<'_> {
+impl<'a> Expression<'a> {
+    /// Returns the range of source this expression was parsed from
+    pub fn span(&self) -> Span {
+        match self {
+            Expression::Binary(ex) | Expression::Logical(ex) | Expression::Coalesce(ex) => {
+                ex.left.span().to(ex.right.span())
+            }
+            Expression::Unary(ex) => ex.operator.span.to(ex.left.span()),
+            Expression::Literal(ex) => ex.value.span,
+            Expression::Grouping(ex) => ex.span,
+            Expression::Variable(ex) => ex.value.span,
+            Expression::Assignment(ex) => ex.identifier.span.to(ex.value.span()),
+            Expression::Function(ex) => ex.span,
+            Expression::Call(ex) => ex.span,
+            Expression::Array(ex) => ex.span,
+            Expression::Index(ex) => ex.span,
+            Expression::IndexAssignment(ex) => ex.target.span().to(ex.value.span()),
+            Expression::None(ex) => ex.keyword.span,
+            Expression::Unwrap(ex) => ex.target.span().to(ex.operator.span),
+            Expression::Record(ex) => ex.span,
+            Expression::Field(ex) => ex.target.span().to(ex.name.span),
+            Expression::FieldAssignment(ex) => ex.target.span().to(ex.value.span()),
+        }
+    }
+
+    /// Returns the token that best represents this expression, used to report its position.
+    pub fn token(&self) -> &Token<'a> {
+        match self {
+            Expression::Binary(ex) => &ex.operator,
+            Expression::Unary(ex) => &ex.operator,
+            Expression::Literal(ex) => &ex.value,
+            Expression::Grouping(ex) => ex.expression.token(),
+            Expression::Variable(ex) => &ex.value,
+            Expression::Assignment(ex) => &ex.identifier,
+            Expression::Logical(ex) => &ex.operator,
+            Expression::Function(ex) => &ex.keyword,
+            Expression::Call(ex) => &ex.paren,
+            Expression::Array(ex) => &ex.bracket,
+            Expression::Index(ex) => &ex.bracket,
+            Expression::IndexAssignment(ex) => &ex.bracket,
+            Expression::None(ex) => &ex.keyword,
+            Expression::Unwrap(ex) => &ex.operator,
+            Expression::Coalesce(ex) => &ex.operator,
+            Expression::Record(ex) => &ex.name,
+            Expression::Field(ex) => &ex.name,
+            Expression::FieldAssignment(ex) => &ex.name,
+        }
+    }
+
     fn format(&self, depth: usize) -> String {
         match self {
             Expression::Binary(ex) => ex.format(depth),
@@ -96,6 +202,17 @@ impl Expression<'_> {
             Expression::Variable(ex) => ex.format(depth),
             Expression::Assignment(ex) => ex.format(depth),
             Expression::Logical(ex) => ex.format(depth),
+            Expression::Function(ex) => ex.format(depth),
+            Expression::Call(ex) => ex.format(depth),
+            Expression::Array(ex) => ex.format(depth),
+            Expression::Index(ex) => ex.format(depth),
+            Expression::IndexAssignment(ex) => ex.format(depth),
+            Expression::None(ex) => ex.format(depth),
+            Expression::Unwrap(ex) => ex.format(depth),
+            Expression::Coalesce(ex) => ex.format(depth),
+            Expression::Record(ex) => ex.format(depth),
+            Expression::Field(ex) => ex.format(depth),
+            Expression::FieldAssignment(ex) => ex.format(depth),
         }
     }
 }
@@ -146,13 +263,15 @@ impl UnaryExpression<'_> {
 #[derive(Debug, Clone)]
 pub struct LiteralExpression<'a> {
     pub value: Token<'a>,
+    pub literal: Literal<'a>,
 }
 
 impl LiteralExpression<'_> {
+    /// Shows the value rather than the lexeme, which differs for literals folded by the optimizer
     fn format(&self, depth: usize) -> String {
         let left_pad = generate_left_pad(depth);
 
-        format!("{}{}", left_pad, self.value.lexeme)
+        format!("{}{}", left_pad, self.literal.source_text())
     }
 }
 
@@ -160,6 +279,8 @@ impl LiteralExpression<'_> {
 #[derive(Debug, Clone)]
 pub struct GroupingExpression<'a> {
     pub expression: Box<Expression<'a>>,
+    /// Includes the parentheses
+    pub span: Span,
 }
 
 impl GroupingExpression<'_> {
@@ -174,13 +295,20 @@ impl GroupingExpression<'_> {
 #[derive(Debug, Clone)]
 pub struct VariableExpression<'a> {
     pub value: Token<'a>,
+    /// Set by the resolver for local variables, globals are looked up by name
+    pub resolved: Option<Resolved>,
 }
 
 impl VariableExpression<'_> {
     pub fn format(&self, depth: usize) -> String {
         let left_pad = generate_left_pad(depth);
 
-        format!("{}VAR {}", left_pad, self.value.lexeme)
+        format!(
+            "{}VAR {}{}",
+            left_pad,
+            self.value.lexeme,
+            format_resolved(self.resolved)
+        )
     }
 }
 
@@ -189,7 +317,7 @@ impl VariableExpression<'_> {
 pub struct VariableDeclaration<'a> {
     pub identifier: Token<'a>,
     pub initializer: Expression<'a>,
-    pub r#type: Option<Token<'a>>,
+    pub r#type: Option<TypeAnnotation<'a>>,
 }
 
 impl VariableDeclaration<'_> {
@@ -198,10 +326,14 @@ impl VariableDeclaration<'_> {
         let children_left_pad = generate_left_pad(depth + 1);
 
         let initializer_value = self.initializer.format(depth + 1);
+        let r#type = match self.r#type {
+            Some(ref r#type) => format!(": {}", r#type),
+            None => "".to_owned(),
+        };
 
         format!(
-            "{0}VAR_DECL\n{1}{2}\n{3}",
-            left_pad, children_left_pad, self.identifier.lexeme, initializer_value
+            "{0}VAR_DECL\n{1}{2}{3}\n{4}",
+            left_pad, children_left_pad, self.identifier.lexeme, r#type, initializer_value
         )
     }
 }
@@ -211,6 +343,8 @@ impl VariableDeclaration<'_> {
 pub struct AssignmentExpression<'a> {
     pub identifier: Token<'a>,
     pub value: Box<Expression<'a>>,
+    /// Set by the resolver for local variables, globals are looked up by name
+    pub resolved: Option<Resolved>,
 }
 
 impl AssignmentExpression<'_> {
@@ -219,10 +353,11 @@ impl AssignmentExpression<'_> {
         let children_left_pad = generate_left_pad(depth + 1);
 
         format!(
-            "{0}VAR_ASSIGN\n{1}{2}\n{3}",
+            "{0}VAR_ASSIGN\n{1}{2}{3}\n{4}",
             left_pad,
             children_left_pad,
             &self.identifier.lexeme,
+            format_resolved(self.resolved),
             self.value.format(depth + 1)
         )
     }
@@ -231,9 +366,13 @@ impl AssignmentExpression<'_> {
 #[cfg_attr(test, derive(PartialEq))]
 #[derive(Debug, Clone)]
 pub struct IfStatement<'a> {
+    /// The name bound to the unwrapped condition in `if x := optional { ... }`
+    pub binding: Option<Token<'a>>,
     pub condition: Expression<'a>,
     pub statements: Vec<Statement<'a>>,
     pub else_statements: Option<Vec<Statement<'a>>>,
+    /// From the `if` keyword to the last closing brace
+    pub span: Span,
 }
 
 #[cfg_attr(test, derive(PartialEq))]
@@ -241,4 +380,377 @@ pub struct IfStatement<'a> {
 pub struct ForStatement<'a> {
     pub condition: Expression<'a>,
     pub statements: Vec<Statement<'a>>,
+    /// From the `for` keyword to the closing brace
+    pub span: Span,
+}
+
+#[cfg_attr(test, derive(PartialEq))]
+#[derive(Debug, Clone)]
+pub struct BlockStatement<'a> {
+    pub statements: Vec<Statement<'a>>,
+    /// Includes the braces
+    pub span: Span,
+}
+
+#[cfg_attr(test, derive(PartialEq))]
+#[derive(Debug, Clone)]
+pub struct ReturnStatement<'a> {
+    pub keyword: Token<'a>,
+    pub value: Option<Expression<'a>>,
+}
+
+#[cfg_attr(test, derive(PartialEq))]
+#[derive(Debug, Clone)]
+pub struct Parameter<'a> {
+    pub identifier: Token<'a>,
+    pub r#type: Option<TypeAnnotation<'a>>,
+}
+
+#[cfg_attr(test, derive(PartialEq))]
+#[derive(Debug, Clone)]
+pub struct TypeAnnotation<'a> {
+    /// The name of the type, or the `fn` keyword of a function type
+    pub name: Token<'a>,
+    /// The parameter and return types of a function type, e.g. `fn(Int): Int`
+    pub function: Option<FunctionAnnotation<'a>>,
+    /// Whether the name was followed by `?`, e.g. `Int?`
+    pub optional: bool,
+}
+
+#[cfg_attr(test, derive(PartialEq))]
+#[derive(Debug, Clone)]
+pub struct FunctionAnnotation<'a> {
+    pub parameters: Vec<TypeAnnotation<'a>>,
+    /// Left out like in a function expression, in which case calls return `Unknown`
+    pub return_type: Option<Box<TypeAnnotation<'a>>>,
+}
+
+impl Display for TypeAnnotation<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(ref function) = self.function {
+            let parameters: Vec<String> = function
+                .parameters
+                .iter()
+                .map(|parameter| parameter.to_string())
+                .collect();
+
+            return match function.return_type {
+                Some(ref return_type) => {
+                    write!(f, "fn({}): {}", parameters.join(", "), return_type)
+                }
+                None => write!(f, "fn({})", parameters.join(", ")),
+            };
+        }
+
+        match self.optional {
+            true => write!(f, "{}?", self.name.lexeme),
+            false => write!(f, "{}", self.name.lexeme),
+        }
+    }
+}
+
+#[cfg_attr(test, derive(PartialEq))]
+#[derive(Debug, Clone)]
+pub struct FunctionExpression<'a> {
+    pub keyword: Token<'a>,
+    pub parameters: Vec<Parameter<'a>>,
+    pub return_type: Option<TypeAnnotation<'a>>,
+    /// Shared so that every closure created from this expression can hold on to its body cheaply
+    pub body: Rc<Vec<Statement<'a>>>,
+    /// From the `fn` keyword to the closing brace of the body
+    pub span: Span,
+}
+
+impl FunctionExpression<'_> {
+    pub fn format(&self, depth: usize) -> String {
+        let left_pad = generate_left_pad(depth);
+        let parameters: Vec<String> = self
+            .parameters
+            .iter()
+            .map(|parameter| match parameter.r#type {
+                Some(ref r#type) => format!("{}: {}", parameter.identifier.lexeme, r#type),
+                None => parameter.identifier.lexeme.to_owned(),
+            })
+            .collect();
+        let return_type = match self.return_type {
+            Some(ref r#type) => format!(": {}", r#type),
+            None => "".to_owned(),
+        };
+
+        format!(
+            "{0}FN({1}){2}\n{3}",
+            left_pad,
+            parameters.join(", "),
+            return_type,
+            Statement::format_block(&self.body, depth + 1)
+        )
+    }
+}
+
+#[cfg_attr(test, derive(PartialEq))]
+#[derive(Debug, Clone)]
+pub struct CallExpression<'a> {
+    pub callee: Box<Expression<'a>>,
+    pub paren: Token<'a>,
+    pub arguments: Vec<Expression<'a>>,
+    /// From the start of the callee to the closing parenthesis
+    pub span: Span,
+}
+
+impl CallExpression<'_> {
+    pub fn format(&self, depth: usize) -> String {
+        let left_pad = generate_left_pad(depth);
+        let children_left_pad = generate_left_pad(depth + 1);
+        let arguments: Vec<String> = self
+            .arguments
+            .iter()
+            .map(|argument| argument.format(depth + 2))
+            .collect();
+
+        let arguments = if arguments.is_empty() {
+            format!("{}ARGS", children_left_pad)
+        } else {
+            format!("{}ARGS\n{}", children_left_pad, arguments.join("\n"))
+        };
+
+        format!(
+            "{0}CALL\n{1}\n{2}",
+            left_pad,
+            self.callee.format(depth + 1),
+            arguments
+        )
+    }
+}
+
+#[cfg_attr(test, derive(PartialEq))]
+#[derive(Debug, Clone)]
+pub struct ArrayExpression<'a> {
+    pub bracket: Token<'a>,
+    pub elements: Vec<Expression<'a>>,
+    /// Includes the brackets
+    pub span: Span,
+}
+
+impl ArrayExpression<'_> {
+    pub fn format(&self, depth: usize) -> String {
+        let left_pad = generate_left_pad(depth);
+        let elements: Vec<String> = self
+            .elements
+            .iter()
+            .map(|element| element.format(depth + 1))
+            .collect();
+
+        if elements.is_empty() {
+            return format!("{}ARRAY", left_pad);
+        }
+
+        format!("{}ARRAY\n{}", left_pad, elements.join("\n"))
+    }
+}
+
+#[cfg_attr(test, derive(PartialEq))]
+#[derive(Debug, Clone)]
+pub struct IndexExpression<'a> {
+    pub target: Box<Expression<'a>>,
+    pub bracket: Token<'a>,
+    pub index: Box<Expression<'a>>,
+    /// From the start of the target to the closing bracket
+    pub span: Span,
+}
+
+impl IndexExpression<'_> {
+    pub fn format(&self, depth: usize) -> String {
+        let left_pad = generate_left_pad(depth);
+
+        format!(
+            "{0}INDEX\n{1}\n{2}",
+            left_pad,
+            self.target.format(depth + 1),
+            self.index.format(depth + 1)
+        )
+    }
+}
+
+#[cfg_attr(test, derive(PartialEq))]
+#[derive(Debug, Clone)]
+pub struct IndexAssignmentExpression<'a> {
+    pub target: Box<Expression<'a>>,
+    pub bracket: Token<'a>,
+    pub index: Box<Expression<'a>>,
+    pub value: Box<Expression<'a>>,
+}
+
+impl IndexAssignmentExpression<'_> {
+    pub fn format(&self, depth: usize) -> String {
+        let left_pad = generate_left_pad(depth);
+
+        format!(
+            "{0}INDEX_ASSIGN\n{1}\n{2}\n{3}",
+            left_pad,
+            self.target.format(depth + 1),
+            self.index.format(depth + 1),
+            self.value.format(depth + 1)
+        )
+    }
+}
+
+#[cfg_attr(test, derive(PartialEq))]
+#[derive(Debug, Clone)]
+pub struct NoneExpression<'a> {
+    pub keyword: Token<'a>,
+}
+
+impl NoneExpression<'_> {
+    pub fn format(&self, depth: usize) -> String {
+        let left_pad = generate_left_pad(depth);
+
+        format!("{}NONE", left_pad)
+    }
+}
+
+#[cfg_attr(test, derive(PartialEq))]
+#[derive(Debug, Clone)]
+pub struct UnwrapExpression<'a> {
+    pub target: Box<Expression<'a>>,
+    pub operator: Token<'a>,
+}
+
+impl UnwrapExpression<'_> {
+    pub fn format(&self, depth: usize) -> String {
+        let left_pad = generate_left_pad(depth);
+
+        format!("{}UNWRAP\n{}", left_pad, self.target.format(depth + 1))
+    }
+}
+
+#[cfg_attr(test, derive(PartialEq))]
+#[derive(Debug, Clone)]
+pub struct RecordDeclaration<'a> {
+    pub name: Token<'a>,
+    pub fields: Vec<RecordField<'a>>,
+    /// From the `record` keyword to the closing brace
+    pub span: Span,
+}
+
+#[cfg_attr(test, derive(PartialEq))]
+#[derive(Debug, Clone)]
+pub struct RecordField<'a> {
+    pub identifier: Token<'a>,
+    pub r#type: TypeAnnotation<'a>,
+}
+
+impl RecordDeclaration<'_> {
+    pub fn format(&self, depth: usize) -> String {
+        let left_pad = generate_left_pad(depth);
+        let children_left_pad = generate_left_pad(depth + 1);
+        let fields: Vec<String> = self
+            .fields
+            .iter()
+            .map(|field| {
+                format!(
+                    "{}{}: {}",
+                    children_left_pad, field.identifier.lexeme, field.r#type
+                )
+            })
+            .collect();
+
+        if fields.is_empty() {
+            return format!("{}RECORD {}", left_pad, self.name.lexeme);
+        }
+
+        format!(
+            "{}RECORD {}\n{}",
+            left_pad,
+            self.name.lexeme,
+            fields.join("\n")
+        )
+    }
+}
+
+#[cfg_attr(test, derive(PartialEq))]
+#[derive(Debug, Clone)]
+pub struct RecordExpression<'a> {
+    pub name: Token<'a>,
+    pub fields: Vec<FieldInitializer<'a>>,
+    /// From the name to the closing brace
+    pub span: Span,
+}
+
+#[cfg_attr(test, derive(PartialEq))]
+#[derive(Debug, Clone)]
+pub struct FieldInitializer<'a> {
+    pub identifier: Token<'a>,
+    pub value: Expression<'a>,
+}
+
+impl RecordExpression<'_> {
+    pub fn format(&self, depth: usize) -> String {
+        let left_pad = generate_left_pad(depth);
+        let children_left_pad = generate_left_pad(depth + 1);
+        let fields: Vec<String> = self
+            .fields
+            .iter()
+            .map(|field| {
+                format!(
+                    "{}{}\n{}",
+                    children_left_pad,
+                    field.identifier.lexeme,
+                    field.value.format(depth + 2)
+                )
+            })
+            .collect();
+
+        if fields.is_empty() {
+            return format!("{}RECORD_INIT {}", left_pad, self.name.lexeme);
+        }
+
+        format!(
+            "{}RECORD_INIT {}\n{}",
+            left_pad,
+            self.name.lexeme,
+            fields.join("\n")
+        )
+    }
+}
+
+#[cfg_attr(test, derive(PartialEq))]
+#[derive(Debug, Clone)]
+pub struct FieldExpression<'a> {
+    pub target: Box<Expression<'a>>,
+    pub name: Token<'a>,
+}
+
+impl FieldExpression<'_> {
+    pub fn format(&self, depth: usize) -> String {
+        let left_pad = generate_left_pad(depth);
+
+        format!(
+            "{}FIELD {}\n{}",
+            left_pad,
+            self.name.lexeme,
+            self.target.format(depth + 1)
+        )
+    }
+}
+
+#[cfg_attr(test, derive(PartialEq))]
+#[derive(Debug, Clone)]
+pub struct FieldAssignmentExpression<'a> {
+    pub target: Box<Expression<'a>>,
+    pub name: Token<'a>,
+    pub value: Box<Expression<'a>>,
+}
+
+impl FieldAssignmentExpression<'_> {
+    pub fn format(&self, depth: usize) -> String {
+        let left_pad = generate_left_pad(depth);
+
+        format!(
+            "{}FIELD_ASSIGN {}\n{}\n{}",
+            left_pad,
+            self.name.lexeme,
+            self.target.format(depth + 1),
+            self.value.format(depth + 1)
+        )
+    }
 }
@@ -0,0 +1,237 @@
+use std::{mem, rc::Rc};
+
+use crate::{
+    statement::generate_left_pad,
+    token::{Span, Token, TokenType},
+};
+
+/// What a node of the concrete syntax tree is. Each one lowers into one kind of `Statement` or
+/// `Expression`, except for the nodes that group the parts of another one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SyntaxKind {
+    /// The whole source, with the trivia and `Eof` at its end
+    Root,
+    /// Code that couldn't be parsed, with the tokens skipped to recover from it
+    Error,
+
+    // Statements
+    ExpressionStatement,
+    VariableDeclaration,
+    /// `{ ... }`, as a statement or as the body of an `if`, `for` or function
+    Block,
+    If,
+    For,
+    Return,
+    RecordDeclaration,
+    /// `name: Type` in a record declaration
+    RecordField,
+    /// `Type` or `Type?`
+    TypeAnnotation,
+
+    // Expressions
+    /// Any operator between two operands, including `&&`, `||` and `??`
+    Binary,
+    Unary,
+    Literal,
+    Grouping,
+    Variable,
+    /// `target = value`, where the target is a variable, an element or a field
+    Assignment,
+    Function,
+    /// `(a: Int, b)` in a function
+    ParameterList,
+    Parameter,
+    Call,
+    /// `(a, b)` in a call
+    ArgumentList,
+    Array,
+    Index,
+    None,
+    Unwrap,
+    RecordLiteral,
+    /// `name: value` in a record literal
+    FieldInitializer,
+    Field,
+}
+
+/// A node of the concrete syntax tree. Nodes never change once built and are shared with `Rc`,
+/// so that tools can build an edited tree that reuses the parts they didn't touch
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxNode<'a> {
+    pub kind: SyntaxKind,
+    pub children: Vec<SyntaxElement<'a>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyntaxElement<'a> {
+    Node(Rc<SyntaxNode<'a>>),
+    Token(Token<'a>),
+}
+
+impl<'a> SyntaxNode<'a> {
+    /// The child nodes, without the tokens between them
+    pub fn nodes(&self) -> impl Iterator<Item = &SyntaxNode<'a>> {
+        self.children.iter().filter_map(|child| match child {
+            SyntaxElement::Node(node) => Some(node.as_ref()),
+            SyntaxElement::Token(_) => None,
+        })
+    }
+
+    /// The tokens that are direct children of the node, without trivia
+    pub fn tokens(&self) -> impl Iterator<Item = &Token<'a>> {
+        self.children.iter().filter_map(|child| match child {
+            SyntaxElement::Token(token) if !token.token_type.is_trivia() => Some(token),
+            _ => None,
+        })
+    }
+
+    /// The first direct token of the given type
+    pub fn token(&self, token_type: TokenType) -> Option<&Token<'a>> {
+        self.tokens().find(|token| token.token_type == token_type)
+    }
+
+    /// Every token under the node in source order, trivia included
+    pub fn descendant_tokens(&self) -> Vec<&Token<'a>> {
+        let mut tokens = Vec::new();
+
+        for child in &self.children {
+            match child {
+                SyntaxElement::Node(node) => tokens.extend(node.descendant_tokens()),
+                SyntaxElement::Token(token) => tokens.push(token),
+            }
+        }
+
+        tokens
+    }
+
+    /// The range of source under the node. Nodes other than the root and errors start and end
+    /// with tokens that aren't trivia, so this is also the range of the code they were parsed from
+    pub fn span(&self) -> Span {
+        let tokens = self.descendant_tokens();
+
+        match (tokens.first(), tokens.last()) {
+            (Some(first), Some(last)) => first.span.to(last.span),
+            _ => Span::default(),
+        }
+    }
+
+    /// One line per node and token, indented by depth, with their byte ranges
+    pub fn format(&self, depth: usize) -> String {
+        let mut lines = vec![format!(
+            "{}{:?}@{}..{}",
+            generate_left_pad(depth),
+            self.kind,
+            self.span().start,
+            self.span().end
+        )];
+
+        for child in &self.children {
+            match child {
+                SyntaxElement::Node(node) => lines.push(node.format(depth + 1)),
+                SyntaxElement::Token(token) => lines.push(format!(
+                    "{}{:?} {:?}@{}..{}",
+                    generate_left_pad(depth + 1),
+                    token.token_type,
+                    token.lexeme,
+                    token.span.start,
+                    token.span.end
+                )),
+            }
+        }
+
+        lines.join("\n")
+    }
+}
+
+/// Builds a syntax tree from the tokens and node boundaries that a parser reports in order
+pub struct TreeBuilder<'a> {
+    /// The kind and the children of each node that hasn't been finished yet, outermost first
+    stack: Vec<(SyntaxKind, Vec<SyntaxElement<'a>>)>,
+}
+
+/// Where a node may be started later, to wrap what was built since, like a binary expression
+/// wraps its left operand once the operator is found
+#[derive(Debug, Clone, Copy)]
+pub struct Checkpoint(usize);
+
+impl<'a> TreeBuilder<'a> {
+    pub fn new() -> TreeBuilder<'a> {
+        TreeBuilder {
+            stack: vec![(SyntaxKind::Root, Vec::new())],
+        }
+    }
+
+    pub fn token(&mut self, token: Token<'a>) {
+        self.children().push(SyntaxElement::Token(token));
+    }
+
+    pub fn start_node(&mut self, kind: SyntaxKind) {
+        self.stack.push((kind, Vec::new()));
+    }
+
+    pub fn checkpoint(&mut self) -> Checkpoint {
+        Checkpoint(self.children().len())
+    }
+
+    /// Starts a node that contains everything added since `checkpoint`
+    pub fn start_node_at(&mut self, checkpoint: Checkpoint, kind: SyntaxKind) {
+        let children = self.children().split_off(checkpoint.0);
+
+        self.stack.push((kind, children));
+    }
+
+    pub fn finish_node(&mut self) {
+        let (kind, children) = self.stack.pop().expect("A node must be started");
+
+        self.children()
+            .push(SyntaxElement::Node(Rc::new(SyntaxNode { kind, children })));
+    }
+
+    /// Finishes every node but the root, as they are, after a parser gave up on them
+    pub fn finish_nodes(&mut self) {
+        while self.stack.len() > 1 {
+            self.finish_node();
+        }
+    }
+
+    pub fn finish(mut self) -> SyntaxNode<'a> {
+        self.finish_nodes();
+
+        let (kind, children) = self.stack.pop().expect("The root is never finished early");
+
+        SyntaxNode { kind, children }
+    }
+
+    fn children(&mut self) -> &mut Vec<SyntaxElement<'a>> {
+        &mut self
+            .stack
+            .last_mut()
+            .expect("The root is never finished early")
+            .1
+    }
+}
+
+impl Default for TreeBuilder<'_> {
+    fn default() -> Self {
+        TreeBuilder::new()
+    }
+}
+
+/// Takes the trivia out of `tokens`, returning it grouped by the token it comes before. Trivia
+/// after the last token is dropped, which can't happen with the `Eof` of the scanner
+pub fn split_trivia<'a>(tokens: Vec<Token<'a>>) -> (Vec<Token<'a>>, Vec<Vec<Token<'a>>>) {
+    let mut code = Vec::new();
+    let mut trivia = Vec::new();
+    let mut pending = Vec::new();
+
+    for token in tokens {
+        if token.token_type.is_trivia() {
+            pending.push(token);
+        } else {
+            code.push(token);
+            trivia.push(mem::take(&mut pending));
+        }
+    }
+
+    (code, trivia)
+}
@@ -0,0 +1,120 @@
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use crate::{
+        assembler::*, compiler::Compiler, disassembler::disassemble, environment::Environment,
+        parser::*, scanner::*, source::*, vm::Vm,
+    };
+
+    fn listing(program: &str) -> String {
+        let tokens = Scanner {
+            source: Source::new(program),
+        }
+        .scan()
+        .unwrap();
+        let statements = Parser::new(tokens).parse().unwrap();
+
+        disassemble(&Compiler::compile(&statements).unwrap())
+    }
+
+    /// Assembles `listing` and runs it, returning the printed result
+    fn run(listing: &str) -> String {
+        let program = assemble(listing).unwrap();
+
+        match Vm::interpret(Rc::new(RefCell::new(Environment::new())), &program) {
+            Ok(value) => value.to_string(),
+            Err(error) => format!(
+                "{} ({}:{})",
+                error.message, error.token.line, error.token.position
+            ),
+        }
+    }
+
+    fn error(listing: &str) -> String {
+        assemble(listing).unwrap_err().to_string()
+    }
+
+    mod round_trip {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        const PROGRAM: &str = "record Point { x: Int, y: Int? }
+            record Empty {}
+            add := fn(a: Int, b: Int): Int { a + b };
+            make := fn() { n := 0; fn(): Int { n = n + 1; n } };
+            counter := make();
+            text := \"tab\\tquote\\\" and\nnewline\";
+            p := Point { x: add(1, 2), y: none };
+            xs := [1.0, 2.5, -3.0];
+            if y := p.y { y; } else { counter(); [p.x, counter(), xs[0] * 2.0, text]; }";
+
+        #[test]
+        fn it_reassembles_disassembled_programs() {
+            let original = listing(PROGRAM);
+            let program = assemble(&original).unwrap();
+
+            assert_eq!(disassemble(&program), original);
+        }
+
+        #[test]
+        fn it_runs_reassembled_programs() {
+            assert_eq!(
+                run(&listing(PROGRAM)),
+                "[3, 2, 2, tab\tquote\" and\nnewline]"
+            );
+            assert_eq!(
+                run(&listing("xs := [1, 2];\nxs[2];")),
+                "Index 2 out of bounds for array of length 2 (2:3)"
+            );
+        }
+    }
+
+    mod errors {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_reports_malformed_listings() {
+            assert_eq!(
+                error("function 0 arity 0 type fn(): Unknown\ncode\n"),
+                "Assembler error at line 1. Expected 'globals'"
+            );
+            assert_eq!(
+                error("globals\n\nfunction 0 arity 0 type fn(): Unknown\ncode\n0000 | Jump\n"),
+                "Assembler error at line 5. Expected jump target"
+            );
+            assert_eq!(
+                error("globals\nfunction 0 arity 0 type fn(): Unknown\ncode\n0001 | Return\n"),
+                "Assembler error at line 4. Expected offset 0000"
+            );
+            assert_eq!(
+                error("globals\nfunction 0 arity 0 type fn(): Unknown\ncode\n0000 | Halt\n"),
+                "Assembler error at line 4. Unknown opcode 'Halt'"
+            );
+            assert_eq!(
+                error("globals\nfunction 0 arity 0 type fn(): Nothing\ncode\n"),
+                "Assembler error at line 2. Invalid function type"
+            );
+            assert_eq!(
+                error("globals\nfunction 0 arity 0 type fn(): Unknown\nconstant 0 literal \"open\ncode\n"),
+                "Assembler error at line 3. Unterminated string"
+            );
+        }
+
+        #[test]
+        fn it_requires_nested_functions_to_be_listed_later() {
+            assert_eq!(
+                error(
+                    "globals
+                    function 0 arity 0 type fn(): Unknown
+                    constant 0 function 0
+                    code
+                    0000 | Closure 0
+                    0001 | Return"
+                ),
+                "Assembler error at line 3. Function 0 must be listed after function 0"
+            );
+        }
+    }
+}
@@ -0,0 +1,182 @@
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use crate::{
+        diagnostic::*, environment::Environment, interpreter::*, parser::*, resolver::*,
+        scanner::*, source::*, type_checker::*,
+    };
+
+    fn diagnostics(source: &str) -> Vec<String> {
+        let (tokens, scanner_errors) = Scanner {
+            source: Source::new(source),
+        }
+        .scan_recovering();
+        let parser_errors = Parser::new(tokens).parse().err().unwrap_or_default();
+
+        merge(&scanner_errors, &parser_errors)
+            .iter()
+            .map(|diagnostic| diagnostic.to_string())
+            .collect()
+    }
+
+    fn render(source: &str, color: bool) -> String {
+        let renderer = Renderer {
+            file_name: "main.mt",
+            source,
+            color,
+        };
+        let (tokens, scanner_errors) = Scanner {
+            source: Source::new(source),
+        }
+        .scan_recovering();
+
+        let mut statements = match Parser::new(tokens).parse() {
+            Ok(statements) if scanner_errors.is_empty() => statements,
+            result => {
+                let parser_errors = result.err().unwrap_or_default();
+
+                return renderer.render(&merge(&scanner_errors, &parser_errors)[0]);
+            }
+        };
+
+        if let Some(error) = Resolver::new().resolve(&mut statements).first() {
+            return renderer.render(&error.into());
+        }
+
+        let environment = Rc::new(RefCell::new(Environment::new()));
+
+        if let Err(errors) = TypeChecker::with_environment(&environment.borrow()).infer(&statements)
+        {
+            return renderer.render(&(&errors[0]).into());
+        }
+
+        let error = Interpreter::interpret(Rc::clone(&environment), &statements).unwrap_err();
+
+        renderer.render(&(&error).into())
+    }
+
+    mod merge {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_reports_scanner_and_parser_errors_in_source_order() {
+            assert_eq!(
+                diagnostics(
+                    "a := 1 @ 2;
+b := (3;
+c := 4.;
+d := fn( {};
+e := 5 # 6;
+"
+                ),
+                vec![
+                    "Scanner error at 1:8. Unknown token",
+                    "Parser error at 2:8. Expected ')' after expression. Got: ;",
+                    "Scanner error at 3:7. Invalid number",
+                    "Parser error at 4:10. Expected parameter name",
+                    "Scanner error at 5:8. Unknown token",
+                ]
+            );
+        }
+
+        #[test]
+        fn it_keeps_parser_errors_when_the_scanner_succeeds() {
+            assert_eq!(
+                diagnostics("x := ;\n"),
+                vec!["Parser error at 1:6. Unexpected token ';'"]
+            );
+            assert_eq!(
+                diagnostics("x := 1 +"),
+                vec!["Parser error at 1:9. Unexpected end of input"]
+            );
+        }
+    }
+
+    mod render {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_underlines_the_offending_lexeme() {
+            assert_eq!(
+                render("x := 1;\ny := x + undefined;\n", false),
+                "Resolver error: Variable 'undefined' not found in the current scope
+ --> main.mt:2:10
+  |
+2 | y := x + undefined;
+  |          ^^^^^^^^^"
+            );
+        }
+
+        #[test]
+        fn it_reports_errors_from_every_stage() {
+            assert!(render("a := 1 @ 2;", false).starts_with("Scanner error: Unknown token\n"));
+            assert!(render("a := (1;", false).starts_with("Parser error: Expected ')'"));
+            assert!(render("1 / 0;", false).starts_with("Runtime error: Division by zero\n"));
+            assert!(render("a := b; b := 1;", false).starts_with("Resolver error: Variable 'b'"));
+            assert!(render("x := 1;\n1 + true;", false).starts_with("Type error: Operator '+'"));
+        }
+
+        #[test]
+        fn it_prints_notes_and_help() {
+            assert_eq!(
+                render("xs := [1, 2];\nxs[5];", false),
+                "Runtime error: Index 5 out of bounds for array of length 2
+ --> main.mt:2:3
+  |
+2 | xs[5];
+  |   ^
+  = note: valid indices are 0 to 1"
+            );
+            assert_eq!(
+                render("1 = 2;", false),
+                "Parser error: Invalid assignment target
+ --> main.mt:1:3
+  |
+1 | 1 = 2;
+  |   ^
+  = help: only variables, array elements and fields can be assigned to"
+            );
+        }
+
+        #[test]
+        fn it_keeps_tabs_and_widens_the_gutter() {
+            let source = format!("{}\t\tx!;", "\n".repeat(9));
+
+            assert_eq!(
+                render(&format!("x: Int? = none;{}", source), false)
+                    .lines()
+                    .skip(1)
+                    .take(4)
+                    .collect::<Vec<_>>(),
+                vec!["  --> main.mt:10:4", "   |", "10 | \t\tx!;", "   | \t\t ^",]
+            );
+        }
+
+        #[test]
+        fn it_renders_warnings() {
+            assert_eq!(
+                render("{\n  x := 1;\n}", true),
+                "\x1b[1;33mResolver warning\x1b[0m: \x1b[1mVariable 'x' is never read\x1b[0m
+ \x1b[1;34m-->\x1b[0m main.mt:2:3
+  \x1b[1;34m|\x1b[0m
+\x1b[1;34m2\x1b[0m \x1b[1;34m|\x1b[0m   x := 1;
+  \x1b[1;34m|\x1b[0m   \x1b[1;33m^\x1b[0m"
+            );
+        }
+
+        #[test]
+        fn it_colors_the_output() {
+            assert_eq!(
+                render("1 / 0;", true),
+                "\x1b[1;31mRuntime error\x1b[0m: \x1b[1mDivision by zero\x1b[0m
+ \x1b[1;34m-->\x1b[0m main.mt:1:3
+  \x1b[1;34m|\x1b[0m
+\x1b[1;34m1\x1b[0m \x1b[1;34m|\x1b[0m 1 / 0;
+  \x1b[1;34m|\x1b[0m   \x1b[1;31m^\x1b[0m"
+            );
+        }
+    }
+}
@@ -0,0 +1,158 @@
+#[cfg(test)]
+mod tests {
+    use crate::formatter::*;
+
+    fn format_ok(source: &str) -> String {
+        let formatted = format(source).unwrap();
+
+        // Formatting is stable, so formatted code never changes again
+        assert_eq!(format(&formatted).unwrap(), formatted);
+
+        formatted
+    }
+
+    mod layout {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_normalizes_spacing() {
+            assert_eq!(
+                format_ok("x:=1+2*  (3-1);y : Int?= none;p:=Point{x:1,y:-x};p.x=xs[0]!??2;"),
+                "x := 1 + 2 * (3 - 1);
+y: Int? = none;
+p := Point { x: 1, y: -x };
+p.x = xs[0]! ?? 2;
+"
+            );
+        }
+
+        #[test]
+        fn it_indents_blocks() {
+            assert_eq!(
+                format_ok(
+                    "record P{a:Int,b :String}
+f := fn(n:Int):Int{
+if n<2{return n;}else{
+  for n>0{n=n-1;}
+}
+{ }
+n
+};"
+                ),
+                "record P { a: Int, b: String }
+f := fn(n: Int): Int {
+    if n < 2 {
+        return n;
+    } else {
+        for n > 0 {
+            n = n - 1;
+        }
+    }
+    {}
+    n
+};
+"
+            );
+        }
+
+        #[test]
+        fn it_keeps_short_function_values_on_one_line() {
+            assert_eq!(
+                format_ok("map(xs, fn(a:Int):Int{a*2});g := fn() {\n  1;\n};"),
+                "map(xs, fn(a: Int): Int { a * 2 });
+g := fn() {
+    1;
+};
+"
+            );
+        }
+
+        #[test]
+        fn it_writes_function_types() {
+            assert_eq!(
+                format_ok("f : fn( Int,fn():Bool ):Int? = g;make := fn():fn(){ fn() {} };"),
+                "f: fn(Int, fn(): Bool): Int? = g;
+make := fn(): fn() { fn() {} };
+"
+            );
+        }
+
+        #[test]
+        fn it_keeps_single_blank_lines() {
+            assert_eq!(
+                format_ok("a := 1;\n\n\n\nb := 2;\nc := 3;\n\n"),
+                "a := 1;\n\nb := 2;\nc := 3;\n"
+            );
+        }
+    }
+
+    mod comments {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_keeps_comments_in_place() {
+            assert_eq!(
+                format_ok(
+                    "// header
+
+x := 1;   // trailing
+if x > 0 {
+// leading
+  x = 2;
+       // closing
+}
+// footer"
+                ),
+                "// header
+
+x := 1; // trailing
+if x > 0 {
+    // leading
+    x = 2;
+    // closing
+}
+// footer
+"
+            );
+        }
+
+        #[test]
+        fn it_moves_comments_inside_expressions_above_the_statement() {
+            assert_eq!(
+                format_ok("xs := [\n  1, // one\n  2\n];"),
+                "// one\nxs := [1, 2];\n"
+            );
+        }
+
+        #[test]
+        fn it_keeps_blocks_with_only_comments_open() {
+            assert_eq!(
+                format_ok("if true {\n// nothing yet\n}"),
+                "if true {\n    // nothing yet\n}\n"
+            );
+        }
+    }
+
+    mod errors {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_refuses_invalid_code() {
+            let diagnostics = format("x := 1\ny := @;").unwrap_err();
+
+            assert_eq!(
+                diagnostics
+                    .iter()
+                    .map(|diagnostic| diagnostic.to_string())
+                    .collect::<Vec<_>>(),
+                vec![
+                    "Parser error at 2:1. Expected ';'",
+                    "Scanner error at 2:6. Unknown token",
+                ]
+            );
+        }
+    }
+}
@@ -0,0 +1,128 @@
+#[cfg(test)]
+mod tests {
+    use crate::highlight::*;
+
+    /// The kind and modifiers of every token but whitespace, by text
+    fn classify(source: &str) -> Vec<(&str, &'static str, Vec<&'static str>)> {
+        highlight(source)
+            .iter()
+            .filter(|highlight| highlight.kind != Kind::Whitespace)
+            .map(|highlight| {
+                (
+                    highlight.token.lexeme,
+                    highlight.kind.name(),
+                    highlight
+                        .modifiers
+                        .iter()
+                        .map(|modifier| modifier.name())
+                        .collect(),
+                )
+            })
+            .collect()
+    }
+
+    mod kinds {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_classifies_names_by_what_they_declare() {
+            assert_eq!(
+                classify("record P { x: Int }\nf := fn(a: P): Int { a.x };\nf(P { x: 1 });"),
+                vec![
+                    ("record", "keyword", vec![]),
+                    ("P", "type", vec!["declaration"]),
+                    ("{", "punctuation", vec![]),
+                    ("x", "property", vec!["declaration"]),
+                    (":", "punctuation", vec![]),
+                    ("Int", "type", vec![]),
+                    ("}", "punctuation", vec![]),
+                    ("f", "function", vec!["declaration"]),
+                    (":=", "operator", vec![]),
+                    ("fn", "keyword", vec![]),
+                    ("(", "punctuation", vec![]),
+                    ("a", "parameter", vec!["declaration"]),
+                    (":", "punctuation", vec![]),
+                    ("P", "type", vec!["reference"]),
+                    (")", "punctuation", vec![]),
+                    (":", "punctuation", vec![]),
+                    ("Int", "type", vec![]),
+                    ("{", "punctuation", vec![]),
+                    ("a", "parameter", vec!["reference"]),
+                    (".", "punctuation", vec![]),
+                    ("x", "property", vec![]),
+                    ("}", "punctuation", vec![]),
+                    (";", "punctuation", vec![]),
+                    ("f", "function", vec!["reference"]),
+                    ("(", "punctuation", vec![]),
+                    ("P", "type", vec!["reference"]),
+                    ("{", "punctuation", vec![]),
+                    ("x", "property", vec![]),
+                    (":", "punctuation", vec![]),
+                    ("1", "number", vec![]),
+                    ("}", "punctuation", vec![]),
+                    (")", "punctuation", vec![]),
+                    (";", "punctuation", vec![]),
+                ]
+            );
+        }
+
+        #[test]
+        fn it_falls_back_to_lexical_kinds_when_the_code_is_broken() {
+            assert_eq!(
+                classify("x := \"a\" @ // note"),
+                vec![
+                    ("x", "variable", vec![]),
+                    (":=", "operator", vec![]),
+                    ("\"a\"", "string", vec![]),
+                    ("@", "error", vec![]),
+                    ("// note", "comment", vec![]),
+                ]
+            );
+        }
+    }
+
+    mod trivia {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_covers_the_whole_source() {
+            let source = "x := 1; // one\n\n\tif x > 0 {\r\n  x = 2.5 @;\n}\n";
+            let highlights = highlight(source);
+
+            let text: String = highlights
+                .iter()
+                .map(|highlight| highlight.token.lexeme)
+                .collect();
+            assert_eq!(text, source);
+
+            // Each token starts where the last one ended
+            let mut end = 0;
+            for highlight in &highlights {
+                assert_eq!(highlight.token.span.start, end);
+                end = highlight.token.span.end;
+            }
+            assert_eq!(end, source.len());
+        }
+    }
+
+    mod json {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_describes_tokens_as_json() {
+            let highlights = highlight("n := 1;");
+
+            assert_eq!(
+                highlights[0].to_json().to_string(),
+                r#"{"kind":"variable","token":"Identifier","text":"n","line":1,"position":1,"span":{"start":0,"end":1},"modifiers":["declaration"]}"#
+            );
+            assert_eq!(
+                highlights[1].to_json().to_string(),
+                r#"{"kind":"whitespace","token":"Whitespace","text":" ","line":1,"position":2,"span":{"start":1,"end":2},"modifiers":[]}"#
+            );
+        }
+    }
+}
@@ -0,0 +1,498 @@
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc, thread};
+
+    use crate::{
+        compiler::Compiler,
+        environment::Environment,
+        interpreter::*,
+        matcha::{Literal, NumberLiteral, Value},
+        parser::*,
+        scanner::*,
+        source::*,
+        vm::Vm,
+    };
+
+    /// Runs `program` with the tree-walker, checking that the VM produces the same result
+    fn interpret(program: &str) -> Result<Value<'_>, InterpreterError<'_>> {
+        let tokens = Scanner {
+            source: Source::new(program),
+        }
+        .scan()
+        .unwrap();
+        let statements = Parser::new(tokens).parse().unwrap();
+
+        let result = Interpreter::interpret(Rc::new(RefCell::new(Environment::new())), &statements);
+        let vm_result = Compiler::compile(&statements).and_then(|compiled| {
+            Vm::interpret(Rc::new(RefCell::new(Environment::new())), &compiled)
+        });
+
+        assert_eq!(
+            outcome(&vm_result),
+            outcome(&result),
+            "VM result of {:?}",
+            program
+        );
+
+        result
+    }
+
+    /// What is observable about a result: the printed value, or where and why it failed
+    fn outcome(result: &Result<Value, InterpreterError>) -> String {
+        match result {
+            Ok(value) => value.to_string(),
+            Err(error) => format!(
+                "{} {:?} {:?} ({}:{})",
+                error.message, error.note, error.help, error.token.line, error.token.position
+            ),
+        }
+    }
+
+    fn integer(value: Value) -> i32 {
+        match value {
+            Value::Literal(Literal::Number(NumberLiteral::Integer(integer))) => integer,
+            _ => panic!("Expected integer, got {:?}", value),
+        }
+    }
+
+    mod expressions {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_evaluates_arithmetic() {
+            let result = interpret("1 * 2 + 3 / 4 - 5 * ((6 - 7) / (8 + 9));").unwrap();
+
+            assert_eq!(integer(result), 2);
+        }
+
+        #[test]
+        fn it_evaluates_logical_operators() {
+            let result = interpret("1 < 2 && !(3 == 4) || false;").unwrap();
+
+            assert_eq!(result.to_string(), "true");
+        }
+
+        #[test]
+        fn it_reports_division_by_zero() {
+            let error = interpret("1 / 0;").unwrap_err();
+
+            assert_eq!(error.message, "Division by zero");
+            assert_eq!((error.token.line, error.token.position), (1, 3));
+        }
+
+        #[test]
+        fn it_reports_integer_overflow() {
+            for (program, position) in [
+                ("x := -2147483647 - 1; x / -1;", 25),
+                ("x := -2147483647 - 1; -x;", 23),
+                ("x := 2147483647; y := x + 1;", 25),
+                ("x := -2147483647; x - 2;", 21),
+                ("65536 * 65536;", 7),
+            ] {
+                let error = interpret(program).unwrap_err();
+
+                assert_eq!(error.message, "Integer overflow");
+                assert_eq!((error.token.line, error.token.position), (1, position));
+            }
+
+            assert_eq!(
+                interpret("-2147483647 - 1;").unwrap().to_string(),
+                "-2147483648"
+            );
+        }
+    }
+
+    mod statements {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_runs_a_for_loop() {
+            let result = interpret(
+                "i := 0;
+                total := 0;
+                for i < 10 {
+                    total = total + i;
+                    i = i + 1;
+                }
+                total;",
+            )
+            .unwrap();
+
+            assert_eq!(integer(result), 45);
+        }
+
+        #[test]
+        fn it_returns_the_value_of_the_taken_branch() {
+            let result = interpret("x := 3; if x > 2 { \"big\"; } else { \"small\"; }").unwrap();
+
+            assert_eq!(result.to_string(), "big");
+        }
+
+        #[test]
+        fn it_assigns_outer_variables_from_inner_scopes() {
+            let result = interpret("x := 1; { y := 2; x = y; } x;").unwrap();
+
+            assert_eq!(integer(result), 2);
+        }
+
+        #[test]
+        fn it_scopes_variables_to_blocks() {
+            let error = interpret("{ inner := 1; } inner;").unwrap_err();
+
+            assert_eq!(
+                error.message,
+                "Variable 'inner' not found in the current scope"
+            );
+        }
+
+        #[test]
+        fn it_rejects_redeclarations_in_the_same_scope() {
+            let error = interpret("x := 1; x := 2;").unwrap_err();
+
+            assert_eq!(error.message, "Variable 'x' already declared in this scope");
+        }
+    }
+
+    mod bitwise_operators {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_evaluates_bitwise_operators() {
+            assert_eq!(integer(interpret("12 & 10;").unwrap()), 8);
+            assert_eq!(integer(interpret("12 | 10;").unwrap()), 14);
+            assert_eq!(integer(interpret("12 ^ 10;").unwrap()), 6);
+            assert_eq!(integer(interpret("~12;").unwrap()), -13);
+            assert_eq!(integer(interpret("1 << 4 | 1;").unwrap()), 17);
+            assert_eq!(integer(interpret("-32 >> 2;").unwrap()), -8);
+        }
+
+        #[test]
+        fn it_rejects_non_integer_operands() {
+            let error = interpret("1.5 & 1;").unwrap_err();
+
+            assert_eq!(
+                error.message,
+                "Operator '&' expects Int operands, got Float"
+            );
+            assert_eq!((error.token.line, error.token.position), (1, 5));
+
+            let error = interpret("~\"a\";").unwrap_err();
+
+            assert_eq!(
+                error.message,
+                "Operator '~' expects an Int operand, got String"
+            );
+        }
+
+        #[test]
+        fn it_rejects_out_of_range_shifts() {
+            assert_eq!(integer(interpret("1 << 31;").unwrap()), i32::MIN);
+
+            let error = interpret("1 << 32;").unwrap_err();
+
+            assert_eq!(error.message, "Shift amount 32 is out of range 0..32");
+
+            let error = interpret("1 >> -1;").unwrap_err();
+
+            assert_eq!(error.message, "Shift amount -1 is out of range 0..32");
+        }
+    }
+
+    mod functions {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_calls_functions() {
+            let result = interpret(
+                "add := fn(a: Int, b: Int): Int { a + b };
+                add(1, add(2, 3));",
+            )
+            .unwrap();
+
+            assert_eq!(integer(result), 6);
+        }
+
+        #[test]
+        fn it_returns_early() {
+            let result = interpret(
+                "fact := fn(n: Int): Int {
+                    if n < 2 {
+                        return 1;
+                    }
+                    n * fact(n - 1)
+                };
+                fact(10);",
+            )
+            .unwrap();
+
+            assert_eq!(integer(result), 3628800);
+        }
+
+        #[test]
+        fn it_captures_the_defining_environment() {
+            let result = interpret(
+                "make_counter := fn() {
+                    count := 0;
+                    fn(): Int {
+                        count = count + 1;
+                        count
+                    }
+                };
+                first := make_counter();
+                second := make_counter();
+                first();
+                first();
+                second();
+                first() * 10 + second();",
+            )
+            .unwrap();
+
+            assert_eq!(integer(result), 32);
+        }
+
+        #[test]
+        fn it_passes_functions_with_annotated_types() {
+            let result = interpret(
+                "make := fn(step: Int): fn(Int): Int { fn(a: Int): Int { a + step } };
+                add: fn(Int): Int = make(2);
+                twice := fn(f: fn(Int): Int, a: Int): Int { f(f(a)) };
+                twice(add, 1);",
+            )
+            .unwrap();
+
+            assert_eq!(integer(result), 5);
+        }
+
+        #[test]
+        fn it_checks_arity() {
+            let error = interpret("f := fn(a) { a }; f(1, 2);").unwrap_err();
+
+            assert_eq!(error.message, "Expected 1 arguments but got 2");
+            assert_eq!((error.token.line, error.token.position), (1, 20));
+        }
+
+        #[test]
+        fn it_rejects_calling_non_functions() {
+            let error = interpret("x := 1; x();").unwrap_err();
+
+            assert_eq!(error.message, "Can only call functions, got Int");
+        }
+
+        #[test]
+        fn it_reports_runaway_recursion() {
+            // Nesting that many calls takes more native stack than test threads have
+            let outcomes = thread::Builder::new()
+                .stack_size(STACK_SIZE)
+                .spawn(|| {
+                    [
+                        "s := fn(n: Int): Int { if n < 1 { return 0; } 1 + s(n - 1) }; s(999);",
+                        "f := fn(n: Int): Int { f(n) }; f(1);",
+                    ]
+                    .map(|program| outcome(&interpret(program)))
+                })
+                .unwrap()
+                .join()
+                .unwrap();
+
+            assert_eq!(
+                outcomes,
+                [
+                    "999".to_owned(),
+                    "Stack overflow Some(\"calls can only be nested 1000 deep\") None (1:25)"
+                        .to_owned()
+                ]
+            );
+        }
+    }
+
+    mod arrays {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_reads_and_writes_elements() {
+            let result = interpret(
+                "xs := [1, 2, 3];
+                xs[0] = xs[1] + xs[2];
+                xs;",
+            )
+            .unwrap();
+
+            assert_eq!(result.to_string(), "[5, 2, 3]");
+        }
+
+        #[test]
+        fn it_shares_arrays_between_aliases() {
+            let result = interpret(
+                "grid := [[0, 0], [0, 0]];
+                row := grid[1];
+                row[0] = 7;
+                grid;",
+            )
+            .unwrap();
+
+            assert_eq!(result.to_string(), "[[0, 0], [7, 0]]");
+        }
+
+        #[test]
+        fn it_reports_out_of_bounds_indices() {
+            let error = interpret("xs := [1, 2];\nxs[2];").unwrap_err();
+
+            assert_eq!(error.message, "Index 2 out of bounds for array of length 2");
+            assert_eq!((error.token.line, error.token.position), (2, 3));
+
+            let error = interpret("[1][-1] = 0;").unwrap_err();
+
+            assert_eq!(
+                error.message,
+                "Index -1 out of bounds for array of length 1"
+            );
+        }
+
+        #[test]
+        fn it_reports_non_integer_indices() {
+            let error = interpret("xs := [1];\nxs[true];").unwrap_err();
+
+            assert_eq!(error.message, "Array index must be an Int, got Bool");
+            assert_eq!((error.token.line, error.token.position), (2, 3));
+        }
+    }
+
+    mod optionals {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_wraps_values_stored_in_optionals() {
+            let result = interpret(
+                "x: Int? = 1;
+                x = 2;
+                x;",
+            )
+            .unwrap();
+
+            assert_eq!(result.to_string(), "2");
+            assert!(matches!(result, Value::Optional(Some(_))));
+
+            let result = interpret("f := fn(a: Int?): Int? { a }; [f(1), f(none)];").unwrap();
+
+            assert_eq!(result.to_string(), "[1, none]");
+        }
+
+        #[test]
+        fn it_unwraps_and_coalesces() {
+            assert_eq!(integer(interpret("x: Int? = 4; x! + 1;").unwrap()), 5);
+            assert_eq!(integer(interpret("x: Int? = none; x ?? 7;").unwrap()), 7);
+            assert_eq!(
+                integer(interpret("x: Int? = none; y: Int? = none; x ?? y ?? 3;").unwrap()),
+                3
+            );
+
+            // The fallback is not evaluated when the optional holds a value
+            let result = interpret(
+                "calls := 0;
+                fallback := fn() { calls = calls + 1; 0 };
+                x: Int? = 1;
+                x ?? fallback();
+                calls;",
+            )
+            .unwrap();
+
+            assert_eq!(integer(result), 0);
+        }
+
+        #[test]
+        fn it_reports_unwrapping_none() {
+            let error = interpret("x: Int? = none;\nx! + 1;").unwrap_err();
+
+            assert_eq!(error.message, "Cannot unwrap a none value");
+            assert_eq!((error.token.line, error.token.position), (2, 2));
+        }
+
+        #[test]
+        fn it_binds_present_values_in_ifs() {
+            let program = |value: &str| {
+                format!(
+                    "x: Int? = {};
+                    result := 0;
+                    if y := x {{ result = y * 2; }} else {{ result = -1; }}
+                    result;",
+                    value
+                )
+            };
+
+            assert_eq!(integer(interpret(&program("21")).unwrap()), 42);
+            assert_eq!(integer(interpret(&program("none")).unwrap()), -1);
+        }
+
+        #[test]
+        fn it_compares_optionals() {
+            let result = interpret(
+                "x: Int? = 1; y: Int? = none;
+                [x == 1, x == none, y == none, y != 1];",
+            )
+            .unwrap();
+
+            assert_eq!(result.to_string(), "[true, false, true, true]");
+        }
+    }
+
+    mod records {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_reads_and_writes_fields() {
+            let result = interpret(
+                "record Point { x: Int, y: Int }
+                p := Point { y: 2, x: 1 };
+                p.x = p.x + p.y;
+                p;",
+            )
+            .unwrap();
+
+            assert_eq!(result.to_string(), "Point { x: 3, y: 2 }");
+        }
+
+        #[test]
+        fn it_shares_records_between_aliases() {
+            let result = interpret(
+                "record Node { value: Int, next: Node? }
+                tail := Node { value: 2, next: none };
+                head := Node { value: 1, next: tail };
+                tail.value = 5;
+                head;",
+            )
+            .unwrap();
+
+            assert_eq!(
+                result.to_string(),
+                "Node { value: 1, next: Node { value: 5, next: none } }"
+            );
+        }
+
+        #[test]
+        fn it_reports_invalid_fields() {
+            let error = interpret("record P { x: Int }\nP { x: 1, y: 2 };").unwrap_err();
+
+            assert_eq!(error.message, "Record P has no field 'y'");
+            assert_eq!((error.token.line, error.token.position), (2, 11));
+
+            let error = interpret("record P { x: Int }\nP {};").unwrap_err();
+
+            assert_eq!(error.message, "Missing field 'x' in P");
+
+            let error = interpret("record P { x: Int }\nP { x: 1 }.z;").unwrap_err();
+
+            assert_eq!(error.message, "Record P has no field 'z'");
+
+            let error = interpret("n := 1;\nn.x = 2;").unwrap_err();
+
+            assert_eq!(error.message, "Can only access fields on records, got Int");
+        }
+    }
+}
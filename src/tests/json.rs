@@ -0,0 +1,36 @@
+#[cfg(test)]
+mod tests {
+    use crate::json::*;
+
+    mod parsing {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_round_trips_values() {
+            let text = r#"{"a":[1,-2.5,true,false,null],"b":{"c":"d\"\n\\"},"e":[]}"#;
+
+            assert_eq!(Json::parse(text).unwrap().to_string(), text);
+        }
+
+        #[test]
+        fn it_reads_whitespace_and_unicode_escapes() {
+            let value = Json::parse(" { \"k\" : \"\\u00e9\\ud83c\\udf75\" } ").unwrap();
+
+            assert_eq!(value.get("k").and_then(Json::as_str), Some("é🍵"));
+        }
+
+        #[test]
+        fn it_rejects_invalid_json() {
+            assert_eq!(
+                Json::parse("{\"a\" 1}"),
+                Err("Expected ':', got '1'".to_owned())
+            );
+            assert_eq!(
+                Json::parse("[1] 2"),
+                Err("Unexpected '2' after the value".to_owned())
+            );
+            assert_eq!(Json::parse("\"open"), Err("Unterminated string".to_owned()));
+        }
+    }
+}
@@ -0,0 +1,323 @@
+#[cfg(test)]
+mod tests {
+    use crate::{json::Json, lsp::*};
+
+    const URI: &str = "file:///main.mt";
+
+    /// Frames `messages` the way an editor would send them
+    fn script(messages: &[Json]) -> Vec<u8> {
+        messages
+            .iter()
+            .flat_map(|message| {
+                let body = message.to_string();
+
+                format!("Content-Length: {}\r\n\r\n{}", body.len(), body).into_bytes()
+            })
+            .collect()
+    }
+
+    /// Runs the server on `messages`, returning its exit code and every message it sent
+    fn session(messages: &[Json]) -> (i32, Vec<Json>) {
+        let input = script(messages);
+        let mut output = Vec::new();
+        let exit_code = serve(&input[..], &mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        let mut replies = Vec::new();
+        let mut rest = output.as_str();
+
+        while let Some((header, body)) = rest.split_once("\r\n\r\n") {
+            let length: usize = header
+                .strip_prefix("Content-Length: ")
+                .unwrap()
+                .parse()
+                .unwrap();
+
+            replies.push(Json::parse(&body[..length]).unwrap());
+            rest = &body[length..];
+        }
+
+        (exit_code, replies)
+    }
+
+    fn request(id: usize, method: &str, params: Json) -> Json {
+        Json::object([
+            ("jsonrpc", "2.0".into()),
+            ("id", id.into()),
+            ("method", method.into()),
+            ("params", params),
+        ])
+    }
+
+    fn notification(method: &str, params: Json) -> Json {
+        Json::object([
+            ("jsonrpc", "2.0".into()),
+            ("method", method.into()),
+            ("params", params),
+        ])
+    }
+
+    fn open(text: &str) -> Json {
+        notification(
+            "textDocument/didOpen",
+            Json::object([(
+                "textDocument",
+                Json::object([
+                    ("uri", URI.into()),
+                    ("languageId", "matcha".into()),
+                    ("version", 1.into()),
+                    ("text", text.into()),
+                ]),
+            )]),
+        )
+    }
+
+    fn at(id: usize, method: &str, line: usize, character: usize) -> Json {
+        request(
+            id,
+            method,
+            Json::object([
+                ("textDocument", Json::object([("uri", URI.into())])),
+                (
+                    "position",
+                    Json::object([("line", line.into()), ("character", character.into())]),
+                ),
+            ]),
+        )
+    }
+
+    fn result(replies: &[Json], id: usize) -> &Json {
+        replies
+            .iter()
+            .find(|reply| reply.get("id") == Some(&Json::from(id)))
+            .and_then(|reply| reply.get("result"))
+            .unwrap()
+    }
+
+    /// The diagnostics of every `publishDiagnostics` notification, as `line:character message`
+    fn published(replies: &[Json]) -> Vec<Vec<String>> {
+        replies
+            .iter()
+            .filter(|reply| {
+                reply.get("method").and_then(Json::as_str)
+                    == Some("textDocument/publishDiagnostics")
+            })
+            .map(|reply| {
+                reply
+                    .get("params")
+                    .unwrap()
+                    .get("diagnostics")
+                    .unwrap()
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|diagnostic| {
+                        let start = diagnostic.get("range").unwrap().get("start").unwrap();
+
+                        format!(
+                            "{}:{} {}",
+                            start.get("line").unwrap(),
+                            start.get("character").unwrap(),
+                            diagnostic.get("message").unwrap().as_str().unwrap()
+                        )
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    mod lifecycle {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_initializes_and_shuts_down() {
+            let (exit_code, replies) = session(&[
+                request(1, "initialize", Json::object([])),
+                notification("initialized", Json::object([])),
+                request(2, "shutdown", Json::Null),
+                notification("exit", Json::Null),
+            ]);
+
+            assert_eq!(exit_code, 0);
+            assert_eq!(replies.len(), 2);
+
+            let capabilities = result(&replies, 1).get("capabilities").unwrap();
+
+            assert_eq!(
+                capabilities.to_string(),
+                "{\"textDocumentSync\":1,\"hoverProvider\":true,\"definitionProvider\":true,\"documentSymbolProvider\":true}"
+            );
+            assert_eq!(result(&replies, 2), &Json::Null);
+        }
+
+        #[test]
+        fn it_exits_with_an_error_without_shutdown() {
+            assert_eq!(session(&[notification("exit", Json::Null)]).0, 1);
+            assert_eq!(session(&[]).0, 1);
+        }
+
+        #[test]
+        fn it_rejects_unknown_methods() {
+            let (_, replies) = session(&[request(7, "textDocument/rename", Json::object([]))]);
+
+            assert_eq!(
+                replies[0].get("error").unwrap().to_string(),
+                "{\"code\":-32601,\"message\":\"Unknown method textDocument/rename\"}"
+            );
+        }
+    }
+
+    mod diagnostics {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_publishes_diagnostics_on_open_change_and_close() {
+            let (_, replies) = session(&[
+                open("x := 1\ny := 2;"),
+                notification(
+                    "textDocument/didChange",
+                    Json::object([
+                        (
+                            "textDocument",
+                            Json::object([("uri", URI.into()), ("version", 2.into())]),
+                        ),
+                        (
+                            "contentChanges",
+                            vec![Json::object([(
+                                "text",
+                                "f := fn() { y := 1; };\nf(z);".into(),
+                            )])]
+                            .into(),
+                        ),
+                    ]),
+                ),
+                notification(
+                    "textDocument/didClose",
+                    Json::object([("textDocument", Json::object([("uri", URI.into())]))]),
+                ),
+            ]);
+
+            assert_eq!(
+                published(&replies),
+                vec![
+                    vec!["1:0 Expected ';'".to_owned()],
+                    vec![
+                        "0:12 Variable 'y' is never read".to_owned(),
+                        "1:2 Variable 'z' not found in the current scope".to_owned(),
+                    ],
+                    vec![],
+                ]
+            );
+        }
+
+        #[test]
+        fn it_publishes_type_errors() {
+            let (_, replies) = session(&[open("x: String = 1;")]);
+
+            assert_eq!(
+                published(&replies),
+                vec![vec![
+                    "0:3 Cannot initialize 'x' of type String with a value of type Int".to_owned()
+                ]]
+            );
+        }
+    }
+
+    mod navigation {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        const PROGRAM: &str = "record Point { x: Int }
+count := 1;
+add := fn(a: Int, b: Int): Int {
+  count = count + a;
+  a + b
+};
+add(count, 2);";
+
+        #[test]
+        fn it_shows_inferred_types_on_hover() {
+            let (_, replies) = session(&[
+                open(PROGRAM),
+                at(1, "textDocument/hover", 6, 5),
+                at(2, "textDocument/hover", 3, 18),
+                at(3, "textDocument/hover", 2, 1),
+                at(4, "textDocument/hover", 6, 12),
+            ]);
+
+            let hover = |id| {
+                result(&replies, id)
+                    .get("contents")
+                    .and_then(|contents| contents.get("value"))
+                    .and_then(Json::as_str)
+                    .map(str::to_owned)
+            };
+
+            assert_eq!(hover(1), Some("```matcha\ncount: Int\n```".to_owned()));
+            assert_eq!(hover(2), Some("```matcha\na: Int\n```".to_owned()));
+            assert_eq!(
+                hover(3),
+                Some("```matcha\nadd: fn(Int, Int): Int\n```".to_owned())
+            );
+            assert_eq!(result(&replies, 4), &Json::Null);
+        }
+
+        #[test]
+        fn it_goes_to_the_declaration() {
+            let (_, replies) = session(&[
+                open(PROGRAM),
+                at(1, "textDocument/definition", 6, 6),
+                at(2, "textDocument/definition", 4, 2),
+                at(3, "textDocument/definition", 6, 0),
+            ]);
+
+            let line = |id| {
+                let reply = result(&replies, id);
+                let start = reply.get("range").unwrap().get("start").unwrap();
+
+                assert_eq!(reply.get("uri").and_then(Json::as_str), Some(URI));
+                (
+                    start.get("line").and_then(Json::as_usize).unwrap(),
+                    start.get("character").and_then(Json::as_usize).unwrap(),
+                )
+            };
+
+            assert_eq!(line(1), (1, 0));
+            assert_eq!(line(2), (2, 10));
+            assert_eq!(line(3), (2, 0));
+        }
+
+        #[test]
+        fn it_lists_top_level_symbols() {
+            let (_, replies) = session(&[
+                open(PROGRAM),
+                request(
+                    1,
+                    "textDocument/documentSymbol",
+                    Json::object([("textDocument", Json::object([("uri", URI.into())]))]),
+                ),
+            ]);
+
+            let symbols: Vec<String> = result(&replies, 1)
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|symbol| {
+                    format!(
+                        "{} {} {}",
+                        symbol.get("name").and_then(Json::as_str).unwrap(),
+                        symbol.get("kind").unwrap(),
+                        symbol.get("detail").and_then(Json::as_str).unwrap_or("-")
+                    )
+                })
+                .collect();
+
+            assert_eq!(
+                symbols,
+                vec!["Point 23 -", "count 13 Int", "add 12 fn(Int, Int): Int"]
+            );
+        }
+    }
+}
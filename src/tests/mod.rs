@@ -1 +1,16 @@
+mod assembler;
+mod diagnostic;
+mod formatter;
+mod highlight;
+mod interpreter;
+mod json;
+mod lsp;
+mod optimizer;
 mod parser;
+mod rename;
+mod repl;
+mod resolver;
+mod scanner;
+mod syntax;
+mod type_checker;
+mod vm;
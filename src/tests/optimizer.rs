@@ -0,0 +1,178 @@
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use crate::{
+        compiler::Compiler, environment::Environment, interpreter::*, optimizer::*, parser::*,
+        resolver::*, scanner::*, source::*, statement::Statement, vm::Vm,
+    };
+
+    fn parse(source: &str) -> Vec<Statement<'_>> {
+        let tokens = Scanner {
+            source: Source::new(source),
+        }
+        .scan()
+        .unwrap();
+        let mut statements = Parser::new(tokens).parse().unwrap();
+        Resolver::new().resolve(&mut statements);
+
+        statements
+    }
+
+    /// The tree printed by `--ast` for the optimized program
+    fn format(source: &str) -> String {
+        optimize(parse(source))
+            .iter()
+            .map(|statement| statement.format(0))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    /// The value of the program before and after optimizing, with the tree-walker and the VM
+    fn results(source: &str) -> Vec<String> {
+        let statements = parse(source);
+        let optimized = optimize(statements.clone());
+
+        [statements, optimized]
+            .iter()
+            .flat_map(|statements| {
+                let result =
+                    Interpreter::interpret(Rc::new(RefCell::new(Environment::new())), statements);
+                let vm_result = Compiler::compile(statements).and_then(|compiled| {
+                    Vm::interpret(Rc::new(RefCell::new(Environment::new())), &compiled)
+                });
+
+                [result, vm_result].map(|result| match result {
+                    Ok(value) => value.to_string(),
+                    Err(error) => error.message,
+                })
+            })
+            .collect()
+    }
+
+    mod folding {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_folds_arithmetic_on_literals() {
+            assert_eq!(
+                format("60 * 60 * 24; -(1 + 2) / 2.0; 1 + 0.5 * 2; !true; (3.0);"),
+                "86400\n-1.5\n2.0\nfalse\n3.0"
+            );
+        }
+
+        #[test]
+        fn it_folds_the_literal_parts_of_an_expression() {
+            assert_eq!(
+                format("x := 1; x * (2 + 3) - 4;"),
+                "VAR_DECL
+├─ x
+├─ 1
+-
+├─ *
+│  ├─ VAR x
+│  ├─ 5
+├─ 4"
+            );
+        }
+
+        #[test]
+        fn it_leaves_what_fails_at_runtime() {
+            assert_eq!(
+                format("1 / 0; 2147483647 + 1;"),
+                "/\n├─ 1\n├─ 0\n+\n├─ 2147483647\n├─ 1"
+            );
+            assert_eq!(
+                format("1 / 0.0; -(-2147483647 - 1);"),
+                "/\n├─ 1\n├─ 0.0\n-\n├─ -2147483648"
+            );
+            assert_eq!(format("1.0 / 0;"), "inf");
+        }
+    }
+
+    mod branches {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_keeps_the_branch_that_runs_in_its_scope() {
+            assert_eq!(
+                format("{ a := 1; if !false { a = 2; } else { a = 3; } if 1 > 2 { a; } }"),
+                "BLOCK
+├─ VAR_DECL
+│  ├─ a
+│  ├─ 1
+├─ BLOCK
+│  ├─ VAR_ASSIGN
+│  │  ├─ a (depth 1, slot 0)
+│  │  ├─ 2
+├─ IF_STMT
+│  ├─ CONDITION
+│  │  ├─ >
+│  │  │  ├─ 1
+│  │  │  ├─ 2
+│  ├─ THEN
+│  │  ├─ BLOCK
+│  │  │  ├─ VAR a (depth 1, slot 0)"
+            );
+            assert_eq!(format("if false { 1; } else { 2; }"), "BLOCK\n├─ 2");
+        }
+
+        #[test]
+        fn it_removes_code_that_never_runs() {
+            assert_eq!(
+                format("a := 1; for false { a = 2; } if false { a = 3; } a;"),
+                "VAR_DECL\n├─ a\n├─ 1\nVAR a"
+            );
+
+            // The value of a block is its last statement, so that is left empty instead
+            assert_eq!(
+                format("a := 1; for !true {}"),
+                "VAR_DECL\n├─ a\n├─ 1\nBLOCK"
+            );
+        }
+
+        #[test]
+        fn it_optimizes_function_bodies() {
+            assert_eq!(
+                format("f := fn(): Int { if true { return 2 * 3; } return 0; };"),
+                "VAR_DECL
+├─ f
+├─ FN(): Int
+│  ├─ BLOCK
+│  │  ├─ BLOCK
+│  │  │  ├─ RETURN
+│  │  │  │  ├─ 6
+│  │  ├─ RETURN
+│  │  │  ├─ 0"
+            );
+        }
+    }
+
+    mod running {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_runs_to_the_same_result() {
+            for (source, expected) in [
+                (
+                    "day := 60 * 60 * 24; if day > 0 { day / (2 + 2); } else { 0; }",
+                    "21600",
+                ),
+                ("x := 1; if false { x = 2; } else { x = x - 3; } x;", "-2"),
+                (
+                    "f := fn(): Int { if !false { 40 + 2; } else { 0; } }; f();",
+                    "42",
+                ),
+                ("x := 0; for false { x = 1; }", "<empty>"),
+                ("1 / 0;", "Division by zero"),
+                ("x := 1 / 0.0; x;", "Division by zero"),
+                ("2147483647 + 1;", "Integer overflow"),
+            ] {
+                assert_eq!(results(source), vec![expected; 4], "{:?}", source);
+            }
+        }
+    }
+}
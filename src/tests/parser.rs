@@ -1,5 +1,7 @@
 #[cfg(test)]
 mod tests {
+    use std::borrow::Cow;
+
     use crate::{matcha::*, parser::*, scanner::*, source::*, statement::*, token::*};
 
     mod numeric_operators {
@@ -26,13 +28,16 @@ mod tests {
                                 lexeme: "1",
                                 line: 1,
                                 position: 1,
-                            }
+                                span: Span::new(0, 1),
+                            },
+                            literal: Literal::Number(NumberLiteral::Integer(1)),
                         })),
                         operator: Token {
                             token_type: TokenType::Plus,
                             lexeme: "+",
                             line: 1,
                             position: 3,
+                            span: Span::new(2, 3),
                         },
                         right: Box::new(Expression::Literal(LiteralExpression {
                             value: Token {
@@ -40,7 +45,9 @@ mod tests {
                                 lexeme: "1",
                                 line: 1,
                                 position: 5,
-                            }
+                                span: Span::new(4, 5),
+                            },
+                            literal: Literal::Number(NumberLiteral::Integer(1)),
                         })),
                     }
                 ))]
@@ -67,13 +74,16 @@ mod tests {
                                     lexeme: "1",
                                     line: 1,
                                     position: 1,
-                                }
+                                    span: Span::new(0, 1),
+                                },
+                                literal: Literal::Number(NumberLiteral::Integer(1)),
                             })),
                             operator: Token {
                                 token_type: TokenType::Plus,
                                 lexeme: "+",
                                 line: 1,
                                 position: 3,
+                                span: Span::new(2, 3),
                             },
                             right: Box::new(Expression::Literal(LiteralExpression {
                                 value: Token {
@@ -81,7 +91,9 @@ mod tests {
                                     lexeme: "1",
                                     line: 1,
                                     position: 5,
-                                }
+                                    span: Span::new(4, 5),
+                                },
+                                literal: Literal::Number(NumberLiteral::Integer(1)),
                             })),
                         })),
                         operator: Token {
@@ -89,6 +101,7 @@ mod tests {
                             lexeme: "+",
                             line: 1,
                             position: 7,
+                            span: Span::new(6, 7),
                         },
                         right: Box::new(Expression::Literal(LiteralExpression {
                             value: Token {
@@ -96,7 +109,9 @@ mod tests {
                                 lexeme: "5",
                                 line: 1,
                                 position: 9,
-                            }
+                                span: Span::new(8, 9),
+                            },
+                            literal: Literal::Number(NumberLiteral::Integer(5)),
                         })),
                     }
                 ))]
@@ -124,13 +139,16 @@ mod tests {
                                         lexeme: "1",
                                         line: 1,
                                         position: 1,
+                                        span: Span::new(0, 1),
                                     },
+                                    literal: Literal::Number(NumberLiteral::Integer(1)),
                                 })),
                                 operator: Token {
                                     token_type: TokenType::Star,
                                     lexeme: "*",
                                     line: 1,
                                     position: 3,
+                                    span: Span::new(2, 3),
                                 },
                                 right: Box::new(Expression::Literal(LiteralExpression {
                                     value: Token {
@@ -138,7 +156,9 @@ mod tests {
                                         lexeme: "2",
                                         line: 1,
                                         position: 5,
+                                        span: Span::new(4, 5),
                                     },
+                                    literal: Literal::Number(NumberLiteral::Integer(2)),
                                 })),
                             })),
                             operator: Token {
@@ -146,6 +166,7 @@ mod tests {
                                 lexeme: "+",
                                 line: 1,
                                 position: 7,
+                                span: Span::new(6, 7),
                             },
                             right: Box::new(Expression::Binary(BinaryExpression {
                                 left: Box::new(Expression::Literal(LiteralExpression {
@@ -154,13 +175,16 @@ mod tests {
                                         lexeme: "3",
                                         line: 1,
                                         position: 9,
+                                        span: Span::new(8, 9),
                                     },
+                                    literal: Literal::Number(NumberLiteral::Integer(3)),
                                 })),
                                 operator: Token {
                                     token_type: TokenType::Slash,
                                     lexeme: "/",
                                     line: 1,
                                     position: 11,
+                                    span: Span::new(10, 11),
                                 },
                                 right: Box::new(Expression::Literal(LiteralExpression {
                                     value: Token {
@@ -168,7 +192,9 @@ mod tests {
                                         lexeme: "4",
                                         line: 1,
                                         position: 13,
+                                        span: Span::new(12, 13),
                                     },
+                                    literal: Literal::Number(NumberLiteral::Integer(4)),
                                 })),
                             })),
                         })),
@@ -177,6 +203,7 @@ mod tests {
                             lexeme: "-",
                             line: 1,
                             position: 15,
+                            span: Span::new(14, 15),
                         },
                         right: Box::new(Expression::Binary(BinaryExpression {
                             left: Box::new(Expression::Literal(LiteralExpression {
@@ -185,17 +212,22 @@ mod tests {
                                     lexeme: "5",
                                     line: 1,
                                     position: 17,
+                                    span: Span::new(16, 17),
                                 },
+                                literal: Literal::Number(NumberLiteral::Integer(5)),
                             })),
                             operator: Token {
                                 token_type: TokenType::Star,
                                 lexeme: "*",
                                 line: 1,
                                 position: 19,
+                                span: Span::new(18, 19),
                             },
                             right: Box::new(Expression::Grouping(GroupingExpression {
+                                span: Span::new(20, 39),
                                 expression: Box::new(Expression::Binary(BinaryExpression {
                                     left: Box::new(Expression::Grouping(GroupingExpression {
+                                        span: Span::new(21, 28),
                                         expression: Box::new(Expression::Binary(
                                             BinaryExpression {
                                                 left: Box::new(Expression::Literal(
@@ -205,7 +237,11 @@ mod tests {
                                                             lexeme: "6",
                                                             line: 1,
                                                             position: 23,
+                                                            span: Span::new(22, 23),
                                                         },
+                                                        literal: Literal::Number(
+                                                            NumberLiteral::Integer(6)
+                                                        ),
                                                     }
                                                 )),
                                                 operator: Token {
@@ -213,6 +249,7 @@ mod tests {
                                                     lexeme: "-",
                                                     line: 1,
                                                     position: 25,
+                                                    span: Span::new(24, 25),
                                                 },
                                                 right: Box::new(Expression::Literal(
                                                     LiteralExpression {
@@ -221,7 +258,11 @@ mod tests {
                                                             lexeme: "7",
                                                             line: 1,
                                                             position: 27,
+                                                            span: Span::new(26, 27),
                                                         },
+                                                        literal: Literal::Number(
+                                                            NumberLiteral::Integer(7)
+                                                        ),
                                                     }
                                                 )),
                                             }
@@ -232,8 +273,10 @@ mod tests {
                                         lexeme: "/",
                                         line: 1,
                                         position: 30,
+                                        span: Span::new(29, 30),
                                     },
                                     right: Box::new(Expression::Grouping(GroupingExpression {
+                                        span: Span::new(31, 38),
                                         expression: Box::new(Expression::Binary(
                                             BinaryExpression {
                                                 left: Box::new(Expression::Literal(
@@ -243,7 +286,11 @@ mod tests {
                                                             lexeme: "8",
                                                             line: 1,
                                                             position: 33,
+                                                            span: Span::new(32, 33),
                                                         },
+                                                        literal: Literal::Number(
+                                                            NumberLiteral::Integer(8)
+                                                        ),
                                                     }
                                                 )),
                                                 operator: Token {
@@ -251,6 +298,7 @@ mod tests {
                                                     lexeme: "+",
                                                     line: 1,
                                                     position: 35,
+                                                    span: Span::new(34, 35),
                                                 },
                                                 right: Box::new(Expression::Literal(
                                                     LiteralExpression {
@@ -259,7 +307,11 @@ mod tests {
                                                             lexeme: "9",
                                                             line: 1,
                                                             position: 37,
+                                                            span: Span::new(36, 37),
                                                         },
+                                                        literal: Literal::Number(
+                                                            NumberLiteral::Integer(9)
+                                                        ),
                                                     }
                                                 )),
                                             }
@@ -298,13 +350,16 @@ mod tests {
                                 lexeme: "1",
                                 line: 2,
                                 position: 1,
-                            }
+                                span: Span::new(1, 2),
+                            },
+                            literal: Literal::Number(NumberLiteral::Integer(1)),
                         })),
                         operator: Token {
                             token_type: TokenType::Star,
                             lexeme: "*",
                             line: 2,
                             position: 3,
+                            span: Span::new(3, 4),
                         },
                         right: Box::new(Expression::Literal(LiteralExpression {
                             value: Token {
@@ -312,7 +367,9 @@ mod tests {
                                 lexeme: "2",
                                 line: 2,
                                 position: 5,
-                            }
+                                span: Span::new(5, 6),
+                            },
+                            literal: Literal::Number(NumberLiteral::Integer(2)),
                         })),
                     })),
                     Statement::Expression(Expression::Binary(BinaryExpression {
@@ -322,13 +379,16 @@ mod tests {
                                 lexeme: "3",
                                 line: 3,
                                 position: 5,
-                            }
+                                span: Span::new(12, 13),
+                            },
+                            literal: Literal::Number(NumberLiteral::Integer(3)),
                         })),
                         operator: Token {
                             token_type: TokenType::Slash,
                             lexeme: "/",
                             line: 3,
                             position: 7,
+                            span: Span::new(14, 15),
                         },
                         right: Box::new(Expression::Literal(LiteralExpression {
                             value: Token {
@@ -336,7 +396,9 @@ mod tests {
                                 lexeme: "4",
                                 line: 3,
                                 position: 9,
-                            }
+                                span: Span::new(16, 17),
+                            },
+                            literal: Literal::Number(NumberLiteral::Integer(4)),
                         })),
                     })),
                     Statement::Expression(Expression::Binary(BinaryExpression {
@@ -347,13 +409,16 @@ mod tests {
                                     lexeme: "5",
                                     line: 4,
                                     position: 1,
-                                }
+                                    span: Span::new(19, 20),
+                                },
+                                literal: Literal::Number(NumberLiteral::Integer(5)),
                             })),
                             operator: Token {
                                 token_type: TokenType::Plus,
                                 lexeme: "+",
                                 line: 4,
                                 position: 2,
+                                span: Span::new(20, 21),
                             },
                             right: Box::new(Expression::Literal(LiteralExpression {
                                 value: Token {
@@ -361,7 +426,9 @@ mod tests {
                                     lexeme: "6",
                                     line: 4,
                                     position: 3,
-                                }
+                                    span: Span::new(21, 22),
+                                },
+                                literal: Literal::Number(NumberLiteral::Integer(6)),
                             })),
                         })),
                         operator: Token {
@@ -369,6 +436,7 @@ mod tests {
                             lexeme: "-",
                             line: 4,
                             position: 4,
+                            span: Span::new(22, 23),
                         },
                         right: Box::new(Expression::Literal(LiteralExpression {
                             value: Token {
@@ -376,7 +444,9 @@ mod tests {
                                 lexeme: "2",
                                 line: 4,
                                 position: 5,
-                            }
+                                span: Span::new(23, 24),
+                            },
+                            literal: Literal::Number(NumberLiteral::Integer(2)),
                         })),
                     }))
                 ]
@@ -406,6 +476,7 @@ mod tests {
                         line: 1,
                         position: 1,
                         lexeme: "my_variable",
+                        span: Span::new(0, 11),
                     },
                     initializer: Expression::Literal(LiteralExpression {
                         value: Token {
@@ -413,7 +484,9 @@ mod tests {
                             lexeme: "15",
                             line: 1,
                             position: 16,
-                        }
+                            span: Span::new(15, 17),
+                        },
+                        literal: Literal::Number(NumberLiteral::Integer(15)),
                     }),
                     r#type: None
                 })]
@@ -443,6 +516,7 @@ mod tests {
                             line: 1,
                             position: 1,
                             lexeme: "a_number",
+                            span: Span::new(0, 8),
                         },
                         initializer: Expression::Literal(LiteralExpression {
                             value: Token {
@@ -450,7 +524,9 @@ mod tests {
                                 lexeme: "1",
                                 line: 1,
                                 position: 13,
-                            }
+                                span: Span::new(12, 13),
+                            },
+                            literal: Literal::Number(NumberLiteral::Integer(1)),
                         }),
                         r#type: None
                     }),
@@ -460,6 +536,7 @@ mod tests {
                             line: 2,
                             position: 24,
                             lexeme: "string",
+                            span: Span::new(38, 44),
                         },
                         initializer: Expression::Literal(LiteralExpression {
                             value: Token {
@@ -467,7 +544,9 @@ mod tests {
                                 lexeme: "\"abc\"",
                                 line: 2,
                                 position: 34,
-                            }
+                                span: Span::new(48, 53),
+                            },
+                            literal: Literal::String(Cow::Borrowed("abc")),
                         }),
                         r#type: None
                     })
@@ -493,6 +572,7 @@ mod tests {
                         line: 1,
                         position: 1,
                         lexeme: "my_variable",
+                        span: Span::new(0, 11),
                     },
                     initializer: Expression::Literal(LiteralExpression {
                         value: Token {
@@ -500,13 +580,20 @@ mod tests {
                             lexeme: "15",
                             line: 1,
                             position: 21,
-                        }
+                            span: Span::new(20, 22),
+                        },
+                        literal: Literal::Number(NumberLiteral::Integer(15)),
                     }),
-                    r#type: Some(Token {
-                        token_type: TokenType::Identifier,
-                        lexeme: "i32",
-                        line: 1,
-                        position: 15,
+                    r#type: Some(TypeAnnotation {
+                        name: Token {
+                            token_type: TokenType::Identifier,
+                            lexeme: "i32",
+                            line: 1,
+                            position: 15,
+                            span: Span::new(14, 17),
+                        },
+                        function: None,
+                        optional: false
                     })
                 })]
             );
@@ -537,12 +624,18 @@ var_5 :u =
                             line: 1,
                             position: 1,
                             lexeme: "var1",
+                            span: Span::new(0, 4),
                         },
-                        r#type: Some(Token {
-                            token_type: TokenType::Identifier,
-                            lexeme: "i32",
-                            line: 1,
-                            position: 8,
+                        r#type: Some(TypeAnnotation {
+                            name: Token {
+                                token_type: TokenType::Identifier,
+                                lexeme: "i32",
+                                line: 1,
+                                position: 8,
+                                span: Span::new(7, 10),
+                            },
+                            function: None,
+                            optional: false
                         }),
                         initializer: Expression::Literal(LiteralExpression {
                             value: Token {
@@ -550,7 +643,9 @@ var_5 :u =
                                 lexeme: "1",
                                 line: 1,
                                 position: 14,
-                            }
+                                span: Span::new(13, 14),
+                            },
+                            literal: Literal::Number(NumberLiteral::Integer(1)),
                         }),
                     }),
                     Statement::VariableDeclaration(VariableDeclaration {
@@ -559,12 +654,18 @@ var_5 :u =
                             line: 1,
                             position: 17,
                             lexeme: "var2",
+                            span: Span::new(16, 20),
                         },
-                        r#type: Some(Token {
-                            token_type: TokenType::Identifier,
-                            lexeme: "a_type",
-                            line: 1,
-                            position: 22,
+                        r#type: Some(TypeAnnotation {
+                            name: Token {
+                                token_type: TokenType::Identifier,
+                                lexeme: "a_type",
+                                line: 1,
+                                position: 22,
+                                span: Span::new(21, 27),
+                            },
+                            function: None,
+                            optional: false
                         }),
                         initializer: Expression::Literal(LiteralExpression {
                             value: Token {
@@ -572,7 +673,9 @@ var_5 :u =
                                 lexeme: "2",
                                 line: 1,
                                 position: 29,
-                            }
+                                span: Span::new(28, 29),
+                            },
+                            literal: Literal::Number(NumberLiteral::Integer(2)),
                         }),
                     }),
                     Statement::VariableDeclaration(VariableDeclaration {
@@ -581,6 +684,7 @@ var_5 :u =
                             line: 2,
                             position: 1,
                             lexeme: "var3",
+                            span: Span::new(31, 35),
                         },
                         r#type: None,
                         initializer: Expression::Literal(LiteralExpression {
@@ -589,7 +693,9 @@ var_5 :u =
                                 lexeme: "16",
                                 line: 2,
                                 position: 9,
-                            }
+                                span: Span::new(39, 41),
+                            },
+                            literal: Literal::Number(NumberLiteral::Integer(16)),
                         }),
                     }),
                     Statement::VariableDeclaration(VariableDeclaration {
@@ -598,12 +704,18 @@ var_5 :u =
                             line: 3,
                             position: 1,
                             lexeme: "var_4",
+                            span: Span::new(43, 48),
                         },
-                        r#type: Some(Token {
-                            token_type: TokenType::Identifier,
-                            lexeme: "u64",
-                            line: 3,
-                            position: 8,
+                        r#type: Some(TypeAnnotation {
+                            name: Token {
+                                token_type: TokenType::Identifier,
+                                lexeme: "u64",
+                                line: 3,
+                                position: 8,
+                                span: Span::new(50, 53),
+                            },
+                            function: None,
+                            optional: false
                         }),
                         initializer: Expression::Variable(VariableExpression {
                             value: Token {
@@ -611,7 +723,9 @@ var_5 :u =
                                 lexeme: "var1",
                                 line: 3,
                                 position: 14,
-                            }
+                                span: Span::new(56, 60),
+                            },
+                            resolved: None,
                         }),
                     }),
                     Statement::VariableDeclaration(VariableDeclaration {
@@ -620,12 +734,18 @@ var_5 :u =
                             line: 4,
                             position: 1,
                             lexeme: "var_5",
+                            span: Span::new(62, 67),
                         },
-                        r#type: Some(Token {
-                            token_type: TokenType::Identifier,
-                            lexeme: "u",
-                            line: 4,
-                            position: 8,
+                        r#type: Some(TypeAnnotation {
+                            name: Token {
+                                token_type: TokenType::Identifier,
+                                lexeme: "u",
+                                line: 4,
+                                position: 8,
+                                span: Span::new(69, 70),
+                            },
+                            function: None,
+                            optional: false
                         }),
                         initializer: Expression::Variable(VariableExpression {
                             value: Token {
@@ -633,7 +753,9 @@ var_5 :u =
                                 lexeme: "var_4",
                                 line: 5,
                                 position: 5,
-                            }
+                                span: Span::new(77, 82),
+                            },
+                            resolved: None,
                         }),
                     })
                 ]
@@ -659,6 +781,7 @@ var_5 :u =
                             line: 1,
                             position: 1,
                             lexeme: "my_variable",
+                            span: Span::new(0, 11),
                         },
                         value: Box::new(Expression::Literal(LiteralExpression {
                             value: Token {
@@ -666,8 +789,11 @@ var_5 :u =
                                 lexeme: "15",
                                 line: 1,
                                 position: 15,
-                            }
-                        }))
+                                span: Span::new(14, 16),
+                            },
+                            literal: Literal::Number(NumberLiteral::Integer(15)),
+                        })),
+                        resolved: None,
                     }
                 ))]
             );
@@ -692,6 +818,7 @@ var_5 :u =
                             line: 1,
                             position: 1,
                             lexeme: "var1",
+                            span: Span::new(0, 4),
                         },
                         value: Box::new(Expression::Literal(LiteralExpression {
                             value: Token {
@@ -699,8 +826,11 @@ var_5 :u =
                                 lexeme: "15",
                                 line: 1,
                                 position: 8,
-                            }
-                        }))
+                                span: Span::new(7, 9),
+                            },
+                            literal: Literal::Number(NumberLiteral::Integer(15)),
+                        })),
+                        resolved: None,
                     })),
                     Statement::Expression(Expression::Assignment(AssignmentExpression {
                         identifier: Token {
@@ -708,6 +838,7 @@ var_5 :u =
                             line: 1,
                             position: 11,
                             lexeme: "var2",
+                            span: Span::new(10, 14),
                         },
                         value: Box::new(Expression::Literal(LiteralExpression {
                             value: Token {
@@ -715,8 +846,11 @@ var_5 :u =
                                 lexeme: "3",
                                 line: 1,
                                 position: 16,
-                            }
-                        }))
+                                span: Span::new(15, 16),
+                            },
+                            literal: Literal::Number(NumberLiteral::Integer(3)),
+                        })),
+                        resolved: None,
                     })),
                     Statement::Expression(Expression::Assignment(AssignmentExpression {
                         identifier: Token {
@@ -724,6 +858,7 @@ var_5 :u =
                             line: 1,
                             position: 19,
                             lexeme: "var3",
+                            span: Span::new(18, 22),
                         },
                         value: Box::new(Expression::Literal(LiteralExpression {
                             value: Token {
@@ -731,11 +866,585 @@ var_5 :u =
                                 lexeme: "4",
                                 line: 1,
                                 position: 25,
-                            }
-                        }))
+                                span: Span::new(24, 25),
+                            },
+                            literal: Literal::Number(NumberLiteral::Integer(4)),
+                        })),
+                        resolved: None,
                     })),
                 ]
             );
         }
     }
+
+    mod literals {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        fn parse_literal(source: &str) -> Result<Literal<'_>, Vec<ParserError<'_>>> {
+            let tokens = Scanner {
+                source: Source::new(source),
+            }
+            .scan()
+            .unwrap();
+
+            let mut statements = Parser::new(tokens).parse()?;
+
+            match statements.pop() {
+                Some(Statement::Expression(Expression::Literal(literal))) => Ok(literal.literal),
+                other => panic!("Expected a literal expression, got {:?}", other),
+            }
+        }
+
+        #[test]
+        fn it_converts_literals_into_typed_values() {
+            assert_eq!(
+                parse_literal("42;").unwrap(),
+                Literal::Number(NumberLiteral::Integer(42))
+            );
+            assert_eq!(
+                parse_literal("2.5;").unwrap(),
+                Literal::Number(NumberLiteral::Float(2.5))
+            );
+            assert_eq!(parse_literal("true;").unwrap(), Literal::Boolean(true));
+            assert_eq!(parse_literal("false;").unwrap(), Literal::Boolean(false));
+            assert_eq!(
+                parse_literal("\"abc\";").unwrap(),
+                Literal::String(Cow::Borrowed("abc"))
+            );
+        }
+
+        #[test]
+        fn it_unescapes_strings() {
+            assert_eq!(
+                parse_literal(r#""say \"hi\"\n\tand \\ leave";"#).unwrap(),
+                Literal::String(Cow::Owned("say \"hi\"\n\tand \\ leave".to_owned()))
+            );
+        }
+
+        #[test]
+        fn it_reports_invalid_escape_sequences() {
+            let errors = parse_literal(r#""bad \q";"#).unwrap_err();
+
+            assert_eq!(errors.len(), 1);
+            assert_eq!(errors[0].message, "Invalid escape sequence '\\q'");
+        }
+
+        #[test]
+        fn it_reports_integer_overflow() {
+            assert_eq!(
+                parse_literal("2147483647;").unwrap(),
+                Literal::Number(NumberLiteral::Integer(i32::MAX))
+            );
+
+            let errors = parse_literal("2147483648;").unwrap_err();
+
+            assert_eq!(errors.len(), 1);
+            assert_eq!(
+                errors[0].message,
+                "Integer literal '2147483648' does not fit in a 32-bit integer"
+            );
+            assert_eq!((errors[0].token.line, errors[0].token.position), (1, 1));
+        }
+    }
+
+    mod bitwise_operators {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        fn format(source: &str) -> String {
+            let tokens = Scanner {
+                source: Source::new(source),
+            }
+            .scan()
+            .unwrap();
+
+            Parser::new(tokens)
+                .parse()
+                .unwrap()
+                .iter()
+                .map(|statement| statement.format(0))
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
+
+        #[test]
+        fn it_uses_c_like_precedence() {
+            assert_eq!(
+                format("a | b ^ c & d == e << f + g;"),
+                "|
+├─ VAR a
+├─ ^
+│  ├─ VAR b
+│  ├─ &
+│  │  ├─ VAR c
+│  │  ├─ ==
+│  │  │  ├─ VAR d
+│  │  │  ├─ <<
+│  │  │  │  ├─ VAR e
+│  │  │  │  ├─ +
+│  │  │  │  │  ├─ VAR f
+│  │  │  │  │  ├─ VAR g"
+            );
+        }
+
+        #[test]
+        fn it_binds_shifts_tighter_than_comparisons() {
+            assert_eq!(
+                format("a >> 1 < ~b && c;"),
+                "&&
+├─ <
+│  ├─ >>
+│  │  ├─ VAR a
+│  │  ├─ 1
+│  ├─ ~
+│  │  ├─ VAR b
+├─ VAR c"
+            );
+        }
+    }
+
+    mod functions {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        fn format(source: &str) -> String {
+            let tokens = Scanner {
+                source: Source::new(source),
+            }
+            .scan()
+            .unwrap();
+
+            Parser::new(tokens)
+                .parse()
+                .unwrap()
+                .iter()
+                .map(|statement| statement.format(0))
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
+
+        fn errors(source: &str) -> Vec<String> {
+            let tokens = Scanner {
+                source: Source::new(source),
+            }
+            .scan()
+            .unwrap();
+
+            Parser::new(tokens)
+                .parse()
+                .unwrap_err()
+                .iter()
+                .map(|error| error.to_string())
+                .collect()
+        }
+
+        #[test]
+        fn it_parses_function_declarations() {
+            assert_eq!(
+                format("add := fn(a: Int, b): Int { return a + b; };"),
+                "VAR_DECL
+├─ add
+├─ FN(a: Int, b): Int
+│  ├─ BLOCK
+│  │  ├─ RETURN
+│  │  │  ├─ +
+│  │  │  │  ├─ VAR a
+│  │  │  │  ├─ VAR b"
+            );
+        }
+
+        #[test]
+        fn it_parses_chained_calls() {
+            assert_eq!(
+                format("make()(1, f(2)); g();"),
+                "CALL
+├─ CALL
+│  ├─ VAR make
+│  ├─ ARGS
+├─ ARGS
+│  ├─ 1
+│  ├─ CALL
+│  │  ├─ VAR f
+│  │  ├─ ARGS
+│  │  │  ├─ 2
+CALL
+├─ VAR g
+├─ ARGS"
+            );
+        }
+
+        #[test]
+        fn it_allows_omitting_the_last_semicolon_of_a_block() {
+            assert_eq!(
+                format("fn() { x := 1; x }();"),
+                "CALL
+├─ FN()
+│  ├─ BLOCK
+│  │  ├─ VAR_DECL
+│  │  │  ├─ x
+│  │  │  ├─ 1
+│  │  ├─ VAR x
+├─ ARGS"
+            );
+        }
+
+        #[test]
+        fn it_rejects_invalid_functions() {
+            assert_eq!(
+                errors("return 1;"),
+                vec!["Parser error at 1:1. Cannot return from top-level code"]
+            );
+            assert_eq!(
+                errors("fn(a, a) {};"),
+                vec!["Parser error at 1:7. Duplicate parameter 'a'"]
+            );
+            assert_eq!(
+                errors("f(1, 2;\n"),
+                vec!["Parser error at 1:7. Expected ')' after arguments"]
+            );
+        }
+    }
+
+    mod arrays {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_parses_array_literals_and_indexing() {
+            let tokens = Scanner {
+                source: Source::new("[1, x][0] = 2;"),
+            }
+            .scan()
+            .unwrap();
+
+            let parser_result = Parser::new(tokens).parse().unwrap();
+
+            assert_eq!(
+                parser_result,
+                vec![Statement::Expression(Expression::IndexAssignment(
+                    IndexAssignmentExpression {
+                        target: Box::new(Expression::Array(ArrayExpression {
+                            bracket: Token {
+                                token_type: TokenType::LeftBracket,
+                                lexeme: "[",
+                                line: 1,
+                                position: 1,
+                                span: Span::new(0, 1),
+                            },
+                            elements: vec![
+                                Expression::Literal(LiteralExpression {
+                                    value: Token {
+                                        token_type: TokenType::Integer,
+                                        lexeme: "1",
+                                        line: 1,
+                                        position: 2,
+                                        span: Span::new(1, 2),
+                                    },
+                                    literal: Literal::Number(NumberLiteral::Integer(1)),
+                                }),
+                                Expression::Variable(VariableExpression {
+                                    value: Token {
+                                        token_type: TokenType::Identifier,
+                                        lexeme: "x",
+                                        line: 1,
+                                        position: 5,
+                                        span: Span::new(4, 5),
+                                    },
+                                    resolved: None,
+                                }),
+                            ],
+                            span: Span::new(0, 6),
+                        })),
+                        bracket: Token {
+                            token_type: TokenType::LeftBracket,
+                            lexeme: "[",
+                            line: 1,
+                            position: 7,
+                            span: Span::new(6, 7),
+                        },
+                        index: Box::new(Expression::Literal(LiteralExpression {
+                            value: Token {
+                                token_type: TokenType::Integer,
+                                lexeme: "0",
+                                line: 1,
+                                position: 8,
+                                span: Span::new(7, 8),
+                            },
+                            literal: Literal::Number(NumberLiteral::Integer(0)),
+                        })),
+                        value: Box::new(Expression::Literal(LiteralExpression {
+                            value: Token {
+                                token_type: TokenType::Integer,
+                                lexeme: "2",
+                                line: 1,
+                                position: 13,
+                                span: Span::new(12, 13),
+                            },
+                            literal: Literal::Number(NumberLiteral::Integer(2)),
+                        })),
+                    }
+                ))]
+            );
+        }
+
+        #[test]
+        fn it_rejects_unterminated_arrays() {
+            let tokens = Scanner {
+                source: Source::new("xs := [1, 2;\n"),
+            }
+            .scan()
+            .unwrap();
+
+            let errors = Parser::new(tokens).parse().unwrap_err();
+
+            assert_eq!(errors.len(), 1);
+            assert_eq!(errors[0].message, "Expected ']' after array elements");
+        }
+    }
+
+    mod optionals {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        fn format(source: &str) -> String {
+            let tokens = Scanner {
+                source: Source::new(source),
+            }
+            .scan()
+            .unwrap();
+
+            Parser::new(tokens)
+                .parse()
+                .unwrap()
+                .iter()
+                .map(|statement| statement.format(0))
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
+
+        #[test]
+        fn it_parses_optional_annotations() {
+            assert_eq!(
+                format("x: Int? = none;\nf := fn(a: Float?): String? { none };\n"),
+                "VAR_DECL
+├─ x: Int?
+├─ NONE
+VAR_DECL
+├─ f
+├─ FN(a: Float?): String?
+│  ├─ BLOCK
+│  │  ├─ NONE"
+            );
+        }
+
+        #[test]
+        fn it_parses_unwrap_and_coalesce() {
+            // `??` binds looser than `||` and groups to the right
+            assert_eq!(
+                format("a ?? b! ?? c || d;\n"),
+                "??
+├─ VAR a
+├─ ??
+│  ├─ UNWRAP
+│  │  ├─ VAR b
+│  ├─ ||
+│  │  ├─ VAR c
+│  │  ├─ VAR d"
+            );
+            assert_eq!(
+                format("!xs[0]!;\n"),
+                "!
+├─ UNWRAP
+│  ├─ INDEX
+│  │  ├─ VAR xs
+│  │  ├─ 0"
+            );
+        }
+
+        #[test]
+        fn it_parses_if_bindings() {
+            assert_eq!(
+                format("if x := y { x; } else { 0; }\n"),
+                "IF_STMT
+├─ BIND x
+├─ CONDITION
+│  ├─ VAR y
+├─ THEN
+│  ├─ BLOCK
+│  │  ├─ VAR x
+├─ ELSE
+│  ├─ BLOCK
+│  │  ├─ 0"
+            );
+        }
+    }
+
+    mod records {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        fn format(source: &str) -> String {
+            let tokens = Scanner {
+                source: Source::new(source),
+            }
+            .scan()
+            .unwrap();
+
+            Parser::new(tokens)
+                .parse()
+                .unwrap()
+                .iter()
+                .map(|statement| statement.format(0))
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
+
+        fn errors(source: &str) -> Vec<String> {
+            let tokens = Scanner {
+                source: Source::new(source),
+            }
+            .scan()
+            .unwrap();
+
+            Parser::new(tokens)
+                .parse()
+                .unwrap_err()
+                .iter()
+                .map(|error| error.message.clone())
+                .collect()
+        }
+
+        #[test]
+        fn it_parses_record_declarations_and_literals() {
+            assert_eq!(
+                format("record Point { x: Int, y: Float?, }\np := Point { x: 1, y: none };\n"),
+                "RECORD Point
+├─ x: Int
+├─ y: Float?
+VAR_DECL
+├─ p
+├─ RECORD_INIT Point
+│  ├─ x
+│  │  ├─ 1
+│  ├─ y
+│  │  ├─ NONE"
+            );
+        }
+
+        #[test]
+        fn it_parses_field_access_and_assignment() {
+            assert_eq!(
+                format("a.b[0].c = a.d;\n"),
+                "FIELD_ASSIGN c
+├─ INDEX
+│  ├─ FIELD b
+│  │  ├─ VAR a
+│  ├─ 0
+├─ FIELD d
+│  ├─ VAR a"
+            );
+        }
+
+        #[test]
+        fn it_does_not_parse_record_literals_in_conditions() {
+            assert_eq!(
+                format("if ready { x: Int = 1; }\n"),
+                "IF_STMT
+├─ CONDITION
+│  ├─ VAR ready
+├─ THEN
+│  ├─ BLOCK
+│  │  ├─ VAR_DECL
+│  │  │  ├─ x: Int
+│  │  │  ├─ 1"
+            );
+            assert_eq!(
+                format("for (P {}).ok {}\n"),
+                "WHILE_STMT
+├─ CONDITION
+│  ├─ FIELD ok
+│  │  ├─ GROUP
+│  │  │  ├─ RECORD_INIT P
+├─ THEN
+│  ├─ BLOCK"
+            );
+        }
+
+        #[test]
+        fn it_rejects_malformed_records() {
+            assert_eq!(
+                errors("record P { x: Int, x: Int }\n"),
+                vec!["Duplicate field 'x'"]
+            );
+            assert_eq!(errors("P { y: 1, y: 2 };\n"), vec!["Duplicate field 'y'"]);
+            assert_eq!(errors("a.1;\n"), vec!["Expected field name after '.'"]);
+        }
+    }
+
+    mod spans {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        fn parse(source: &str) -> Vec<Statement<'_>> {
+            let tokens = Scanner {
+                source: Source::new(source),
+            }
+            .scan()
+            .unwrap();
+
+            Parser::new(tokens).parse().unwrap()
+        }
+
+        fn text(source: &str, span: Span) -> &str {
+            &source[span.start..span.end]
+        }
+
+        #[test]
+        fn it_spans_statements_without_their_semicolons() {
+            let source = "record P { x: Int }
+x: Int? = -(1 + 2) * 3;
+if y := x {
+    y;
+} else { f(P { x: 1 }.x)[0]!; }
+for true { x = [1, 2]; }
+{ }";
+
+            assert_eq!(
+                parse(source)
+                    .iter()
+                    .map(|statement| text(source, statement.span()))
+                    .collect::<Vec<_>>(),
+                vec![
+                    "record P { x: Int }",
+                    "x: Int? = -(1 + 2) * 3",
+                    "if y := x {\n    y;\n} else { f(P { x: 1 }.x)[0]!; }",
+                    "for true { x = [1, 2]; }",
+                    "{ }",
+                ]
+            );
+        }
+
+        #[test]
+        fn it_spans_nested_expressions() {
+            let source = "f(P { x: 1 }.x)[0]! ?? fn(a: Int) { a };";
+            let Statement::Expression(Expression::Coalesce(coalesce)) = &parse(source)[0] else {
+                panic!("Expected a coalesce expression");
+            };
+            let Expression::Unwrap(unwrap) = coalesce.left.as_ref() else {
+                panic!("Expected an unwrap expression");
+            };
+            let Expression::Index(index) = unwrap.target.as_ref() else {
+                panic!("Expected an index expression");
+            };
+            let Expression::Call(call) = index.target.as_ref() else {
+                panic!("Expected a call expression");
+            };
+
+            assert_eq!(text(source, coalesce.right.span()), "fn(a: Int) { a }");
+            assert_eq!(text(source, index.span), "f(P { x: 1 }.x)[0]");
+            assert_eq!(text(source, call.span), "f(P { x: 1 }.x)");
+            assert_eq!(text(source, call.arguments[0].span()), "P { x: 1 }.x");
+        }
+    }
 }
@@ -0,0 +1,140 @@
+#[cfg(test)]
+mod tests {
+    use crate::rename::*;
+
+    /// The messages of a refused rename, or the renamed source
+    fn rename_at(
+        source: &str,
+        line: u64,
+        position: u64,
+        name: &str,
+    ) -> Result<String, Vec<String>> {
+        rename(source, line, position, name).map_err(|diagnostics| {
+            diagnostics
+                .iter()
+                .map(|diagnostic| diagnostic.to_string())
+                .collect()
+        })
+    }
+
+    mod renaming {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_renames_the_declaration_and_every_use() {
+            let source = "x := 1;\nf := fn(): Int { x = x + 1; x };\n{ x := 2; x; }\nf();";
+
+            let expected =
+                "count := 1;\nf := fn(): Int { count = count + 1; count };\n{ x := 2; x; }\nf();";
+
+            // From the declaration or from any use
+            assert_eq!(rename_at(source, 1, 1, "count"), Ok(expected.to_owned()));
+            assert_eq!(rename_at(source, 2, 22, "count"), Ok(expected.to_owned()));
+        }
+
+        #[test]
+        fn it_renames_parameters_and_bindings() {
+            assert_eq!(
+                rename_at(
+                    "f := fn(a: Int?): Int {\n  if b := a { b } else { 0 }\n};",
+                    2,
+                    6,
+                    "value"
+                ),
+                Ok("f := fn(a: Int?): Int {\n  if value := a { value } else { 0 }\n};".to_owned())
+            );
+            assert_eq!(
+                rename_at("f := fn(a: Int): Int { a * 2 };", 1, 24, "n"),
+                Ok("f := fn(n: Int): Int { n * 2 };".to_owned())
+            );
+        }
+    }
+
+    mod refusals {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_refuses_keywords_and_invalid_names() {
+            assert_eq!(
+                rename_at("x := 1;", 1, 1, "return"),
+                Err(vec![
+                    "Rename error at 1:1. Cannot rename 'x' to the keyword 'return'".to_owned()
+                ])
+            );
+
+            for name in ["1x", "a b", "", "x;"] {
+                assert_eq!(
+                    rename_at("x := 1;", 1, 1, name),
+                    Err(vec![format!(
+                        "Rename error at 1:1. '{}' is not a valid name",
+                        name
+                    )])
+                );
+            }
+        }
+
+        #[test]
+        fn it_refuses_names_that_would_be_captured_or_redeclared() {
+            // The inner `y` would hide the outer one from `x + y`
+            assert_eq!(
+                rename_at(
+                    "x := 1;\ny := 2;\n{\n  z := 3;\n  w := y + z;\n}",
+                    4,
+                    3,
+                    "y"
+                ),
+                Err(vec![
+                    "Rename error at 5:8. Renaming 'z' to 'y' would change what a name refers to"
+                        .to_owned()
+                ])
+            );
+
+            // The outer `x` would be hidden by the inner `y` where it is used
+            assert_eq!(
+                rename_at("x := 1;\n{\n  y := 2;\n  x + y;\n}", 1, 1, "y"),
+                Err(vec![
+                    "Rename error at 4:3. Renaming 'x' to 'y' would change what a name refers to"
+                        .to_owned()
+                ])
+            );
+
+            assert_eq!(
+                rename_at("x := 1;\ny := 2;", 1, 1, "y"),
+                Err(vec![
+                    "Rename error at 2:1. Renaming 'x' to 'y' would change what a name refers to"
+                        .to_owned()
+                ])
+            );
+        }
+
+        #[test]
+        fn it_only_renames_variables() {
+            assert_eq!(
+                rename_at("record P { x: Int }\np: P = P { x: 1 };", 2, 4, "Q"),
+                Err(vec!["Rename error at 2:4. 'P' is not a variable".to_owned()])
+            );
+            assert_eq!(
+                rename_at("x := 1 + 2;", 1, 6, "y"),
+                Err(vec![
+                    "Rename error at 1:6. Expected the name of a variable to rename".to_owned()
+                ])
+            );
+        }
+
+        #[test]
+        fn it_needs_a_program_that_parses() {
+            assert_eq!(
+                rename_at("x := ;", 1, 1, "y"),
+                Err(vec!["Parser error at 1:6. Unexpected token ';'".to_owned()])
+            );
+            assert_eq!(
+                rename_at("x := y;", 1, 1, "z"),
+                Err(vec![
+                    "Resolver error at 1:6. Variable 'y' not found in the current scope".to_owned()
+                ])
+            );
+        }
+    }
+}
@@ -0,0 +1,286 @@
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use typed_arena::Arena;
+
+    use crate::{environment::Environment, matcha::*, repl::*, result_text, run, Options};
+
+    /// Runs each line in the same environment, like the REPL does, returning the exit codes
+    fn session(
+        options: &Options,
+        lines: &[&'static str],
+    ) -> (Vec<u8>, Rc<RefCell<Environment<'static>>>) {
+        let environment = Rc::new(RefCell::new(Environment::new()));
+        let codes = lines
+            .iter()
+            .map(|line| run(options, "<repl>", line, Rc::clone(&environment)))
+            .collect();
+
+        (codes, environment)
+    }
+
+    mod environment {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_keeps_functions_arrays_and_records_across_lines() {
+            for vm in [false, true] {
+                let (codes, environment) = session(
+                    &Options {
+                        vm,
+                        ..Options::default()
+                    },
+                    &[
+                        "record Point { x: Int, y: Int }",
+                        "points := [Point { x: 1, y: 2 }];",
+                        "make := fn() { count := 0; fn(): Int { count = count + 1; count } };",
+                        "counter := make();",
+                        "counter(); points[0].x = counter();",
+                        "total := points[0].x + points[0].y;",
+                    ],
+                );
+
+                assert_eq!(codes, vec![0; 6], "vm: {}", vm);
+                assert_eq!(environment.borrow().values["total"].to_string(), "4");
+                assert_eq!(
+                    environment.borrow().values["points"].to_string(),
+                    "[Point { x: 2, y: 2 }]"
+                );
+            }
+        }
+
+        #[test]
+        fn it_keeps_declarations_made_before_an_error() {
+            let (codes, environment) =
+                session(&Options::default(), &["x := 1; y := x / 0;", "x = x + 1;"]);
+
+            assert_eq!(codes, vec![1, 0]);
+            assert_eq!(environment.borrow().values["x"].to_string(), "2");
+            assert!(!environment.borrow().values.contains_key("y"));
+        }
+    }
+    mod input {
+        use super::*;
+
+        #[test]
+        fn it_waits_for_unfinished_statements() {
+            for source in [
+                "if x {",
+                "f := fn(a: Int,",
+                "xs := [1,\n2",
+                "x := (1 + 2",
+                "x := 1",
+                "x := 1 +",
+                "s := \"first line\n",
+                "record P {",
+            ] {
+                assert!(is_incomplete(source), "{:?} is incomplete", source);
+            }
+        }
+
+        #[test]
+        fn it_runs_complete_or_broken_statements() {
+            for source in [
+                "x := 1;",
+                "if x {\n  y;\n}",
+                "s := \"multi\nline\";",
+                "record P { x: Int }",
+                "",
+                // Errors before the end of the input can't be fixed by typing more
+                "x := ) {",
+                "x := 1 2",
+                "x := 1 @ {",
+                "}",
+            ] {
+                assert!(!is_incomplete(source), "{:?} is complete", source);
+            }
+        }
+    }
+    mod completion {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_completes_keywords_and_declared_names() {
+            let (_, environment) = session(
+                &Options::default(),
+                &["first := 1;", "fizz := fn() {};", "other := 2;"],
+            );
+            let environment = environment.borrow();
+
+            assert_eq!(
+                completions(&environment, "x := fi", 7),
+                (5, vec!["first".to_owned(), "fizz".to_owned()])
+            );
+            assert_eq!(
+                completions(&environment, "re", 2),
+                (0, vec!["record".to_owned(), "return".to_owned()])
+            );
+            assert_eq!(
+                completions(&environment, "f(oth)", 5),
+                (2, vec!["other".to_owned()])
+            );
+        }
+
+        #[test]
+        fn it_completes_nothing_without_a_word() {
+            let (_, environment) = session(&Options::default(), &["x := 1;"]);
+
+            assert_eq!(completions(&environment.borrow(), "x + ", 4), (4, vec![]));
+        }
+    }
+
+    mod commands {
+        use super::*;
+        use pretty_assertions::assert_eq;
+        use std::{env, fs};
+
+        /// Runs a command in `session`, returning what it wrote
+        fn command(session: &mut Session, line: &str) -> (Flow, String) {
+            let mut output = Vec::new();
+            let flow = session.command(line, &mut output).unwrap();
+
+            (flow, String::from_utf8(output).unwrap())
+        }
+
+        #[test]
+        fn it_lists_the_environment_with_types() {
+            let sources = Arena::new();
+            let mut session = Session::new(&Options::default(), &sources);
+            session.run(
+                "<repl>",
+                "xs := [1.5]; name := \"matcha\"; n := 1;".to_owned(),
+            );
+
+            assert_eq!(
+                command(&mut session, ":env"),
+                (
+                    Flow::Continue,
+                    "n: Int\nname: String\nxs: [Float]\n".to_owned()
+                )
+            );
+        }
+
+        #[test]
+        fn it_shows_types_without_evaluating() {
+            let sources = Arena::new();
+            let mut session = Session::new(&Options::default(), &sources);
+            session.run("<repl>", "x := 1;".to_owned());
+
+            assert_eq!(command(&mut session, ":type x + 1").1, "Int\n");
+            assert_eq!(command(&mut session, ":type x + \"a\";").1, "");
+
+            command(&mut session, ":type x = 2");
+            assert_eq!(session.environment.borrow().values["x"].to_string(), "1");
+        }
+
+        #[test]
+        fn it_toggles_output_options() {
+            let sources = Arena::new();
+            let mut session = Session::new(&Options::default(), &sources);
+
+            assert_eq!(command(&mut session, ":ast").1, "AST output on\n");
+            assert!(session.options.ast);
+            assert_eq!(command(&mut session, ":ast").1, "AST output off\n");
+            assert!(!session.options.ast);
+
+            // Code after the command is run with the option on just for that input
+            command(&mut session, ":tokens x := 1;");
+            assert!(!session.options.lexer_out);
+            assert_eq!(session.environment.borrow().values["x"].to_string(), "1");
+        }
+
+        #[test]
+        fn it_loads_files_and_resets() {
+            let path = env::temp_dir().join("matcha_repl_load.mt");
+            fs::write(
+                &path,
+                "double := fn(a: Int): Int { a * 2 };\nfour := double(2);",
+            )
+            .unwrap();
+
+            let sources = Arena::new();
+            let mut session = Session::new(&Options::default(), &sources);
+            command(&mut session, &format!(":load {}", path.display()));
+            fs::remove_file(&path).unwrap();
+
+            assert_eq!(session.environment.borrow().values["four"].to_string(), "4");
+
+            let (flow, output) = command(&mut session, ":load missing.mt");
+            assert_eq!(flow, Flow::Continue);
+            assert!(output.starts_with("Could not read missing.mt: "));
+        }
+
+        #[test]
+        fn it_keeps_inputs_for_as_long_as_the_session() {
+            let sources = Arena::new();
+            let mut session = Session::new(&Options::default(), &sources);
+            session.run("<repl>", "name := \"matcha\";".to_owned());
+            session.run("<repl>", "f := fn(): String { name };".to_owned());
+            // Runtime errors keep what was declared before them, which borrows from the input
+            session.run("<repl>", "tea := f(); 1 / 0;".to_owned());
+
+            assert_eq!(sources.len(), 3);
+            assert_eq!(
+                session.environment.borrow().values["tea"].to_string(),
+                "matcha"
+            );
+
+            // Resetting ends the session, which frees its inputs along with the environment
+            assert_eq!(
+                command(&mut session, ":reset"),
+                (Flow::Reset, String::new())
+            );
+        }
+
+        #[test]
+        fn it_quits_and_rejects_unknown_commands() {
+            let sources = Arena::new();
+            let mut session = Session::new(&Options::default(), &sources);
+
+            assert_eq!(command(&mut session, ":quit").0, Flow::Quit);
+            assert!(command(&mut session, ":exit")
+                .1
+                .starts_with("Unknown command :exit. Commands: :env"));
+        }
+    }
+
+    mod echo {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_shows_values_with_their_types() {
+            let (_, environment) = session(&Options::default(), &["xs := [1, 2];"]);
+            let xs = &environment.borrow().values["xs"];
+            let r#type = Type::Array(Box::new(Type::Integer));
+
+            assert_eq!(
+                result_text(xs, &r#type, true),
+                Some("[1, 2]: [Int]".to_owned())
+            );
+            assert_eq!(result_text(xs, &r#type, false), Some("[1, 2]".to_owned()));
+        }
+
+        #[test]
+        fn it_shows_nothing_for_statements() {
+            let (_, environment) = session(&Options::default(), &["x := 1;"]);
+            let x = &environment.borrow().values["x"];
+
+            // `if true { x; }` has a value but isn't an expression statement
+            assert_eq!(result_text(x, &Type::Empty, true), None);
+            assert_eq!(result_text(&Value::Empty, &Type::Empty, true), None);
+        }
+
+        #[test]
+        fn it_echoes_in_the_repl() {
+            assert!(
+                Session::new(&Options::default(), &Arena::new())
+                    .options
+                    .echo
+            );
+        }
+    }
+}
@@ -0,0 +1,266 @@
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use crate::{
+        environment::Environment,
+        interpreter::*,
+        matcha::{Literal, NumberLiteral, Value},
+        parser::*,
+        resolver::*,
+        scanner::*,
+        source::*,
+        statement::Statement,
+    };
+
+    fn parse(source: &str) -> Vec<Statement<'_>> {
+        let tokens = Scanner {
+            source: Source::new(source),
+        }
+        .scan()
+        .unwrap();
+
+        Parser::new(tokens).parse().unwrap()
+    }
+
+    fn diagnostics(source: &str) -> Vec<String> {
+        Resolver::new()
+            .resolve(&mut parse(source))
+            .iter()
+            .map(|error| match error.note {
+                Some(ref note) => format!("{} ({})", error, note),
+                None => error.to_string(),
+            })
+            .collect()
+    }
+
+    /// The tree printed by `--ast`, which shows where variables were resolved
+    fn format(source: &str) -> String {
+        let mut statements = parse(source);
+        Resolver::new().resolve(&mut statements);
+
+        statements
+            .iter()
+            .map(|statement| statement.format(0))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    mod resolution {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_resolves_locals_by_depth_and_slot() {
+            assert_eq!(
+                format("g := 1; { a := 1; b := 2; { b = a + g; } b; }"),
+                "VAR_DECL
+├─ g
+├─ 1
+BLOCK
+├─ VAR_DECL
+│  ├─ a
+│  ├─ 1
+├─ VAR_DECL
+│  ├─ b
+│  ├─ 2
+├─ BLOCK
+│  ├─ VAR_ASSIGN
+│  │  ├─ b (depth 1, slot 1)
+│  │  ├─ +
+│  │  │  ├─ VAR a (depth 1, slot 0)
+│  │  │  ├─ VAR g
+├─ VAR b (depth 0, slot 1)"
+            );
+        }
+
+        #[test]
+        fn it_resolves_parameters_bindings_and_later_declarations() {
+            assert_eq!(
+                format(
+                    "x: Int? = 1;
+                    if y := x { f := fn(n) { n + y + later }; later := 2; f(1); }"
+                ),
+                "VAR_DECL
+├─ x: Int?
+├─ 1
+IF_STMT
+├─ BIND y
+├─ CONDITION
+│  ├─ VAR x
+├─ THEN
+│  ├─ BLOCK
+│  │  ├─ VAR_DECL
+│  │  │  ├─ f
+│  │  │  ├─ FN(n)
+│  │  │  │  ├─ BLOCK
+│  │  │  │  │  ├─ +
+│  │  │  │  │  │  ├─ +
+│  │  │  │  │  │  │  ├─ VAR n (depth 0, slot 0)
+│  │  │  │  │  │  │  ├─ VAR y (depth 1, slot 0)
+│  │  │  │  │  │  ├─ VAR later (depth 1, slot 2)
+│  │  ├─ VAR_DECL
+│  │  │  ├─ later
+│  │  │  ├─ 2
+│  │  ├─ CALL
+│  │  │  ├─ VAR f (depth 0, slot 1)
+│  │  │  ├─ ARGS
+│  │  │  │  ├─ 1"
+            );
+        }
+
+        #[test]
+        fn it_runs_resolved_programs() {
+            let source = "x := 1;
+                {
+                    y := x;
+                    x := 10;
+                    counter := fn() { x = x + 1; x };
+                    counter();
+                    [y, x, counter(), x];
+                }";
+            let mut statements = parse(source);
+
+            assert!(Resolver::new().resolve(&mut statements).is_empty());
+
+            let environment = Rc::new(RefCell::new(Environment::new()));
+            let result = Interpreter::interpret(Rc::clone(&environment), &statements).unwrap();
+
+            assert_eq!(result.to_string(), "[1, 11, 12, 12]");
+            assert!(matches!(
+                environment.borrow().values["x"],
+                Value::Literal(Literal::Number(NumberLiteral::Integer(1)))
+            ));
+        }
+    }
+
+    mod diagnostics {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_reports_uses_before_declaration() {
+            assert_eq!(
+                diagnostics("a := b;\nb := 1;"),
+                vec!["Resolver error at 1:6. Variable 'b' is used before its declaration ('b' is declared at 2:1)"]
+            );
+            assert_eq!(
+                diagnostics("{ x = 1; x := 2; x; }"),
+                vec!["Resolver error at 1:3. Variable 'x' is used before its declaration ('x' is declared at 1:10)"]
+            );
+
+            // Functions run after the declarations of the scopes around them
+            assert!(diagnostics("f := fn() { g() }; g := fn() { 1 }; f();").is_empty());
+        }
+
+        #[test]
+        fn it_reports_unknown_variables() {
+            assert_eq!(
+                diagnostics("{ inner := 1; inner; } inner; outer = 2;"),
+                vec![
+                    "Resolver error at 1:24. Variable 'inner' not found in the current scope",
+                    "Resolver error at 1:31. Cannot assign a value to undeclared variable 'outer'",
+                ]
+            );
+
+            let mut environment = Environment::new();
+            environment.values.insert("outer".to_owned(), Value::Empty);
+
+            assert!(Resolver::with_environment(&environment)
+                .resolve(&mut parse("outer = 2;"))
+                .is_empty());
+        }
+
+        #[test]
+        fn it_reports_redeclarations_in_the_same_scope() {
+            assert_eq!(
+                diagnostics("x := 1;\nx := 2;\n{ x := 3; x; }"),
+                vec!["Resolver error at 2:1. Variable 'x' already declared in this scope ('x' was first declared at 1:1)"]
+            );
+            assert_eq!(
+                diagnostics("record P {}\nrecord P { x: Int }"),
+                vec!["Resolver error at 2:8. Record 'P' already declared in this scope ('P' was first declared at 1:8)"]
+            );
+            assert_eq!(
+                diagnostics("f := fn(a) { a := 1; a };"),
+                vec!["Resolver error at 1:14. Variable 'a' already declared in this scope ('a' was first declared at 1:9)"]
+            );
+
+            let variable = Resolver::new().resolve(&mut parse("x := 1;\nx := 2;"));
+            let record = Resolver::new().resolve(&mut parse("record P {}\nrecord P {}"));
+
+            assert_eq!(
+                variable[0].help,
+                Some("use `=` instead of `:=` to assign a new value to it")
+            );
+            assert_eq!(record[0].help, None);
+        }
+
+        #[test]
+        fn it_warns_about_unused_variables() {
+            assert_eq!(
+                diagnostics(
+                    "unused_global := 1;
+                    f := fn(unused_parameter) {
+                        written := 1;
+                        written = 2;
+                        record Unused {}
+                        read := 3;
+                        read
+                    };"
+                ),
+                vec!["Resolver warning at 3:25. Variable 'written' is never read"]
+            );
+        }
+    }
+
+    mod references {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        /// Every name as `name l:p -> l:p`, pointing at its declaration
+        fn references(source: &str) -> Vec<String> {
+            let (_, references) = Resolver::new().resolve_references(&mut parse(source));
+
+            references
+                .iter()
+                .map(|reference| {
+                    format!(
+                        "{} {}:{} -> {}:{}",
+                        reference.name.lexeme,
+                        reference.name.line,
+                        reference.name.position,
+                        reference.declaration.line,
+                        reference.declaration.position
+                    )
+                })
+                .collect()
+        }
+
+        #[test]
+        fn it_links_names_to_the_declarations_they_see() {
+            assert_eq!(
+                references(
+                    "record P { x: Int }
+x := 1;
+f := fn(p: P): Int { x := p.x; x };
+x = f(P { x: x });"
+                ),
+                vec![
+                    "P 1:8 -> 1:8",
+                    "x 2:1 -> 2:1",
+                    "f 3:1 -> 3:1",
+                    "p 3:9 -> 3:9",
+                    "P 3:12 -> 1:8",
+                    "x 3:22 -> 3:22",
+                    "p 3:27 -> 3:9",
+                    "x 3:32 -> 3:22",
+                    "x 4:1 -> 2:1",
+                    "f 4:5 -> 3:1",
+                    "P 4:7 -> 1:8",
+                    "x 4:14 -> 2:1",
+                ]
+            );
+        }
+    }
+}
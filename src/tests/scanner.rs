@@ -0,0 +1,203 @@
+#[cfg(test)]
+mod tests {
+    use crate::{scanner::*, source::*, token::*};
+
+    fn scan(source: &str) -> (Vec<Token<'_>>, Vec<String>) {
+        let (tokens, errors) = Scanner {
+            source: Source::new(source),
+        }
+        .scan_recovering();
+
+        (
+            tokens,
+            errors.iter().map(|error| error.to_string()).collect(),
+        )
+    }
+
+    mod recovery {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_reports_every_error() {
+            let (_, errors) = scan("a @ b;\nc := 1.;\nd $ é;\n\"open");
+
+            assert_eq!(
+                errors,
+                vec![
+                    "Scanner error at 1:3. Unknown token",
+                    "Scanner error at 2:7. Invalid number",
+                    "Scanner error at 3:3. Unknown token",
+                    "Scanner error at 3:5. Unknown token",
+                    "Scanner error at 4:5. Unterminated string",
+                ]
+            );
+        }
+
+        #[test]
+        fn it_replaces_invalid_text_with_error_tokens() {
+            let (tokens, _) = scan("a @ b;\n");
+
+            assert_eq!(
+                tokens
+                    .iter()
+                    .map(|token| (token.token_type, token.lexeme))
+                    .collect::<Vec<_>>(),
+                vec![
+                    (TokenType::Identifier, "a"),
+                    (TokenType::Error, "@"),
+                    (TokenType::Identifier, "b"),
+                    (TokenType::SemiColon, ";"),
+                    (TokenType::Eof, ""),
+                ]
+            );
+        }
+
+        #[test]
+        fn it_fails_scan_with_every_error() {
+            let errors = Scanner {
+                source: Source::new("@ #\n"),
+            }
+            .scan()
+            .unwrap_err();
+
+            assert_eq!(errors.len(), 2);
+        }
+    }
+
+    mod spans {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        fn spans(source: &str) -> Vec<(&str, u64, u64, &str)> {
+            let (tokens, _) = scan(source);
+
+            tokens
+                .iter()
+                .map(|token| {
+                    (
+                        token.lexeme,
+                        token.line,
+                        token.position,
+                        &source[token.span.start..token.span.end],
+                    )
+                })
+                .collect()
+        }
+
+        #[test]
+        fn it_maps_tokens_back_to_the_source() {
+            assert_eq!(
+                spans("x := 1.5;\nx >= 2"),
+                vec![
+                    ("x", 1, 1, "x"),
+                    (":=", 1, 3, ":="),
+                    ("1.5", 1, 6, "1.5"),
+                    (";", 1, 9, ";"),
+                    ("x", 2, 1, "x"),
+                    (">=", 2, 3, ">="),
+                    ("2", 2, 6, "2"),
+                    ("", 2, 7, ""),
+                ]
+            );
+        }
+
+        #[test]
+        fn it_spans_multi_line_strings() {
+            assert_eq!(
+                spans("a := \"one\ntwo\";\nb;"),
+                vec![
+                    ("a", 1, 1, "a"),
+                    (":=", 1, 3, ":="),
+                    ("\"one\ntwo\"", 1, 6, "\"one\ntwo\""),
+                    (";", 2, 5, ";"),
+                    ("b", 3, 1, "b"),
+                    (";", 3, 2, ";"),
+                    ("", 3, 3, ""),
+                ]
+            );
+        }
+
+        #[test]
+        fn it_counts_positions_in_characters_and_spans_in_bytes() {
+            let (tokens, _) = scan("\"héllo\" + é");
+
+            assert_eq!(
+                tokens
+                    .iter()
+                    .map(|token| (token.position, token.span))
+                    .collect::<Vec<_>>(),
+                vec![
+                    (1, Span::new(0, 8)),
+                    (9, Span::new(9, 10)),
+                    (11, Span::new(11, 13)),
+                    (12, Span::new(13, 13)),
+                ]
+            );
+        }
+    }
+
+    mod trivia {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        fn scan_with_trivia(source: &str) -> Vec<Token<'_>> {
+            Scanner {
+                source: Source::new(source),
+            }
+            .scan_with_trivia()
+            .0
+        }
+
+        #[test]
+        fn it_keeps_comments_only_when_asked() {
+            let source = "x := 1; // one\n// two\nx / 2;";
+            let tokens = scan_with_trivia(source);
+
+            assert_eq!(
+                tokens
+                    .iter()
+                    .filter(|token| token.token_type == TokenType::Comment)
+                    .map(|token| (token.lexeme, token.line, token.position))
+                    .collect::<Vec<_>>(),
+                vec![("// one", 1, 9), ("// two", 2, 1)]
+            );
+            assert!(scan(source)
+                .0
+                .iter()
+                .all(|token| !token.token_type.is_trivia()));
+        }
+
+        #[test]
+        fn it_keeps_whitespace_runs_with_where_they_start() {
+            let tokens = scan_with_trivia("x :=\t1;\n\n  y");
+
+            assert_eq!(
+                tokens
+                    .iter()
+                    .filter(|token| token.token_type == TokenType::Whitespace)
+                    .map(|token| (token.lexeme, token.line, token.position))
+                    .collect::<Vec<_>>(),
+                vec![(" ", 1, 2), ("\t", 1, 5), ("\n\n  ", 1, 8)]
+            );
+            assert_eq!(tokens.last().unwrap().line, 3);
+        }
+
+        #[test]
+        fn it_reproduces_the_source() {
+            for source in [
+                "f := fn(a: Int): Int { a * 2 }; // double\r\n\tf(1);\n",
+                "s := \"é\" ; x := 1. @ y",
+                "x := \"unterminated\n  string",
+                "",
+            ] {
+                let text: String = scan_with_trivia(source)
+                    .iter()
+                    .map(|token| token.lexeme)
+                    .collect();
+
+                assert_eq!(text, source);
+            }
+        }
+    }
+}
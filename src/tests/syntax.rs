@@ -0,0 +1,134 @@
+#[cfg(test)]
+mod tests {
+    use crate::{parser::*, scanner::*, source::*, syntax::*, token::*};
+
+    fn scan_with_trivia(source: &str) -> Vec<Token<'_>> {
+        Scanner {
+            source: Source::new(source),
+        }
+        .scan_with_trivia()
+        .0
+    }
+
+    fn parse_syntax(source: &str) -> (SyntaxNode<'_>, Vec<String>) {
+        let (tree, errors) = Parser::new(scan_with_trivia(source)).parse_syntax();
+
+        (tree, errors.iter().map(|error| error.to_string()).collect())
+    }
+
+    fn text(node: &SyntaxNode) -> String {
+        node.descendant_tokens()
+            .iter()
+            .map(|token| token.lexeme)
+            .collect()
+    }
+
+    /// The kinds of the nodes under `node`, depth first
+    fn kinds(node: &SyntaxNode) -> Vec<SyntaxKind> {
+        node.nodes()
+            .flat_map(|child| [vec![child.kind], kinds(child)].concat())
+            .collect()
+    }
+
+    mod lossless {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_reproduces_the_source() {
+            for source in [
+                "f := fn(a: Int, b) : Int {\n    // sum\n    a + b\n};\n\n",
+                "record P { x: Int?, }\np := P{x:1};\tp.x = (p.x ?? 2) * -3; // done",
+                "xs := [1 , 2];\nif y := xs[0] { y } else { xs[1]! }\nfor false {}\r\n",
+            ] {
+                let (tree, errors) = parse_syntax(source);
+
+                assert_eq!(errors, Vec::<String>::new());
+                assert_eq!(text(&tree), source);
+            }
+        }
+
+        #[test]
+        fn it_keeps_code_that_does_not_parse() {
+            let source = "x := ) + 1;\ny := 2; // fine\nz := fn(a, a) {};\"unterminated";
+            let (tree, errors) = parse_syntax(source);
+
+            assert_eq!(errors.len(), 3);
+            assert_eq!(text(&tree), source);
+            assert_eq!(
+                tree.nodes().map(|node| node.kind).collect::<Vec<_>>(),
+                vec![
+                    SyntaxKind::Error,
+                    SyntaxKind::VariableDeclaration,
+                    SyntaxKind::Error,
+                    SyntaxKind::Error
+                ]
+            );
+        }
+    }
+
+    mod shape {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_nests_nodes_by_precedence() {
+            let (tree, _) = parse_syntax("a = b + c * d(e)[0];");
+
+            assert_eq!(
+                kinds(&tree),
+                vec![
+                    SyntaxKind::ExpressionStatement,
+                    SyntaxKind::Assignment,
+                    SyntaxKind::Variable,
+                    SyntaxKind::Binary,
+                    SyntaxKind::Variable,
+                    SyntaxKind::Binary,
+                    SyntaxKind::Variable,
+                    SyntaxKind::Index,
+                    SyntaxKind::Call,
+                    SyntaxKind::Variable,
+                    SyntaxKind::ArgumentList,
+                    SyntaxKind::Variable,
+                    SyntaxKind::Literal,
+                ]
+            );
+        }
+
+        #[test]
+        fn it_leaves_trivia_between_nodes_outside_of_them() {
+            let source = "// first\nx := 1;  // one\n";
+            let (tree, _) = parse_syntax(source);
+            let declaration = tree.nodes().next().unwrap();
+
+            assert_eq!(declaration.span(), Span::new(9, 16));
+            assert_eq!(text(declaration), "x := 1;");
+            assert_eq!(
+                tree.tokens()
+                    .map(|token| token.token_type)
+                    .collect::<Vec<_>>(),
+                vec![TokenType::Eof]
+            );
+        }
+    }
+
+    mod lowering {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_lowers_the_same_statements_with_or_without_trivia() {
+            let source = "record P { x: Int }\nf := fn(p: P): Int {\n  // x\n  return p.x;\n};\nf(P { x: 1 });";
+            let without_trivia = Scanner {
+                source: Source::new(source),
+            }
+            .scan()
+            .unwrap();
+
+            assert_eq!(
+                Parser::new(scan_with_trivia(source)).parse().unwrap(),
+                Parser::new(without_trivia).parse().unwrap()
+            );
+        }
+    }
+}
@@ -0,0 +1,393 @@
+#[cfg(test)]
+mod tests {
+    use crate::{parser::*, scanner::*, source::*, type_checker::*};
+
+    fn check(program: &str) -> Vec<String> {
+        let tokens = Scanner {
+            source: Source::new(program),
+        }
+        .scan()
+        .unwrap();
+        let statements = Parser::new(tokens).parse().unwrap();
+
+        match TypeChecker::new().infer(&statements) {
+            Ok(_) => vec![],
+            Err(errors) => errors.iter().map(|error| error.to_string()).collect(),
+        }
+    }
+
+    mod declarations {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_accepts_matching_annotations() {
+            assert_eq!(
+                check("a: Int = 5; b: Float = 1.5; c: String = \"c\"; d: Bool = a > 2;"),
+                Vec::<String>::new()
+            );
+        }
+
+        #[test]
+        fn it_rejects_mismatched_annotations() {
+            assert_eq!(
+                check("x: Int = \"five\";"),
+                vec!["Type error at 1:4. Cannot initialize 'x' of type Int with a value of type String"]
+            );
+        }
+
+        #[test]
+        fn it_rejects_unknown_type_names() {
+            assert_eq!(
+                check("x: i32 = 5;"),
+                vec!["Type error at 1:4. Unknown type 'i32'"]
+            );
+        }
+
+        #[test]
+        fn it_checks_assignments_against_the_declared_type() {
+            assert_eq!(
+                check(
+                    "x := 1;
+{ x = true; }"
+                ),
+                vec!["Type error at 2:3. Cannot assign a value of type Bool to 'x' of type Int"]
+            );
+        }
+    }
+
+    mod expressions {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_infers_numeric_promotion() {
+            assert_eq!(check("x: Float = 1 + 2.5;"), Vec::<String>::new());
+            assert_eq!(
+                check("x: Int = 1 * 2.5;"),
+                vec!["Type error at 1:4. Cannot initialize 'x' of type Int with a value of type Float"]
+            );
+        }
+
+        #[test]
+        fn it_only_allows_integer_bitwise_operands() {
+            assert_eq!(check("x: Int = ~1 << 2 & 3 | 4 ^ 5;"), Vec::<String>::new());
+            assert_eq!(
+                check("1.5 >> 1; ~true;"),
+                vec![
+                    "Type error at 1:5. Operator '>>' cannot be applied to Float and Int",
+                    "Type error at 1:11. Operator '~' cannot be applied to Bool",
+                ]
+            );
+        }
+
+        #[test]
+        fn it_only_compares_literals_and_their_optionals() {
+            assert_eq!(
+                check("a: Int? = 1; a == 1.5; \"a\" != \"b\"; x := none; x == true;"),
+                Vec::<String>::new()
+            );
+            assert_eq!(
+                check(
+                    "record P { x: Int }\n[1] == [1]; P { x: 1 } != P { x: 1 }; f := fn(a: Int): Int { a }; f == f;"
+                ),
+                vec![
+                    "Type error at 2:5. Can't compare [Int] with [Int]",
+                    "Type error at 2:24. Can't compare P with P",
+                    "Type error at 2:69. Can't compare fn(Int): Int with fn(Int): Int",
+                ]
+            );
+        }
+
+        #[test]
+        fn it_reports_every_error() {
+            assert_eq!(
+                check("1 + \"a\"; !3; true == 1;"),
+                vec![
+                    "Type error at 1:3. Operator '+' cannot be applied to Int and String",
+                    "Type error at 1:10. Operator '!' cannot be applied to Int",
+                    "Type error at 1:19. Can't compare Bool with Int",
+                ]
+            );
+        }
+    }
+
+    mod conditions {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_rejects_non_boolean_conditions() {
+            assert_eq!(
+                check("i := 0; if i { } for \"forever\" { }"),
+                vec![
+                    "Type error at 1:12. Expected condition of type Bool, got Int",
+                    "Type error at 1:22. Expected condition of type Bool, got String",
+                ]
+            );
+        }
+
+        #[test]
+        fn it_scopes_declarations_to_blocks() {
+            assert_eq!(
+                check("x := 1; if x > 0 { x := \"shadow\"; x = \"ok\"; } x = 2;"),
+                Vec::<String>::new()
+            );
+        }
+    }
+
+    mod functions {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_infers_call_results() {
+            assert_eq!(
+                check(
+                    "add := fn(a: Int, b: Int): Int { a + b };
+                    double := fn(a: Float) { a * 2.0 };
+                    x: Int = add(1, 2);
+                    y: Float = double(1.5);"
+                ),
+                Vec::<String>::new()
+            );
+        }
+
+        #[test]
+        fn it_checks_return_types() {
+            assert_eq!(
+                check(
+                    "f := fn(a: Int): Int {
+    if a > 0 { return \"positive\"; }
+    true
+};"
+                ),
+                vec![
+                    "Type error at 2:16. Expected a return value of type Int, got String",
+                    "Type error at 3:5. Expected the function body to evaluate to Int, got Bool",
+                ]
+            );
+        }
+
+        #[test]
+        fn it_checks_calls() {
+            assert_eq!(
+                check(
+                    "f := fn(a: Int, b: String) { b };
+f(1);
+f(\"1\", \"2\");
+x := 3;
+x();"
+                ),
+                vec![
+                    "Type error at 2:2. Expected 2 arguments but got 1",
+                    "Type error at 3:3. Expected argument 1 to be of type Int, got String",
+                    "Type error at 5:2. Cannot call a value of type Int",
+                ]
+            );
+        }
+
+        #[test]
+        fn it_checks_function_type_annotations() {
+            assert_eq!(
+                check(
+                    "make := fn(start: Int): fn(): Int { fn(): Int { start } };
+counter: fn(): Int = make(1);
+apply := fn(f: fn(Int): Int?, x: Int): Int { f(x) ?? 0 };
+n: Int = apply(fn(a: Int): Int? { a * 2 }, counter());"
+                ),
+                Vec::<String>::new()
+            );
+            assert_eq!(
+                check(
+                    "f: fn(Int): Int = fn(a: String): Int { 1 };
+g: fn(Int, Int) = fn(a: Int) { a };"
+                ),
+                vec![
+                    "Type error at 1:4. Cannot initialize 'f' of type fn(Int): Int with a value of type fn(String): Int",
+                    "Type error at 2:4. Cannot initialize 'g' of type fn(Int, Int): Unknown with a value of type fn(Int): Int",
+                ]
+            );
+        }
+
+        #[test]
+        fn it_checks_recursive_functions() {
+            assert_eq!(
+                check("fact := fn(n: Int): Int { if n < 2 { return 1; } n * fact(n - 1) };"),
+                Vec::<String>::new()
+            );
+        }
+    }
+
+    mod arrays {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_infers_element_types() {
+            assert_eq!(
+                check("xs := [[1], []]; x: Int = xs[0][0]; xs[1] = [2];"),
+                Vec::<String>::new()
+            );
+        }
+
+        #[test]
+        fn it_reports_array_errors() {
+            assert_eq!(
+                check(
+                    "xs := [1, \"two\"];
+ys := [1.5];
+ys[0] = 1;
+ys[\"0\"];
+3[0];"
+                ),
+                vec![
+                    "Type error at 1:11. Array elements must share a type, expected Int, got String",
+                    "Type error at 3:3. Cannot assign a value of type Int to an element of type Float",
+                    "Type error at 4:3. Array index must be an Int, got String",
+                    "Type error at 5:2. Can only index arrays, got Int",
+                ]
+            );
+        }
+    }
+
+    mod optionals {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_accepts_optional_values() {
+            assert_eq!(
+                check(
+                    "a: Int? = none; a = 5; b: Int = a ?? 0; c: Int = a! + 1;
+f := fn(x: Float?): Float { if y := x { y } else { 0.0 } };
+f(1.5); f(none); d := a == none;"
+                ),
+                Vec::<String>::new()
+            );
+        }
+
+        #[test]
+        fn it_makes_arrays_of_values_and_none_optional_in_either_order() {
+            assert_eq!(
+                check(
+                    "a := [1, none]; b := [none, 1]; c := [[none], [2]];
+w: Int = a[1] ?? b[0] ?? c[1][0] ?? 0;
+x: Int = a[0];
+y: Int = b[1];"
+                ),
+                vec![
+                    "Type error at 3:4. Cannot initialize 'x' of type Int with a value of type Int?",
+                    "Type error at 4:4. Cannot initialize 'y' of type Int with a value of type Int?",
+                ]
+            );
+            assert_eq!(
+                check("[none, 1, \"a\"];"),
+                vec!["Type error at 1:11. Array elements must share a type, expected Int?, got String"]
+            );
+        }
+
+        #[test]
+        fn it_requires_unwrapping_optionals() {
+            assert_eq!(
+                check(
+                    "a: Int? = 1;
+b: Int = a;
+a + 1;
+2!;
+3 ?? 4;
+a ?? \"x\";
+if y := 5 { y; }
+a = \"s\";"
+                ),
+                vec![
+                    "Type error at 2:4. Cannot initialize 'b' of type Int with a value of type Int?",
+                    "Type error at 3:3. Operator '+' cannot be applied to Int? and Int",
+                    "Type error at 4:2. Cannot unwrap a non-optional value of type Int",
+                    "Type error at 5:3. Operator '??' expects an optional value, got Int",
+                    "Type error at 6:3. Cannot use a fallback of type String for a value of type Int?",
+                    "Type error at 7:4. Expected an optional value to bind to 'y', got Int",
+                    "Type error at 8:1. Cannot assign a value of type String to 'a' of type Int?",
+                ]
+            );
+        }
+    }
+
+    mod records {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_checks_fields() {
+            assert_eq!(
+                check(
+                    "record Point { x: Int, y: Float }
+p: Point = Point { x: 1, y: 2.5 };
+total: Float = p.x + p.y;
+record Node { value: Int, next: Node? }
+n := Node { value: 1, next: none };
+if next := n.next { v: Int = next.value; }"
+                ),
+                Vec::<String>::new()
+            );
+        }
+
+        #[test]
+        fn it_reports_field_errors() {
+            assert_eq!(
+                check(
+                    "record Point { x: Int, y: Int }
+p := Point { x: \"1\", z: 2 };
+p.x = true;
+s: String = p.y;
+p.w;
+n := 1;
+n.x;
+q := n { };"
+                ),
+                vec![
+                    "Type error at 2:14. Cannot initialize field 'x' of type Int with a value of type String",
+                    "Type error at 2:22. Record Point has no field 'z'",
+                    "Type error at 2:6. Missing field 'y' in Point",
+                    "Type error at 3:3. Cannot assign a value of type Bool to field 'x' of type Int",
+                    "Type error at 4:4. Cannot initialize 's' of type String with a value of type Int",
+                    "Type error at 5:3. Record Point has no field 'w'",
+                    "Type error at 7:3. Can only access fields on records, got Int",
+                    "Type error at 8:6. 'n' is not a record, got Int",
+                ]
+            );
+        }
+    }
+
+    mod inference {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        fn infer(program: &str) -> String {
+            let tokens = Scanner {
+                source: Source::new(program),
+            }
+            .scan()
+            .unwrap();
+            let statements = Parser::new(tokens).parse().unwrap();
+
+            TypeChecker::new().infer(&statements).unwrap().to_string()
+        }
+
+        #[test]
+        fn it_infers_the_type_of_the_last_expression() {
+            assert_eq!(infer("x := 1; x + 2;"), "Int");
+            assert_eq!(infer("xs := [1.5]; xs[0];"), "Float");
+            assert_eq!(
+                infer("f := fn(a: Int): String { \"\" }; f;"),
+                "fn(Int): String"
+            );
+        }
+
+        #[test]
+        fn it_infers_empty_for_statements() {
+            assert_eq!(infer("x := 1;"), "Empty");
+            assert_eq!(infer("1; if true { 2; }"), "Empty");
+        }
+    }
+}
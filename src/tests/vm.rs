@@ -0,0 +1,214 @@
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use crate::{
+        assembler::assemble, compiler::*, environment::Environment, interpreter::InterpreterError,
+        matcha::Value, parser::*, scanner::*, source::*, vm::*,
+    };
+
+    fn run<'a>(
+        program: &'a str,
+        environment: Rc<RefCell<Environment<'a>>>,
+    ) -> Result<Value<'a>, InterpreterError<'a>> {
+        let tokens = Scanner {
+            source: Source::new(program),
+        }
+        .scan()
+        .unwrap();
+        let statements = Parser::new(tokens).parse().unwrap();
+        let program = Compiler::compile(&statements)?;
+
+        Vm::interpret(environment, &program)
+    }
+
+    fn interpret(program: &str) -> Result<Value<'_>, InterpreterError<'_>> {
+        run(program, Rc::new(RefCell::new(Environment::new())))
+    }
+
+    mod closures {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_captures_each_loop_iteration_separately() {
+            let result = interpret(
+                "closures := [fn() { 0 }, fn() { 0 }, fn() { 0 }];
+                i := 0;
+                for i < 3 {
+                    j := i * 10;
+                    closures[i] = fn() { j };
+                    i = i + 1;
+                }
+                [closures[0](), closures[1](), closures[2]()];",
+            )
+            .unwrap();
+
+            assert_eq!(result.to_string(), "[0, 10, 20]");
+        }
+
+        #[test]
+        fn it_shares_captured_variables_between_closures() {
+            let result = interpret(
+                "{
+                    count := 0;
+                    increment := fn() { count = count + 1; };
+                    read := fn() { count };
+                    increment();
+                    increment();
+                    count = count * 10;
+                    read();
+                }",
+            )
+            .unwrap();
+
+            assert_eq!(result.to_string(), "20");
+        }
+
+        #[test]
+        fn it_captures_through_nested_functions() {
+            let result = interpret(
+                "outer := fn(a) {
+                    middle := fn(b) {
+                        fn(c) { a * 100 + b * 10 + c }
+                    };
+                    middle(2)
+                };
+                outer(1)(3);",
+            )
+            .unwrap();
+
+            assert_eq!(result.to_string(), "123");
+        }
+    }
+
+    mod globals {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn it_reads_and_writes_back_the_environment() {
+            let source = String::from("x = x + 1; y := x * 2; y;");
+            let environment = Rc::new(RefCell::new(Environment::new()));
+            environment.borrow_mut().values.insert(
+                "x".to_owned(),
+                run("1;", Rc::new(RefCell::new(Environment::new()))).unwrap(),
+            );
+
+            let result = run(&source, Rc::clone(&environment)).unwrap();
+
+            assert_eq!(result.to_string(), "4");
+            assert_eq!(environment.borrow().values["x"].to_string(), "2");
+            assert_eq!(environment.borrow().values["y"].to_string(), "4");
+        }
+
+        #[test]
+        fn it_keeps_variables_captured_before_an_error() {
+            let environment = Rc::new(RefCell::new(Environment::new()));
+            run("g := fn() { 0 };", Rc::clone(&environment)).unwrap();
+
+            let error = run(
+                "{ a := 1; b := 2; d := 3; c := 5; g = fn() { c }; z := 0; 1 / z; }",
+                Rc::clone(&environment),
+            )
+            .unwrap_err();
+            assert_eq!(error.message, "Division by zero");
+
+            let result = run("g();", Rc::clone(&environment)).unwrap();
+
+            assert_eq!(result.to_string(), "5");
+        }
+
+        #[test]
+        fn it_resolves_globals_declared_after_a_function() {
+            let result = interpret(
+                "is_even := fn(n) { if n == 0 { true } else { is_odd(n - 1) } };
+                is_odd := fn(n) { if n == 0 { false } else { is_even(n - 1) } };
+                is_even(10);",
+            )
+            .unwrap();
+
+            assert_eq!(result.to_string(), "true");
+        }
+
+        #[test]
+        fn it_reports_redeclarations_when_they_are_reached() {
+            let result = interpret("f := fn() { a := 1; a := 2; }; 5;").unwrap();
+
+            assert_eq!(result.to_string(), "5");
+
+            let error = interpret("f := fn() { a := 1; a := 2; }; f();").unwrap_err();
+
+            assert_eq!(error.message, "Variable 'a' already declared in this scope");
+            assert_eq!((error.token.line, error.token.position), (1, 21));
+        }
+    }
+    mod listings {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        fn run_listing(listing: &str) -> Result<Value<'_>, InterpreterError<'_>> {
+            let program = assemble(listing).unwrap();
+
+            Vm::interpret(Rc::new(RefCell::new(Environment::new())), &program)
+        }
+
+        #[test]
+        fn it_runs_hand_written_listings() {
+            // total := 0; i := 1; for i <= 10 { total = total + i; i = i + 1; } total;
+            let result = run_listing(
+                "globals total
+
+                function 0 arity 0 type fn(): Unknown
+                constant 0 literal 0
+                constant 1 literal 1
+                constant 2 literal 10
+                code
+                0000  1:1  DeclareGlobal 0 variable total
+                0001  |    Constant      0
+                0002  |    DefineGlobal  0
+                0003  |    Constant      1          ; i, kept in slot 1
+                0004  |    GetLocal      1
+                0005  |    Constant      2
+                0006  3:3  LessEqual                <=
+                0007  3:1  JumpIfFalse   0019       for
+                0008  4:5  GetGlobal     0          total
+                0009  |    GetLocal      1
+                0010  4:19 Add                      +
+                0011  4:5  SetGlobal     0          total
+                0012  |    Pop
+                0013  |    GetLocal      1
+                0014  |    Constant      1
+                0015  5:11 Add                      +
+                0016  |    SetLocal      1
+                0017  |    Pop
+                0018  |    Jump          0004
+                0019  6:1  GetGlobal     0          total
+                0020  |    Return",
+            )
+            .unwrap();
+
+            assert_eq!(result.to_string(), "55");
+        }
+
+        #[test]
+        fn it_reports_errors_at_listed_locations() {
+            let error = run_listing(
+                "globals
+
+                function 0 arity 0 type fn(): Unknown
+                constant 0 literal 15
+                constant 1 literal 0
+                code
+                0000  |    Constant 0
+                0001  |    Constant 1
+                0002  7:4  Divide   /
+                0003  |    Return",
+            )
+            .unwrap_err();
+
+            assert_eq!(error.message, "Division by zero");
+            assert_eq!((error.token.line, error.token.position), (7, 4));
+        }
+    }
+}
@@ -16,6 +16,7 @@ pub enum TokenType {
     Slash,
     Star,
     BitwiseNot,
+    Question,
 
     // Multiple characters
     Bang,
@@ -34,6 +35,7 @@ pub enum TokenType {
     LeftShift,
     RightShift,
     VarDec,
+    DoubleQuestion,
 
     // Literals
     Identifier,
@@ -47,8 +49,25 @@ pub enum TokenType {
     True,
     False,
     For,
+    Fn,
+    Return,
+    None,
+    Record,
 
     Eof,
+    /// A `//` comment, only kept by `Scanner::scan_with_trivia`
+    Comment,
+    /// A run of spaces, tabs and newlines, only kept by `Scanner::scan_with_trivia`
+    Whitespace,
+    /// Text the scanner could not make sense of, kept so that scanning can continue past it
+    Error,
+}
+
+impl TokenType {
+    /// Whether the token has no meaning to the parser, like comments and whitespace
+    pub fn is_trivia(self) -> bool {
+        matches!(self, TokenType::Comment | TokenType::Whitespace)
+    }
 }
 
 impl PartialEq for TokenType {
@@ -57,12 +76,33 @@ impl PartialEq for TokenType {
     }
 }
 
+/// A range of bytes in the source, from `start` up to but not including `end`
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    #[inline]
+    pub const fn new(start: usize, end: usize) -> Span {
+        Span { start, end }
+    }
+
+    /// Returns the smallest span that covers both `self` and `other`
+    #[inline]
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Token<'a> {
     pub token_type: TokenType,
     pub lexeme: &'a str,
     pub line: u64,
     pub position: u64,
+    pub span: Span,
 }
 
 impl<'a> Token<'a> {
@@ -72,12 +112,14 @@ impl<'a> Token<'a> {
         lexeme: &'a str,
         line: u64,
         position: u64,
+        span: Span,
     ) -> Token<'a> {
         Token {
             token_type,
             lexeme,
             line,
             position,
+            span,
         }
     }
 }
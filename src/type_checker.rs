@@ -0,0 +1,990 @@
+use std::{collections::HashMap, fmt::Display};
+
+use crate::{
+    environment::Environment,
+    matcha::{RecordType, Type},
+    statement::{
+        ArrayExpression, AssignmentExpression, BinaryExpression, CallExpression, Expression,
+        FieldAssignmentExpression, ForStatement, FunctionExpression, IfStatement,
+        IndexAssignmentExpression, RecordDeclaration, RecordExpression, ReturnStatement, Statement,
+        TypeAnnotation, UnaryExpression, UnwrapExpression, VariableDeclaration,
+    },
+    token::{Span, Token, TokenType},
+};
+
+#[derive(Debug)]
+pub struct TypeError<'a> {
+    pub message: String,
+    pub token: Token<'a>,
+    pub note: Option<String>,
+    pub help: Option<&'static str>,
+}
+
+impl TypeError<'_> {
+    pub fn new(message: String, token: Token) -> TypeError {
+        TypeError {
+            message,
+            token,
+            note: None,
+            help: None,
+        }
+    }
+}
+
+impl Display for TypeError<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Type error at {}:{}. {}",
+            self.token.line, self.token.position, self.message
+        )
+    }
+}
+
+/// The function whose body is currently being checked
+struct FunctionContext {
+    return_type: Type,
+    has_return: bool,
+}
+
+pub struct TypeChecker<'a> {
+    scopes: Vec<HashMap<String, Type>>,
+    functions: Vec<FunctionContext>,
+    errors: Vec<TypeError<'a>>,
+    /// The type of every variable declaration and use, by the span of its name
+    types: Vec<(Span, Type)>,
+}
+
+impl Default for TypeChecker<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> TypeChecker<'a> {
+    pub fn new() -> TypeChecker<'a> {
+        TypeChecker {
+            scopes: vec![HashMap::new()],
+            functions: Vec::new(),
+            errors: Vec::new(),
+            types: Vec::new(),
+        }
+    }
+
+    /// Creates a checker that knows the types of the variables already defined in `environment`
+    pub fn with_environment(environment: &Environment) -> TypeChecker<'a> {
+        let globals = environment
+            .values
+            .iter()
+            .map(|(name, value)| (name.to_owned(), value.get_type()))
+            .collect();
+
+        TypeChecker {
+            scopes: vec![globals],
+            functions: Vec::new(),
+            errors: Vec::new(),
+            types: Vec::new(),
+        }
+    }
+
+    /// Checks `statements`, returning the type of the last one if it is an expression and `Empty`
+    /// otherwise
+    pub fn infer(mut self, statements: &[Statement<'a>]) -> Result<Type, Vec<TypeError<'a>>> {
+        let r#type = self.statements(statements);
+
+        if self.errors.is_empty() {
+            return Ok(r#type);
+        }
+
+        Err(self.errors)
+    }
+
+    /// Checks `statements`, returning the type of every variable declaration and use by the span
+    /// of its name, in source order, along with the errors found
+    pub fn types(
+        mut self,
+        statements: &[Statement<'a>],
+    ) -> (Vec<(Span, Type)>, Vec<TypeError<'a>>) {
+        self.statements(statements);
+        self.types.sort_by_key(|(span, _)| span.start);
+
+        (self.types, self.errors)
+    }
+
+    fn statements(&mut self, statements: &[Statement<'a>]) -> Type {
+        let mut r#type = Type::Empty;
+
+        for statement in statements {
+            r#type = match statement {
+                Statement::Expression(expression) => self.expression(expression),
+                statement => {
+                    self.statement(statement);
+                    Type::Empty
+                }
+            };
+        }
+
+        r#type
+    }
+
+    fn statement(&mut self, statement: &Statement<'a>) {
+        match statement {
+            Statement::Expression(expression) => {
+                self.expression(expression);
+            }
+            Statement::VariableDeclaration(declaration) => self.variable_declaration(declaration),
+            Statement::Block(block) => self.block(&block.statements),
+            Statement::If(if_statement) => self.if_statement(if_statement),
+            Statement::For(for_statement) => self.for_statement(for_statement),
+            Statement::Return(return_statement) => self.return_statement(return_statement),
+            Statement::Record(record) => self.record_declaration(record),
+        }
+    }
+
+    fn record_declaration(&mut self, record: &RecordDeclaration<'a>) {
+        let name = record.name.lexeme.to_owned();
+
+        // Declared without fields first, so fields can refer to the record itself
+        self.declare(
+            record.name.lexeme,
+            Type::RecordDeclaration(RecordType {
+                name: name.clone(),
+                fields: Vec::new(),
+            }),
+        );
+
+        let fields = record
+            .fields
+            .iter()
+            .map(|field| {
+                (
+                    field.identifier.lexeme.to_owned(),
+                    self.annotation(Some(&field.r#type)),
+                )
+            })
+            .collect();
+
+        self.declare(
+            record.name.lexeme,
+            Type::RecordDeclaration(RecordType { name, fields }),
+        );
+    }
+
+    fn variable_declaration(&mut self, declaration: &VariableDeclaration<'a>) {
+        // Declare functions before checking their bodies so they can call themselves
+        if let Expression::Function(ref function) = declaration.initializer {
+            self.declare(
+                declaration.identifier.lexeme,
+                TypeChecker::signature(function),
+            );
+        }
+
+        let initializer = self.expression(&declaration.initializer);
+
+        let r#type = match declaration.r#type {
+            Some(ref annotation) => {
+                let declared = self.annotation(Some(annotation));
+
+                if !TypeChecker::is_assignable(&declared, &initializer) {
+                    self.mismatch(
+                        format!(
+                            "Cannot initialize '{}' of type {} with a value of type {}",
+                            declaration.identifier.lexeme, declared, initializer
+                        ),
+                        &annotation.name,
+                        &declared,
+                        &initializer,
+                    );
+                }
+
+                declared
+            }
+            None => initializer,
+        };
+
+        self.types
+            .push((declaration.identifier.span, r#type.clone()));
+        self.declare(declaration.identifier.lexeme, r#type);
+    }
+
+    fn return_statement(&mut self, return_statement: &ReturnStatement<'a>) {
+        let value = match return_statement.value {
+            Some(ref value) => self.expression(value),
+            None => Type::Empty,
+        };
+
+        let Some(function) = self.functions.last_mut() else {
+            return;
+        };
+
+        function.has_return = true;
+
+        if !TypeChecker::is_assignable(&function.return_type, &value) {
+            let message = format!(
+                "Expected a return value of type {}, got {}",
+                function.return_type, value
+            );
+            let return_type = function.return_type.clone();
+
+            self.mismatch(message, &return_statement.keyword, &return_type, &value);
+        }
+    }
+
+    fn block(&mut self, statements: &[Statement<'a>]) {
+        self.scopes.push(HashMap::new());
+
+        for statement in statements {
+            self.statement(statement);
+        }
+
+        self.scopes.pop();
+    }
+
+    fn if_statement(&mut self, if_statement: &IfStatement<'a>) {
+        match if_statement.binding {
+            Some(ref binding) => self.if_binding(if_statement, binding),
+            None => {
+                self.condition(&if_statement.condition);
+                self.block(&if_statement.statements);
+            }
+        }
+
+        if let Some(ref else_statements) = if_statement.else_statements {
+            self.block(else_statements);
+        }
+    }
+
+    /// Checks `if x := optional { ... }`, where `x` holds the unwrapped value inside the block
+    fn if_binding(&mut self, if_statement: &IfStatement<'a>, binding: &Token<'a>) {
+        let r#type = match self.expression(&if_statement.condition) {
+            Type::Optional(inner) => *inner,
+            Type::Unknown => Type::Unknown,
+            r#type => {
+                self.error(
+                    format!(
+                        "Expected an optional value to bind to '{}', got {}",
+                        binding.lexeme, r#type
+                    ),
+                    binding,
+                );
+
+                r#type
+            }
+        };
+
+        self.types.push((binding.span, r#type.clone()));
+        self.scopes
+            .push(HashMap::from([(binding.lexeme.to_owned(), r#type)]));
+
+        for statement in &if_statement.statements {
+            self.statement(statement);
+        }
+
+        self.scopes.pop();
+    }
+
+    fn for_statement(&mut self, for_statement: &ForStatement<'a>) {
+        self.condition(&for_statement.condition);
+        self.block(&for_statement.statements);
+    }
+
+    fn condition(&mut self, condition: &Expression<'a>) {
+        let r#type = self.expression(condition);
+
+        if !TypeChecker::is_assignable(&Type::Boolean, &r#type) {
+            self.error(
+                format!("Expected condition of type Bool, got {}", r#type),
+                condition.token(),
+            );
+        }
+    }
+
+    fn expression(&mut self, expression: &Expression<'a>) -> Type {
+        match expression {
+            Expression::Literal(literal) => literal.literal.get_type(),
+            Expression::Grouping(grouping) => self.expression(&grouping.expression),
+            Expression::Variable(variable) => {
+                let r#type = self.lookup(variable.value.lexeme);
+                self.types.push((variable.value.span, r#type.clone()));
+
+                r#type
+            }
+            Expression::Unary(unary) => self.unary(unary),
+            Expression::Binary(binary) => self.binary(binary),
+            Expression::Logical(logical) => self.logical(logical),
+            Expression::Assignment(assignment) => self.assignment(assignment),
+            Expression::Function(function) => self.function(function),
+            Expression::Call(call) => self.call(call),
+            Expression::Array(array) => self.array(array),
+            Expression::Index(index) => self.index(&index.target, &index.index, &index.bracket),
+            Expression::IndexAssignment(assignment) => self.index_assignment(assignment),
+            Expression::None(_) => Type::Optional(Box::new(Type::Unknown)),
+            Expression::Unwrap(unwrap) => self.unwrap(unwrap),
+            Expression::Coalesce(coalesce) => self.coalesce(coalesce),
+            Expression::Record(record) => self.record(record),
+            Expression::Field(field) => self.field(&field.target, &field.name),
+            Expression::FieldAssignment(assignment) => self.field_assignment(assignment),
+        }
+    }
+
+    fn record(&mut self, record: &RecordExpression<'a>) -> Type {
+        let values: Vec<Type> = record
+            .fields
+            .iter()
+            .map(|field| self.expression(&field.value))
+            .collect();
+
+        let declaration = match self.lookup(record.name.lexeme) {
+            Type::RecordDeclaration(declaration) => declaration,
+            Type::Unknown => return Type::Unknown,
+            r#type => {
+                self.error(
+                    format!("'{}' is not a record, got {}", record.name.lexeme, r#type),
+                    &record.name,
+                );
+
+                return Type::Unknown;
+            }
+        };
+
+        for (initializer, value) in record.fields.iter().zip(values.iter()) {
+            match declaration.field(initializer.identifier.lexeme) {
+                Some(field) if !TypeChecker::is_assignable(field, value) => self.error(
+                    format!(
+                        "Cannot initialize field '{}' of type {} with a value of type {}",
+                        initializer.identifier.lexeme, field, value
+                    ),
+                    &initializer.identifier,
+                ),
+                Some(_) => {}
+                None => self.error(
+                    format!(
+                        "Record {} has no field '{}'",
+                        declaration.name, initializer.identifier.lexeme
+                    ),
+                    &initializer.identifier,
+                ),
+            }
+        }
+
+        for (field, _) in &declaration.fields {
+            if !record
+                .fields
+                .iter()
+                .any(|initializer| initializer.identifier.lexeme == field)
+            {
+                self.error(
+                    format!("Missing field '{}' in {}", field, declaration.name),
+                    &record.name,
+                );
+            }
+        }
+
+        Type::Record(declaration)
+    }
+
+    /// Checks `target.name` and returns the type of the field
+    fn field(&mut self, target: &Expression<'a>, name: &Token<'a>) -> Type {
+        let record = match self.expression(target) {
+            Type::Record(record) => record,
+            Type::Unknown => return Type::Unknown,
+            r#type => {
+                self.error(
+                    format!("Can only access fields on records, got {}", r#type),
+                    name,
+                );
+
+                return Type::Unknown;
+            }
+        };
+
+        // Records that refer to themselves only know their fields through the declaration
+        let field = match self.lookup(&record.name) {
+            Type::RecordDeclaration(declaration) if declaration == record => {
+                declaration.field(name.lexeme).cloned()
+            }
+            _ => record.field(name.lexeme).cloned(),
+        };
+
+        match field {
+            Some(field) => field,
+            None => {
+                self.error(
+                    format!("Record {} has no field '{}'", record.name, name.lexeme),
+                    name,
+                );
+
+                Type::Unknown
+            }
+        }
+    }
+
+    fn field_assignment(&mut self, assignment: &FieldAssignmentExpression<'a>) -> Type {
+        let field = self.field(&assignment.target, &assignment.name);
+        let value = self.expression(&assignment.value);
+
+        if !TypeChecker::is_assignable(&field, &value) {
+            self.error(
+                format!(
+                    "Cannot assign a value of type {} to field '{}' of type {}",
+                    value, assignment.name.lexeme, field
+                ),
+                &assignment.name,
+            );
+        }
+
+        Type::Empty
+    }
+
+    fn unwrap(&mut self, unwrap: &UnwrapExpression<'a>) -> Type {
+        match self.expression(&unwrap.target) {
+            Type::Optional(inner) => *inner,
+            Type::Unknown => Type::Unknown,
+            r#type => {
+                self.error(
+                    format!("Cannot unwrap a non-optional value of type {}", r#type),
+                    &unwrap.operator,
+                );
+
+                r#type
+            }
+        }
+    }
+
+    fn coalesce(&mut self, coalesce: &BinaryExpression<'a>) -> Type {
+        let left = self.expression(&coalesce.left);
+        let right = self.expression(&coalesce.right);
+
+        let inner = match left {
+            Type::Optional(inner) => *inner,
+            Type::Unknown => return right,
+            r#type => {
+                self.error(
+                    format!("Operator '??' expects an optional value, got {}", r#type),
+                    &coalesce.operator,
+                );
+
+                return r#type;
+            }
+        };
+
+        match right {
+            // Falling back to another optional keeps the result optional
+            Type::Optional(ref fallback) if TypeChecker::is_assignable(&inner, fallback) => {
+                Type::Optional(Box::new(inner))
+            }
+            ref fallback if TypeChecker::is_assignable(&inner, fallback) => inner,
+            fallback => {
+                self.error(
+                    format!(
+                        "Cannot use a fallback of type {} for a value of type {}?",
+                        fallback, inner
+                    ),
+                    &coalesce.operator,
+                );
+
+                inner
+            }
+        }
+    }
+
+    fn array(&mut self, array: &ArrayExpression<'a>) -> Type {
+        let mut element_type = Type::Unknown;
+
+        for element in &array.elements {
+            let r#type = self.expression(element);
+
+            match TypeChecker::unify(&element_type, &r#type) {
+                Some(unified) => element_type = unified,
+                None => self.error(
+                    format!(
+                        "Array elements must share a type, expected {}, got {}",
+                        element_type, r#type
+                    ),
+                    element.token(),
+                ),
+            }
+        }
+
+        Type::Array(Box::new(element_type))
+    }
+
+    /// Checks `target[index]` and returns the type of the element
+    fn index(
+        &mut self,
+        target: &Expression<'a>,
+        index: &Expression<'a>,
+        bracket: &Token<'a>,
+    ) -> Type {
+        let target = self.expression(target);
+        let index = self.expression(index);
+
+        if !TypeChecker::is_assignable(&Type::Integer, &index) {
+            self.error(
+                format!("Array index must be an Int, got {}", index),
+                bracket,
+            );
+        }
+
+        match target {
+            Type::Array(element) => *element,
+            Type::Unknown => Type::Unknown,
+            _ => {
+                self.error(format!("Can only index arrays, got {}", target), bracket);
+
+                Type::Unknown
+            }
+        }
+    }
+
+    fn index_assignment(&mut self, assignment: &IndexAssignmentExpression<'a>) -> Type {
+        let element = self.index(&assignment.target, &assignment.index, &assignment.bracket);
+        let value = self.expression(&assignment.value);
+
+        if !TypeChecker::is_assignable(&element, &value) {
+            self.error(
+                format!(
+                    "Cannot assign a value of type {} to an element of type {}",
+                    value, element
+                ),
+                &assignment.bracket,
+            );
+        }
+
+        Type::Empty
+    }
+
+    fn function(&mut self, function: &FunctionExpression<'a>) -> Type {
+        let mut scope = HashMap::new();
+        let mut parameters = Vec::<Type>::new();
+
+        for parameter in &function.parameters {
+            let r#type = self.annotation(parameter.r#type.as_ref());
+
+            self.types.push((parameter.identifier.span, r#type.clone()));
+            scope.insert(parameter.identifier.lexeme.to_owned(), r#type.clone());
+            parameters.push(r#type);
+        }
+
+        let declared_return_type = self.annotation(function.return_type.as_ref());
+
+        self.scopes.push(scope);
+        self.functions.push(FunctionContext {
+            return_type: declared_return_type.clone(),
+            has_return: false,
+        });
+
+        let (last_statement, last_type) = match function.body.split_last() {
+            Some((Statement::Expression(last), rest)) => {
+                for statement in rest {
+                    self.statement(statement);
+                }
+
+                (Some(last), self.expression(last))
+            }
+            _ => {
+                for statement in function.body.iter() {
+                    self.statement(statement);
+                }
+
+                (None, Type::Unknown)
+            }
+        };
+
+        let context = self.functions.pop().expect("Function context must exist");
+        self.scopes.pop();
+
+        let return_type = match (function.return_type.is_some(), last_statement) {
+            (true, Some(last)) => {
+                // The trailing expression is the implicit return value
+                if !TypeChecker::is_assignable(&declared_return_type, &last_type) {
+                    self.error(
+                        format!(
+                            "Expected the function body to evaluate to {}, got {}",
+                            declared_return_type, last_type
+                        ),
+                        last.token(),
+                    );
+                }
+
+                declared_return_type
+            }
+            (true, None) => declared_return_type,
+            // Without an annotation the return type can only be inferred from the trailing expression
+            (false, _) if !context.has_return => last_type,
+            (false, _) => Type::Unknown,
+        };
+
+        Type::Function {
+            parameters,
+            return_type: Box::new(return_type),
+        }
+    }
+
+    fn call(&mut self, call: &CallExpression<'a>) -> Type {
+        let callee = self.expression(&call.callee);
+        let arguments: Vec<Type> = call
+            .arguments
+            .iter()
+            .map(|argument| self.expression(argument))
+            .collect();
+
+        match callee {
+            Type::Function {
+                parameters,
+                return_type,
+            } => {
+                if parameters.len() != arguments.len() {
+                    self.error(
+                        format!(
+                            "Expected {} arguments but got {}",
+                            parameters.len(),
+                            arguments.len()
+                        ),
+                        &call.paren,
+                    );
+
+                    return *return_type;
+                }
+
+                for (index, (parameter, argument)) in
+                    parameters.iter().zip(arguments.iter()).enumerate()
+                {
+                    if !TypeChecker::is_assignable(parameter, argument) {
+                        self.mismatch(
+                            format!(
+                                "Expected argument {} to be of type {}, got {}",
+                                index + 1,
+                                parameter,
+                                argument
+                            ),
+                            call.arguments[index].token(),
+                            parameter,
+                            argument,
+                        );
+                    }
+                }
+
+                *return_type
+            }
+            Type::Unknown => Type::Unknown,
+            _ => {
+                self.error(
+                    format!("Cannot call a value of type {}", callee),
+                    &call.paren,
+                );
+
+                Type::Unknown
+            }
+        }
+    }
+
+    fn unary(&mut self, unary: &UnaryExpression<'a>) -> Type {
+        let operand = self.expression(&unary.left);
+
+        if operand == Type::Unknown {
+            return Type::Unknown;
+        }
+
+        match unary.operator.token_type {
+            TokenType::Minus if operand.is_numeric() => operand,
+            TokenType::Bang if operand == Type::Boolean => Type::Boolean,
+            TokenType::BitwiseNot if operand == Type::Integer => Type::Integer,
+            _ => {
+                self.error(
+                    format!(
+                        "Operator '{}' cannot be applied to {}",
+                        unary.operator.lexeme, operand
+                    ),
+                    &unary.operator,
+                );
+
+                Type::Unknown
+            }
+        }
+    }
+
+    fn binary(&mut self, binary: &BinaryExpression<'a>) -> Type {
+        let left = self.expression(&binary.left);
+        let right = self.expression(&binary.right);
+
+        if let TokenType::DoubleEqual | TokenType::BangEqual = binary.operator.token_type {
+            return self.equality(&binary.operator, &left, &right);
+        }
+
+        if left == Type::Unknown || right == Type::Unknown {
+            return match binary.operator.token_type {
+                TokenType::Greater
+                | TokenType::GreaterEqual
+                | TokenType::Less
+                | TokenType::LessEqual => Type::Boolean,
+                _ => Type::Unknown,
+            };
+        }
+
+        match binary.operator.token_type {
+            TokenType::Plus | TokenType::Minus | TokenType::Star | TokenType::Slash
+                if left.is_numeric() && right.is_numeric() =>
+            {
+                // Mixing integers and floats promotes the result to a float
+                if left == Type::Integer && right == Type::Integer {
+                    Type::Integer
+                } else {
+                    Type::Float
+                }
+            }
+            TokenType::Greater
+            | TokenType::GreaterEqual
+            | TokenType::Less
+            | TokenType::LessEqual
+                if left.is_numeric() && right.is_numeric() =>
+            {
+                Type::Boolean
+            }
+            TokenType::BitwiseAnd
+            | TokenType::BitwiseOr
+            | TokenType::BitwiseXor
+            | TokenType::LeftShift
+            | TokenType::RightShift
+                if left == Type::Integer && right == Type::Integer =>
+            {
+                Type::Integer
+            }
+            _ => {
+                self.error(
+                    format!(
+                        "Operator '{}' cannot be applied to {} and {}",
+                        binary.operator.lexeme, left, right
+                    ),
+                    &binary.operator,
+                );
+
+                Type::Unknown
+            }
+        }
+    }
+
+    fn logical(&mut self, logical: &BinaryExpression<'a>) -> Type {
+        for operand in [&logical.left, &logical.right] {
+            let r#type = self.expression(operand);
+
+            if !TypeChecker::is_assignable(&Type::Boolean, &r#type) {
+                self.error(
+                    format!(
+                        "Operator '{}' expects Bool operands, got {}",
+                        logical.operator.lexeme, r#type
+                    ),
+                    &logical.operator,
+                );
+            }
+        }
+
+        Type::Boolean
+    }
+
+    fn assignment(&mut self, assignment: &AssignmentExpression<'a>) -> Type {
+        let value = self.expression(&assignment.value);
+        let target = self.lookup(assignment.identifier.lexeme);
+        self.types
+            .push((assignment.identifier.span, target.clone()));
+
+        if !TypeChecker::is_assignable(&target, &value) {
+            self.mismatch(
+                format!(
+                    "Cannot assign a value of type {} to '{}' of type {}",
+                    value, assignment.identifier.lexeme, target
+                ),
+                &assignment.identifier,
+                &target,
+                &value,
+            );
+        }
+
+        Type::Empty
+    }
+
+    /// Resolves a type annotation, reporting unknown type names
+    fn annotation(&mut self, annotation: Option<&TypeAnnotation<'a>>) -> Type {
+        let Some(annotation) = annotation else {
+            return Type::Unknown;
+        };
+
+        if let Some(ref function) = annotation.function {
+            return Type::Function {
+                parameters: function
+                    .parameters
+                    .iter()
+                    .map(|parameter| self.annotation(Some(parameter)))
+                    .collect(),
+                return_type: Box::new(self.annotation(function.return_type.as_deref())),
+            };
+        }
+
+        let r#type = match Type::from_name(annotation.name.lexeme) {
+            Some(r#type) => r#type,
+            None => match self.lookup(annotation.name.lexeme) {
+                Type::RecordDeclaration(record) => Type::Record(record),
+                _ => {
+                    self.error(
+                        format!("Unknown type '{}'", annotation.name.lexeme),
+                        &annotation.name,
+                    );
+                    self.note("the built-in types are Int, Float, String and Bool".to_owned());
+
+                    Type::Unknown
+                }
+            },
+        };
+
+        match annotation.optional {
+            true => Type::Optional(Box::new(r#type)),
+            false => r#type,
+        }
+    }
+
+    /// The type of a function according to its annotations, without checking its body
+    fn signature(function: &FunctionExpression) -> Type {
+        Type::Function {
+            parameters: function
+                .parameters
+                .iter()
+                .map(|parameter| Type::from_annotation(parameter.r#type.as_ref()))
+                .collect(),
+            return_type: Box::new(Type::from_annotation(function.return_type.as_ref())),
+        }
+    }
+
+    /// The type that both types can be assigned to, if any, in whichever order they come. `none`
+    /// and a value of another type make an optional of that type, like `[none, 1]` is `[Int?]`
+    fn unify(left: &Type, right: &Type) -> Option<Type> {
+        match (left, right) {
+            (Type::Unknown, r#type) | (r#type, Type::Unknown) => Some(r#type.clone()),
+            (Type::Optional(_), _) | (_, Type::Optional(_)) => {
+                TypeChecker::unify(left.unwrapped(), right.unwrapped())
+                    .map(|inner| Type::Optional(Box::new(inner)))
+            }
+            (Type::Array(left), Type::Array(right)) => {
+                TypeChecker::unify(left, right).map(|element| Type::Array(Box::new(element)))
+            }
+            (left, right) if TypeChecker::is_assignable(left, right) => Some(left.clone()),
+            (left, right) if TypeChecker::is_assignable(right, left) => Some(right.clone()),
+            _ => None,
+        }
+    }
+
+    /// `==` and `!=`, which compare numbers, strings and booleans by value
+    fn equality(&mut self, operator: &Token<'a>, left: &Type, right: &Type) -> Type {
+        // Optionals compare with `none` and with values of their inner type
+        let (inner_left, inner_right) = (left.unwrapped(), right.unwrapped());
+        let equatable = TypeChecker::is_equatable(left) && TypeChecker::is_equatable(right);
+        let comparable = if !equatable {
+            false
+        } else if inner_left.is_numeric() && inner_right.is_numeric() {
+            true
+        } else if matches!(left, Type::Optional(_)) || matches!(right, Type::Optional(_)) {
+            TypeChecker::is_assignable(inner_left, inner_right)
+        } else {
+            *left == Type::Unknown || *right == Type::Unknown || left == right
+        };
+
+        if !comparable {
+            self.error(format!("Can't compare {} with {}", left, right), operator);
+
+            if !equatable {
+                self.help("only numbers, strings, booleans and their optionals can be compared");
+            }
+        }
+
+        Type::Boolean
+    }
+
+    /// Whether values of the type can be compared with `==`. Arrays, records and functions are
+    /// shared by reference and have no equality of their own
+    fn is_equatable(r#type: &Type) -> bool {
+        matches!(
+            r#type.unwrapped(),
+            Type::Integer | Type::Float | Type::String | Type::Boolean | Type::Unknown
+        )
+    }
+
+    fn is_assignable(target: &Type, value: &Type) -> bool {
+        match (target, value) {
+            (Type::Unknown, _) | (_, Type::Unknown) => true,
+            (
+                Type::Function {
+                    parameters: target_parameters,
+                    return_type: target_return_type,
+                },
+                Type::Function {
+                    parameters: value_parameters,
+                    return_type: value_return_type,
+                },
+            ) => {
+                target_parameters.len() == value_parameters.len()
+                    && target_parameters
+                        .iter()
+                        .zip(value_parameters.iter())
+                        .all(|(target, value)| TypeChecker::is_assignable(value, target))
+                    && TypeChecker::is_assignable(target_return_type, value_return_type)
+            }
+            (Type::Array(target), Type::Array(value)) => TypeChecker::is_assignable(target, value),
+            (Type::Optional(target), Type::Optional(value)) => {
+                TypeChecker::is_assignable(target, value)
+            }
+            // Plain values are wrapped implicitly, but optionals must be unwrapped explicitly
+            (Type::Optional(target), value) => TypeChecker::is_assignable(target, value),
+            _ => target == value,
+        }
+    }
+
+    #[inline]
+    fn declare(&mut self, name: &str, r#type: Type) {
+        self.scopes
+            .last_mut()
+            .expect("There must always be a global scope")
+            .insert(name.to_owned(), r#type);
+    }
+
+    /// Unknown variables are left for the interpreter to report
+    #[inline]
+    fn lookup(&self, name: &str) -> Type {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .cloned()
+            .unwrap_or(Type::Unknown)
+    }
+
+    #[inline]
+    fn error(&mut self, message: String, token: &Token<'a>) {
+        self.errors.push(TypeError::new(message, token.clone()));
+    }
+
+    /// Reports a value that doesn't fit its target, suggesting how to unwrap optionals
+    fn mismatch(&mut self, message: String, token: &Token<'a>, target: &Type, value: &Type) {
+        self.error(message, token);
+
+        if let Type::Optional(inner) = value {
+            if TypeChecker::is_assignable(target, inner) {
+                self.help("unwrap the value with `!`, or provide a fallback with `??`");
+            }
+        }
+    }
+
+    /// Attaches a note to the last reported error
+    #[inline]
+    fn note(&mut self, note: String) {
+        if let Some(error) = self.errors.last_mut() {
+            error.note = Some(note);
+        }
+    }
+
+    /// Attaches a suggestion to the last reported error
+    #[inline]
+    fn help(&mut self, help: &'static str) {
+        if let Some(error) = self.errors.last_mut() {
+            error.help = Some(help);
+        }
+    }
+}
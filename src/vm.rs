@@ -0,0 +1,625 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    chunk::{Chunk, Constant, OpCode, Program},
+    environment::Environment,
+    interpreter::{Interpreter, InterpreterError, MAX_CALL_DEPTH},
+    matcha::{Array, Closure, Literal, NumberLiteral, Record, Upvalue, UpvalueState, Value},
+    token::Token,
+};
+
+/// Runs programs compiled by `Compiler`, producing the same values and errors as the tree-walker
+pub struct Vm<'a> {
+    stack: Vec<Value<'a>>,
+    /// The callers of the running function. The running one is kept out of the list
+    frames: Vec<Frame<'a>>,
+    /// Indexed like `Program::globals`, `None` until declared
+    globals: Vec<Option<Value<'a>>>,
+    /// Upvalues that still point at a slot of the stack
+    open_upvalues: Vec<Upvalue<'a>>,
+}
+
+struct Frame<'a> {
+    closure: Rc<Closure<'a>>,
+    ip: usize,
+    /// The slot of the callee, which the function's locals follow
+    base: usize,
+}
+
+impl<'a> Frame<'a> {
+    fn chunk(&self) -> &Chunk<'a> {
+        &self.closure.prototype.chunk
+    }
+
+    /// The token of the instruction being executed, used to report errors
+    fn token(&self) -> Token<'a> {
+        self.chunk().token(self.ip - 1).clone()
+    }
+
+    fn name(&self, index: u16) -> &'a str {
+        match self.chunk().constants[index as usize] {
+            Constant::Name(name) => name,
+            ref constant => unreachable!("Expected a name, got {:?}", constant),
+        }
+    }
+}
+
+fn integer<'a>(value: i32) -> Value<'a> {
+    Value::Literal(Literal::Number(NumberLiteral::Integer(value)))
+}
+
+fn boolean<'a>(value: bool) -> Value<'a> {
+    Value::Literal(Literal::Boolean(value))
+}
+
+impl<'a> Vm<'a> {
+    /// Runs `program` with the globals of `environment`, writing them back once it finishes
+    pub fn interpret(
+        environment: Rc<RefCell<Environment<'a>>>,
+        program: &Program<'a>,
+    ) -> Result<Value<'a>, InterpreterError<'a>> {
+        let globals = program
+            .globals
+            .iter()
+            .map(|name| environment.borrow().values.get(*name).cloned())
+            .collect();
+        let mut vm = Vm {
+            stack: Vec::new(),
+            frames: Vec::new(),
+            globals,
+            open_upvalues: Vec::new(),
+        };
+
+        let main = Rc::new(Closure {
+            prototype: Rc::clone(&program.main),
+            upvalues: Vec::new(),
+        });
+        vm.stack.push(Value::Closure(Rc::clone(&main)));
+
+        let result = vm.run(Frame {
+            closure: main,
+            ip: 0,
+            base: 0,
+        });
+
+        // An error leaves the variables it interrupted on the stack, so closures that outlive the
+        // run, like one assigned to a global, take their values with them either way
+        vm.close_upvalues(0);
+
+        let mut environment = environment.borrow_mut();
+
+        for (name, value) in program.globals.iter().zip(vm.globals) {
+            if let Some(value) = value {
+                environment.values.insert((*name).to_owned(), value);
+            }
+        }
+
+        result
+    }
+
+    fn run(&mut self, mut frame: Frame<'a>) -> Result<Value<'a>, InterpreterError<'a>> {
+        loop {
+            let op = frame.chunk().code[frame.ip];
+            frame.ip += 1;
+
+            match op {
+                OpCode::Constant(index) => {
+                    let value = match frame.chunk().constants[index as usize] {
+                        Constant::Literal(ref literal) => Value::Literal(literal.clone()),
+                        Constant::Record(ref declaration) => {
+                            Value::RecordDeclaration(Rc::clone(declaration))
+                        }
+                        ref constant => unreachable!("Expected a value, got {:?}", constant),
+                    };
+
+                    self.stack.push(value);
+                }
+                OpCode::Empty => self.stack.push(Value::Empty),
+                OpCode::None => self.stack.push(Value::Optional(None)),
+                OpCode::Pop => {
+                    self.pop();
+                }
+
+                OpCode::GetLocal(slot) => {
+                    let value = self.stack[frame.base + slot as usize].clone();
+
+                    self.stack.push(value);
+                }
+                OpCode::SetLocal(slot) => {
+                    let value = self.pop();
+                    let previous = &mut self.stack[frame.base + slot as usize];
+                    *previous = Interpreter::reassign(previous, value);
+
+                    self.stack.push(Value::Empty);
+                }
+                OpCode::GetUpvalue(index) => {
+                    let value = match *frame.closure.upvalues[index as usize].borrow() {
+                        UpvalueState::Open(slot) => self.stack[slot].clone(),
+                        UpvalueState::Closed(ref value) => value.clone(),
+                    };
+
+                    self.stack.push(value);
+                }
+                OpCode::SetUpvalue(index) => {
+                    let value = self.pop();
+                    let mut upvalue = frame.closure.upvalues[index as usize].borrow_mut();
+                    let previous = match *upvalue {
+                        UpvalueState::Open(slot) => &mut self.stack[slot],
+                        UpvalueState::Closed(ref mut value) => value,
+                    };
+                    *previous = Interpreter::reassign(previous, value);
+                    drop(upvalue);
+
+                    self.stack.push(Value::Empty);
+                }
+                OpCode::GetGlobal(index) => match self.globals[index as usize] {
+                    Some(ref value) => self.stack.push(value.clone()),
+                    None => {
+                        let identifier = frame.token();
+
+                        return Err(InterpreterError::new(
+                            format!(
+                                "Variable '{}' not found in the current scope",
+                                identifier.lexeme
+                            ),
+                            identifier,
+                        ));
+                    }
+                },
+                OpCode::SetGlobal(index) => {
+                    let value = self.pop();
+
+                    match self.globals[index as usize] {
+                        Some(ref mut previous) => {
+                            *previous = Interpreter::reassign(previous, value);
+                        }
+                        None => {
+                            let identifier = frame.token();
+
+                            return Err(InterpreterError::new(
+                                format!(
+                                    "Cannot assign a value to undeclared variable '{}'",
+                                    identifier.lexeme
+                                ),
+                                identifier,
+                            ));
+                        }
+                    }
+
+                    self.stack.push(Value::Empty);
+                }
+                OpCode::DeclareGlobal { index, record } => {
+                    if self.globals[index as usize].is_some() {
+                        return Err(Vm::redeclaration(frame.token(), record));
+                    }
+                }
+                OpCode::DefineGlobal(index) => {
+                    self.globals[index as usize] = Some(self.pop());
+                }
+                OpCode::Redeclare { record } => {
+                    return Err(Vm::redeclaration(frame.token(), record));
+                }
+                OpCode::EndScope(count) => {
+                    let value = self.pop();
+
+                    self.drop_locals(count);
+                    self.stack.push(value);
+                }
+                OpCode::PopScope(count) => self.drop_locals(count),
+
+                // Integers that overflow fall back to the tree-walker, which reports the error
+                OpCode::Add => {
+                    self.binary(&frame, |left, right| left.checked_add(right).map(integer))?
+                }
+                OpCode::Subtract => {
+                    self.binary(&frame, |left, right| left.checked_sub(right).map(integer))?
+                }
+                OpCode::Multiply => {
+                    self.binary(&frame, |left, right| left.checked_mul(right).map(integer))?
+                }
+                OpCode::Greater => {
+                    self.binary(&frame, |left, right| Some(boolean(left > right)))?
+                }
+                OpCode::GreaterEqual => {
+                    self.binary(&frame, |left, right| Some(boolean(left >= right)))?
+                }
+                OpCode::Less => self.binary(&frame, |left, right| Some(boolean(left < right)))?,
+                OpCode::LessEqual => {
+                    self.binary(&frame, |left, right| Some(boolean(left <= right)))?
+                }
+                OpCode::Equal => self.binary(&frame, |left, right| Some(boolean(left == right)))?,
+                OpCode::NotEqual => {
+                    self.binary(&frame, |left, right| Some(boolean(left != right)))?
+                }
+                // Division and bitwise operators have errors to report even for integers
+                OpCode::Divide
+                | OpCode::BitwiseAnd
+                | OpCode::BitwiseOr
+                | OpCode::BitwiseXor
+                | OpCode::LeftShift
+                | OpCode::RightShift => self.binary(&frame, |_, _| None)?,
+                OpCode::Negate | OpCode::Not | OpCode::BitwiseNot => {
+                    let value = self.pop();
+
+                    self.stack
+                        .push(Interpreter::unary_operation(&frame.token(), value)?);
+                }
+
+                OpCode::Jump(target) => frame.ip = target as usize,
+                OpCode::JumpIfFalse(target) => {
+                    let condition = self.pop();
+
+                    if !Vm::condition(&frame, condition)? {
+                        frame.ip = target as usize;
+                    }
+                }
+                OpCode::And(target) => {
+                    let left = self.pop();
+
+                    if !Vm::condition(&frame, left)? {
+                        self.stack.push(boolean(false));
+                        frame.ip = target as usize;
+                    }
+                }
+                OpCode::Or(target) => {
+                    let left = self.pop();
+
+                    if Vm::condition(&frame, left)? {
+                        self.stack.push(boolean(true));
+                        frame.ip = target as usize;
+                    }
+                }
+                OpCode::Bool => {
+                    let right = self.pop();
+                    let right = Vm::condition(&frame, right)?;
+
+                    self.stack.push(boolean(right));
+                }
+
+                OpCode::Wrap => {
+                    let value = self.pop();
+
+                    self.stack.push(Interpreter::optional(value));
+                }
+                OpCode::Unwrap => {
+                    let value = match self.pop() {
+                        Value::Optional(Some(value)) => *value,
+                        value => Interpreter::unwrap_optional(value, &frame.token())?,
+                    };
+
+                    self.stack.push(value);
+                }
+                OpCode::Coalesce(target) => {
+                    let left = self.pop();
+
+                    if let Some(value) = Interpreter::coalesced(left, &frame.token())? {
+                        self.stack.push(value);
+                        frame.ip = target as usize;
+                    }
+                }
+                OpCode::Bind(target) => {
+                    let value = self.pop();
+
+                    match Interpreter::bound(value, &frame.token())? {
+                        Some(value) => self.stack.push(value),
+                        None => frame.ip = target as usize,
+                    }
+                }
+
+                OpCode::Closure(index) => {
+                    let prototype = match frame.chunk().constants[index as usize] {
+                        Constant::Function(ref prototype) => Rc::clone(prototype),
+                        ref constant => unreachable!("Expected a function, got {:?}", constant),
+                    };
+                    let upvalues = prototype
+                        .captures
+                        .iter()
+                        .map(|capture| match capture.local {
+                            true => self.capture(frame.base + capture.index as usize),
+                            false => Rc::clone(&frame.closure.upvalues[capture.index as usize]),
+                        })
+                        .collect();
+
+                    self.stack.push(Value::Closure(Rc::new(Closure {
+                        prototype,
+                        upvalues,
+                    })));
+                }
+                OpCode::CheckCall(arguments) => match self.peek(0) {
+                    Value::Closure(closure) if closure.prototype.arity == arguments as usize => {}
+                    Value::Closure(closure) => {
+                        return Err(Interpreter::arity_error(
+                            closure.prototype.arity,
+                            arguments as usize,
+                            &frame.token(),
+                        ))
+                    }
+                    value => return Err(Interpreter::not_callable(value, &frame.token())),
+                },
+                OpCode::Call(arguments) => {
+                    let base = self.stack.len() - arguments as usize - 1;
+                    let closure = match self.stack[base] {
+                        Value::Closure(ref closure) => Rc::clone(closure),
+                        ref value => unreachable!("Expected a checked callee, got {:?}", value),
+                    };
+
+                    // The running frame isn't in `frames`, so this is the depth of the caller
+                    if self.frames.len() >= MAX_CALL_DEPTH {
+                        return Err(Interpreter::stack_overflow(&frame.token()));
+                    }
+
+                    let caller = std::mem::replace(
+                        &mut frame,
+                        Frame {
+                            closure,
+                            ip: 0,
+                            base,
+                        },
+                    );
+                    self.frames.push(caller);
+                }
+                OpCode::Return => {
+                    let result = self.pop();
+
+                    self.close_upvalues(frame.base);
+                    self.stack.truncate(frame.base);
+
+                    match self.frames.pop() {
+                        Some(caller) => {
+                            frame = caller;
+                            self.stack.push(result);
+                        }
+                        None => return Ok(result),
+                    }
+                }
+
+                OpCode::Array(length) => {
+                    let elements = self.stack.split_off(self.stack.len() - length as usize);
+
+                    self.stack
+                        .push(Value::Array(Rc::new(RefCell::new(elements))));
+                }
+                OpCode::CheckArray => {
+                    if !matches!(self.peek(0), Value::Array(_)) {
+                        Interpreter::array_of(self.pop(), &frame.token())?;
+                    }
+                }
+                OpCode::GetIndex => {
+                    let index = self.pop();
+                    let elements = self.array();
+                    let position = Vm::position(&frame, &elements, index)?;
+                    let value = elements.borrow()[position].clone();
+
+                    self.stack.push(value);
+                }
+                OpCode::CheckIndex => {
+                    let index = self.pop();
+                    let elements = self.array();
+                    Vm::position(&frame, &elements, index.clone())?;
+
+                    self.stack.push(Value::Array(elements));
+                    self.stack.push(index);
+                }
+                OpCode::SetIndex => {
+                    let value = self.pop();
+                    let index = self.pop();
+                    let elements = self.array();
+                    let position = Vm::position(&frame, &elements, index)?;
+
+                    let mut elements = elements.borrow_mut();
+                    elements[position] = Interpreter::reassign(&elements[position], value);
+                    drop(elements);
+
+                    self.stack.push(Value::Empty);
+                }
+
+                OpCode::CheckRecord => {
+                    if !matches!(self.peek(0), Value::RecordDeclaration(_)) {
+                        Interpreter::record_declaration_of(self.pop(), &frame.token())?;
+                    }
+                }
+                OpCode::CheckRecordField { name, depth } => {
+                    let Value::RecordDeclaration(declaration) = self.peek(depth as usize) else {
+                        unreachable!("Expected a checked record declaration");
+                    };
+                    let name = frame.name(name);
+
+                    if !declaration
+                        .fields
+                        .iter()
+                        .any(|field| field.identifier.lexeme == name)
+                    {
+                        Interpreter::initializer_index(declaration, &frame.token())?;
+                    }
+                }
+                OpCode::Record(names) => {
+                    let Constant::Names(ref names) = frame.chunk().constants[names as usize] else {
+                        unreachable!("Expected the names of the fields");
+                    };
+                    let values = self.stack.split_off(self.stack.len() - names.len());
+                    let Value::RecordDeclaration(declaration) = self.pop() else {
+                        unreachable!("Expected a checked record declaration");
+                    };
+
+                    let mut fields: Vec<Option<Value>> = vec![None; declaration.fields.len()];
+
+                    for (name, value) in names.iter().zip(values) {
+                        let index = declaration
+                            .fields
+                            .iter()
+                            .position(|field| field.identifier.lexeme == *name)
+                            .expect("Fields are checked before their values");
+
+                        fields[index] = Some(value);
+                    }
+
+                    self.stack.push(Interpreter::record_value(
+                        declaration,
+                        fields,
+                        &frame.token(),
+                    )?);
+                }
+                OpCode::GetField(name) => {
+                    let target = self.pop();
+                    let (record, index) = Vm::field(&frame, target, name)?;
+                    let value = record.borrow().fields[index].clone();
+
+                    self.stack.push(value);
+                }
+                OpCode::CheckField(name) => {
+                    let target = self.peek(0).clone();
+
+                    Vm::field(&frame, target, name)?;
+                }
+                OpCode::SetField(name) => {
+                    let value = self.pop();
+                    let target = self.pop();
+                    let (record, index) = Vm::field(&frame, target, name)?;
+
+                    let mut record = record.borrow_mut();
+                    record.fields[index] = Interpreter::reassign(&record.fields[index], value);
+                    drop(record);
+
+                    self.stack.push(Value::Empty);
+                }
+            }
+        }
+    }
+
+    // Helpers:
+
+    fn pop(&mut self) -> Value<'a> {
+        self.stack.pop().expect("Stack must not be empty")
+    }
+
+    fn peek(&self, depth: usize) -> &Value<'a> {
+        &self.stack[self.stack.len() - 1 - depth]
+    }
+
+    /// Pops an array that was already checked by `CheckArray`
+    fn array(&mut self) -> Array<'a> {
+        match self.pop() {
+            Value::Array(elements) => elements,
+            value => unreachable!("Expected a checked array, got {:?}", value),
+        }
+    }
+
+    /// Applies the operator of the current instruction, using `integers` as a fast path when both
+    /// operands are integers and it has a result
+    fn binary(
+        &mut self,
+        frame: &Frame<'a>,
+        integers: impl Fn(i32, i32) -> Option<Value<'a>>,
+    ) -> Result<(), InterpreterError<'a>> {
+        let right = self.pop();
+        let left = self.pop();
+
+        let fast = match (&left, &right) {
+            (
+                Value::Literal(Literal::Number(NumberLiteral::Integer(left))),
+                Value::Literal(Literal::Number(NumberLiteral::Integer(right))),
+            ) => integers(*left, *right),
+            _ => None,
+        };
+
+        let value = match fast {
+            Some(value) => value,
+            None => Interpreter::binary_operation(&frame.token(), left, right)?,
+        };
+
+        self.stack.push(value);
+
+        Ok(())
+    }
+
+    fn condition(frame: &Frame<'a>, value: Value<'a>) -> Result<bool, InterpreterError<'a>> {
+        match value {
+            Value::Literal(Literal::Boolean(boolean)) => Ok(boolean),
+            value => Interpreter::unwrap_bool(value)
+                .map_err(|message| InterpreterError::new(message, frame.token())),
+        }
+    }
+
+    fn position(
+        frame: &Frame<'a>,
+        elements: &Array<'a>,
+        index: Value<'a>,
+    ) -> Result<usize, InterpreterError<'a>> {
+        if let Value::Literal(Literal::Number(NumberLiteral::Integer(integer))) = index {
+            if let Ok(position) = usize::try_from(integer) {
+                if position < elements.borrow().len() {
+                    return Ok(position);
+                }
+            }
+        }
+
+        Interpreter::position_of(elements, index, &frame.token())
+    }
+
+    fn field(
+        frame: &Frame<'a>,
+        target: Value<'a>,
+        name: u16,
+    ) -> Result<(Record<'a>, usize), InterpreterError<'a>> {
+        if let Value::Record(ref record) = target {
+            if let Some(index) = record.borrow().field_index(frame.name(name)) {
+                return Ok((Rc::clone(record), index));
+            }
+        }
+
+        Interpreter::field_of(target, &frame.token())
+    }
+
+    fn redeclaration(identifier: Token<'a>, record: bool) -> InterpreterError<'a> {
+        let kind = if record { "Record" } else { "Variable" };
+
+        InterpreterError::new(
+            format!(
+                "{} '{}' already declared in this scope",
+                kind, identifier.lexeme
+            ),
+            identifier,
+        )
+    }
+
+    fn drop_locals(&mut self, count: u16) {
+        let start = self.stack.len() - count as usize;
+
+        self.close_upvalues(start);
+        self.stack.truncate(start);
+    }
+
+    /// Shares the upvalue of `slot` if a closure already captured it
+    fn capture(&mut self, slot: usize) -> Upvalue<'a> {
+        let existing = self
+            .open_upvalues
+            .iter()
+            .find(|upvalue| matches!(*upvalue.borrow(), UpvalueState::Open(open) if open == slot));
+
+        if let Some(upvalue) = existing {
+            return Rc::clone(upvalue);
+        }
+
+        let upvalue = Rc::new(RefCell::new(UpvalueState::Open(slot)));
+        self.open_upvalues.push(Rc::clone(&upvalue));
+
+        upvalue
+    }
+
+    /// Moves the values of the slots from `start` upwards into the upvalues that capture them
+    fn close_upvalues(&mut self, start: usize) {
+        let stack = &self.stack;
+
+        self.open_upvalues.retain(|upvalue| {
+            let mut state = upvalue.borrow_mut();
+
+            match *state {
+                UpvalueState::Open(slot) if slot >= start => {
+                    *state = UpvalueState::Closed(stack[slot].clone());
+                    false
+                }
+                _ => true,
+            }
+        });
+    }
+}